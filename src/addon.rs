@@ -0,0 +1,107 @@
+//! This module describes packages that are installed into a plugin's data folder rather than as
+//! plugins of their own, like PlaceholderAPI expansions or Skript scripts. The plugin they're for
+//! loads them itself, so they're left out of everything that reads plugin.yml files. A pkg.yml
+//! entry with `install_to` is one of them, and says which folder it goes in, relative to the
+//! plugin directory:
+//!
+//! ```yaml
+//! vault-expansion:
+//!   maven: com.extendedclip.papi.expansion:vault
+//!   install_to: PlaceholderAPI/expansions
+//! my-script:
+//!   url: "https://example.com/scripts/my-script-{version}.sk"
+//!   version: "1.2"
+//!   install_to: Skript/scripts
+//!   extension: sk
+//! ```
+//!
+//! Addons are named like plugins are, `<package>@<version>`, but with the `extension` they're
+//! loaded by, which is `jar` unless the entry says otherwise.
+
+use crate::lock;
+use std::error::Error;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use yaml_rust::Yaml;
+
+const DEFAULT_EXTENSION: &'static str = "jar";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A pkg.yml entry's `install_to` or `extension` isn't usable, because it isn't a folder
+    // inside the plugin directory or isn't a plain extension. Takes the package name as a param.
+    DestinationInvalid(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::DestinationInvalid(s) => format!(
+                    "'{}' should be installed to a folder in the plugin directory, like \
+                     PlaceholderAPI/expansions, with an extension like sk",
+                    s
+                ),
+            }
+        )
+    }
+}
+
+/// Where in a plugin's data folder a package is installed
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddonTarget {
+    /// The folder the package goes in, relative to the plugin directory
+    pub dir: PathBuf,
+    /// The extension the package's file is given, without the dot
+    pub extension: String,
+}
+
+impl AddonTarget {
+    /// Reads where a pkg.yml entry is installed to, or `None` if it's a plugin
+    ///
+    /// # Errors
+    /// * [`ErrorKind::DestinationInvalid`](enum.ErrorKind.html#variant.DestinationInvalid) - the entry has an `install_to` key, but it isn't usable
+    pub fn from_yaml(name: &str, entry: &Yaml) -> Result<Option<AddonTarget>, ErrorKind> {
+        let invalid = || ErrorKind::DestinationInvalid(name.to_string());
+        let dir = match &entry["install_to"] {
+            Yaml::BadValue => return Ok(None),
+            Yaml::String(dir) => PathBuf::from(dir),
+            _ => return Err(invalid()),
+        };
+        // Addons belong to a plugin, so they can't be installed anywhere else
+        let inside = dir.components().count() > 0
+            && dir.components().all(|c| matches!(c, Component::Normal(_)));
+        if !inside {
+            return Err(invalid());
+        }
+
+        let extension = match &entry["extension"] {
+            Yaml::BadValue => DEFAULT_EXTENSION.to_string(),
+            Yaml::String(extension) => extension.trim_start_matches('.').to_string(),
+            _ => return Err(invalid()),
+        };
+        if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(invalid());
+        }
+
+        Ok(Some(AddonTarget {
+            dir: dir,
+            extension: extension,
+        }))
+    }
+
+    /// The name a version of the package is installed under, relative to the plugin directory,
+    /// with `/` between folders on every platform so that lockfiles can be shared
+    pub fn file(&self, file_stem: &str) -> String {
+        lock::file_name(&self.dir.join(format!("{}.{}", file_stem, self.extension)))
+    }
+}
+
+/// Whether a file named in the lockfile is in a plugin's data folder, rather than being a plugin
+pub fn is_addon_file(file: &str) -> bool {
+    Path::new(file).components().count() > 1
+}
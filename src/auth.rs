@@ -0,0 +1,363 @@
+//! This module keeps the tokens that sources need, for `dropper auth login`, so that secrets
+//! don't have to be written into the config. A token is kept in the platform's keychain where
+//! there is one: the login keychain on macOS, through `security`, and the Secret Service on
+//! Linux, through `secret-tool`. The credentials file only says which host it's for, and which
+//! header to send it in:
+//!
+//! ```yaml
+//! api.curseforge.com:
+//!   token: keychain
+//!   header: x-api-key
+//! ```
+//!
+//! Where there's no keychain, or `DROPPER_KEYCHAIN` is `off`, the token itself is kept in the
+//! credentials file instead of `keychain`, and the file is only readable by its owner. A token
+//! is sent in the `Authorization` header as a bearer token, unless another header is given.
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+// What the keychain keeps dropper's tokens under
+const SERVICE: &'static str = "dropper";
+const KEYCHAIN_VAR: &'static str = "DROPPER_KEYCHAIN";
+/// The `token` that says a host's token is in the keychain
+pub const IN_KEYCHAIN: &'static str = "keychain";
+const DEFAULT_HEADER: &'static str = "Authorization";
+
+/// The sources dropper knows the tokens of, as (source, host, header)
+const KNOWN_SOURCES: [(&'static str, &'static str, &'static str); 2] = [
+    ("github", "api.github.com", DEFAULT_HEADER),
+    ("curseforge", "api.curseforge.com", "x-api-key"),
+];
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A source isn't one dropper knows, or a host. Takes the source as a param.
+    SourceUnknown(String),
+    // The keychain couldn't be used. Takes what went wrong as a param.
+    KeychainFailed(String),
+    // The credentials file couldn't be understood. Takes its path as a param.
+    CredentialsFileInvalid(String),
+    // An empty token was given to log in with
+    TokenEmpty,
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::SourceUnknown(s) => format!(
+                    "'{}' isn't a source dropper knows the token of ({}), or a host",
+                    s,
+                    KNOWN_SOURCES
+                        .iter()
+                        .map(|(source, _, _)| *source)
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                ),
+                ErrorKind::KeychainFailed(s) => format!("the keychain couldn't be used: {}", s),
+                ErrorKind::CredentialsFileInvalid(s) => {
+                    format!("the credentials file {} is corrupt", s)
+                }
+                ErrorKind::TokenEmpty => "no token was given, so nothing was changed".to_string(),
+            }
+        )
+    }
+}
+
+/// Where a token ended up, as reported by `login`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Storage {
+    /// In the platform's keychain
+    Keychain,
+    /// In the credentials file, since the keychain couldn't be used. Takes why as a param.
+    CredentialsFile(String),
+}
+
+/// The host a source's token is for, and the header it's sent in. Anything that looks like a
+/// host stands for itself, with the token sent as a bearer token.
+///
+/// # Errors
+/// * [`ErrorKind::SourceUnknown`](enum.ErrorKind.html#variant.SourceUnknown) - the source isn't a known one or a host
+pub fn host_of(source: &str) -> Result<(String, String), ErrorKind> {
+    let source = source.to_lowercase();
+    if let Some((_, host, header)) = KNOWN_SOURCES.iter().find(|(name, _, _)| *name == source) {
+        return Ok((host.to_string(), header.to_string()));
+    }
+    match source.contains('.') && url::Host::parse(&source).is_ok() {
+        true => Ok((source, DEFAULT_HEADER.to_string())),
+        false => Err(ErrorKind::SourceUnknown(source)),
+    }
+}
+
+/// The value of the header a host's token is sent in
+pub fn header_value(header: &str, token: &str) -> String {
+    match header.eq_ignore_ascii_case(DEFAULT_HEADER) {
+        true => format!("Bearer {}", token),
+        false => token.to_string(),
+    }
+}
+
+/// Keeps the token for a source, in the keychain if there is one, and records it in the
+/// credentials file. A token the source already had is replaced.
+///
+/// # Errors
+/// * [`ErrorKind::SourceUnknown`](enum.ErrorKind.html#variant.SourceUnknown) - the source isn't a known one or a host
+/// * [`ErrorKind::CredentialsFileInvalid`](enum.ErrorKind.html#variant.CredentialsFileInvalid) - the credentials file is corrupt
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn login(credentials_file: &Path, source: &str, token: &str) -> Result<Storage, Box<Error>> {
+    let (host, header) = host_of(source)?;
+    let storage = match keychain_set(&host, token) {
+        Ok(()) => Storage::Keychain,
+        Err(e) => Storage::CredentialsFile(e.to_string()),
+    };
+
+    let mut entry = Hash::new();
+    entry.insert(
+        Yaml::from_str("token"),
+        Yaml::String(match storage {
+            Storage::Keychain => IN_KEYCHAIN.to_string(),
+            Storage::CredentialsFile(_) => token.to_string(),
+        }),
+    );
+    if header != DEFAULT_HEADER {
+        entry.insert(Yaml::from_str("header"), Yaml::String(header));
+    }
+    let mut doc = read_credentials(credentials_file)?;
+    doc.insert(Yaml::String(host), Yaml::Hash(entry));
+    write_credentials(credentials_file, &doc)?;
+    Ok(storage)
+}
+
+/// Forgets the token for a source, from the keychain and the credentials file. Returns whether
+/// there was one.
+///
+/// # Errors
+/// * [`ErrorKind::SourceUnknown`](enum.ErrorKind.html#variant.SourceUnknown) - the source isn't a known one or a host
+/// * [`ErrorKind::CredentialsFileInvalid`](enum.ErrorKind.html#variant.CredentialsFileInvalid) - the credentials file is corrupt
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn logout(credentials_file: &Path, source: &str) -> Result<bool, Box<Error>> {
+    let (host, _) = host_of(source)?;
+    let in_keychain = keychain_delete(&host);
+    let mut doc = read_credentials(credentials_file)?;
+    let in_file = doc.remove(&Yaml::String(host)).is_some();
+    if in_file {
+        write_credentials(credentials_file, &doc)?;
+    }
+    Ok(in_keychain || in_file)
+}
+
+/// Reads a host's token out of the keychain, or `None` if it isn't there or there's no keychain
+pub fn keychain_get(host: &str) -> Option<String> {
+    let mut command = match keychain_tool()? {
+        "security" => {
+            let mut command = Command::new("security");
+            command.args(["find-generic-password", "-s", SERVICE, "-a", host, "-w"]);
+            command
+        }
+        _ => {
+            let mut command = Command::new("secret-tool");
+            command.args(["lookup", "service", SERVICE, "account", host]);
+            command
+        }
+    };
+    let output = command.stderr(Stdio::null()).output().ok()?;
+    let token = String::from_utf8(output.stdout)
+        .ok()?
+        .trim_end()
+        .to_string();
+    match output.status.success() && !token.is_empty() {
+        true => Some(token),
+        false => None,
+    }
+}
+
+/// Puts a host's token in the keychain, replacing whatever it had
+///
+/// # Errors
+/// * [`ErrorKind::KeychainFailed`](enum.ErrorKind.html#variant.KeychainFailed) - there's no keychain, or it wouldn't take the token
+fn keychain_set(host: &str, token: &str) -> Result<(), ErrorKind> {
+    let failed = |why: String| ErrorKind::KeychainFailed(why);
+    let tool = keychain_tool().ok_or_else(|| failed("there's no keychain".to_string()))?;
+    // Both tools are given the token on stdin, which keeps it out of the process list:
+    // secret-tool reads the secret from there, and security runs the commands it's sent there
+    let (mut command, input) = match tool {
+        "security" => {
+            if token.contains(['"', '\\', '\n', '\r']) {
+                return Err(failed(
+                    "the token has quotes, backslashes or line breaks in it".to_string(),
+                ));
+            }
+            let mut command = Command::new("security");
+            command.arg("-i");
+            let input = format!(
+                "add-generic-password -U -s {} -a {} -w \"{}\"\n",
+                SERVICE, host, token
+            );
+            (command, input)
+        }
+        _ => {
+            let mut command = Command::new("secret-tool");
+            command
+                .args(["store", "--label", &format!("dropper: {}", host)])
+                .args(["service", SERVICE, "account", host]);
+            (command, token.to_string())
+        }
+    };
+    let result = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .and_then(|mut child| {
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(input.as_bytes())?;
+            }
+            child.wait()
+        });
+    match result {
+        Ok(status) if !status.success() => Err(failed(format!("{} exited with {}", tool, status))),
+        Err(e) => Err(failed(format!("{} couldn't be run: {}", tool, e))),
+        // security doesn't exit with the status of the commands it runs, so the token is looked
+        // up again to be sure it's there
+        Ok(_) if keychain_get(host).as_deref() != Some(token) => {
+            Err(failed(format!("{} didn't keep the token", tool)))
+        }
+        Ok(_) => Ok(()),
+    }
+}
+
+/// Takes a host's token out of the keychain, returning whether it was there
+fn keychain_delete(host: &str) -> bool {
+    let mut command = match keychain_tool() {
+        Some("security") => {
+            let mut command = Command::new("security");
+            command.args(["delete-generic-password", "-s", SERVICE, "-a", host]);
+            command
+        }
+        Some(_) => {
+            // secret-tool doesn't say whether there was anything to clear
+            if keychain_get(host).is_none() {
+                return false;
+            }
+            let mut command = Command::new("secret-tool");
+            command.args(["clear", "service", SERVICE, "account", host]);
+            command
+        }
+        None => return false,
+    };
+    command
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// The command that gets at the platform's keychain, or `None` if there isn't one to use
+fn keychain_tool() -> Option<&'static str> {
+    if env::var(KEYCHAIN_VAR).is_ok_and(|value| value == "off") {
+        return None;
+    }
+    let tool = match (cfg!(target_os = "macos"), cfg!(unix)) {
+        (true, _) => "security",
+        (false, true) => "secret-tool",
+        (false, false) => return None,
+    };
+    // Checks that the tool is there at all, since running it is the only way to find out
+    let found = Command::new(tool)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok();
+    match found {
+        true => Some(tool),
+        false => None,
+    }
+}
+
+/// The top level of the credentials file, which is empty if there isn't one
+fn read_credentials(path: &Path) -> Result<Hash, Box<Error>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Hash::new()),
+        Err(e) => return Err(Box::new(e)),
+    };
+    let invalid = || {
+        Box::new(ErrorKind::CredentialsFileInvalid(
+            path.display().to_string(),
+        ))
+    };
+    match YamlLoader::load_from_str(&contents)
+        .map_err(|_| invalid())?
+        .into_iter()
+        .next()
+    {
+        Some(Yaml::Hash(doc)) => Ok(doc),
+        None | Some(Yaml::Null) => Ok(Hash::new()),
+        Some(_) => Err(invalid()),
+    }
+}
+
+/// Writes the credentials file, readable only by its owner since it can hold tokens
+fn write_credentials(path: &Path, doc: &Hash) -> Result<(), Box<Error>> {
+    let mut tmp_string = String::new();
+    YamlEmitter::new(&mut tmp_string)
+        .dump(&Yaml::Hash(doc.clone()))
+        .unwrap();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // A file that was already there keeps the permissions it had when it's opened, so they're
+    // restricted before anything is written to it
+    let mut file = open_private(path)?;
+    restrict(path)?;
+    file.write_all(format!("{}\n", tmp_string).as_bytes())?;
+    Ok(())
+}
+
+/// Opens a file for writing, emptying it, and creates it readable only by its owner if it isn't
+/// there yet
+#[cfg(unix)]
+fn open_private(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn open_private(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+}
+
+#[cfg(unix)]
+fn restrict(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
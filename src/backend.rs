@@ -13,26 +13,156 @@
 //! * Newest Patch: `WorldEdit: 6.1.*` / `WorldEdit@6.1.*`
 //! * Newest Minor: `WorldEdit: 6.*` / `WorldEdit@6.*`
 //! * Newest Major (Newest release): `WorldEdit: *` / `WorldEdit`
+//! * Newest build of a release channel: `Geyser: latest-dev` / `Geyser@latest-dev`, or
+//!   `latest-beta` or `latest-release`
+//!
+//! On the command line, a package can also be given by its numeric project ID, which doesn't
+//! change when the project is renamed: `31043@6.1.9` or `id:31043@6.1.9`.
+//!
+//! ## Sources
+//! A package comes from the config's `plugin_website` unless its name says otherwise, both in the
+//! pkg.yml and on the command line: `bukkit:vault: *` / `bukkit:vault`, or
+//! `bukkit:id:31043@6.1.9`. The source a package was installed from is remembered in the
+//! lockfile, so later updates look for it in the same place. A few popular plugins are best
+//! installed from somewhere else than the plugin website, and have
+//! [presets](../presets/index.html) saying where.
+//!
+//! ## Environments
+//! Servers that share a pkg.yml can each run a few packages of their own. Entries under
+//! `environments` are laid over the rest of the pkg.yml when dropper is run with `--env`, adding
+//! packages or replacing the entries of ones that are already listed:
+//!
+//! ```yaml
+//! WorldEdit: 6.1.*
+//! environments:
+//!   dev:
+//!     WorldEdit: 7.*
+//!     spark: "*"
+//! ```
 
-use crate::parser::VERSION_CODE_REGEX;
-use crate::parser::{PluginFetchable, PluginSearchable};
+use crate::addon;
+use crate::addon::AddonTarget;
+use crate::blocklist::Blocklist;
+use crate::build;
+use crate::build::GitSource;
+use crate::bundle;
+use crate::bundle::Manifest;
+use crate::cache;
+use crate::cancel;
+use crate::cancel::{CancelToken, Cancellable};
+use crate::checksum;
+use crate::complete;
+use crate::direct::DirectSource;
+use crate::docs::Docs;
+use crate::downloads::DownloadsSource;
+use crate::format;
+use crate::github::GithubSource;
+use crate::hangar::HangarSource;
+use crate::hash;
+use crate::history::{Event, History};
+use crate::hooks::{Hook, HookContext, Hooks};
+use crate::http;
+use crate::http::Credentials;
+use crate::jar;
+use crate::jar::PluginDescription;
+use crate::journal::Journal;
+use crate::lock;
+use crate::lock::{LockedPackage, Lockfile};
+use crate::logs;
+use crate::logs::Problem;
+use crate::libraries;
+use crate::libraries::Library;
+use crate::listing::ListingSource;
+use crate::manual::ManualPackage;
+use crate::maven;
+use crate::maven::MavenSource;
+use crate::parser;
+use crate::parser::{
+    Channel, Lookup, PackageMetadata, PluginFetchable, PluginFile, PluginListing,
+    PluginSearchable, SearchFilters,
+};
+use crate::plan;
+use crate::presets;
+use crate::repo;
+use crate::repo::{Index, RepoSource};
+use crate::plan::{Change, Plan};
+use crate::resolver;
+use crate::resolver::{Constraint, Requirement};
+use crate::restart::Restart;
+use crate::s3::S3Config;
+use crate::smoke;
+use crate::smoke::{Outcome, TestRun, TestServer};
+use crate::state::{InstallReason, InstalledPackage, State};
+use crate::store;
+use crate::store::InstallStrategy;
+use crate::template;
 use crate::text_assets;
+use crate::version::Version;
+use glob::Pattern;
 use regex::Regex;
+use reqwest::{Client, StatusCode};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::{copy, Read, Write};
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{fmt, fs, io};
+use url::Url;
 use yaml_rust::{YamlLoader, YamlEmitter, Yaml};
 use yaml_rust::yaml::Hash;
 
-const CONFIG_ROOT: &'static str = "./.dropper";
-const CONFIG_PATH: &'static str = "./.dropper/config.yml";
-const PKG_LIST_PATH: &'static str = "./pkg.yml";
-const DOWNLOAD_DIR: &'static str = "./plugins";
+const CONFIG_ROOT: &'static str = ".dropper";
+const CONFIG_FILE: &'static str = "config.yml";
+const STATE_FILE: &'static str = "state.yml";
+const HISTORY_FILE: &'static str = "history.yml";
+const CREDENTIALS_FILE: &'static str = "credentials.yml";
+const JOURNAL_DIR: &'static str = "journal";
+const QUARANTINE_DIR: &'static str = "quarantine";
+const DOCS_DIR: &'static str = "docs";
+const HTTP_CACHE_DIR: &'static str = "cache/http";
+const NAMES_FILE: &'static str = "cache/names.yml";
+const BUILD_DIR: &'static str = "build";
+const BUNDLE_DIR: &'static str = "bundle";
+const PKG_LIST_FILE: &'static str = "pkg.yml";
+const VARS_FILE: &'static str = "vars.yml";
+const LOCK_FILE: &'static str = "dropper.lock";
+const PLAN_FILE: &'static str = "dropper.plan";
+const CRASH_REPORTS_DIR: &'static str = "crash-reports";
+const DOWNLOAD_DIR: &'static str = "plugins";
+// Lives inside the plugin directory, so that moving a finished download into place is a rename
+// on the same filesystem rather than a copy
+const STAGING_DIR: &'static str = ".dropper-staging";
+const PARTIAL_EXTENSION: &'static str = "part";
+// How many packages to fetch information about at once. Enough to hide how slow the plugin
+// website's pages are, without hammering it.
+const METADATA_THREADS: usize = 8;
 
 const VERSION_SPLIT_CHAR: char = '@';
+// Separates the source from the rest of a package specifier
+const SOURCE_SPLIT_CHAR: char = ':';
+// Package names can be given as numeric project IDs, optionally marked with this
+const PROJECT_ID_PREFIX: &'static str = "id:";
+
+// The pkg.yml key that holds the overlays of each environment, rather than a package
+const ENVIRONMENTS_KEY: &'static str = "environments";
+
+// Who requirements read from the pkg.yml are attributed to
+const PKG_LIST_REQUIRER: &'static str = "pkg.yml";
+
+// How many of a package's versions to list when the one asked for isn't there
+const MAX_LISTED_VERSIONS: usize = 5;
+// How long before a crash changes to packages are worth pointing out, in seconds
+const CRASH_LOOKBACK: u64 = 60 * 60 * 24 * 3;
+
+// A package that's been resolved, as its (name, version, link)
+type Resolved = (String, String, String);
+// A package's actual name, its project ID if it was named by one, and whether the name was cached
+type ProjectName = (String, Option<String>, bool);
 
 #[derive(Debug)]
 pub enum ErrorKind {
@@ -47,6 +177,84 @@ pub enum ErrorKind {
     ConfigMissing,
     // There was some issue with the package list.
     PkgListInvalid,
+    // A package required by the package list could not be found. Takes the package name as a
+    // param.
+    PkgNotFound(String),
+    // A plugin targets a newer server API than the server provides. Takes the plugin name, its
+    // api-version, and the server version as params.
+    ApiVersionTooNew(String, String, String),
+    // Other installed plugins depend on the package being removed. Takes the package name and
+    // the names of its dependents as params.
+    PkgRequired(String, Vec<String>),
+    // A download didn't match a digest published or locked for it. Takes the download link, the
+    // digest's algorithm, the expected digest, the actual digest, and where the download was
+    // quarantined as params.
+    HashMismatch(String, String, String, String, PathBuf),
+    // A download didn't give us a JAR. Takes the download link and what went wrong as params.
+    DownloadFailed(String, String),
+    // The package being updated isn't installed. Takes the package name as a param.
+    PkgNotInstalled(String),
+    // A plugin doesn't say which server API it targets, on a server where that matters. Takes
+    // the plugin name and the server version as params.
+    ApiVersionMissing(String, String),
+    // A package has no build for the server version, only one for an older version. Takes the
+    // package name, the server version, and the older version as params.
+    CompatFallback(String, String, String),
+    // The config's plugin_website isn't one there's a parser for. Takes the website and the
+    // closest supported one, if any is close, as params.
+    WebsiteUnknown(String, Option<String>),
+    // Strict mode is on, and a compatibility check failed. Takes the check and what went wrong
+    // as params.
+    CheckFailed(Check, String),
+    // A version is on the blocklist. Takes the package name, the version, and why it's blocked
+    // as params.
+    VersionBlocked(String, String, String),
+    // A package specifier names a source there's no parser for. Takes the source, the supported
+    // sources, and the closest supported one, if any is close, as params.
+    SourceUnknown(String, Vec<String>, Option<String>),
+    // The pkg.yml has no overlay for the environment dropper was run with. Takes the
+    // environment, the ones the pkg.yml has, and the closest of those, if any is close, as
+    // params.
+    EnvUnknown(String, Vec<String>, Option<String>),
+    // A download link doesn't lead to anything anymore. Takes the link as a param.
+    DownloadGone(String),
+    // A locked version's file was taken down by its author. Takes the package name, the
+    // version, and the nearest version that's still available, if one is, as params.
+    VersionYanked(String, String, Option<String>),
+    // The version being installed is older than the one that's installed. Takes the package
+    // name, the installed version, and the older version as params.
+    DowngradeRefused(String, String, String),
+    // A package has no builds at all for the server version. Takes the package name and the
+    // server version as params.
+    NoBuilds(String, String),
+    // A package doesn't have the version that was asked for. Takes the package name, the
+    // version, the versions it does have, newest first, and the closest of those, if any is
+    // close, as params.
+    VersionNotFound(String, String, Vec<String>, Option<String>),
+    // The server can't be moved to another version, because some packages have no build for
+    // it. Takes the server version and the packages as params.
+    MigrationBlocked(String, Vec<String>),
+    // An unmanaged JAR can't be adopted into a package. Takes the JAR and why as params.
+    AdoptFailed(String, String),
+    // The package is managed by hand, so dropper can't download it. Takes the package name and
+    // the JAR the pkg.yml expects as params.
+    PkgManual(String, String),
+    // An operation failed, and rolling back what it had done failed too. Takes what the
+    // operation failed with, and what went wrong rolling it back, as params.
+    RollbackFailed(String, String),
+    // A batch of updates was rolled back, because a package in it couldn't be updated. Takes
+    // the package name as a param.
+    BatchRolledBack(String),
+    // A plugin registers itself under a name that can't be a folder in the plugin directory, so
+    // it has no data folder to put configs in. Takes the package name and the plugin's name as
+    // params.
+    PluginNameInvalid(String, String),
+    // The test server didn't start with every plugin enabled
+    TestFailed,
+    // The JARs don't match a SHA256SUMS file. Takes the file as a param.
+    ChecksumsFailed(String),
+    // A locked JAR doesn't match the lockfile, or a JAR managed by hand its pkg.yml entry
+    VerifyFailed,
 }
 
 impl Error for ErrorKind {}
@@ -62,21 +270,852 @@ impl fmt::Display for ErrorKind {
                 ErrorKind::ConfigInvalid(s) => format!("param '{}' missing from config", s),
                 ErrorKind::ConfigMissing => "the config file was not found!".to_string(),
                 ErrorKind::PkgListInvalid => "the package list file is incorrectly formatter".to_string(),
+                ErrorKind::PkgNotFound(s) => format!("package '{}' could not be found", s),
+                ErrorKind::ApiVersionTooNew(name, api, server) => format!(
+                    "{} targets server API {}, which is newer than this server's {}",
+                    name, api, server
+                ),
+                ErrorKind::PkgRequired(name, dependents) => format!(
+                    "{} is needed by {}, which would break without it",
+                    name,
+                    dependents.join(", ")
+                ),
+                ErrorKind::HashMismatch(link, algorithm, expected, actual, quarantined) => format!(
+                    "the download from {} is corrupt (expected {} {}, got {}); it has been kept in {} to be looked into",
+                    link,
+                    algorithm.to_uppercase(),
+                    expected,
+                    actual,
+                    quarantined.display()
+                ),
+                ErrorKind::DownloadFailed(link, why) => {
+                    format!("the download from {} failed: {}", link, why)
+                }
+                ErrorKind::PkgNotInstalled(s) => format!("package '{}' isn't installed", s),
+                ErrorKind::ApiVersionMissing(name, server) => format!(
+                    "{} doesn't declare an api-version, so server version {} will load it as a legacy plugin",
+                    name, server
+                ),
+                ErrorKind::CompatFallback(name, server, version) => format!(
+                    "{} has no build for server version {}, only one for {}",
+                    name, server, version
+                ),
+                ErrorKind::WebsiteUnknown(website, closest) => format!(
+                    "plugin_website '{}' isn't supported; it can be one of: {}{}",
+                    website,
+                    parser::PLUGIN_WEBSITES.join(", "),
+                    match closest {
+                        Some(closest) => format!(" (did you mean '{}'?)", closest),
+                        None => String::new(),
+                    }
+                ),
+                ErrorKind::CheckFailed(check, s) => format!(
+                    "{} (strict mode is on; use --force={} to go ahead anyway)",
+                    s,
+                    check.name()
+                ),
+                ErrorKind::VersionBlocked(name, version, reason) => format!(
+                    "{} {} is blocked: {} (use --allow-blocked to install it anyway)",
+                    name, version, reason
+                ),
+                ErrorKind::SourceUnknown(source, sources, closest) => format!(
+                    "there's no package source named '{}'; it can be one of: {}{}",
+                    source,
+                    sources.join(", "),
+                    match closest {
+                        Some(closest) => format!(" (did you mean '{}'?)", closest),
+                        None => String::new(),
+                    }
+                ),
+                ErrorKind::DownloadGone(link) => format!("there's nothing at {} anymore", link),
+                ErrorKind::VersionYanked(name, version, nearest) => format!(
+                    "{} {} has been taken down from where it was downloaded{}",
+                    name,
+                    version,
+                    match nearest {
+                        Some(nearest) => format!(
+                            "; the nearest version still available is {} (install it with `dropper install {}@{}{}`)",
+                            nearest,
+                            name,
+                            nearest,
+                            match PackageBackend::is_older(nearest, version) {
+                                true => " --allow-downgrade",
+                                false => "",
+                            }
+                        ),
+                        None => String::new(),
+                    }
+                ),
+                ErrorKind::DowngradeRefused(name, installed, version) => format!(
+                    "{} {} is older than the installed {} (use --allow-downgrade to install it anyway)",
+                    name, version, installed
+                ),
+                ErrorKind::NoBuilds(name, server) => format!(
+                    "{} has no builds for server version {} (set compat_fallback in the config to use one for an older version)",
+                    name, server
+                ),
+                ErrorKind::VersionNotFound(name, version, available, closest) => format!(
+                    "{} has no version {}{}; the newest it has are: {}{} (install one with `dropper install {}@<version>`)",
+                    name,
+                    version,
+                    match closest {
+                        Some(closest) => format!(" (did you mean {}@{}?)", name, closest),
+                        None => String::new(),
+                    },
+                    available
+                        .iter()
+                        .take(MAX_LISTED_VERSIONS)
+                        .cloned()
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    match available.len() > MAX_LISTED_VERSIONS {
+                        true => ", ...",
+                        false => "",
+                    },
+                    name
+                ),
+                ErrorKind::EnvUnknown(env, envs, closest) => format!(
+                    "the pkg.yml has no environment named '{}'{}{}",
+                    env,
+                    match envs.is_empty() {
+                        true => String::new(),
+                        false => format!("; it has: {}", envs.join(", ")),
+                    },
+                    match closest {
+                        Some(closest) => format!(" (did you mean '{}'?)", closest),
+                        None => String::new(),
+                    }
+                ),
+                ErrorKind::MigrationBlocked(server, names) => format!(
+                    "the server can't be moved to {}, since {} {} no build for it",
+                    server,
+                    names.join(", "),
+                    match names.len() {
+                        1 => "has",
+                        _ => "have",
+                    }
+                ),
+                ErrorKind::AdoptFailed(file, why) => format!("{} can't be adopted: {}", file, why),
+                ErrorKind::RollbackFailed(why, rollback) => format!(
+                    "{}, and rolling back what had been done failed too ({}), so the server may be left half way through it",
+                    why, rollback
+                ),
+                ErrorKind::BatchRolledBack(name) => format!(
+                    "nothing was updated, since {} couldn't be",
+                    name
+                ),
+                ErrorKind::TestFailed => {
+                    "the test server didn't start with every plugin enabled".to_string()
+                }
+                ErrorKind::ChecksumsFailed(s) => format!("the JARs don't match {}", s),
+                ErrorKind::VerifyFailed => {
+                    "the JARs don't match the lockfile and the pkg.yml".to_string()
+                }
+                ErrorKind::PluginNameInvalid(name, plugin_name) => format!(
+                    "{} registers itself as '{}', which can't be a folder in the plugin directory, so it has nowhere to keep its configs",
+                    name, plugin_name
+                ),
+                ErrorKind::PkgManual(name, file) => format!(
+                    "{} is managed by hand, so dropper won't download it; put it in the plugin directory as {} yourself",
+                    name, file
+                ),
             }
         )
     }
 }
 
+/// Where a server's files live. Everything dropper manages is relative to the server root
+/// (the project directory), except for the plugin directory, which can be pointed anywhere so
+/// that automation can install into servers laid out differently.
+#[derive(Debug, Clone)]
+pub struct ProjectPaths {
+    /// The server root, which holds the pkg.yml and the .dropper folder
+    pub project_dir: PathBuf,
+    /// Where plugin JARs are installed to
+    pub plugin_dir: PathBuf,
+}
+
+impl ProjectPaths {
+    /// Creates the paths for a server rooted at `project_dir`.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_dir` - The server root
+    /// * `plugin_dir` - Where to install plugins to. Defaults to the `plugins` folder in the server root.
+    pub fn new(project_dir: PathBuf, plugin_dir: Option<PathBuf>) -> ProjectPaths {
+        ProjectPaths {
+            plugin_dir: plugin_dir.unwrap_or(project_dir.join(DOWNLOAD_DIR)),
+            project_dir: project_dir,
+        }
+    }
+
+    /// The .dropper folder, which holds dropper's own files
+    pub fn config_root(&self) -> PathBuf {
+        self.project_dir.join(CONFIG_ROOT)
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.config_root().join(CONFIG_FILE)
+    }
+
+    /// Where the journal of the operation in progress is kept
+    pub fn journal_dir(&self) -> PathBuf {
+        self.config_root().join(JOURNAL_DIR)
+    }
+
+    /// Where JARs that shouldn't be in the plugin directory are moved to, rather than deleted
+    pub fn quarantine_dir(&self) -> PathBuf {
+        self.config_root().join(QUARANTINE_DIR)
+    }
+
+    /// Where what a package's source says about it is kept, if `save_docs` is on
+    pub fn docs_dir(&self, name: &str) -> PathBuf {
+        self.config_root().join(DOCS_DIR).join(name)
+    }
+
+    /// Where dropper records what it has installed
+    pub fn state_file(&self) -> PathBuf {
+        self.config_root().join(STATE_FILE)
+    }
+
+    /// Where pages from the plugin website are cached
+    pub fn http_cache_dir(&self) -> PathBuf {
+        self.config_root().join(HTTP_CACHE_DIR)
+    }
+
+    /// Where the package names that searches turn up are kept, for shell completion
+    pub fn names_file(&self) -> PathBuf {
+        self.config_root().join(NAMES_FILE)
+    }
+
+    /// Where packages are built from source
+    pub fn build_dir(&self) -> PathBuf {
+        self.config_root().join(BUILD_DIR)
+    }
+
+    /// Where a bundle is unpacked while it's being installed
+    pub fn bundle_dir(&self) -> PathBuf {
+        self.config_root().join(BUNDLE_DIR)
+    }
+
+    /// Where headers and cookies for hosts that need a login are kept, apart from the config so
+    /// that the config can be shared
+    pub fn credentials_file(&self) -> PathBuf {
+        self.config_root().join(CREDENTIALS_FILE)
+    }
+
+    /// Where the variables for templates are kept, apart from the config so that secrets can be
+    /// kept out of git
+    pub fn vars_file(&self) -> PathBuf {
+        self.project_dir.join(VARS_FILE)
+    }
+
+    /// Where dropper records every change it has made
+    pub fn history_file(&self) -> PathBuf {
+        self.config_root().join(HISTORY_FILE)
+    }
+
+    pub fn pkg_list(&self) -> PathBuf {
+        self.project_dir.join(PKG_LIST_FILE)
+    }
+
+    pub fn lockfile(&self) -> PathBuf {
+        self.project_dir.join(LOCK_FILE)
+    }
+
+    /// Where `dropper plan` writes its plan by default
+    pub fn plan_file(&self) -> PathBuf {
+        self.project_dir.join(PLAN_FILE)
+    }
+
+    /// Where downloads are staged before being moved into the plugin directory
+    pub fn staging_dir(&self) -> PathBuf {
+        self.plugin_dir.join(STAGING_DIR)
+    }
+
+    /// Where `dropper checksum write` lists the digests of the JARs dropper manages, unless it's
+    /// told otherwise
+    pub fn checksums_file(&self) -> PathBuf {
+        self.plugin_dir.join(checksum::SUMS_FILE)
+    }
+
+    /// Where the server writes a report each time it crashes
+    pub fn crash_reports_dir(&self) -> PathBuf {
+        self.project_dir.join(CRASH_REPORTS_DIR)
+    }
+
+}
+
+/// By default, the server is the current directory
+impl Default for ProjectPaths {
+    fn default() -> Self {
+        ProjectPaths::new(PathBuf::from("."), None)
+    }
+}
+
+/// Whether a package has a build for some server version
+#[derive(Debug)]
+pub enum Compatibility {
+    /// There is a build, and this is the newest version the pkg.yml allows
+    Compatible(String),
+    /// There is no build that the pkg.yml allows
+    Incompatible,
+    /// The plugin website couldn't tell us. Takes the reason as a param.
+    Unknown(String),
+}
+
+/// A compatibility check that fails operations in strict mode, and that `--force` can override
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Check {
+    /// The plugin targets a newer server API than the server provides
+    ApiVersion,
+    /// The plugin doesn't say which server versions it supports
+    GameVersion,
+    /// The package has no build for the server version, so one for an older version is used
+    Fallback,
+}
+
+impl Check {
+    pub const ALL: [Check; 3] = [Check::ApiVersion, Check::GameVersion, Check::Fallback];
+
+    /// The name the check goes by on the command line and in the history
+    pub fn name(&self) -> &'static str {
+        match self {
+            Check::ApiVersion => "api-version",
+            Check::GameVersion => "game-version",
+            Check::Fallback => "fallback",
+        }
+    }
+
+    /// Finds a check by its name
+    pub fn from_name(name: &str) -> Option<Check> {
+        Check::ALL.iter().cloned().find(|check| check.name() == name)
+    }
+}
+
+/// Everything that doesn't line up between the pkg.yml, the lockfile, the state file, and the
+/// plugin directory, as found by `PackageBackend::pkg_status`
+#[derive(Debug, Default)]
+pub struct StatusReport {
+    /// Packages in the pkg.yml that were never installed
+    pub not_installed: Vec<String>,
+    /// Locked packages whose JAR isn't in the plugin directory, as (name, file)
+    pub missing: Vec<(String, String)>,
+    /// Packages managed by hand whose JAR isn't in the plugin directory, as (name, file)
+    pub manual_missing: Vec<(String, String)>,
+    /// JARs in the plugin directory that dropper didn't install, that aren't managed by hand, and
+    /// that aren't ignored
+    pub unmanaged: Vec<String>,
+    /// Packages installed at a version something else disagrees with, as (name, explanation)
+    pub mismatched: Vec<(String, String)>,
+    /// Downloads waiting on the JARs they replace to stop being used
+    pub staged: Vec<String>,
+}
+
+impl StatusReport {
+    /// Whether everything lines up
+    pub fn is_clean(&self) -> bool {
+        self.not_installed.is_empty()
+            && self.missing.is_empty()
+            && self.manual_missing.is_empty()
+            && self.unmanaged.is_empty()
+            && self.mismatched.is_empty()
+            && self.staged.is_empty()
+    }
+}
+
+/// How the locked JARs compare to the lockfile, and the JARs managed by hand to the pkg.yml, as
+/// found by `PackageBackend::pkg_verify`
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Locked JARs that match the lockfile, as (name, file)
+    pub verified: Vec<(String, String)>,
+    /// Locked JARs that aren't in the plugin directory, as (name, file)
+    pub missing: Vec<(String, String)>,
+    /// Locked JARs that don't match the lockfile's digests, as (name, file)
+    pub modified: Vec<(String, String)>,
+    /// With a deep check, locked JARs whose plugin.yml doesn't say they're the locked version,
+    /// as (name, explanation)
+    pub mismatched: Vec<(String, String)>,
+    /// JARs managed by hand that match the digests the pkg.yml gives, or that are there, if it
+    /// doesn't give any, as (name, file)
+    pub manual_verified: Vec<(String, String)>,
+    /// JARs managed by hand that aren't in the plugin directory, as (name, file)
+    pub manual_missing: Vec<(String, String)>,
+    /// JARs managed by hand that don't match the digests the pkg.yml gives, or whose plugin.yml
+    /// doesn't say they're the version it gives with a deep check, as (name, explanation)
+    pub manual_mismatched: Vec<(String, String)>,
+}
+
+impl VerifyReport {
+    /// Whether every locked JAR matches the lockfile, and every JAR managed by hand its entry
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty()
+            && self.modified.is_empty()
+            && self.mismatched.is_empty()
+            && self.manual_missing.is_empty()
+            && self.manual_mismatched.is_empty()
+    }
+}
+
+/// A JAR in the plugin directory that dropper doesn't manage, as found by
+/// `PackageBackend::pkg_unmanaged`
+#[derive(Debug)]
+pub struct UnmanagedJar {
+    /// The JAR, relative to the plugin directory
+    pub file: String,
+    /// What the JAR's plugin.yml says it is, if it has one that can be read
+    pub description: Option<PluginDescription>,
+    /// The projects the plugin is most likely published as, at most one per source, as the
+    /// source and the project's listing there
+    pub guesses: Vec<(String, PluginListing)>,
+}
+
+/// What `PackageBackend::pkg_sync` changed in the plugin directory. Each list holds JAR file
+/// names.
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Locked JARs that were missing, and were downloaded
+    pub installed: Vec<String>,
+    /// Locked JARs that were downloaded again, because another version of the package was
+    /// there instead, or the JAR didn't match the lockfile's digest
+    pub replaced: Vec<String>,
+    /// JARs that the lockfile doesn't have, and that aren't ignored
+    pub removed: Vec<String>,
+}
+
+impl SyncReport {
+    /// Whether the plugin directory already matched the lockfile
+    pub fn is_empty(&self) -> bool {
+        self.installed.is_empty() && self.replaced.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// How the plugin directory compares to a SHA256SUMS file, as found by
+/// `PackageBackend::pkg_check_checksums`. Each list holds JAR file names, relative to the plugin
+/// directory.
+#[derive(Debug, Default)]
+pub struct ChecksumReport {
+    /// JARs that match their digests
+    pub matched: Vec<String>,
+    /// JARs that don't match their digests
+    pub failed: Vec<String>,
+    /// JARs that are listed, but aren't in the plugin directory
+    pub missing: Vec<String>,
+    /// JARs dropper manages that aren't listed
+    pub unlisted: Vec<String>,
+}
+
+impl ChecksumReport {
+    /// Whether every JAR matches, and every JAR dropper manages is listed
+    pub fn is_clean(&self) -> bool {
+        self.failed.is_empty() && self.missing.is_empty() && self.unlisted.is_empty()
+    }
+}
+
+/// What changed in the plugin directory without dropper changing it, as found by
+/// `PackageBackend::pkg_drift`
+#[derive(Debug, Default)]
+pub struct DriftReport {
+    /// JARs that turned up, that the lockfile doesn't have, and that aren't ignored
+    pub added: Vec<String>,
+    /// Locked JARs that disappeared, as (name, file)
+    pub removed: Vec<(String, String)>,
+}
+
+impl DriftReport {
+    /// Whether nothing drifted
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// What moving the server to another version would take, as found by
+/// `PackageBackend::pkg_migration`
+#[derive(Debug, Default)]
+pub struct Migration {
+    /// The server version being moved to
+    pub server_version: String,
+    /// The packages in the pkg.yml that have a build for the new version, with the version that
+    /// would be installed, as (name, version)
+    pub packages: Vec<(String, String)>,
+    /// Packages whose pkg.yml constraint rules out every build for the new version, but that
+    /// have one otherwise, as (name, old constraint, new constraint)
+    pub constraints: Vec<(String, String, String)>,
+    /// Packages with no build for the new version at all, as (name, why)
+    pub blockers: Vec<(String, String)>,
+}
+
+impl Migration {
+    /// Whether some package would stop the server from being moved
+    pub fn is_blocked(&self) -> bool {
+        !self.blockers.is_empty()
+    }
+}
+
+/// The rendered configs that were changed on the server since dropper rendered them, as found by
+/// `PackageBackend::pkg_config_drift`. Each list holds (package, file) pairs, with files relative
+/// to the plugin directory.
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    /// Configs whose contents aren't what dropper rendered anymore
+    pub modified: Vec<(String, String)>,
+    /// Configs that aren't there anymore
+    pub deleted: Vec<(String, String)>,
+}
+
+impl ConfigReport {
+    /// Whether every rendered config is as dropper left it
+    pub fn is_clean(&self) -> bool {
+        self.modified.is_empty() && self.deleted.is_empty()
+    }
+}
+
+/// How the installed plugins fared on a test server, as found by `PackageBackend::pkg_test`
+#[derive(Debug)]
+pub struct TestReport {
+    pub run: TestRun,
+    /// What failed to load or enable, as (name, why). Packages go by their package name, and
+    /// plugins dropper didn't install by their plugin name or their JAR.
+    pub failed: Vec<(String, String)>,
+    /// Installed packages whose plugin the server didn't enable, without saying why
+    pub not_enabled: Vec<String>,
+}
+
+impl TestReport {
+    /// Whether the server started with every plugin enabled
+    pub fn is_clean(&self) -> bool {
+        self.run.outcome == Outcome::Started
+            && self.failed.is_empty()
+            && self.not_enabled.is_empty()
+    }
+}
+
+/// What updating a batch of packages did, as found by `PackageBackend::pkg_update_batch`
+#[derive(Debug, Default)]
+pub struct BatchUpdate {
+    /// The packages that were updated, as (name, old version, new version)
+    pub updated: Vec<(String, String, String)>,
+    /// The packages that were already at the newest version
+    pub up_to_date: Vec<String>,
+    /// The packages that couldn't be updated, as (name, why)
+    pub failed: Vec<(String, String)>,
+    /// Whether a package failing rolled back the rest of the batch, in which case `updated`
+    /// lists what was undone
+    pub rolled_back: bool,
+}
+
+/// An update that's waiting for a package, as found by `PackageBackend::pkg_upgrades`
+#[derive(Debug)]
+pub struct Upgrade {
+    pub name: String,
+    /// The installed version
+    pub from: String,
+    /// The version the package would be updated to
+    pub to: String,
+    /// The channel the new version was published to
+    pub channel: Channel,
+    /// Where the new version's changes are written up, if its source says
+    pub changelog: Option<String>,
+}
+
+/// A version of a package, as listed by `PackageBackend::pkg_versions`
+#[derive(Debug)]
+pub struct VersionListing {
+    pub file: PluginFile,
+    /// Whether the file is for the server version, or doesn't say
+    pub compatible: bool,
+    /// Whether the pkg.yml's constraints on the package allow the version
+    pub allowed: bool,
+    /// Why the version is on the blocklist, if it is
+    pub blocked: Option<String>,
+    /// Whether the version is the installed one
+    pub installed: bool,
+}
+
+/// A problem a server's log reports with a plugin, as found by `PackageBackend::pkg_analyze`.
+/// Problems that are reported more than once are only diagnosed once.
+#[derive(Debug)]
+pub struct Diagnosis {
+    /// The package the plugin is from, if dropper manages it
+    pub package: Option<String>,
+    /// The plugin, or its JAR, the log blames
+    pub culprit: String,
+    /// What went wrong, like "couldn't be enabled"
+    pub what: String,
+    /// The exception that explained it, if the log has one
+    pub explanation: Option<String>,
+    /// How many times the log reports it
+    pub count: usize,
+    /// What could be done about it, like updating the package
+    pub suggestions: Vec<String>,
+}
+
+/// What a crash report says about the installed packages, as found by
+/// `PackageBackend::pkg_crash`
+#[derive(Debug)]
+pub struct CrashReport {
+    /// What the report says happened
+    pub description: String,
+    /// When the server crashed, in seconds since the Unix epoch
+    pub time: u64,
+    /// The packages whose code is in the crash's stacks, most likely first
+    pub suspects: Vec<Suspect>,
+    /// What dropper changed shortly before the crash, for packages that aren't suspects
+    pub changes: Vec<Event>,
+}
+
+/// An installed package whose code is in a crash's stacks
+#[derive(Debug)]
+pub struct Suspect {
+    pub package: String,
+    /// The plugin the package's code goes by
+    pub plugin: String,
+    /// The first of the crash's frames that's in the package's code
+    pub frame: String,
+    /// How many of the crash's frames are in the package's code
+    pub frames: usize,
+    /// What dropper changed about the package shortly before the crash, oldest first
+    pub changes: Vec<Event>,
+}
+
+/// Which packages the installed plugins are from, so that problems a server reports with them
+/// can be blamed on the package
+struct PluginOwners {
+    by_file: BTreeMap<String, String>,
+    by_plugin: BTreeMap<String, String>,
+    // The Java package of each plugin's main class, for stack traces that don't name a plugin
+    by_class: Vec<(String, String)>,
+    // The plugin names the packages' plugins go by, by package
+    plugins: BTreeMap<String, String>,
+}
+
+impl PluginOwners {
+    /// The plugin, or JAR, a problem is with, and the package it's from if dropper manages it
+    fn blame(&self, problem: &Problem) -> Option<(String, Option<String>)> {
+        let (culprit, package) = match (&problem.file, &problem.plugin) {
+            (Some(file), _) => (file.clone(), self.by_file.get(file)),
+            (None, Some(plugin)) => (plugin.clone(), self.by_plugin.get(&plugin.to_lowercase())),
+            (None, None) => {
+                let package = problem.frames.iter().find_map(|frame| self.owner(frame))?;
+                (self.plugins.get(package).unwrap_or(package).clone(), Some(package))
+            }
+        };
+        Some((culprit, package.cloned()))
+    }
+
+    /// The package a class or method is from, if it's in a package's plugin
+    fn owner(&self, frame: &str) -> Option<&String> {
+        self.by_class
+            .iter()
+            .find(|(prefix, _)| frame.starts_with(prefix.as_str()))
+            .map(|(_, package)| package)
+    }
+}
+
+/// What installing a package into a sandbox brought with it, as found by
+/// `PackageBackend::pkg_try`
+#[derive(Debug)]
+pub struct Trial {
+    /// The package that was tried
+    pub name: String,
+    pub version: String,
+    /// Every package that was installed into the sandbox, starting with the one that was tried
+    /// and followed by its dependencies
+    pub packages: Vec<TrialPackage>,
+    /// What installing into the sandbox warned about, like compatibility problems
+    pub warnings: Vec<String>,
+}
+
+/// A package installed into a sandbox by `PackageBackend::pkg_try`
+#[derive(Debug)]
+pub struct TrialPackage {
+    pub name: String,
+    pub version: String,
+    pub reason: InstallReason,
+    /// Where the package was installed in the sandbox
+    pub file: PathBuf,
+    /// The package's plugin.yml, unless it's an addon or doesn't have one
+    pub description: Option<PluginDescription>,
+    /// The files in the package's JAR
+    pub contents: Vec<String>,
+    /// The version the server already has installed, if it has the package
+    pub installed: Option<String>,
+}
+
+/// A package dropper has installed, and why, as found by `PackageBackend::pkg_installed`
+#[derive(Debug)]
+pub struct InstalledListing {
+    pub name: String,
+    pub version: String,
+    /// Whether the user asked for the package, or it was pulled in by another one
+    pub reason: InstallReason,
+    /// Whether the package is a dependency that nothing installed explicitly needs anymore, which
+    /// `pkg_autoremove` would remove
+    pub orphaned: bool,
+}
+
+/// Why a package is on the server, as found by `PackageBackend::pkg_why`
+#[derive(Debug)]
+pub struct Explanation {
+    pub name: String,
+    /// The locked version, if the package is installed
+    pub version: Option<String>,
+    /// Why the package was installed, if dropper installed it
+    pub reason: Option<InstallReason>,
+    /// What the pkg.yml requires of the package, from its own entry and from other entries'
+    /// `requires`
+    pub requirements: Vec<Requirement>,
+    /// The installed packages that need the package, each as a chain from an explicitly
+    /// installed package down to the one that `depend`s on it directly
+    pub chains: Vec<Vec<String>>,
+    /// The change from the history that put the installed version on the server, if it's there
+    pub installed_by: Option<Event>,
+}
+
+/// How old an installed package is, next to the newest version the pkg.yml allows, as found by
+/// `PackageBackend::pkg_outdated`. Times are in seconds since the Unix epoch.
+#[derive(Debug)]
+pub struct PackageAge {
+    pub name: String,
+    pub installed: String,
+    /// When the installed version was installed
+    pub installed_at: Option<u64>,
+    /// When the installed version was released
+    pub installed_released: Option<u64>,
+    /// The newest version allowed, or `None` if it couldn't be found out
+    pub newest: Option<String>,
+    /// When the newest version was released
+    pub newest_released: Option<u64>,
+    /// Why the installed version is on the blocklist, if it is
+    pub blocked: Option<String>,
+}
+
+impl PackageAge {
+    /// Whether there's a newer version to update to
+    pub fn is_outdated(&self) -> bool {
+        match &self.newest {
+            Some(newest) => *newest != self.installed,
+            None => false,
+        }
+    }
+}
+
+/// What `PackageBackend::download` put into the plugin directory
+#[derive(Debug, Default)]
+struct Downloaded {
+    /// The algorithms the download was checked with
+    algorithms: Vec<String>,
+    /// Where the JARs that were installed are inside the distribution they came from, if they
+    /// were picked out of it with `jars` patterns
+    jars: Vec<String>,
+    /// The files put into the plugin directory besides the JARs, relative to it
+    files: Vec<String>,
+}
+
+/// What `PackageBackend::fetch_partial` downloads with
+struct Downloader<'d> {
+    client: &'d Client,
+    credentials: &'d Credentials,
+    /// The fastest to download at, in bytes per second
+    max_download_rate: Option<u64>,
+    cancel: &'d CancelToken,
+}
+
 /// Struct to hold the configuration information for the backend
 pub struct PackageBackend<'a> {
     pub plugin_website: String,
     pub package_parser: &'a PluginFetchable,
     pub server_version: String,
+    pub paths: ProjectPaths,
+    /// Shared with the package parser, so that downloads reuse its connections
+    pub client: Client,
+    /// In strict mode, compatibility problems that would otherwise be warnings are errors
+    pub strict: bool,
+    /// The strict mode checks to let packages through anyway. Overrides are written to the
+    /// history.
+    pub forced: Vec<Check>,
+    /// Whether packages without a build for the server version may use a build for an older one
+    pub compat_fallback: bool,
+    /// Whether what packages' sources say about them is saved when they're installed, for
+    /// reading offline
+    pub save_docs: bool,
+    /// Patterns for JARs in the plugin directory that are managed by hand, and aren't drift
+    pub ignore: Vec<Pattern>,
+    /// Commands to run before and after packages change
+    pub hooks: Hooks,
+    /// How to restart the server once packages have been updated, if it should be
+    pub restart: Option<Restart>,
+    /// How to start a throwaway copy of the server to check that plugins load, if it can be
+    pub test_server: Option<TestServer>,
+    /// Values to fill in plugin config templates with
+    pub variables: BTreeMap<String, String>,
+    /// The fastest to download at, in bytes per second, or `None` for as fast as possible
+    pub max_download_rate: Option<u64>,
+    /// Headers and cookies for hosts that need a login
+    pub credentials: Credentials,
+    /// Versions that are never installed, unless `allow_blocked` is set
+    pub blocklist: Blocklist,
+    pub allow_blocked: bool,
+    /// Whether installing an older version than the installed one is allowed
+    pub allow_downgrade: bool,
+    /// Whether installed JARs are copies, or links into the content store
+    pub install_strategy: InstallStrategy,
+    /// How big the caches may get altogether, in bytes, before the oldest of what's in them is
+    /// pruned after a change, or `None` to let them grow
+    pub cache_max_size: Option<u64>,
+    /// The environment whose pkg.yml overlay applies, if any
+    pub env: Option<String>,
+    /// The pkg.yml entries that packages without one are installed with, keyed by package name:
+    /// the built-in presets, and the config's
+    pub presets: BTreeMap<String, Yaml>,
+    /// Cancels the operation in progress, which is then rolled back. It should be the same token
+    /// the package parsers were built with, so that their requests are cancelled too.
+    pub cancel: CancelToken,
+    warnings: RefCell<Vec<String>>,
+    /// The checks overridden since the last change was written to the history, as (package, check)
+    overrides: RefCell<Vec<(String, Check)>>,
+    /// The journal of the operation in progress, if there is one
+    journal: RefCell<Option<Journal>>,
+    /// The files of packages fetched ahead of time by `prefetch`
+    prefetched: RefCell<BTreeMap<String, Vec<PluginFile>>>,
+    /// The package parser for each source packages can name, keyed by source
+    sources: BTreeMap<String, &'a PluginFetchable>,
+    /// The sources of packages that don't come from the plugin website, keyed by package name
+    package_sources: RefCell<BTreeMap<String, String>>,
+    /// The repositories of packages the pkg.yml builds from source, keyed by package name
+    git_sources: RefCell<BTreeMap<String, GitSource>>,
+    /// The artifacts of packages the pkg.yml fetches from Maven repositories, keyed by package
+    /// name
+    maven_sources: RefCell<BTreeMap<String, MavenSource>>,
+    /// The URLs of packages the pkg.yml downloads straight from one, keyed by package name
+    direct_sources: RefCell<BTreeMap<String, DirectSource>>,
+    /// The directory listings of packages the pkg.yml fetches from one, keyed by package name
+    listing_sources: RefCell<BTreeMap<String, ListingSource>>,
+    /// The packages the pkg.yml fetches from self-hosted repositories, keyed by package name
+    repo_sources: RefCell<BTreeMap<String, RepoSource>>,
+    /// The releases of GitHub repositories packages are fetched from, keyed by package name
+    github_sources: RefCell<BTreeMap<String, GithubSource>>,
+    /// The Hangar projects packages are fetched from, keyed by package name
+    hangar_sources: RefCell<BTreeMap<String, HangarSource>>,
+    /// The download API projects packages are fetched from, keyed by package name
+    downloads_sources: RefCell<BTreeMap<String, DownloadsSource>>,
+    /// The packages without a pkg.yml entry that are installed from a preset's source
+    preset_packages: RefCell<BTreeSet<String>>,
+    /// The packages the pkg.yml says are managed by hand, keyed by package name
+    manual_packages: RefCell<BTreeMap<String, ManualPackage>>,
+    /// The indexes of self-hosted repositories fetched so far, keyed by URL, since many packages
+    /// can come from the same one
+    repo_indexes: RefCell<BTreeMap<String, Index>>,
+    /// The files of the bundle being installed, as `file://` links keyed by the links they were
+    /// downloaded from, which are fetched in their place
+    bundled: RefCell<BTreeMap<String, String>>,
 }
 
 impl<'a> PackageBackend<'a> {
     /// Creates a new backend instance. This is performed on each command, or on each app startup.
-    /// Takes in a package parser to use for feeding the backend information.
+    /// Takes in a package parser to use for feeding the backend information, along with the HTTP
+    /// client to download packages with.
     ///
     /// # Errors
     /// Any of these errors means that some part of the configuration is missing, and as such,
@@ -84,104 +1123,532 @@ impl<'a> PackageBackend<'a> {
     /// action, as `PackageBackend::init` is destructive, and needs confirmation.
     /// * [`ErrorKind::YamlInvalid`](enum.ErrorKind.html#variant.YamlInvalid) - one of the YML files is invalid
     /// * `std::io::ErrorKind::*` - an IO error occured
-    pub fn new(package_parser: &'a PluginFetchable) -> Result<PackageBackend<'a>, Box<Error>> {
+    pub fn new(
+        package_parser: &'a PluginFetchable,
+        paths: ProjectPaths,
+        client: Client,
+    ) -> Result<PackageBackend<'a>, Box<Error>> {
         // Check if the config environment is valid
-        PackageBackend::validate()?;
+        PackageBackend::validate(&paths)?;
 
         // Read the config - we can expect it to exist because validate checks for
         // its existance.
-        let config_yml = PackageBackend::read_yaml_file(CONFIG_PATH)?.unwrap();
+        let config_yml = PackageBackend::read_yaml_file(&paths.config_file())?.unwrap();
+
+        let mut ignore = Vec::new();
+        if let Yaml::Array(patterns) = &config_yml[0]["ignore"] {
+            for pattern in patterns {
+                match pattern.as_str().map(Pattern::new) {
+                    Some(Ok(pattern)) => ignore.push(pattern),
+                    _ => return Err(Box::new(ErrorKind::ConfigInvalid("ignore".to_string()))),
+                }
+            }
+        }
+
+        let max_download_rate = match &config_yml[0]["max_download_rate"] {
+            Yaml::BadValue | Yaml::Null => None,
+            rate => match Self::yaml_to_rate(rate) {
+                Some(rate) => Some(rate),
+                None => {
+                    return Err(Box::new(ErrorKind::ConfigInvalid(
+                        "max_download_rate".to_string(),
+                    )))
+                }
+            },
+        };
+
+        let cache_max_size = match &config_yml[0]["cache_max_size"] {
+            Yaml::BadValue | Yaml::Null => None,
+            Yaml::String(size) if cache::parse_size(size).is_some() => cache::parse_size(size),
+            Yaml::Integer(size) if *size > 0 => Some(*size as u64),
+            _ => {
+                return Err(Box::new(ErrorKind::ConfigInvalid(
+                    "cache_max_size".to_string(),
+                )))
+            }
+        };
+
+        let plugin_website = config_yml[0]["plugin_website"]
+            .clone()
+            .into_string()
+            .unwrap();
+        let mut sources = BTreeMap::new();
+        sources.insert(plugin_website.clone(), package_parser);
+
+        // The vars file is for secrets, so it wins over the config
+        let mut variables = template::variables_from_yaml(&config_yml[0]["variables"]);
+        let mut warnings = Vec::new();
+        if let Some(vars_yml) = PackageBackend::read_yaml_file(&paths.vars_file())? {
+            if let Some(doc) = vars_yml.first() {
+                let (vars, unset) = template::variables_from_vars_file(doc);
+                variables.extend(vars);
+                for (variable, env) in unset {
+                    warnings.push(format!(
+                        "{} reads '{}' from the environment variable {}, which isn't set",
+                        VARS_FILE, variable, env
+                    ));
+                }
+            }
+        }
+
+        // The state file is dropper's own, so it's kept in the newest format
+        if let Some(version) = State::upgrade(&paths.state_file())? {
+            warnings.push(format!(
+                "upgraded the state file from format {}; it was kept as it was in {}",
+                version,
+                format::backup_path(&paths.state_file(), version).display()
+            ));
+        }
+
+        // Packages keep the source they were installed from
+        let package_sources = Lockfile::load(&paths.lockfile())?
+            .packages
+            .into_iter()
+            .filter_map(|(name, locked)| locked.source.map(|source| (name, source)))
+            .collect();
 
         Ok(PackageBackend {
-            plugin_website: config_yml[0]["plugin_website"]
-                .clone()
-                .into_string()
-                .unwrap(),
+            plugin_website: plugin_website,
             package_parser: package_parser,
             server_version: config_yml[0]["server_version"]
                 .clone()
                 .into_string()
                 .unwrap(),
+            strict: config_yml[0]["strict"].as_bool().unwrap_or(false),
+            forced: Vec::new(),
+            compat_fallback: config_yml[0]["compat_fallback"].as_bool().unwrap_or(false),
+            save_docs: config_yml[0]["save_docs"].as_bool().unwrap_or(false),
+            ignore: ignore,
+            hooks: Hooks::from_yaml(&config_yml[0]["hooks"])?,
+            restart: Restart::from_yaml(&config_yml[0]["restart"])?,
+            test_server: TestServer::from_yaml(&config_yml[0]["test_server"])?,
+            variables: variables,
+            max_download_rate: max_download_rate,
+            credentials: Self::configured_credentials(&paths)?,
+            blocklist: Blocklist::from_yaml(&config_yml[0]["blocklist"])?,
+            allow_blocked: false,
+            allow_downgrade: false,
+            install_strategy: InstallStrategy::from_yaml(&config_yml[0]["install_strategy"])?,
+            cache_max_size: cache_max_size,
+            env: None,
+            presets: Self::yaml_to_presets(&config_yml[0]["presets"])?,
+            cancel: CancelToken::new(),
+            paths: paths,
+            client: client,
+            warnings: RefCell::new(warnings),
+            overrides: RefCell::new(Vec::new()),
+            journal: RefCell::new(None),
+            prefetched: RefCell::new(BTreeMap::new()),
+            sources: sources,
+            package_sources: RefCell::new(package_sources),
+            git_sources: RefCell::new(BTreeMap::new()),
+            maven_sources: RefCell::new(BTreeMap::new()),
+            direct_sources: RefCell::new(BTreeMap::new()),
+            listing_sources: RefCell::new(BTreeMap::new()),
+            repo_sources: RefCell::new(BTreeMap::new()),
+            github_sources: RefCell::new(BTreeMap::new()),
+            hangar_sources: RefCell::new(BTreeMap::new()),
+            downloads_sources: RefCell::new(BTreeMap::new()),
+            preset_packages: RefCell::new(BTreeSet::new()),
+            manual_packages: RefCell::new(BTreeMap::new()),
+            repo_indexes: RefCell::new(BTreeMap::new()),
+            bundled: RefCell::new(BTreeMap::new()),
         })
     }
 
-    /// The initalization function for the backend. This is performed only on the first run, or if the .dropper folder is ever deleted
-    ///
-    /// This creates a folder at the server root caled .dropper, and in it, places a default config file
-    /// called `config.yml`, as well as a SQLite DB for keeping track of package installs.
-    ///
-    /// It also dumps a blank `pkg.yml` to the server root directory if it does not exist yet.
-    ///
-    /// # Warning
-    /// This command is by design destructive! It will kill the config folder, along with its files,
-    /// so it is advised to prompt the user before running this! The interface should check to see if
-    /// a non-empty `.dropper` exists before running this, prompting the user if so.
+    /// The source a package comes from: the one its name gave, or the plugin website
+    fn source_of(&self, name: &str) -> String {
+        match self.package_sources.borrow().get(name) {
+            Some(source) => source.clone(),
+            None => self.plugin_website.clone(),
+        }
+    }
+
+    /// The package parser for a source
     ///
     /// # Errors
-    /// * `std::io::ErrorKind::*` - an IO error occured
-    pub fn init() -> Result<(), Box<Error>> {
-        // Create the directory for the config files
-        if Path::new(CONFIG_ROOT).exists() {
-            fs::remove_dir_all(CONFIG_ROOT)?;
+    /// * [`ErrorKind::SourceUnknown`](enum.ErrorKind.html#variant.SourceUnknown) - there's no parser for the source
+    fn source_parser(&self, source: &str) -> Result<&'a PluginFetchable, Box<Error>> {
+        match self.sources.get(source) {
+            Some(parser) => Ok(*parser),
+            None => {
+                let names = self.sources.keys().map(|s| s.as_str()).collect::<Vec<&str>>();
+                Err(Box::new(ErrorKind::SourceUnknown(
+                    source.to_string(),
+                    names.iter().map(|s| s.to_string()).collect(),
+                    Self::closest_match(source, &names).map(|s| s.to_string()),
+                )))
+            }
         }
-        fs::create_dir(CONFIG_ROOT)?;
+    }
 
-        // Dump a default config file in there
-        let mut config = File::create(CONFIG_PATH)?;
-        config.write_all(text_assets::CONFIG_YAML_DEFAULT);
+    /// The package parser for the source a package comes from
+    fn parser_of(&self, name: &str) -> Result<&'a PluginFetchable, Box<Error>> {
+        self.source_parser(&self.source_of(name))
+    }
 
-        // Create a pkg.yml if one does not exist yet
-        let pkg_list = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(PKG_LIST_PATH)?;
+    /// Whether the pkg.yml says where to find a package itself, rather than it coming from a
+    /// package parser
+    fn has_own_source(&self, name: &str) -> bool {
+        self.git_sources.borrow().contains_key(name)
+            || self.maven_sources.borrow().contains_key(name)
+            || self.direct_sources.borrow().contains_key(name)
+            || self.listing_sources.borrow().contains_key(name)
+            || self.repo_sources.borrow().contains_key(name)
+            || self.github_sources.borrow().contains_key(name)
+            || self.hangar_sources.borrow().contains_key(name)
+            || self.downloads_sources.borrow().contains_key(name)
+    }
 
-        Ok(())
+    /// Forgets where a package's pkg.yml entry, or its preset, said to find it
+    fn forget_own_source(&self, name: &str) {
+        self.git_sources.borrow_mut().remove(name);
+        self.maven_sources.borrow_mut().remove(name);
+        self.direct_sources.borrow_mut().remove(name);
+        self.listing_sources.borrow_mut().remove(name);
+        self.repo_sources.borrow_mut().remove(name);
+        self.github_sources.borrow_mut().remove(name);
+        self.hangar_sources.borrow_mut().remove(name);
+        self.downloads_sources.borrow_mut().remove(name);
+        self.preset_packages.borrow_mut().remove(name);
     }
 
-    /// Ensures that the config files both exist and can be read
-    ///
-    /// # Behavior
-    /// The only error this function can throw is if it detects that the config/pkg files are corrupt or
-    /// malformed. The interface should handle what happens at this point (e.g. display the YML validation
-    /// output, or prompt them if they wish to re-initialize)
+    /// Remembers where a package comes from, for everything that looks it up afterwards. Packages
+    /// named without a source come from the plugin website.
+    fn set_source(&self, name: &str, source: Option<&str>) {
+        let mut package_sources = self.package_sources.borrow_mut();
+        match source {
+            Some(source) if source != self.plugin_website => {
+                package_sources.insert(name.to_string(), source.to_string())
+            }
+            _ => package_sources.remove(name),
+        };
+    }
+
+    /// Reads the presets out of the config, over the built-in ones. A preset is a pkg.yml entry
+    /// like any other, and `false` turns a built-in one off.
     ///
     /// # Errors
-    /// * [`ErrorKind::YamlInvalid`](enum.ErrorKind.html#variant.YamlInvalid) - one of the YML files is invalid
-    /// * `std::io::ErrorKind::*` - an IO error occured
-    pub fn validate() -> Result<(), Box<Error>> {
-        let config = match PackageBackend::read_yaml_file(CONFIG_PATH)? {
-            Some(c) => c,
-            None => return Err(Box::new(ErrorKind::ConfigMissing)),
+    /// * [`ErrorKind::ConfigInvalid`](enum.ErrorKind.html#variant.ConfigInvalid) - the presets aren't a map of package names to entries
+    fn yaml_to_presets(yaml: &Yaml) -> Result<BTreeMap<String, Yaml>, Box<Error>> {
+        let mut presets = presets::PRESETS
+            .iter()
+            .map(|preset| {
+                let entry = YamlLoader::load_from_str(preset.entry).unwrap().remove(0);
+                (preset.name.to_string(), entry)
+            })
+            .collect::<BTreeMap<String, Yaml>>();
+        let invalid = || ErrorKind::ConfigInvalid("presets".to_string());
+        let configured = match yaml {
+            Yaml::BadValue | Yaml::Null => return Ok(presets),
+            Yaml::Hash(configured) => configured,
+            _ => return Err(Box::new(invalid())),
         };
-        // Read all of the fields we need, and ensure they can be parsed into the
-        // right type.
-        let config_doc = &config[0];
 
-        match config_doc["server_version"].clone().into_string() {
-            Some(_) => {}
-            None => {
-                return Err(Box::new(ErrorKind::ConfigInvalid(
-                    "server_version".to_string(),
-                )))
-            }
+        for (name, entry) in configured.iter() {
+            let name = name.as_str().ok_or_else(invalid)?.to_lowercase();
+            match entry {
+                Yaml::Boolean(false) => presets.remove(&name),
+                Yaml::Hash(_) => presets.insert(name, entry.clone()),
+                _ => return Err(Box::new(invalid())),
+            };
         }
+        Ok(presets)
+    }
 
-        match config_doc["plugin_website"].clone().into_string() {
-            Some(_) => {}
-            None => {
-                return Err(Box::new(ErrorKind::ConfigInvalid(
-                    "plugin_website".to_string(),
+    /// Reads a download rate out of the config, which is written like `500K`, or in bytes
+    fn yaml_to_rate(yaml: &Yaml) -> Option<u64> {
+        match yaml {
+            Yaml::Integer(i) if *i > 0 => Some(*i as u64),
+            Yaml::String(s) => http::parse_rate(s),
+            _ => None,
+        }
+    }
+
+    /// Reads the server version out of the config. Package parsers are built for a specific
+    /// server version, so the frontend needs this before it can construct a backend.
+    ///
+    /// # Errors
+    /// The same as [`PackageBackend::validate`](#method.validate)
+    pub fn configured_server_version(paths: &ProjectPaths) -> Result<String, Box<Error>> {
+        PackageBackend::validate(paths)?;
+        let config_yml = PackageBackend::read_yaml_file(&paths.config_file())?.unwrap();
+
+        Ok(config_yml[0]["server_version"]
+            .clone()
+            .into_string()
+            .unwrap())
+    }
+
+    /// Reads the headers and cookies to send to hosts that need a login, from the config's `auth`
+    /// key and the credentials file (see [`http::Credentials`](../http/struct.Credentials.html)),
+    /// and the keys for S3 buckets, from the config's `s3` key and the environment. The
+    /// credentials file wins where both have credentials for a host. Neither has to exist, so
+    /// that lockfiles can be deployed to servers without a config.
+    ///
+    /// # Errors
+    /// * [`http::ErrorKind::CredentialsInvalid`](../http/enum.ErrorKind.html#variant.CredentialsInvalid) - a host's credentials can't be sent
+    /// * [`s3::ErrorKind::ConfigInvalid`](../s3/enum.ErrorKind.html#variant.ConfigInvalid) - the config's `s3` key isn't set up right
+    /// * [`ErrorKind::YamlInvalid`](enum.ErrorKind.html#variant.YamlInvalid) - the config or credentials file is invalid
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn configured_credentials(paths: &ProjectPaths) -> Result<Credentials, Box<Error>> {
+        let mut credentials = Credentials::default();
+        // Buckets can be reached with keys from the environment alone
+        let mut s3 = S3Config::from_yaml(&Yaml::BadValue)?;
+        if let Some(config_yml) = PackageBackend::read_yaml_file(&paths.config_file())? {
+            if let Some(doc) = config_yml.first() {
+                credentials.extend(Credentials::from_yaml(&doc["auth"])?);
+                s3 = S3Config::from_yaml(&doc["s3"])?;
+            }
+        }
+        if let Some(credentials_yml) = PackageBackend::read_yaml_file(&paths.credentials_file())? {
+            if let Some(doc) = credentials_yml.first() {
+                credentials.extend(Credentials::from_yaml(doc)?);
+            }
+        }
+        credentials.set_s3(s3);
+        Ok(credentials)
+    }
+
+    /// Rolls back whatever operation was in progress the last time dropper was killed, returning
+    /// its description. Frontends should call this first thing, before even constructing a
+    /// backend, since an interrupted operation may have left the config in a bad state.
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn recover(paths: &ProjectPaths) -> Result<Option<String>, Box<Error>> {
+        Journal::recover(&paths.journal_dir())
+    }
+
+    /// Installs exactly what a lockfile pins, from its URLs, checking each download against its
+    /// digest. Unlike everything else, this doesn't need the server to be set up with dropper:
+    /// there doesn't have to be a config or a pkg.yml, and neither is created. That way a
+    /// lockfile made on a build machine can be deployed onto a bare server. Other versions of
+    /// the locked packages are removed, and JARs that already match are left alone. Credentials
+    /// are still sent if the server has them. Returns the (name, version) of each package that
+    /// was downloaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `lockfile` - The lockfile to install from
+    /// * `paths` - The server to install into
+    /// * `client` - The HTTP client to download with
+    /// * `max_download_rate` - The fastest to download at, in bytes per second
+    /// * `cancel` - Stops installing once it's cancelled, leaving whatever was installed so far
+    ///
+    /// # Errors
+    /// * [`ErrorKind::HashMismatch`](enum.ErrorKind.html#variant.HashMismatch) - a download didn't match the lockfile
+    /// * [`ErrorKind::VersionYanked`](enum.ErrorKind.html#variant.VersionYanked) - a locked version's file has been taken down
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * [`lock::ErrorKind::FileOutside`](../lock/enum.ErrorKind.html#variant.FileOutside) - a locked file isn't inside the plugin directory
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn install_from_lock(
+        lockfile: &Path,
+        paths: &ProjectPaths,
+        client: &Client,
+        max_download_rate: Option<u64>,
+        cancel: &CancelToken,
+    ) -> Result<Vec<(String, String)>, Box<Error>> {
+        if !lockfile.exists() {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} doesn't exist", lockfile.display()),
+            )));
+        }
+        let lockfile = Lockfile::load(lockfile)?;
+        let credentials = Self::configured_credentials(paths)?;
+        let downloader = Downloader {
+            client: client,
+            credentials: &credentials,
+            max_download_rate: max_download_rate,
+            cancel: cancel,
+        };
+        fs::create_dir_all(&paths.plugin_dir)?;
+
+        let mut installed = Vec::new();
+        for (name, locked) in lockfile.packages.iter() {
+            cancel.check()?;
+            let target = paths.plugin_dir.join(&locked.file);
+            let extras = locked
+                .extra_files()
+                .iter()
+                .map(|file| paths.plugin_dir.join(file))
+                .collect::<Vec<PathBuf>>();
+            if !target.exists()
+                || extras.iter().any(|extra| !extra.exists())
+                || hash::verify_file(&target, &locked.hashes)?.is_some()
+            {
+                let (partial, picked) = Self::fetch_partial(
+                    &downloader,
+                    paths,
+                    &locked.file,
+                    &locked.url,
+                    &locked.hashes,
+                    &Self::locked_patterns(locked),
+                )
+                // There's no source to look for other versions in here
+                .map_err(|e| match e.downcast_ref::<ErrorKind>() {
+                    Some(ErrorKind::DownloadGone(_)) => Box::new(ErrorKind::VersionYanked(
+                        name.clone(),
+                        locked.version.clone(),
+                        None,
+                    )),
+                    _ => e,
+                })?;
+                // Without a state file there's nowhere to record the configs of a distribution
+                let distribution = Self::distribution_partial(paths, &locked.file);
+                if distribution.exists() {
+                    for jar in picked.iter().skip(1) {
+                        let staged =
+                            Self::stage_extra_jar(paths, &distribution, &locked.file, jar)?;
+                        fs::rename(&staged, paths.plugin_dir.join(staged.file_name().unwrap()))?;
+                    }
+                    fs::remove_file(&distribution)?;
+                }
+                fs::create_dir_all(target.parent().unwrap())?;
+                fs::rename(&partial, &target)?;
+                installed.push((name.clone(), locked.version.clone()));
+            }
+
+            // Other versions of addons are in the same folder as the locked one
+            let prefix = format!("{}{}", name, VERSION_SPLIT_CHAR);
+            let dir = target.parent().unwrap();
+            let extension = target.extension().unwrap_or_default();
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+                let file = match path.strip_prefix(&paths.plugin_dir) {
+                    Ok(file) => lock::file_name(file),
+                    Err(_) => continue,
+                };
+                if file_name.starts_with(&prefix)
+                    && path.extension() == Some(extension)
+                    && !locked.owns(&file)
+                {
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+
+        // Nothing stays staged, since the server isn't known to be running
+        Self::remove_empty_dirs(&paths.staging_dir());
+
+        Ok(installed)
+    }
+
+    /// The initalization function for the backend. This is performed only on the first run, or if the .dropper folder is ever deleted
+    ///
+    /// This creates a folder at the server root caled .dropper, and in it, places a default config file
+    /// called `config.yml`, as well as a SQLite DB for keeping track of package installs.
+    ///
+    /// It also dumps a blank `pkg.yml` to the server root directory if it does not exist yet.
+    ///
+    /// # Warning
+    /// This command is by design destructive! It will kill the config folder, along with its files,
+    /// so it is advised to prompt the user before running this! The interface should check to see if
+    /// a non-empty `.dropper` exists before running this, prompting the user if so.
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn init(paths: &ProjectPaths) -> Result<(), Box<Error>> {
+        // Create the directory for the config files
+        if paths.config_root().exists() {
+            fs::remove_dir_all(paths.config_root())?;
+        }
+        fs::create_dir(paths.config_root())?;
+
+        // Dump a default config file in there
+        let mut config = File::create(paths.config_file())?;
+        config.write_all(text_assets::CONFIG_YAML_DEFAULT);
+
+        // Create a pkg.yml if one does not exist yet
+        let pkg_list = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(paths.pkg_list())?;
+
+        Ok(())
+    }
+
+    /// Ensures that the config files both exist and can be read
+    ///
+    /// # Behavior
+    /// The only error this function can throw is if it detects that the config/pkg files are corrupt or
+    /// malformed. The interface should handle what happens at this point (e.g. display the YML validation
+    /// output, or prompt them if they wish to re-initialize)
+    ///
+    /// # Errors
+    /// * [`ErrorKind::YamlInvalid`](enum.ErrorKind.html#variant.YamlInvalid) - one of the YML files is invalid
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn validate(paths: &ProjectPaths) -> Result<(), Box<Error>> {
+        let config = match PackageBackend::read_yaml_file(&paths.config_file())? {
+            Some(c) => c,
+            None => return Err(Box::new(ErrorKind::ConfigMissing)),
+        };
+        // Read all of the fields we need, and ensure they can be parsed into the
+        // right type.
+        let config_doc = &config[0];
+
+        match config_doc["server_version"].clone().into_string() {
+            Some(_) => {}
+            None => {
+                return Err(Box::new(ErrorKind::ConfigInvalid(
+                    "server_version".to_string(),
+                )))
+            }
+        }
+
+        match config_doc["plugin_website"].clone().into_string() {
+            Some(ref website) if parser::PLUGIN_WEBSITES.contains(&website.as_str()) => {}
+            Some(website) => {
+                let closest = Self::closest_match(&website, &parser::PLUGIN_WEBSITES);
+                return Err(Box::new(ErrorKind::WebsiteUnknown(
+                    website,
+                    closest.map(|s| s.to_string()),
+                )));
+            }
+            None => {
+                return Err(Box::new(ErrorKind::ConfigInvalid(
+                    "plugin_website".to_string(),
                 )))
             }
         }
 
         // No need to valdate Some/None for pkg: it doesn't _need_ to exist for all
         // operations (like install), and it will be created for other ops (like add)
-        let pkg = PackageBackend::read_yaml_file(PKG_LIST_PATH)?;
+        let pkg = PackageBackend::read_yaml_file(&paths.pkg_list())?;
         Ok(())
     }
 
+    /// Finds the candidate closest to a misspelled name, going by edit distance. Candidates that
+    /// are more than a third different aren't close enough to suggest.
+    fn closest_match<'b>(name: &str, candidates: &[&'b str]) -> Option<&'b str> {
+        let name = name.to_lowercase();
+        candidates
+            .iter()
+            .map(|candidate| (Self::edit_distance(&name, &candidate.to_lowercase()), *candidate))
+            .filter(|(distance, candidate)| *distance <= candidate.len().div_ceil(3))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate)
+    }
+
+    /// The Levenshtein distance between two strings: how many characters have to be inserted,
+    /// deleted, or substituted to turn one into the other
+    fn edit_distance(a: &str, b: &str) -> usize {
+        let b = b.chars().collect::<Vec<char>>();
+        let mut row = (0..=b.len()).collect::<Vec<usize>>();
+        for (i, ca) in a.chars().enumerate() {
+            let mut previous = row[0];
+            row[0] = i + 1;
+            for (j, cb) in b.iter().enumerate() {
+                let substituted = previous + if ca == *cb { 0 } else { 1 };
+                previous = row[j + 1];
+                row[j + 1] = substituted.min(row[j] + 1).min(previous + 1);
+            }
+        }
+        row[b.len()]
+    }
+
     /// Internal helper function to validate the existance of a YAML file
     ///
     /// # Possible Results
@@ -192,7 +1659,7 @@ impl<'a> PackageBackend<'a> {
     /// # Errors
     /// * [`ErrorKind::YamlInvalid`](enum.ErrorKind.html#variant.YamlInvalid) - one of the YML files is invalid
     /// * `std::io::ErrorKind::*` - an IO error occured
-    fn read_yaml_file(path: &str) -> Result<Option<Vec<yaml_rust::Yaml>>, Box<Error>> {
+    fn read_yaml_file(path: &Path) -> Result<Option<Vec<yaml_rust::Yaml>>, Box<Error>> {
         let mut file = match File::open(path) {
             Ok(f) => f,
             Err(e) => {
@@ -212,7 +1679,7 @@ impl<'a> PackageBackend<'a> {
         // or return a validation error if YamlLoader is not able to parse.
         match YamlLoader::load_from_str(&contents) {
             Ok(yaml) => Ok(Some(yaml)),
-            Err(_e) => Err(Box::new(ErrorKind::YamlInvalid(path.to_string()))),
+            Err(_e) => Err(Box::new(ErrorKind::YamlInvalid(path.display().to_string()))),
         }
     }
 
@@ -222,149 +1689,5927 @@ impl<'a> PackageBackend<'a> {
     /// # Arguments
     ///
     /// * `pkg_specifier` - A string slice that represents the package and version the user wishes
-    ///                     to add. It should be in the package specifier format defined above.
+    ///   to add. It should be in the package specifier format defined above.
     ///
     pub fn pkg_add(&self, pkg_specifier: &str) -> Result<Option<(String, String)>, Box<Error>> {
+        self.transaction(&format!("add {}", pkg_specifier), || {
+            self.add(pkg_specifier, InstallReason::Explicit)
+        })
+    }
+
+    /// Does the work of `pkg_add`, recording why the package is being added
+    fn add(
+        &self,
+        pkg_specifier: &str,
+        reason: InstallReason,
+    ) -> Result<Option<(String, String)>, Box<Error>> {
         // First install the package, and be sure that went well
-        let (name, version) = match self.pkg_install(pkg_specifier)? {
+        let (name, version) = match self.install(pkg_specifier, reason)? {
             Some(tup) => tup,
             None => return Ok(None),
         };
 
-        let pkg_yml = match Self::read_yaml_file(PKG_LIST_PATH)? {
-            Some(yml) => yml,
-            // If we couldn't find the YML file, then we create it and start fresh
-            None => {
-                let mut pkg_file = File::create(PKG_LIST_PATH)?;
-                pkg_file.write_all(b"---\n")?;
-                Self::read_yaml_file(PKG_LIST_PATH)?.unwrap()
-            }
-        };
+        self.add_to_pkg_list(&name, &version)?;
 
-        let doc = &pkg_yml[0];
-        // Add the package to the existing YML
-        let mut hash = match doc {
-            Yaml::Hash(h) => h.clone(),
-            Yaml::Null => Hash::new(),
-            _ => return Err(Box::new(ErrorKind::PkgListInvalid))
-        };
+        Ok(Some((name, version)))
+    }
 
-        hash.insert(Yaml::from_str(name.as_str()), Yaml::from_str(version.as_str()));
+    /// Adds an entry for an installed package to the pkg.yml, or changes the version of the one
+    /// it already has
+    fn add_to_pkg_list(&self, name: &str, version: &str) -> Result<(), Box<Error>> {
+        // With an environment, the package only goes in that environment's overlay
+        let mut pkg_list = self.read_pkg_list()?;
+        let mut hash = self.pkg_section(&pkg_list)?;
 
-        // Write the package list YML back
-        let mut pkg_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(PKG_LIST_PATH)?;
-        let mut tmp_string = String::new();
-        let mut emitter = YamlEmitter::new(&mut tmp_string);
-        emitter.dump(&Yaml::Hash(hash)).unwrap();
-        tmp_string = format!("{}\n", tmp_string);
-        pkg_file.write_all(&tmp_string.into_bytes())?;
+        // The entry is keyed by the source too if the package doesn't come from the plugin
+        // website
+        // and a package installed from its preset gets the preset as its entry
+        let old_key = Self::pkg_list_key(&hash, name);
+        let old_entry = match &old_key {
+            Some(key) => hash.get(key),
+            None if self.preset_packages.borrow().contains(name) => self.presets.get(name),
+            None => None,
+        };
+        let entry = Self::with_version(old_entry, version);
+        if let Some(old_key) = &old_key {
+            hash.remove(old_key);
+        }
+        let source = self.source_of(name);
+        let key = match source == self.plugin_website {
+            true => name.to_string(),
+            false => format!("{}{}{}", source, SOURCE_SPLIT_CHAR, name),
+        };
+        hash.insert(Yaml::String(key), entry);
 
-        Ok(Some((name, version)))
+        self.set_pkg_section(&mut pkg_list, hash);
+        self.write_pkg_list(pkg_list)?;
+        Ok(())
     }
 
     /// The installer function which takes in a package specifier and installs that package to the user's
-    /// plugin directory. Can return a tuple of (name, version)
+    /// plugin directory. Can return a tuple of (name, version), or `None` if there's no such
+    /// package.
     ///
     /// # Arguments
     ///
     /// * `pkg_specifier` - A string slice that represents the package and version the user wishes
-    ///                     to add. It should be in the package specifier format defined above.
+    ///   to add. It should be in the package specifier format defined above.
     ///
     /// # Errors
-    /// *
+    /// * [`ErrorKind::PkgSpecInvalid`](enum.ErrorKind.html#variant.PkgSpecInvalid) - the package specifier is malformed
+    /// * [`ErrorKind::NoBuilds`](enum.ErrorKind.html#variant.NoBuilds) - the package has no builds for the server version
+    /// * [`ErrorKind::VersionNotFound`](enum.ErrorKind.html#variant.VersionNotFound) - the package doesn't have the version asked for
+    /// * [`ErrorKind::DowngradeRefused`](enum.ErrorKind.html#variant.DowngradeRefused) - the version is older than the installed one
+    /// * [`ErrorKind::PkgManual`](enum.ErrorKind.html#variant.PkgManual) - the pkg.yml says the package is managed by hand
+    /// * `std::io::ErrorKind::*` - an IO error occured
     pub fn pkg_install(&self, pkg_specifier: &str) -> Result<Option<(String, String)>, Box<Error>> {
+        self.transaction(&format!("install {}", pkg_specifier), || {
+            self.install(pkg_specifier, InstallReason::Explicit)
+        })
+    }
+
+    /// Does the work of `pkg_install`, recording why the package is being installed
+    fn install(
+        &self,
+        pkg_specifier: &str,
+        reason: InstallReason,
+    ) -> Result<Option<(String, String)>, Box<Error>> {
+        // The pkg.yml may say where the package comes from, or that it's built from source
+        self.pkg_requirements()?;
+
         // Parse the package specifier
-        let (pkg_url, name, version) =
-            match Self::parse_package_specifier(pkg_specifier.to_string())? {
-                // A version was specified: fetch that specific version
-                (name, Some(version)) => match self.package_parser.fetch(&name, &version)? {
-                    Some(link) => (link, name, version),
-                    None => return Ok(None),
-                },
-                // No version was specified: get the newest version
-                (name, None) => match self.package_parser.find_newest_version(&name)? {
-                    Some((version, link)) => (link, name, version),
+        let (source, name, version) = Self::parse_package_specifier(pkg_specifier.to_string())?;
+        self.check_not_manual(&name)?;
+        // Naming a source skips the package's preset
+        if source.is_some() && self.preset_packages.borrow().contains(&name) {
+            self.forget_own_source(&name);
+        }
+        let source = source.unwrap_or_else(|| self.source_of(&name));
+        self.source_parser(&source)?;
+
+        let mut refresh = false;
+        loop {
+            let (name, project_id, cached) =
+                match self.resolve_project_id(&source, &name, refresh)? {
+                    Some(tup) => tup,
                     None => return Ok(None),
-                },
-            };
+                };
+            self.set_source(&name, Some(&source));
 
-        let mut response = reqwest::get(&pkg_url)?;
+            match self.find_version(&name, version.clone())? {
+                Some((version, pkg_url)) => {
+                    // Going back to an older version replaces the installed one, like an update
+                    let installed = Lockfile::load(&self.paths.lockfile())?
+                        .packages
+                        .get(&name)
+                        .filter(|locked| locked.is_newer_than(&version))
+                        .map(|locked| locked.version.clone());
+                    if let (Some(installed), false) = (&installed, self.allow_downgrade) {
+                        return Err(Box::new(ErrorKind::DowngradeRefused(
+                            name,
+                            installed.clone(),
+                            version,
+                        )));
+                    }
+                    let old_version = installed.as_deref();
+                    self.install_resolved(&name, &version, &pkg_url, reason, old_version)?;
+                    if let Some(project_id) = project_id {
+                        self.record_project_id(&name, &project_id)?;
+                    }
+                    return Ok(Some((name, version)));
+                }
+                // The project may have been renamed since its name was cached
+                None if cached && !refresh => refresh = true,
+                None => return Ok(None),
+            }
+        }
+    }
 
-        let mut plugin_file = {
-            let filename = format!("{}/{}@{}.jar", DOWNLOAD_DIR, name, version);
-            File::create(filename)?
-        };
-        copy(&mut response, &mut plugin_file);
-        Ok(Some((name, version)))
+    /// Fails if the pkg.yml says a package is managed by hand, for everything that would
+    /// download it
+    fn check_not_manual(&self, name: &str) -> Result<(), Box<Error>> {
+        match self.manual_packages.borrow().get(name) {
+            Some(manual) => Err(Box::new(ErrorKind::PkgManual(
+                name.to_string(),
+                manual.file.clone(),
+            ))),
+            None => Ok(()),
+        }
     }
 
-    /// The update function which takes in a package name, checks to see if it's been installed, and
-    /// by default installs the newest version according to the user's pkg.yml.
-    ///
-    /// # Arguments
-    ///
-    /// * `pkg_name` - A string slice that represents the package name that the user wishes to update.
+    /// Finds the (version, link) to install for the version half of a package specifier, or
+    /// `None` if there is no such version
+    fn find_version(
+        &self,
+        name: &str,
+        version: Option<String>,
+    ) -> Result<Option<(String, String)>, Box<Error>> {
+        // Packages built from source are always built at the ref the pkg.yml gives
+        if let Some(git) = self.git_sources.borrow().get(name) {
+            return Ok(Some((git.version(), git.link())));
+        }
+        // and ones downloaded from a URL always have the version it pins
+        if let Some(direct) = self.direct_sources.borrow().get(name) {
+            return Ok(Some((direct.version.clone(), direct.url.clone())));
+        }
+
+        match version {
+            // A version was specified: fetch that specific version
+            Some(version) => match Constraint::parse(&version) {
+                // Asking for a blocked version by name shouldn't look like it doesn't exist
+                Constraint::Exact(_) if self.blocklist.reason(name, &version).is_some() => {
+                    self.check_blocked(name, &version)?;
+                    self.exact_version(name, version)
+                }
+                Constraint::Exact(_) => self.exact_version(name, version),
+                // A wildcard was specified: find the newest version it covers
+                constraint => {
+                    let requirement = Requirement {
+                        required_by: "the command line".to_string(),
+                        package: name.to_string(),
+                        constraint: constraint,
+                    };
+                    self.resolve_package(name, &[requirement])
+                }
+            },
+            // No version was specified: get the newest version
+            None => {
+                let requirement = Requirement {
+                    required_by: "the command line".to_string(),
+                    package: name.to_string(),
+                    constraint: Constraint::Any,
+                };
+                self.resolve_package(name, &[requirement])
+            }
+        }
+    }
+
+    /// Finds the link to an exact version of a package, or `None` if there is no such package
     ///
     /// # Errors
-    /// If the package name specified is not installed, then the Result
-    /// will contain an appropriate error, and will need to be handled with whatever frontend is being
-    /// used.
-    ///
-    /// Additionally, this function can return a `OperationNothingToDo` if the package is already  up to date.
-    pub fn pkg_update(&self, pkg_specifier: &str) -> Result<bool, Box<Error>> {
-        unimplemented!();
+    /// * [`ErrorKind::NoBuilds`](enum.ErrorKind.html#variant.NoBuilds) - the package has no builds for the server version
+    /// * [`ErrorKind::VersionNotFound`](enum.ErrorKind.html#variant.VersionNotFound) - the package doesn't have the version
+    fn exact_version(
+        &self,
+        name: &str,
+        version: String,
+    ) -> Result<Option<(String, String)>, Box<Error>> {
+        let lookup = match self.has_own_source(name) {
+            false => self.parser_of(name)?.fetch(name, &version)?,
+            // Packages with their own source are looked up among their files, which aren't
+            // filtered by the blocklist here since the version has already been let through
+            true => match self.package_files(name)? {
+                Some(files) => Lookup::among(&files, &version),
+                None => Lookup::NoPackage,
+            },
+        };
+
+        match lookup {
+            Lookup::Found(link) => Ok(Some((version, link))),
+            Lookup::NoPackage => Ok(None),
+            Lookup::NoBuilds => Err(self.no_builds(name)),
+            Lookup::NoVersion(available) => {
+                let closest = Self::closest_match(
+                    &version,
+                    &available.iter().map(|v| v.as_str()).collect::<Vec<&str>>(),
+                )
+                .map(|closest| closest.to_string());
+                Err(Box::new(ErrorKind::VersionNotFound(
+                    name.to_string(),
+                    version,
+                    available,
+                    closest,
+                )))
+            }
+        }
+    }
+
+    /// The error for a package with no builds for the server version
+    fn no_builds(&self, name: &str) -> Box<Error> {
+        Box::new(ErrorKind::NoBuilds(name.to_string(), self.server_version.clone()))
     }
 
-    /// An internal function to parse out the package name and version from a package specifier
+    /// Package names that are numeric project IDs are looked up on the package's source, returning
+    /// the package's actual name along with the ID, and whether the name came from the cache in
+    /// the state file. Other names are returned as they are. Returns `None` if there's no project
+    /// with the ID.
     ///
     /// # Arguments
-    /// * `pkg_specifier` - A string slice that represents the package and version the user wishes
-    ///                     to add. It should be in the package specifier format defined above.
     ///
-    /// # Errors
-    /// * [`ErrorKind::PkgSpecInvalid`](enum.ErrorKind.html#variant.PkgSpecInvalid) - the package specifier was invalid
+    /// * `source` - The source the package comes from
+    /// * `name` - The package name from a package specifier
+    /// * `refresh` - Look the ID up even if its name is cached
+    fn resolve_project_id(
+        &self,
+        source: &str,
+        name: &str,
+        refresh: bool,
+    ) -> Result<Option<ProjectName>, Box<Error>> {
+        let id = name.trim_start_matches(PROJECT_ID_PREFIX);
+        if !id.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(Some((name.to_string(), None, false)));
+        }
+
+        let mut state = State::load(&self.paths.state_file())?;
+        if !refresh {
+            if let Some(cached) = state.project_name(source, id) {
+                return Ok(Some((cached.to_string(), Some(id.to_string()), true)));
+            }
+        }
+
+        let names = state
+            .project_names
+            .entry(source.to_string())
+            .or_insert_with(BTreeMap::new);
+        let found = self.source_parser(source)?.find_project_by_id(id)?;
+        match &found {
+            Some(found) => names.insert(id.to_string(), found.clone()),
+            None => names.remove(id),
+        };
+        state.save(&self.paths.state_file())?;
+
+        Ok(found.map(|name| (name, Some(id.to_string()), false)))
+    }
+
+    /// Remembers the project ID of a package in the lockfile
+    fn record_project_id(&self, name: &str, project_id: &str) -> Result<(), Box<Error>> {
+        let mut lockfile = Lockfile::load(&self.paths.lockfile())?;
+        if let Some(locked) = lockfile.packages.get_mut(name) {
+            locked.project_id = Some(project_id.to_string());
+            lockfile.save(&self.paths.lockfile())?;
+        }
+        Ok(())
+    }
+
+    /// Installs every package in the pkg.yml, along with any packages they require. The version of
+    /// each package is picked to satisfy every constraint placed on it, and everything is resolved
+    /// up front so that a conflict is reported before anything is downloaded. Returns the
+    /// (name, version) of each installed package.
     ///
-    /// # Non Error Return Value
-    /// A tuple containing the package name and an option of version code. If none, assume the newest
-    /// package is acceptable.
-    fn parse_package_specifier(
-        pkg_specifier: String,
-    ) -> Result<(String, Option<String>), ErrorKind> {
-        let name_re = Regex::new(r"^\w+$").unwrap();
-        if pkg_specifier.contains(VERSION_SPLIT_CHAR) {
-            // A version was specified along with the package
-            let components = pkg_specifier
-                .split(VERSION_SPLIT_CHAR)
-                .collect::<Vec<&str>>();
-            // Anything more than two components means that one too many separators appeared
-            match components.len() {
-                2 => {
-                    let version_re = Regex::new(VERSION_CODE_REGEX).unwrap();
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`ErrorKind::PkgNotFound`](enum.ErrorKind.html#variant.PkgNotFound) - a required package doesn't exist
+    /// * [`resolver::ErrorKind::Conflict`](../resolver/enum.ErrorKind.html#variant.Conflict) - the constraints on a package can't all be met
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_install_all(&self) -> Result<Vec<(String, String)>, Box<Error>> {
+        self.transaction("install", || self.install_all())
+    }
 
-                    if !name_re.is_match(&components[0]) {
-                        return Err(ErrorKind::PkgSpecInvalid(pkg_specifier));
-                    }
+    /// Does the work of `pkg_install_all`
+    fn install_all(&self) -> Result<Vec<(String, String)>, Box<Error>> {
+        let requirements = self.pkg_requirements()?;
+        let resolved = self.resolve_all(&requirements)?;
 
-                    if !version_re.is_match(&components[1]) {
-                        return Err(ErrorKind::PkgSpecInvalid(pkg_specifier));
-                    }
+        let state = State::load(&self.paths.state_file())?;
+        let mut installed = Vec::new();
+        for (name, version, link) in resolved {
+            let reason = Self::default_reason(&state, &requirements, &name);
+            self.install_resolved(&name, &version, &link, reason, None)?;
+            installed.push((name, version));
+        }
 
-                    // At this point, the components are valid and can be passed out
-                    Ok((components[0].to_string(), Some(components[1].to_string())))
-                }
-                // More than two components were found
-                _ => Err(ErrorKind::PkgSpecInvalid(pkg_specifier)),
+        Ok(installed)
+    }
+
+    /// Resolves every package the requirements mention, returning the (name, version, link) of
+    /// each. Nothing is downloaded.
+    fn resolve_all(
+        &self,
+        requirements: &[Requirement],
+    ) -> Result<Vec<Resolved>, Box<Error>> {
+        let mut packages: Vec<String> = Vec::new();
+        for requirement in requirements.iter() {
+            if !packages
+                .iter()
+                .any(|p| p.eq_ignore_ascii_case(&requirement.package))
+            {
+                packages.push(requirement.package.clone());
             }
-        } else {
-            // No version was specified along with the package
-            // Ensure that the package name is just one valid word
-            match name_re.is_match(&pkg_specifier) {
-                true => Ok((pkg_specifier, None)),
-                false => Err(ErrorKind::PkgSpecInvalid(pkg_specifier)),
+        }
+
+        self.prefetch(&packages);
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        let mut resolved = Vec::new();
+        for package in packages {
+            // Downgraded packages stay where they are, as long as the requirements still allow it
+            if let Some(locked) = lockfile.packages.get(&package).filter(|locked| {
+                locked.downgraded
+                    && requirements
+                        .iter()
+                        .filter(|r| r.package.eq_ignore_ascii_case(&package))
+                        .all(|r| r.constraint.matches(&locked.version))
+            }) {
+                resolved.push((package.clone(), locked.version.clone(), locked.url.clone()));
+                continue;
+            }
+            match self.resolve_package(&package, requirements)? {
+                Some((version, link)) => resolved.push((package, version, link)),
+                None => return Err(Box::new(ErrorKind::PkgNotFound(package))),
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// Packages only listed as requirements of other packages are dependencies, unless we
+    /// already know better
+    fn default_reason(state: &State, requirements: &[Requirement], name: &str) -> InstallReason {
+        match state.packages.get(name) {
+            Some(package) => package.reason,
+            None => match requirements
+                .iter()
+                .any(|r| r.package == name && r.required_by == PKG_LIST_REQUIRER)
+            {
+                true => InstallReason::Explicit,
+                false => InstallReason::Dependency,
+            },
+        }
+    }
+
+    /// Installs a version of a package that has already been picked, running its hooks and
+    /// rendering its configs. If `old_version` is given, this is an update, and the JAR of the
+    /// old version is removed once the new one is in place.
+    fn install_resolved(
+        &self,
+        name: &str,
+        version: &str,
+        link: &str,
+        reason: InstallReason,
+        old_version: Option<&str>,
+    ) -> Result<(), Box<Error>> {
+        self.check_blocked(name, version)?;
+
+        let (pre, post) = match old_version {
+            Some(_) => (Hook::PreUpdate, Hook::PostUpdate),
+            None => (Hook::PreInstall, Hook::PostInstall),
+        };
+
+        self.run_hook(pre, name, version, old_version)?;
+        let downloaded = self.download(name, version, link, None, &self.jar_patterns(name)?)?;
+
+        if old_version.is_some() {
+            let new_file = self.installed_file(name, version);
+            let mut new_files = vec![new_file.clone()];
+            for jar in downloaded.jars.iter().skip(1) {
+                new_files.push(lock::extra_jar_name(&new_file, jar));
+            }
+            for jar in self.installed_jars(name)? {
+                if !new_files.contains(&self.relative_file(&jar)) {
+                    self.journal_remove(&jar)?;
+                }
             }
         }
+
+        self.record_install(name, version, link, reason, &downloaded)?;
+        self.record_history(
+            match old_version {
+                Some(old) if Self::is_older(version, old) => "downgrade",
+                Some(_) => "update",
+                None => "install",
+            },
+            name,
+            version,
+            old_version,
+        )?;
+        self.render_configs(name, version)?;
+        if self.save_docs {
+            self.save_package_docs(name, version);
+        }
+        self.run_hook(post, name, version, old_version)
+    }
+
+    /// Saves what a package's source says about it into its docs folder. The docs are only
+    /// nice to have, so failing to save them is a warning.
+    fn save_package_docs(&self, name: &str, version: &str) {
+        let metadata = match self.pkg_metadata(name) {
+            Ok(Some(metadata)) => metadata,
+            Ok(None) => return,
+            Err(e) => return self.warn(format!("couldn't save the docs of {}: {}", name, e)),
+        };
+        let docs = Docs {
+            name: metadata.display_name.unwrap_or(metadata.slug),
+            version: version.to_string(),
+            authors: metadata.authors,
+            description: metadata.description,
+        };
+        if let Err(e) = docs.save(&self.paths.docs_dir(name)) {
+            self.warn(format!("couldn't save the docs of {}: {}", name, e));
+        }
+    }
+
+    /// The docs saved for a package when it was installed, if `save_docs` was on
+    pub fn pkg_docs(&self, name: &str) -> Option<Docs> {
+        Docs::load(&self.paths.docs_dir(name))
+    }
+
+    /// Writes up every installed package, with its saved docs, as Markdown, for handing the
+    /// server over to someone else
+    ///
+    /// # Errors
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * [`state::ErrorKind::StateInvalid`](../state/enum.ErrorKind.html#variant.StateInvalid) - the state file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_handover(&self) -> Result<String, Box<Error>> {
+        let mut markdown = format!("# Plugins on this server ({})\n\n", self.server_version);
+        for package in self.pkg_installed()? {
+            let docs = match self.pkg_docs(&package.name) {
+                // Docs saved for an older version still describe the package
+                Some(docs) => Docs {
+                    version: package.version.clone(),
+                    ..docs
+                },
+                None => Docs {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    authors: Vec::new(),
+                    description: None,
+                },
+            };
+            markdown.push_str(&docs.to_markdown(2));
+            markdown.push_str(&format!(
+                "Installed as `{}`{}\n\n",
+                package.name,
+                match package.reason {
+                    InstallReason::Explicit => "",
+                    InstallReason::Dependency => ", as a dependency of other plugins",
+                }
+            ));
+        }
+        Ok(markdown)
+    }
+
+    /// Adds a change to a package to the history, along with the checks that were overridden
+    /// to make it
+    fn record_history(
+        &self,
+        action: &str,
+        name: &str,
+        version: &str,
+        from: Option<&str>,
+    ) -> Result<(), Box<Error>> {
+        let mut event = Event::now(action, name, version);
+        event.from = from.map(|v| v.to_string());
+
+        let mut overrides = self.overrides.borrow_mut();
+        for (package, check) in overrides.iter() {
+            let check = check.name().to_string();
+            if package == name && !event.forced.contains(&check) {
+                event.forced.push(check);
+            }
+        }
+        overrides.retain(|(package, _)| package != name);
+
+        History::append(&self.paths.history_file(), event)
+    }
+
+    /// Works out what it would take to bring the server in line with the pkg.yml, without
+    /// changing anything: packages to install, packages to move to another version, and locked
+    /// packages that the pkg.yml doesn't mention anymore, which are removed.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`ErrorKind::PkgNotFound`](enum.ErrorKind.html#variant.PkgNotFound) - a required package doesn't exist
+    /// * [`resolver::ErrorKind::Conflict`](../resolver/enum.ErrorKind.html#variant.Conflict) - the constraints on a package can't all be met
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_plan(&self) -> Result<Plan, Box<Error>> {
+        let requirements = self.pkg_requirements()?;
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        let state = State::load(&self.paths.state_file())?;
+
+        let mut changes = Vec::new();
+        for (name, version, link) in self.resolve_all(&requirements)? {
+            match lockfile.packages.get(&name) {
+                None => changes.push(Change::Install {
+                    reason: Self::default_reason(&state, &requirements, &name),
+                    package: name,
+                    version: version,
+                    url: link,
+                }),
+                Some(locked) if locked.version != version => changes.push(Change::Update {
+                    package: name,
+                    from: locked.version.clone(),
+                    to: version,
+                    url: link,
+                }),
+                Some(_) => {}
+            }
+        }
+
+        for (name, locked) in lockfile.packages.iter() {
+            if !requirements
+                .iter()
+                .any(|r| r.package.eq_ignore_ascii_case(name))
+            {
+                changes.push(Change::Remove {
+                    package: name.clone(),
+                    version: locked.version.clone(),
+                });
+            }
+        }
+
+        Ok(Plan {
+            lock_md5: self.lock_md5()?,
+            changes: changes,
+        })
+    }
+
+    /// Carries out a plan made by `pkg_plan`, exactly as it was made. Returns the changes that
+    /// were made.
+    ///
+    /// # Errors
+    /// * [`plan::ErrorKind::PlanStale`](../plan/enum.ErrorKind.html#variant.PlanStale) - the lockfile has changed since the plan was made
+    /// * [`ErrorKind::PkgRequired`](enum.ErrorKind.html#variant.PkgRequired) - a package to remove is needed by other plugins
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_apply(&self, plan: &Plan) -> Result<Vec<Change>, Box<Error>> {
+        self.transaction("apply", || self.apply(plan))
+    }
+
+    /// Does the work of `pkg_apply`
+    fn apply(&self, plan: &Plan) -> Result<Vec<Change>, Box<Error>> {
+        if self.lock_md5()? != plan.lock_md5 {
+            return Err(Box::new(plan::ErrorKind::PlanStale));
+        }
+
+        let state = State::load(&self.paths.state_file())?;
+        let mut applied = Vec::new();
+        for change in plan.changes.iter() {
+            match change {
+                Change::Install {
+                    package,
+                    version,
+                    url,
+                    reason,
+                } => self.install_resolved(package, version, url, *reason, None)?,
+                Change::Update {
+                    package,
+                    from,
+                    to,
+                    url,
+                } => {
+                    let reason = state
+                        .packages
+                        .get(package)
+                        .map_or(InstallReason::Explicit, |p| p.reason);
+                    self.install_resolved(package, to, url, reason, Some(from))?
+                }
+                Change::Remove { package, .. } => {
+                    self.remove(package, false)?;
+                }
+            }
+            applied.push(change.clone());
+        }
+
+        Ok(applied)
+    }
+
+    /// The MD5 of the lockfile, or `None` if there isn't one yet
+    fn lock_md5(&self) -> Result<Option<String>, Box<Error>> {
+        match self.paths.lockfile().exists() {
+            true => Ok(Some(hash::md5_file(&self.paths.lockfile())?)),
+            false => Ok(None),
+        }
+    }
+
+    /// Collects every version constraint in the pkg.yml. An entry is either a version, or a map
+    /// with a `version` and the constraints it places on other packages under `requires`:
+    ///
+    /// ```yaml
+    /// protocollib: "4.*"
+    /// someplugin:
+    ///   version: "2.*"
+    ///   requires:
+    ///     protocollib: "4.4.*"
+    /// ```
+    ///
+    /// Package names can be prefixed with the source they come from, like `bukkit:protocollib`.
+    /// Entries with a `git` repository are built from source, entries with `maven` coordinates
+    /// come from a Maven repository, entries with a `listing` come from a directory listing, and
+    /// entries with a `repo` come from a self-hosted repository, and entries with a `github`,
+    /// `hangar` or `downloads` source come from GitHub releases, Hangar or a download API; see the
+    /// [`build`](../build/index.html), [`maven`](../maven/index.html),
+    /// [`listing`](../listing/index.html), [`repo`](../repo/index.html),
+    /// [`github`](../github/index.html), [`hangar`](../hangar/index.html) and
+    /// [`downloads`](../downloads/index.html) modules. Packages without an entry that have a
+    /// [preset](../presets/index.html) come from the preset's source. Entries marked `manual`
+    /// are [managed by hand](../manual/index.html), so they aren't requirements, and nor are
+    /// the constraints other entries place on them; only what they require is. Dependencies
+    /// installed for what packages `depend` on, like libraries, are required by those packages.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`ErrorKind::YamlInvalid`](enum.ErrorKind.html#variant.YamlInvalid) - the pkg.yml is invalid
+    /// * [`build::ErrorKind::SourceInvalid`](../build/enum.ErrorKind.html#variant.SourceInvalid) - an entry's git source is incomplete
+    /// * [`maven::ErrorKind::CoordinatesInvalid`](../maven/enum.ErrorKind.html#variant.CoordinatesInvalid) - an entry's Maven coordinates are invalid
+    /// * [`listing::ErrorKind::SourceInvalid`](../listing/enum.ErrorKind.html#variant.SourceInvalid) - an entry's directory listing or pattern is invalid
+    /// * [`repo::ErrorKind::SourceInvalid`](../repo/enum.ErrorKind.html#variant.SourceInvalid) - an entry's repository isn't a URL
+    /// * [`github::ErrorKind::SourceInvalid`](../github/enum.ErrorKind.html#variant.SourceInvalid) - an entry's GitHub repository or asset pattern is invalid
+    /// * [`hangar::ErrorKind::SourceInvalid`](../hangar/enum.ErrorKind.html#variant.SourceInvalid) - an entry's Hangar project or platform is invalid
+    /// * [`downloads::ErrorKind::SourceInvalid`](../downloads/enum.ErrorKind.html#variant.SourceInvalid) - an entry's download API project isn't a URL
+    /// * [`manual::ErrorKind::SourceInvalid`](../manual/enum.ErrorKind.html#variant.SourceInvalid) - an entry marked `manual` doesn't say which JAR to expect
+    /// * [`state::ErrorKind::StateInvalid`](../state/enum.ErrorKind.html#variant.StateInvalid) - the state file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_requirements(&self) -> Result<Vec<Requirement>, Box<Error>> {
+        let hash = self.pkg_entries()?;
+
+        let mut requirements = Vec::new();
+        let mut manual_packages = BTreeMap::new();
+        for (name, entry) in hash.iter() {
+            let name = match name.as_str() {
+                Some(name) => self.read_source(name),
+                None => return Err(Box::new(ErrorKind::PkgListInvalid)),
+            };
+
+            if let Some(manual) = ManualPackage::from_yaml(&name, entry)? {
+                manual_packages.insert(name.clone(), manual);
+            } else {
+                let version = match entry {
+                    Yaml::Hash(_) => Self::yaml_version(&entry["version"]),
+                    _ => Self::yaml_version(entry),
+                };
+                // Coordinates that pin the version stand in for a `version`
+                let version = version.or(self.register_sources(&name, entry)?);
+                // Where addons go is only looked up when it's needed, but it's checked here
+                AddonTarget::from_yaml(&name, entry)?;
+                requirements.push(Requirement {
+                    required_by: PKG_LIST_REQUIRER.to_string(),
+                    package: name.clone(),
+                    constraint: Constraint::parse(&version.unwrap_or("*".to_string())),
+                });
+            }
+
+            if let Yaml::Hash(deps) = &entry["requires"] {
+                for (dep, version) in deps.iter() {
+                    match (dep.as_str(), Self::yaml_version(version)) {
+                        (Some(dep), Some(version)) => requirements.push(Requirement {
+                            required_by: name.clone(),
+                            package: self.read_source(dep),
+                            constraint: Constraint::parse(&version),
+                        }),
+                        _ => return Err(Box::new(ErrorKind::PkgListInvalid)),
+                    }
+                }
+            }
+        }
+
+        // Packages the pkg.yml doesn't have an entry for are installed from their preset, unless
+        // they were installed from a source of their own
+        let mut preset_packages = BTreeSet::new();
+        for (name, entry) in self.presets.iter() {
+            let sourced = self.package_sources.borrow().contains_key(name);
+            if Self::pkg_list_key(&hash, name).is_some() || self.has_own_source(name) || sourced {
+                continue;
+            }
+            self.register_sources(name, entry)?;
+            preset_packages.insert(name.clone());
+        }
+        self.preset_packages.replace(preset_packages);
+
+        // There's nothing to resolve for packages managed by hand
+        requirements.retain(|r| {
+            !manual_packages
+                .keys()
+                .any(|name| name.eq_ignore_ascii_case(&r.package))
+        });
+        self.manual_packages.replace(manual_packages);
+
+        // Dependencies that were installed for a package's `depend`s, like libraries, aren't in
+        // the pkg.yml, but they're required for as long as that package is
+        let state = State::load(&self.paths.state_file())?;
+        let orphans = state.orphans();
+        for (name, package) in state.packages.iter() {
+            if orphans.contains(name) {
+                continue;
+            }
+            for dep in package.depend.iter() {
+                let provider = match state.provider_of(dep) {
+                    Some(provider) => provider,
+                    None => continue,
+                };
+                let is_dependency = state.packages[provider].reason == InstallReason::Dependency;
+                if is_dependency
+                    && !requirements
+                        .iter()
+                        .any(|r| r.package.eq_ignore_ascii_case(provider))
+                {
+                    requirements.push(Requirement {
+                        required_by: name.clone(),
+                        package: provider.to_string(),
+                        constraint: Constraint::Any,
+                    });
+                }
+            }
+        }
+
+        Ok(requirements)
+    }
+
+    /// Remembers where a pkg.yml entry, or a preset, says to find a package, and returns the
+    /// version its Maven coordinates pin, if they do
+    fn register_sources(&self, name: &str, entry: &Yaml) -> Result<Option<String>, Box<Error>> {
+        let mut version = None;
+        if let Some(git) = GitSource::from_yaml(name, entry)? {
+            self.git_sources.borrow_mut().insert(name.to_string(), git);
+        }
+        if let Some(maven) = MavenSource::from_yaml(name, entry)? {
+            version = maven.version.clone();
+            self.maven_sources.borrow_mut().insert(name.to_string(), maven);
+        }
+        if let Some(direct) = DirectSource::from_yaml(name, entry)? {
+            self.direct_sources.borrow_mut().insert(name.to_string(), direct);
+        }
+        if let Some(listing) = ListingSource::from_yaml(name, entry)? {
+            self.listing_sources.borrow_mut().insert(name.to_string(), listing);
+        }
+        if let Some(repo) = RepoSource::from_yaml(name, entry, &self.paths.project_dir)? {
+            self.repo_sources.borrow_mut().insert(name.to_string(), repo);
+        }
+        if let Some(github) = GithubSource::from_yaml(name, entry)? {
+            self.github_sources.borrow_mut().insert(name.to_string(), github);
+        }
+        if let Some(hangar) = HangarSource::from_yaml(name, entry)? {
+            self.hangar_sources.borrow_mut().insert(name.to_string(), hangar);
+        }
+        if let Some(downloads) = DownloadsSource::from_yaml(name, entry)? {
+            self.downloads_sources.borrow_mut().insert(name.to_string(), downloads);
+        }
+        Ok(version)
+    }
+
+    /// Splits the source off a package name from the pkg.yml, remembering it, and returns the
+    /// rest of the name
+    fn read_source(&self, key: &str) -> String {
+        let (source, name) = Self::split_source(key);
+        self.set_source(name, source);
+        name.to_string()
+    }
+
+    /// The pkg.yml key of a package, which may be prefixed with its source
+    fn pkg_list_key(hash: &Hash, name: &str) -> Option<Yaml> {
+        hash.keys()
+            .find(|key| key.as_str().map(|key| Self::split_source(key).1) == Some(name))
+            .cloned()
+    }
+
+    /// Reads the config files a package's pkg.yml entry wants rendered into its plugin's data
+    /// folder, as (destination, template) pairs. Destinations are relative to the data folder,
+    /// and templates to the server root:
+    ///
+    /// ```yaml
+    /// essentials:
+    ///   version: "2.*"
+    ///   configs:
+    ///     config.yml: templates/essentials/config.yml
+    /// ```
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted, or a destination leaves the data folder
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_configs(&self, name: &str) -> Result<Vec<(PathBuf, PathBuf)>, Box<Error>> {
+        let hash = self.pkg_entries()?;
+        let entry = match Self::pkg_list_key(&hash, name).and_then(|key| hash.get(&key)) {
+            Some(entry) => entry,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut configs = Vec::new();
+        if let Yaml::Hash(files) = &entry["configs"] {
+            for (dest, template) in files.iter() {
+                let (dest, template) = match (dest.as_str(), template.as_str()) {
+                    (Some(dest), Some(template)) => (PathBuf::from(dest), PathBuf::from(template)),
+                    _ => return Err(Box::new(ErrorKind::PkgListInvalid)),
+                };
+                // Configs belong to the plugin, so they can't be written anywhere else
+                if !dest.components().all(|c| matches!(c, Component::Normal(_))) {
+                    return Err(Box::new(ErrorKind::PkgListInvalid));
+                }
+                configs.push((dest, self.paths.project_dir.join(template)));
+            }
+        }
+
+        Ok(configs)
+    }
+
+    /// Renders the config templates for a package that was just installed, replacing whatever
+    /// configs were there. On top of the variables from the config file, templates can use
+    /// `package`, `version` and `server_version`. A config that was changed on the server since
+    /// dropper last rendered it is left alone, and the new render is written next to it with a
+    /// `.new` extension instead. Returns the files that were written.
+    fn render_configs(&self, name: &str, version: &str) -> Result<Vec<PathBuf>, Box<Error>> {
+        let configs = self.pkg_configs(name)?;
+        if configs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut variables = self.variables.clone();
+        for (key, value) in [
+            ("package", name),
+            ("version", version),
+            ("server_version", self.server_version.as_str()),
+        ]
+        .iter()
+        {
+            variables
+                .entry(key.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+
+        // Plugins keep their configs in a folder named after the name they register under
+        let data_dir = match self.installed_description(name, version) {
+            Ok(description) => self.data_dir(name, &description.name)?,
+            Err(_) => self.data_dir(name, name)?,
+        };
+
+        let mut state = State::load(&self.paths.state_file())?;
+        let mut rendered_configs = state
+            .packages
+            .get(name)
+            .map(|package| package.configs.clone())
+            .unwrap_or_default();
+        let mut written = Vec::new();
+        for (dest, template_path) in configs {
+            let rendered = template::render(
+                &template_path.display().to_string(),
+                &fs::read_to_string(&template_path)?,
+                &variables,
+            )?;
+
+            let mut dest = data_dir.join(dest);
+            let file = self.relative_file(&dest);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if self.config_modified(&dest, rendered_configs.get(&file))? {
+                // The recorded MD5 is kept, so the config still shows as changed
+                self.warn(format!(
+                    "{} was changed since it was rendered, so it was left alone, and the new \
+                     render was saved next to it",
+                    file
+                ));
+                let mut new_name = dest.file_name().unwrap_or_default().to_os_string();
+                new_name.push(".new");
+                dest.set_file_name(new_name);
+            } else {
+                rendered_configs.insert(file, format!("{:x}", md5::compute(&rendered)));
+            }
+            if dest.exists() {
+                self.journal_remove(&dest)?;
+            }
+            self.journal_added(&dest)?;
+            fs::write(&dest, rendered)?;
+            written.push(dest);
+        }
+
+        if let Some(package) = state.packages.get_mut(name) {
+            package.configs = rendered_configs;
+            state.save(&self.paths.state_file())?;
+        }
+        Ok(written)
+    }
+
+    /// The data folder of a package's plugin, which is named after the name the plugin registers
+    /// under. That name comes from the plugin's JAR, so it has to be a single folder name before
+    /// it's trusted with a path.
+    fn data_dir(&self, name: &str, plugin_name: &str) -> Result<PathBuf, Box<Error>> {
+        let mut components = Path::new(plugin_name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => Ok(self.paths.plugin_dir.join(plugin_name)),
+            _ => Err(Box::new(ErrorKind::PluginNameInvalid(
+                name.to_string(),
+                plugin_name.to_string(),
+            ))),
+        }
+    }
+
+    /// Whether a rendered config no longer has the MD5 it was rendered with. Configs that were
+    /// never rendered, or have been deleted, haven't been changed.
+    fn config_modified(&self, path: &Path, recorded: Option<&String>) -> Result<bool, Box<Error>> {
+        match recorded {
+            Some(md5) if path.exists() => Ok(&hash::md5_file(path)? != md5),
+            _ => Ok(false),
+        }
+    }
+
+    /// Checks the configs dropper rendered against what it rendered, to find the ones that were
+    /// changed or deleted on the server since
+    ///
+    /// # Errors
+    /// * [`state::ErrorKind::StateInvalid`](../state/enum.ErrorKind.html#variant.StateInvalid) - the state file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_config_drift(&self) -> Result<ConfigReport, Box<Error>> {
+        let state = State::load(&self.paths.state_file())?;
+        let mut report = ConfigReport::default();
+        for (name, package) in state.packages.iter() {
+            for (file, md5) in package.configs.iter() {
+                let path = self.paths.plugin_dir.join(file);
+                let entry = (name.clone(), file.clone());
+                if !path.exists() {
+                    report.deleted.push(entry);
+                } else if self.config_modified(&path, Some(md5))? {
+                    report.modified.push(entry);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Checks whether each package in the pkg.yml has a build for another server version. This is
+    /// meant for deciding whether the server can be upgraded, so the constraints in the pkg.yml
+    /// still apply.
+    ///
+    /// # Arguments
+    ///
+    /// * `parser` - A package parser for the server version in question
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_compatibility(
+        &self,
+        parser: &PluginFetchable,
+    ) -> Result<Vec<(String, Compatibility)>, Box<Error>> {
+        let requirements = self.pkg_requirements()?;
+
+        Ok(requirements
+            .iter()
+            .filter(|r| r.required_by == PKG_LIST_REQUIRER)
+            .map(|r| {
+                let compatibility = match parser.enumerate_files(&r.package) {
+                    Ok(Some(files)) => {
+                        let versions = Self::in_channel(&r.package, &requirements, files)
+                            .into_iter()
+                            .map(|file| file.version)
+                            .collect::<Vec<String>>();
+                        match resolver::resolve(&r.package, &requirements, &versions) {
+                            Ok(i) => Compatibility::Compatible(versions[i].clone()),
+                            Err(_) => Compatibility::Incompatible,
+                        }
+                    }
+                    Ok(None) => Compatibility::Incompatible,
+                    Err(e) => Compatibility::Unknown(e.to_string()),
+                };
+                (r.package.clone(), compatibility)
+            })
+            .collect())
+    }
+
+    /// Works out what moving the server to another version would take. This has to be asked of a
+    /// backend for the new version, made with a package parser for it and with its
+    /// `server_version` set to it, since the packages are looked up the way they would be once
+    /// the server is moved. Nothing is changed.
+    ///
+    /// A package whose pkg.yml constraint rules out every build for the new version, but that
+    /// has one the rest of the requirements allow, has its constraint moved to that build.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_migration(&self) -> Result<Migration, Box<Error>> {
+        let requirements = self.pkg_requirements()?;
+        let mut migration = Migration {
+            server_version: self.server_version.clone(),
+            ..Migration::default()
+        };
+
+        let listed = requirements
+            .iter()
+            .filter(|r| r.required_by == PKG_LIST_REQUIRER)
+            .collect::<Vec<&Requirement>>();
+        self.prefetch(&listed.iter().map(|r| r.package.clone()).collect::<Vec<String>>());
+        for listed in listed {
+            let name = &listed.package;
+            let why = match self.resolve_package(name, &requirements) {
+                Ok(Some((version, _))) => {
+                    migration.packages.push((name.clone(), version));
+                    continue;
+                }
+                Ok(None) => ErrorKind::PkgNotFound(name.clone()).to_string(),
+                Err(e) => e.to_string(),
+            };
+
+            // Only the user's own constraint can be moved
+            let others = requirements
+                .iter()
+                .filter(|r| !(r.required_by == PKG_LIST_REQUIRER && &r.package == name))
+                .cloned()
+                .collect::<Vec<Requirement>>();
+            match self.resolve_package(name, &others) {
+                Ok(Some((version, _))) if !self.has_own_source(name) => {
+                    migration.constraints.push((
+                        name.clone(),
+                        listed.constraint.to_string(),
+                        version.clone(),
+                    ));
+                    migration.packages.push((name.clone(), version));
+                }
+                _ => migration.blockers.push((name.clone(), why)),
+            }
+        }
+
+        Ok(migration)
+    }
+
+    /// Moves the server to another version, as worked out by `pkg_migration`: the config's
+    /// `server_version` and the pkg.yml constraints are changed, and every package that needs
+    /// another version for the new server version is updated, as one operation. Like
+    /// `pkg_migration`, this has to be asked of a backend for the new version. Returns the
+    /// (name, version) of each package that was installed.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::MigrationBlocked`](enum.ErrorKind.html#variant.MigrationBlocked) - some packages have no build for the new version
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`ErrorKind::PkgNotFound`](enum.ErrorKind.html#variant.PkgNotFound) - a required package doesn't exist
+    /// * [`resolver::ErrorKind::Conflict`](../resolver/enum.ErrorKind.html#variant.Conflict) - the constraints on a package can't all be met
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_migrate(&self, migration: &Migration) -> Result<Vec<(String, String)>, Box<Error>> {
+        if migration.is_blocked() {
+            return Err(Box::new(ErrorKind::MigrationBlocked(
+                migration.server_version.clone(),
+                migration.blockers.iter().map(|(name, _)| name.clone()).collect(),
+            )));
+        }
+
+        self.transaction(
+            &format!("migrate --to {}", migration.server_version),
+            || self.migrate(migration),
+        )
+    }
+
+    /// Does the work of `pkg_migrate`
+    fn migrate(&self, migration: &Migration) -> Result<Vec<(String, String)>, Box<Error>> {
+        if !migration.constraints.is_empty() {
+            // Constraints are changed wherever they're written, which may be the base entries
+            // even when there's an environment
+            let mut pkg_list = self.read_pkg_list()?;
+            let mut section = self.pkg_section(&pkg_list)?;
+            for (name, _, constraint) in migration.constraints.iter() {
+                match Self::pkg_list_key(&section, name) {
+                    Some(_) => Self::set_entry_version(&mut section, name, constraint),
+                    None => Self::set_entry_version(&mut pkg_list, name, constraint),
+                }
+            }
+            self.set_pkg_section(&mut pkg_list, section);
+            self.write_pkg_list(pkg_list)?;
+        }
+        Self::write_server_version(&self.paths, &migration.server_version)?;
+
+        let requirements = self.pkg_requirements()?;
+        let resolved = self.resolve_all(&requirements)?;
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        let state = State::load(&self.paths.state_file())?;
+        let mut installed = Vec::new();
+        for (name, version, link) in resolved {
+            let old_version = lockfile.packages.get(&name).map(|locked| locked.version.as_str());
+            if old_version == Some(version.as_str()) {
+                continue;
+            }
+            let reason = Self::default_reason(&state, &requirements, &name);
+            self.install_resolved(&name, &version, &link, reason, old_version)?;
+            installed.push((name, version));
+        }
+
+        Ok(installed)
+    }
+
+    /// Changes the `server_version` in the config. The config is written by hand, so only that
+    /// line is touched, to keep the rest of it as it was.
+    fn write_server_version(paths: &ProjectPaths, server_version: &str) -> Result<(), Box<Error>> {
+        let config = fs::read_to_string(paths.config_file())?;
+        let line = format!("server_version: \"{}\"", server_version);
+        let mut found = false;
+        let mut lines = config
+            .lines()
+            .map(|l| match l.starts_with("server_version:") {
+                true => {
+                    found = true;
+                    line.clone()
+                }
+                false => l.to_string(),
+            })
+            .collect::<Vec<String>>();
+        if !found {
+            lines.push(line);
+        }
+        fs::write(paths.config_file(), format!("{}\n", lines.join("\n")))?;
+        Ok(())
+    }
+
+    /// Finds the newest version of a package that satisfies all of the given requirements,
+    /// returning its (version, link), or `None` if the package doesn't exist.
+    ///
+    /// With `compat_fallback` on, a package with no suitable build for the server version falls
+    /// back on the newest suitable build for the closest older server version, with a warning
+    /// (or an error, in strict mode).
+    fn resolve_package(
+        &self,
+        name: &str,
+        requirements: &[Requirement],
+    ) -> Result<Option<(String, String)>, Box<Error>> {
+        let result = self.package_files(name).and_then(|files| match files {
+            Some(ref files) if files.is_empty() => Err(self.no_builds(name)),
+            files => Self::resolve_files(name, requirements, self.unblocked(name, files)),
+        });
+        // Only package parsers know about builds for other server versions
+        if !self.compat_fallback || self.has_own_source(name) {
+            return result;
+        }
+        match &result {
+            Ok(_) => return result,
+            Err(e) if !Self::is_missing_build(&**e) => return result,
+            Err(_) => {}
+        }
+
+        let server = Version::find(&self.server_version);
+        let package_parser = self.parser_of(name)?;
+        for version in package_parser.game_versions() {
+            // Only older server versions are any good
+            if Version::find(&version) >= server {
+                continue;
+            }
+            let parser = match package_parser.for_game_version(&version) {
+                Some(parser) => parser,
+                None => break,
+            };
+            if let Ok(Some(found)) = self.resolve_with(parser.as_ref(), name, requirements) {
+                self.doubt(
+                    Check::Fallback,
+                    name,
+                    ErrorKind::CompatFallback(
+                        name.to_string(),
+                        self.server_version.clone(),
+                        version,
+                    ),
+                )?;
+                return Ok(Some(found));
+            }
+        }
+
+        result
+    }
+
+    /// Does the work of `resolve_package` with a specific package parser
+    fn resolve_with(
+        &self,
+        parser: &PluginFetchable,
+        name: &str,
+        requirements: &[Requirement],
+    ) -> Result<Option<(String, String)>, Box<Error>> {
+        let files = self.unblocked(name, parser.enumerate_files(name)?);
+        Self::resolve_files(name, requirements, files)
+    }
+
+    /// Refuses versions on the blocklist, unless `allow_blocked` is set
+    ///
+    /// # Errors
+    /// * [`ErrorKind::VersionBlocked`](enum.ErrorKind.html#variant.VersionBlocked) - the version is blocked
+    fn check_blocked(&self, name: &str, version: &str) -> Result<(), Box<Error>> {
+        match (self.blocklist.reason(name, version), self.allow_blocked) {
+            (Some(reason), false) => Err(Box::new(ErrorKind::VersionBlocked(
+                name.to_string(),
+                version.to_string(),
+                reason.to_string(),
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Leaves the blocked versions out of a package's files, so that resolving it picks the newest
+    /// version that isn't blocked. Nothing is left out if `allow_blocked` is set.
+    fn unblocked(&self, name: &str, files: Option<Vec<PluginFile>>) -> Option<Vec<PluginFile>> {
+        match self.allow_blocked {
+            true => files,
+            false => files.map(|files| {
+                files
+                    .into_iter()
+                    .filter(|file| self.blocklist.reason(name, &file.version).is_none())
+                    .collect()
+            }),
+        }
+    }
+
+    /// Does the work of `resolve_package` with a package's files, or `None` if it doesn't exist
+    fn resolve_files(
+        name: &str,
+        requirements: &[Requirement],
+        files: Option<Vec<PluginFile>>,
+    ) -> Result<Option<(String, String)>, Box<Error>> {
+        let files = match files {
+            Some(files) => files,
+            None => return Ok(None),
+        };
+
+        let files = Self::in_channel(name, requirements, files);
+        let versions = files
+            .iter()
+            .map(|file| file.version.clone())
+            .collect::<Vec<String>>();
+        let i = resolver::resolve(name, requirements, &versions)?;
+        Ok(Some((files[i].version.clone(), files[i].link.clone())))
+    }
+
+    /// Leaves out the files that aren't in the channels the requirements on a package track.
+    /// Versions don't say which channel they're in, so this has to happen before they're
+    /// resolved.
+    fn in_channel(
+        name: &str,
+        requirements: &[Requirement],
+        files: Vec<PluginFile>,
+    ) -> Vec<PluginFile> {
+        let channel = requirements
+            .iter()
+            .filter(|r| r.package.eq_ignore_ascii_case(name))
+            .filter_map(|r| match r.constraint {
+                Constraint::Channel(channel) => Some(channel),
+                _ => None,
+            })
+            .max();
+        match channel {
+            Some(channel) => files
+                .into_iter()
+                .filter(|file| file.channel >= channel)
+                .collect(),
+            None => files,
+        }
+    }
+
+    /// Fetches the files of many packages at once, a few at a time, so that operations on all of
+    /// them don't wait on the plugin website one package after another. What's fetched is kept
+    /// for as long as the backend is around. Packages that fail to fetch, or that aren't found,
+    /// which may only be because the website is having trouble, are left for whatever needs them
+    /// to fetch again, and report.
+    pub fn prefetch(&self, names: &[String]) {
+        let missing = names
+            .iter()
+            .filter(|name| !self.prefetched.borrow().contains_key(*name))
+            .filter(|name| !self.has_own_source(name))
+            .cloned()
+            .collect::<Vec<String>>();
+
+        // Packages from sources there's no parser for are left to fail when they're needed
+        let missing = missing
+            .into_iter()
+            .filter_map(|name| self.parser_of(&name).ok().map(|parser| (name, parser)))
+            .collect::<Vec<(String, &PluginFetchable)>>();
+
+        // Errors can't be sent between threads, and only packages that were found are kept
+        let fetched = parallel_map(&missing, |(name, parser)| {
+            match parser.enumerate_files(name) {
+                Ok(Some(files)) => Some(files),
+                _ => None,
+            }
+        });
+
+        let mut prefetched = self.prefetched.borrow_mut();
+        for ((name, _), files) in missing.into_iter().zip(fetched) {
+            if let Some(files) = files {
+                prefetched.insert(name, files);
+            }
+        }
+    }
+
+    /// The files of a package, from what was prefetched if it was. Packages built from source
+    /// only have the one file, for the ref the pkg.yml asks for, and so do packages downloaded
+    /// from a URL.
+    fn package_files(&self, name: &str) -> Result<Option<Vec<PluginFile>>, Box<Error>> {
+        self.cancel.check()?;
+        if let Some(git) = self.git_sources.borrow().get(name) {
+            return Ok(Some(vec![PluginFile::new(git.version(), git.link())]));
+        }
+        if let Some(direct) = self.direct_sources.borrow().get(name) {
+            return Ok(Some(vec![PluginFile::new(
+                direct.version.clone(),
+                direct.url.clone(),
+            )]));
+        }
+        if let Some(maven) = self.maven_sources.borrow().get(name) {
+            return maven.enumerate_files(&self.client, &self.credentials);
+        }
+        if let Some(listing) = self.listing_sources.borrow().get(name) {
+            return listing.enumerate_files(&self.client, &self.credentials);
+        }
+        if let Some(repo) = self.repo_sources.borrow().get(name) {
+            return Ok(self.repo_metadata(repo)?.map(|metadata| metadata.versions));
+        }
+        if let Some(github) = self.github_sources.borrow().get(name) {
+            return github.enumerate_files(&self.client, &self.credentials);
+        }
+        if let Some(hangar) = self.hangar_sources.borrow().get(name) {
+            return hangar.enumerate_files(&self.client, &self.credentials);
+        }
+        if let Some(downloads) = self.downloads_sources.borrow().get(name) {
+            return downloads.enumerate_files(&self.client, &self.credentials);
+        }
+        if let Some(files) = self.prefetched.borrow().get(name) {
+            return Ok(Some(files.clone()));
+        }
+        self.parser_of(name)?.enumerate_files(name)
+    }
+
+    /// Everything a package's source says about it, or `None` if the source doesn't have it.
+    /// Packages built from source only have the one version, for the ref the pkg.yml asks for.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::SourceUnknown`](enum.ErrorKind.html#variant.SourceUnknown) - the package comes from a source there's no parser for
+    /// * [`maven::ErrorKind::MetadataInvalid`](../maven/enum.ErrorKind.html#variant.MetadataInvalid) - the package's Maven metadata couldn't be read
+    /// * [`repo::ErrorKind::IndexInvalid`](../repo/enum.ErrorKind.html#variant.IndexInvalid) - the package's repository index couldn't be read
+    /// * [`parser::ErrorKind::RequestFailed`](../parser/enum.ErrorKind.html#variant.RequestFailed) - the plugin website couldn't be reached
+    pub fn pkg_metadata(&self, name: &str) -> Result<Option<PackageMetadata>, Box<Error>> {
+        // Reading the pkg.yml is what finds out which packages have sources of their own
+        self.pkg_requirements()?;
+        if self.git_sources.borrow().contains_key(name)
+            || self.direct_sources.borrow().contains_key(name)
+            || self.listing_sources.borrow().contains_key(name)
+            || self.github_sources.borrow().contains_key(name)
+            || self.hangar_sources.borrow().contains_key(name)
+            || self.downloads_sources.borrow().contains_key(name)
+        {
+            return Ok(self
+                .package_files(name)?
+                .map(|files| PackageMetadata::from_files(name, files)));
+        }
+        if let Some(maven) = self.maven_sources.borrow().get(name) {
+            return maven.package_metadata(&self.client, &self.credentials);
+        }
+        if let Some(repo) = self.repo_sources.borrow().get(name) {
+            return self.repo_metadata(repo);
+        }
+        self.parser_of(name)?.package_metadata(name)
+    }
+
+    /// Lists every version a package's source knows of, newest first, or `None` if the source
+    /// doesn't have the package. A version in the package specifier, like `worldedit@6.*`,
+    /// leaves out the versions it doesn't cover.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgSpecInvalid`](enum.ErrorKind.html#variant.PkgSpecInvalid) - the package specifier is malformed
+    /// * [`ErrorKind::SourceUnknown`](enum.ErrorKind.html#variant.SourceUnknown) - the package comes from a source there's no parser for
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`parser::ErrorKind::RequestFailed`](../parser/enum.ErrorKind.html#variant.RequestFailed) - the plugin website couldn't be reached
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_versions(
+        &self,
+        pkg_specifier: &str,
+    ) -> Result<Option<Vec<VersionListing>>, Box<Error>> {
+        let requirements = self.pkg_requirements()?;
+        let (source, name, version) = Self::parse_package_specifier(pkg_specifier.to_string())?;
+        // Naming a source skips the package's preset, like it does when installing
+        if let Some(source) = source {
+            if self.preset_packages.borrow().contains(&name) {
+                self.forget_own_source(&name);
+            }
+            self.source_parser(&source)?;
+            self.set_source(&name, Some(&source));
+        }
+        let files = match self.package_files(&name)? {
+            Some(files) => files,
+            None => return Ok(None),
+        };
+
+        let installed = Lockfile::load(&self.paths.lockfile())?
+            .packages
+            .get(&name)
+            .map(|locked| locked.version.clone());
+        let wanted = version.map(|version| Constraint::parse(&version));
+        let constraints = requirements
+            .iter()
+            .filter(|r| r.package.eq_ignore_ascii_case(&name))
+            .map(|r| &r.constraint)
+            .collect::<Vec<&Constraint>>();
+        let allows = |constraint: &Constraint, file: &PluginFile| match constraint {
+            Constraint::Channel(channel) => file.channel >= *channel,
+            constraint => constraint.matches(&file.version),
+        };
+
+        Ok(Some(
+            files
+                .into_iter()
+                .filter(|file| wanted.as_ref().is_none_or(|wanted| allows(wanted, file)))
+                .map(|file| VersionListing {
+                    compatible: file.is_for(&self.server_version),
+                    allowed: constraints.iter().all(|constraint| allows(constraint, &file)),
+                    blocked: self
+                        .blocklist
+                        .reason(&name, &file.version)
+                        .map(|reason| reason.to_string()),
+                    installed: installed.as_ref() == Some(&file.version),
+                    file: file,
+                })
+                .collect(),
+        ))
+    }
+
+    /// What a self-hosted repository says about a package, fetching its index if it hasn't been
+    /// yet
+    fn repo_metadata(&self, repo: &RepoSource) -> Result<Option<PackageMetadata>, Box<Error>> {
+        if !self.repo_indexes.borrow().contains_key(&repo.index_url) {
+            let index = Index::fetch(&self.client, &self.credentials, &repo.index_url)?;
+            self.repo_indexes
+                .borrow_mut()
+                .insert(repo.index_url.clone(), index);
+        }
+        let indexes = self.repo_indexes.borrow();
+        Ok(repo.package_metadata(&indexes[&repo.index_url], &self.server_version))
+    }
+
+    /// Suggests the package that was probably meant by a package specifier that didn't find one:
+    /// the closest name among the packages dropper already knows about and the ones a search for
+    /// it turns up. The suggestion keeps the specifier's source and version. Searching is only a
+    /// nicety here, so if it fails there's just less to suggest from.
+    ///
+    /// # Arguments
+    ///
+    /// * `pkg_specifier` - The package specifier that didn't find a package
+    /// * `searcher` - Searches the plugin website
+    pub fn pkg_suggest(&self, pkg_specifier: &str, searcher: &PluginSearchable) -> Option<String> {
+        let (source, name, version) =
+            Self::parse_package_specifier(pkg_specifier.to_string()).ok()?;
+
+        let mut candidates = complete::remembered(&self.paths.names_file());
+        if let Ok(lockfile) = Lockfile::load(&self.paths.lockfile()) {
+            candidates.extend(lockfile.packages.keys().cloned());
+        }
+        // Only the plugin website can be searched
+        if source.as_ref().is_none_or(|source| *source == self.plugin_website) {
+            if let Ok(listings) = searcher.search_listings(&name, &SearchFilters::default()) {
+                candidates.extend(listings.into_iter().map(|listing| listing.name));
+            }
+        }
+
+        let candidates = candidates
+            .iter()
+            .map(|candidate| candidate.as_str())
+            .filter(|candidate| !candidate.eq_ignore_ascii_case(&name))
+            .collect::<Vec<&str>>();
+        let closest = Self::closest_match(&name, &candidates)?;
+        Some(format!(
+            "{}{}{}",
+            source.map_or(String::new(), |source| format!("{}{}", source, SOURCE_SPLIT_CHAR)),
+            closest,
+            version.map_or(String::new(), |version| format!("{}{}", VERSION_SPLIT_CHAR, version))
+        ))
+    }
+
+    /// Whether an error from resolving a package means it has no suitable build for the server
+    /// version, rather than something having gone wrong
+    fn is_missing_build(e: &(Error + 'static)) -> bool {
+        if let Some(parser::ErrorKind::ServerVersionNotFound(_)) = e.downcast_ref() {
+            return true;
+        }
+        if let Some(ErrorKind::NoBuilds(_, _)) = e.downcast_ref() {
+            return true;
+        }
+        matches!(
+            e.downcast_ref::<resolver::ErrorKind>(),
+            Some(resolver::ErrorKind::Conflict(_, _))
+        )
+    }
+
+    /// Downloads a package's JAR into the plugin directory, checking it against the `expected`
+    /// digests, or against the digests the plugin website publishes if those aren't given.
+    ///
+    /// If the download is a distribution, the JARs that `jars` match are installed out of it, or
+    /// the one plugin JAR in it if there are no patterns. Its default configs are installed too
+    /// if the package's pkg.yml entry has `bundled_configs: true`.
+    ///
+    /// The JAR is downloaded into the staging directory first, so a half finished or corrupt
+    /// download never ends up where the server would load it. If the JAR being replaced is in use, the download
+    /// stays staged until `apply_staged` can swap it in.
+    fn download(
+        &self,
+        name: &str,
+        version: &str,
+        link: &str,
+        expected: Option<&BTreeMap<String, String>>,
+        jars: &[Pattern],
+    ) -> Result<Downloaded, Box<Error>> {
+        let expected = match expected {
+            Some(hashes) => hashes.clone(),
+            None => self.published_hashes(name, link)?,
+        };
+        let file_name = self.installed_file(name, version);
+        // A bundle being installed has the file already
+        let bundled = self.bundled.borrow().get(link).cloned();
+        let (partial, picked) = Self::fetch_partial(
+            &self.downloader(),
+            &self.paths,
+            &file_name,
+            bundled.as_ref().map_or(link, |bundled| bundled.as_str()),
+            &expected,
+            jars,
+        )?;
+        let staging_dir = self.paths.staging_dir();
+        let distribution = Self::distribution_partial(&self.paths, &file_name);
+
+        // Read the API version out of the JAR before it goes anywhere the server would load it.
+        // Addons aren't loaded by the server, so it doesn't matter what they target.
+        let description = match addon::is_addon_file(&file_name) {
+            true => None,
+            false => PluginDescription::from_jar(&partial).ok(),
+        };
+        if let Some(description) = &description {
+            if let Err(e) = self.check_api_version(name, description) {
+                fs::remove_file(&partial)?;
+                if distribution.exists() {
+                    fs::remove_file(&distribution)?;
+                }
+                return Err(e);
+            }
+            self.check_command_collisions(description)?;
+        }
+
+        let mut files = Vec::new();
+        let mut extras = Vec::new();
+        if distribution.exists() {
+            for jar in picked.iter().skip(1) {
+                extras.push(Self::stage_extra_jar(&self.paths, &distribution, &file_name, jar)?);
+            }
+            if let (Some(description), true) = (&description, self.has_bundled_configs(name)?) {
+                files = self.extract_bundled_configs(
+                    name,
+                    &distribution,
+                    &picked[0],
+                    &description.name,
+                )?;
+            }
+            fs::remove_file(&distribution)?;
+        }
+
+        let staged = staging_dir.join(&file_name);
+        if self.link_from_store(&partial, &staged) {
+            fs::remove_file(&partial)?;
+        } else {
+            fs::rename(&partial, &staged)?;
+        }
+        self.swap_in(&staged)?;
+        for extra in extras.iter() {
+            self.swap_in(extra)?;
+        }
+
+        Ok(Downloaded {
+            algorithms: expected.keys().cloned().collect(),
+            jars: match jars.is_empty() {
+                true => Vec::new(),
+                false => picked,
+            },
+            files: files,
+        })
+    }
+
+    /// The digests published for a file of a package, wherever the package comes from. Nothing
+    /// is published for some sources, in which case there are none.
+    fn published_hashes(
+        &self,
+        name: &str,
+        link: &str,
+    ) -> Result<BTreeMap<String, String>, Box<Error>> {
+        // Nothing is published for builds
+        if GitSource::from_link(link).is_some() {
+            return Ok(BTreeMap::new());
+        }
+        // Listings are often of a Maven repository's folders, and are checked like one
+        if self.maven_sources.borrow().contains_key(name)
+            || self.listing_sources.borrow().contains_key(name)
+        {
+            return MavenSource::published_hashes(&self.client, &self.credentials, link);
+        }
+        // Only the pkg.yml can say what a file from a URL should be
+        if let Some(direct) = self.direct_sources.borrow().get(name) {
+            return Ok(direct.hashes.clone());
+        }
+        // and only the index or API a file was listed by can say what a file from a repository,
+        // GitHub, Hangar or a download API should be
+        if self.repo_sources.borrow().contains_key(name)
+            || self.github_sources.borrow().contains_key(name)
+            || self.hangar_sources.borrow().contains_key(name)
+            || self.downloads_sources.borrow().contains_key(name)
+        {
+            return Ok(self
+                .package_files(name)?
+                .unwrap_or_default()
+                .into_iter()
+                .find(|file| file.link == link)
+                .map_or(BTreeMap::new(), |file| file.hashes));
+        }
+        // A website's files page may list them too, and it's been read already to find the file
+        let listed = self
+            .package_files(name)?
+            .unwrap_or_default()
+            .into_iter()
+            .find(|file| file.link == link)
+            .map_or(BTreeMap::new(), |file| file.hashes);
+        match listed.is_empty() {
+            true => self.parser_of(name)?.published_hashes(link),
+            false => Ok(listed),
+        }
+    }
+
+    /// Reads the patterns a package's pkg.yml entry picks the JARs to install out of its
+    /// distribution with. They're matched against the JARs' paths inside the distribution, and
+    /// can be given as a list or as a single pattern:
+    ///
+    /// ```yaml
+    /// essentialsx:
+    ///   version: "2.*"
+    ///   jars:
+    ///     - "EssentialsX-*.jar"
+    ///     - "EssentialsXChat-*.jar"
+    /// ```
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted, or a pattern is invalid
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    fn jar_patterns(&self, name: &str) -> Result<Vec<Pattern>, Box<Error>> {
+        let hash = self.pkg_entries()?;
+        let patterns = match Self::pkg_list_key(&hash, name).and_then(|key| hash.get(&key)) {
+            Some(entry) => match &entry["jars"] {
+                Yaml::Array(patterns) => patterns.iter().map(|p| p.as_str()).collect(),
+                Yaml::String(pattern) => Some(vec![pattern.as_str()]),
+                Yaml::BadValue => Some(Vec::new()),
+                _ => None,
+            },
+            None => Some(Vec::new()),
+        };
+
+        patterns
+            .ok_or_else(|| Box::new(ErrorKind::PkgListInvalid) as Box<Error>)?
+            .into_iter()
+            .map(|p| Pattern::new(p).map_err(|_| Box::new(ErrorKind::PkgListInvalid) as Box<Error>))
+            .collect()
+    }
+
+    /// The patterns that pick out exactly the JARs a package was locked with
+    fn locked_patterns(locked: &LockedPackage) -> Vec<Pattern> {
+        locked
+            .jars
+            .iter()
+            .filter_map(|jar| Pattern::new(&Pattern::escape(jar)).ok())
+            .collect()
+    }
+
+    /// Takes a JAR besides the main one out of a distribution and into the staging directory,
+    /// under the name it's installed as. Returns where it was staged.
+    fn stage_extra_jar(
+        paths: &ProjectPaths,
+        distribution: &Path,
+        file_name: &str,
+        jar: &str,
+    ) -> Result<PathBuf, Box<Error>> {
+        let staged = paths.staging_dir().join(lock::extra_jar_name(file_name, jar));
+        let partial = staged.with_extension(format!("jar.{}", PARTIAL_EXTENSION));
+        jar::extract_file(distribution, jar, &partial)?;
+        fs::rename(&partial, &staged)?;
+        Ok(staged)
+    }
+
+    /// Whether a package's pkg.yml entry asks for the configs bundled with its distribution
+    fn has_bundled_configs(&self, name: &str) -> Result<bool, Box<Error>> {
+        let hash = self.pkg_entries()?;
+        Ok(Self::pkg_list_key(&hash, name)
+            .and_then(|key| hash.get(&key))
+            .and_then(|entry| entry["bundled_configs"].as_bool())
+            .unwrap_or(false))
+    }
+
+    /// Takes the default configs out of a distribution and into the plugin's data folder,
+    /// journaling each one so that they're taken back out if the install is rolled back. Configs
+    /// that are already on the server are left alone. Returns the configs that were taken out,
+    /// relative to the plugin directory.
+    fn extract_bundled_configs(
+        &self,
+        name: &str,
+        distribution: &Path,
+        jar: &str,
+        plugin_name: &str,
+    ) -> Result<Vec<String>, Box<Error>> {
+        let data_dir = self.data_dir(name, plugin_name)?;
+        let extracted = jar::extract_data_folder(distribution, jar, plugin_name, &data_dir)?;
+
+        let mut files = Vec::new();
+        for file in extracted {
+            self.journal_added(&data_dir.join(&file))?;
+            files.push(lock::file_name(&Path::new(plugin_name).join(file)));
+        }
+        Ok(files)
+    }
+
+    /// Stages a download as a link into the content store, if the install strategy calls for
+    /// it. Returns `false` if the download should be staged as a copy instead, warning about it
+    /// if that's because the link couldn't be made.
+    fn link_from_store(&self, partial: &Path, staged: &Path) -> bool {
+        if self.install_strategy == InstallStrategy::Copy {
+            return false;
+        }
+        let result = match store::store_dir() {
+            Some(store) => {
+                // Remembered first, so that pruning never misses a link
+                let remembered = match self.install_strategy {
+                    InstallStrategy::Symlink => {
+                        store::remember_linked_dir(&store, &self.paths.plugin_dir)
+                    }
+                    _ => Ok(()),
+                };
+                remembered.and_then(|()| {
+                    store::link(&store, self.install_strategy, partial, staged)
+                })
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "there's no cache folder to keep it in",
+            )),
+        };
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                self.warn(format!(
+                    "couldn't {} {} from the content store ({}), so it was copied instead",
+                    self.install_strategy.name(),
+                    staged.file_name().unwrap().to_string_lossy(),
+                    e
+                ));
+                false
+            }
+        }
+    }
+
+    /// Downloads a JAR into the staging directory, under a name the server and `apply_staged`
+    /// both leave alone, and checks it against the `expected` digests. The download is kept
+    /// under `max_download_rate` bytes per second, if that's given, and given up on if `cancel` is
+    /// cancelled. Returns where the download was saved. Files with `file://` links, which local
+    /// repositories have, are copied instead.
+    ///
+    /// Blocked downloads tend to get an error page rather than an error status, so the download
+    /// has to look like a JAR as well: it can't be a web page, the name it's served under (from
+    /// `Content-Disposition`, or the URL) has to end in `.jar` or `.zip`, and it has to be a ZIP
+    /// archive.
+    ///
+    /// A ZIP with a plugin JAR in it, or with JARs that `jars` match, is a distribution. The
+    /// main JAR is taken out of it, and the distribution is kept next to the download, where
+    /// [`distribution_partial`](#method.distribution_partial) says, for its other JARs and its
+    /// configs. The digests are those of the distribution, or those of the JAR the lockfile pins.
+    /// Returns where the JAR was saved, and where the JARs are inside the distribution, the main
+    /// one's first, if it was one.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::DownloadFailed`](enum.ErrorKind.html#variant.DownloadFailed) - the download failed, or isn't a JAR
+    /// * [`ErrorKind::HashMismatch`](enum.ErrorKind.html#variant.HashMismatch) - the download didn't match the digest
+    /// * [`cancel::ErrorKind::Cancelled`](../cancel/enum.ErrorKind.html#variant.Cancelled) - the download was cancelled
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    fn fetch_partial(
+        downloader: &Downloader,
+        paths: &ProjectPaths,
+        file_name: &str,
+        link: &str,
+        expected: &BTreeMap<String, String>,
+        jars: &[Pattern],
+    ) -> Result<(PathBuf, Vec<String>), Box<Error>> {
+        let Downloader {
+            client,
+            credentials,
+            max_download_rate,
+            cancel,
+        } = *downloader;
+        cancel.check()?;
+        if let Some(git) = GitSource::from_link(link) {
+            return Ok((Self::build_partial(paths, file_name, &git)?, Vec::new()));
+        }
+
+        let failed = |why: String| Box::new(ErrorKind::DownloadFailed(link.to_string(), why));
+        let partial = paths
+            .staging_dir()
+            .join(format!("{}.{}", file_name, PARTIAL_EXTENSION));
+        // Files in local repositories are copied, since there's nothing to request them from
+        if let Some(path) = repo::local_file(link) {
+            if !path.is_file() {
+                return Err(Box::new(ErrorKind::DownloadGone(link.to_string())));
+            }
+            fs::create_dir_all(partial.parent().unwrap())?;
+            fs::copy(&path, &partial)?;
+            return Self::check_partial(paths, partial, file_name, link, expected, jars);
+        }
+
+        let response = credentials.fetch(client, link)?;
+        // Where the download went tells apart a removed file from a blocked host
+        let traced = |why: String| match http::trace_redirects(link, credentials) {
+            Ok(trace) => failed(format!("{}\n{}", why, trace)),
+            Err(_) => failed(why),
+        };
+
+        // A file that was taken down isn't coming back, however many times it's retried
+        match response.status() {
+            StatusCode::NOT_FOUND | StatusCode::GONE => {
+                return Err(Box::new(ErrorKind::DownloadGone(link.to_string())))
+            }
+            status if !status.is_success() => {
+                return Err(traced(format!("the server answered {}", status)))
+            }
+            _ => {}
+        }
+        if http::is_html(&response) {
+            return Err(traced(
+                "got a web page instead of a JAR; the download may be blocked".to_string(),
+            ));
+        }
+        // Addons are whatever their plugin loads, so there's no telling what they should look like
+        let addon = addon::is_addon_file(file_name);
+        if let (Some(served_as), false) = (http::file_name(&response), addon) {
+            // Only file names with an extension tell us anything
+            let served_as_lower = served_as.to_lowercase();
+            if served_as.contains('.')
+                && !served_as_lower.ends_with(".jar")
+                && !served_as_lower.ends_with(".zip")
+            {
+                return Err(failed(format!("got {}, which isn't a JAR", served_as)));
+            }
+        }
+
+        fs::create_dir_all(partial.parent().unwrap())?;
+        {
+            // Windows won't move a file that is still open, so it's closed at the end of this block
+            let mut plugin_file = File::create(&partial)?;
+            let mut response = Cancellable::new(response, cancel.clone());
+            let copied = match max_download_rate {
+                Some(rate) => copy(&mut http::Throttled::new(response, rate), &mut plugin_file),
+                None => copy(&mut response, &mut plugin_file),
+            };
+            if let Err(e) = copied {
+                drop(plugin_file);
+                fs::remove_file(&partial)?;
+                cancel.check()?;
+                return Err(Box::new(e));
+            }
+        }
+        Self::check_partial(paths, partial, file_name, link, expected, jars)
+    }
+
+    /// What this backend's packages are downloaded with
+    fn downloader(&self) -> Downloader<'_> {
+        Downloader {
+            client: &self.client,
+            credentials: &self.credentials,
+            max_download_rate: self.max_download_rate,
+            cancel: &self.cancel,
+        }
+    }
+
+    /// Checks a download that `fetch_partial` saved, taking the JAR out of it if it's a
+    /// distribution. Downloads that aren't what they should be are removed, or quarantined if
+    /// they don't match their digests.
+    fn check_partial(
+        paths: &ProjectPaths,
+        partial: PathBuf,
+        file_name: &str,
+        link: &str,
+        expected: &BTreeMap<String, String>,
+        jars: &[Pattern],
+    ) -> Result<(PathBuf, Vec<String>), Box<Error>> {
+        let failed = |why: String| Box::new(ErrorKind::DownloadFailed(link.to_string(), why));
+        if addon::is_addon_file(file_name) {
+            return match hash::verify_file(&partial, expected)? {
+                Some(mismatch) => Err(Self::quarantine_download(
+                    paths, &partial, file_name, link, mismatch,
+                )?),
+                None => Ok((partial, Vec::new())),
+            };
+        }
+        if !jar::is_zip(&partial)? {
+            fs::remove_file(&partial)?;
+            return Err(failed("the file isn't a JAR".to_string()));
+        }
+
+        let distribution = Self::distribution_partial(paths, file_name);
+        let remove_download = || -> io::Result<()> {
+            fs::remove_file(&partial)?;
+            match distribution.exists() {
+                true => fs::remove_file(&distribution),
+                false => Ok(()),
+            }
+        };
+        let mut picked = Vec::new();
+        match jar::distribution_jars(&partial, jars) {
+            Ok(Some(found)) => {
+                fs::rename(&partial, &distribution)?;
+                jar::extract_file(&distribution, &found[0], &partial)?;
+                picked = found;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                remove_download()?;
+                return Err(failed(match e.downcast_ref::<jar::ErrorKind>() {
+                    Some(jar::ErrorKind::DistributionAmbiguous(_, jars)) => format!(
+                        "it has several plugin JARs in it ({}); pick which to install with `jars`",
+                        jars.join(", ")
+                    ),
+                    Some(jar::ErrorKind::DistributionUnmatched(_, patterns)) => {
+                        format!("none of the JARs in it match {}", patterns.join(", "))
+                    }
+                    _ => e.to_string(),
+                }));
+            }
+        }
+        if let Err(e) = jar::validate(&partial) {
+            remove_download()?;
+            return Err(failed(match e.downcast_ref::<jar::ErrorKind>() {
+                Some(jar::ErrorKind::DescriptionMissing(_)) => {
+                    "the JAR has no plugin.yml, so it isn't a plugin".to_string()
+                }
+                Some(jar::ErrorKind::ArchiveInvalid(_)) => {
+                    "the JAR is truncated or corrupt".to_string()
+                }
+                _ => e.to_string(),
+            }));
+        }
+
+        let downloaded = match distribution.exists() {
+            true => &distribution,
+            false => &partial,
+        };
+        let mut mismatch = hash::verify_file(downloaded, expected)?;
+        // The lockfile pins what was installed, which is the JAR of a distribution
+        if mismatch.is_some()
+            && downloaded != &partial
+            && hash::verify_file(&partial, expected)?.is_none()
+        {
+            mismatch = None;
+        }
+        if let Some(mismatch) = mismatch {
+            if downloaded != &partial {
+                fs::remove_file(&partial)?;
+            }
+            return Err(Self::quarantine_download(
+                paths, downloaded, file_name, link, mismatch,
+            )?);
+        }
+
+        Ok((partial, picked))
+    }
+
+    /// Where `fetch_partial` keeps the distribution a JAR was taken out of, until its configs
+    /// have been taken out too. It's named like a partial download, so it's never swapped in.
+    fn distribution_partial(paths: &ProjectPaths, file_name: &str) -> PathBuf {
+        paths
+            .staging_dir()
+            .join(format!("{}.zip.{}", file_name, PARTIAL_EXTENSION))
+    }
+
+    /// Moves a download that didn't match its digest into the quarantine directory rather than
+    /// deleting it, since it may have been tampered with or corrupted on the way, and writes a
+    /// report next to it saying where it came from and how it didn't match. Downloads are kept
+    /// under the time they were quarantined, so that repeated failures don't overwrite each
+    /// other. Returns the [`ErrorKind::HashMismatch`](enum.ErrorKind.html#variant.HashMismatch)
+    /// to give up with, which says where the download was moved to.
+    ///
+    /// # Arguments
+    ///
+    /// * `partial` - The download in the staging directory
+    /// * `file_name` - The name the JAR would have been installed under
+    /// * `link` - Where the download came from
+    /// * `digests` - The digest's algorithm, the expected digest, and the actual digest
+    fn quarantine_download(
+        paths: &ProjectPaths,
+        partial: &Path,
+        file_name: &str,
+        link: &str,
+        digests: (String, String, String),
+    ) -> Result<Box<Error>, Box<Error>> {
+        let (algorithm, expected, actual) = digests;
+        // Addons are named with the folder they go in, which isn't wanted here
+        let file_name = Path::new(file_name).file_name().unwrap().to_string_lossy();
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let quarantine_dir = paths.quarantine_dir();
+        fs::create_dir_all(&quarantine_dir)?;
+        let quarantined = quarantine_dir.join(format!("{}-{}", time, file_name));
+        fs::rename(partial, &quarantined)?;
+
+        let mut report = Hash::new();
+        let mut insert = |key: &str, value: String| {
+            report.insert(Yaml::from_str(key), Yaml::String(value));
+        };
+        insert("url", link.to_string());
+        insert("file", file_name.to_string());
+        insert("algorithm", algorithm.clone());
+        insert("expected", expected.clone());
+        insert("actual", actual.clone());
+        report.insert(Yaml::from_str("time"), Yaml::Integer(time as i64));
+
+        let mut tmp_string = String::new();
+        YamlEmitter::new(&mut tmp_string)
+            .dump(&Yaml::Hash(report))
+            .unwrap();
+        let report_file = quarantine_dir.join(format!("{}-{}.report.yml", time, file_name));
+        fs::write(report_file, format!("{}\n", tmp_string))?;
+        Ok(Box::new(ErrorKind::HashMismatch(
+            link.to_string(),
+            algorithm,
+            expected,
+            actual,
+            quarantined,
+        )))
+    }
+
+    /// Builds a package from source into the staging directory, like `fetch_partial` downloads
+    /// one, reusing a cached build if there is one. Builds aren't reproducible, so there are no
+    /// digests to check the JAR against.
+    fn build_partial(
+        paths: &ProjectPaths,
+        file_name: &str,
+        git: &GitSource,
+    ) -> Result<PathBuf, Box<Error>> {
+        let work_dir = paths.build_dir().join(file_name.trim_end_matches(".jar"));
+        let cache = build::build_cache_dir();
+        let built = git.build(&work_dir, cache.as_deref())?;
+
+        let staging_dir = paths.staging_dir();
+        fs::create_dir_all(&staging_dir)?;
+        let partial = staging_dir.join(format!("{}.{}", file_name, PARTIAL_EXTENSION));
+        fs::copy(&built, &partial)?;
+        fs::remove_dir_all(&work_dir)?;
+
+        Ok(partial)
+    }
+
+    /// Makes sure a plugin doesn't target a newer server API than the server provides, by
+    /// comparing as many components of the version as the plugin's `api-version` has (so that
+    /// `1.13` is fine on a `1.13.2` server). Servers with versions that aren't numbered aren't
+    /// checked.
+    ///
+    /// Plugins without an `api-version` are fine, except in strict mode on 1.13 and newer
+    /// servers, which only load them as legacy plugins.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::CheckFailed`](enum.ErrorKind.html#variant.CheckFailed) - in strict mode, the plugin targets a newer API or doesn't say which
+    fn check_api_version(
+        &self,
+        name: &str,
+        description: &PluginDescription,
+    ) -> Result<(), Box<Error>> {
+        let api_version = match &description.api_version {
+            Some(v) => v,
+            None => {
+                let legacy = Version::find(&self.server_version)
+                    .is_some_and(|server| server >= Version::from_components(vec![1, 13]));
+                if self.strict && legacy {
+                    self.doubt(
+                        Check::GameVersion,
+                        name,
+                        ErrorKind::ApiVersionMissing(
+                            description.name.clone(),
+                            self.server_version.clone(),
+                        ),
+                    )?;
+                }
+                return Ok(());
+            }
+        };
+
+        match (
+            Version::find(api_version),
+            Version::find(&self.server_version),
+        ) {
+            (Some(api), Some(server)) => {
+                // `api-version: 1.13` is good for every 1.13.x server
+                let server = Version::from_components(
+                    server
+                        .components
+                        .into_iter()
+                        .take(api.components.len())
+                        .collect(),
+                );
+                if api > server {
+                    self.doubt(
+                        Check::ApiVersion,
+                        name,
+                        ErrorKind::ApiVersionTooNew(
+                            description.name.clone(),
+                            api_version.clone(),
+                            self.server_version.clone(),
+                        ),
+                    )?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Warns about the commands a plugin registers that an installed plugin registers too, by
+    /// name or alias. Only one of them gets to handle the command, and which one it is isn't
+    /// obvious. Other versions of the same plugin don't count.
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured while reading the plugin directory
+    fn check_command_collisions(&self, description: &PluginDescription) -> Result<(), Box<Error>> {
+        // Command names aren't case sensitive
+        let labels = |plugin: &PluginDescription| {
+            plugin
+                .commands
+                .iter()
+                .flat_map(|command| {
+                    let mut labels = vec![command.name.to_lowercase()];
+                    labels.extend(command.aliases.iter().map(|alias| alias.to_lowercase()));
+                    labels
+                })
+                .collect::<Vec<String>>()
+        };
+        let new_labels = labels(description);
+        if new_labels.is_empty() {
+            return Ok(());
+        }
+
+        for installed in jar::installed_plugins(&self.paths.plugin_dir)? {
+            if installed.name.eq_ignore_ascii_case(&description.name) {
+                continue;
+            }
+            let mut shared = labels(&installed)
+                .into_iter()
+                .filter(|label| new_labels.contains(label))
+                .map(|label| format!("/{}", label))
+                .collect::<Vec<String>>();
+            shared.sort();
+            shared.dedup();
+            if !shared.is_empty() {
+                self.warn(format!(
+                    "{} and {} both register {}; only one of them will handle {}",
+                    description.name,
+                    installed.name,
+                    shared.join(", "),
+                    match shared.len() {
+                        1 => "it",
+                        _ => "each",
+                    }
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `version` is older than `other`. Versions that aren't numbered can't be told
+    /// apart, so neither is older.
+    fn is_older(version: &str, other: &str) -> bool {
+        match (Version::parse(version), Version::parse(other)) {
+            (Some(version), Some(other)) => version < other,
+            _ => false,
+        }
+    }
+
+    /// Raises a doubt about whether a package will work on the server. Outside of strict mode it
+    /// is a warning. In strict mode it fails the operation, unless the check was forced, in which
+    /// case the override is remembered for the package's history entry.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::CheckFailed`](enum.ErrorKind.html#variant.CheckFailed) - strict mode is on, and the check wasn't forced
+    fn doubt(&self, check: Check, name: &str, doubt: ErrorKind) -> Result<(), Box<Error>> {
+        if !self.strict {
+            self.warn(format!("{}; it may not work", doubt));
+        } else if self.forced.contains(&check) {
+            self.warn(format!("{}; going ahead because of --force", doubt));
+            self.overrides
+                .borrow_mut()
+                .push((name.to_string(), check));
+        } else {
+            return Err(Box::new(ErrorKind::CheckFailed(check, doubt.to_string())));
+        }
+        Ok(())
+    }
+
+    /// Records a problem that didn't stop an operation, but that the user should hear about
+    fn warn(&self, warning: String) {
+        self.warnings.borrow_mut().push(warning);
+    }
+
+    /// Takes the warnings raised since this was last called, for the frontend to display
+    pub fn take_warnings(&self) -> Vec<String> {
+        self.warnings.replace(Vec::new())
+    }
+
+    /// Moves a staged JAR into the plugin directory, replacing any JAR of the same name. Returns
+    /// `false` and leaves the JAR staged if the one it replaces is locked.
+    fn swap_in(&self, staged: &Path) -> Result<bool, Box<Error>> {
+        // The staging directory is laid out like the plugin directory, for addons' folders
+        let target = match staged.strip_prefix(self.paths.staging_dir()) {
+            Ok(file) => self.paths.plugin_dir.join(file),
+            Err(_) => return Ok(false),
+        };
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Move whatever is being replaced aside first, so it can be restored
+        if target.exists() {
+            if let Err(e) = self.journal_remove(&target) {
+                return match e.downcast_ref::<io::Error>() {
+                    Some(e) if Self::is_locked(e) => Ok(false),
+                    _ => Err(e),
+                };
+            }
+        }
+
+        self.journal_added(&target)?;
+        fs::rename(staged, &target)?;
+        Self::remove_empty_parents(staged, &self.paths.staging_dir());
+        Ok(true)
+    }
+
+    /// Runs an operation that changes the server, journaling it so that it can be rolled back if
+    /// dropper is killed partway through. Operations started from inside another one are part of
+    /// the outer operation's journal.
+    fn transaction<T, F>(&self, description: &str, f: F) -> Result<T, Box<Error>>
+    where
+        F: FnOnce() -> Result<T, Box<Error>>,
+    {
+        self.journaled(description, false, f)
+    }
+
+    /// Undoes everything a journaled operation did, for `journaled`, given the downloads that
+    /// were staged before it started
+    fn roll_back(&self, staged: &[PathBuf]) -> Result<(), Box<Error>> {
+        Journal::recover(&self.paths.journal_dir())?;
+        self.remove_partial_downloads()?;
+        for path in self.staged()? {
+            if !staged.contains(&path) {
+                fs::remove_file(&path)?;
+            }
+        }
+        Self::remove_empty_dirs(&self.paths.staging_dir());
+        Ok(())
+    }
+
+    /// Runs an operation like `transaction`, except that failing at all rolls back everything it
+    /// did, rather than only being cancelled doing so
+    fn atomic_transaction<T, F>(&self, description: &str, f: F) -> Result<T, Box<Error>>
+    where
+        F: FnOnce() -> Result<T, Box<Error>>,
+    {
+        self.journaled(description, true, f)
+    }
+
+    /// Does the work of `transaction` and `atomic_transaction`
+    fn journaled<T, F>(&self, description: &str, atomic: bool, f: F) -> Result<T, Box<Error>>
+    where
+        F: FnOnce() -> Result<T, Box<Error>>,
+    {
+        if self.journal.borrow().is_some() {
+            return f();
+        }
+        self.cancel.check()?;
+
+        let manifests = vec![
+            self.paths.config_file(),
+            self.paths.pkg_list(),
+            self.paths.lockfile(),
+            self.paths.state_file(),
+            self.paths.history_file(),
+        ];
+        // Downloads left waiting on the server are only the operation's own if they're new
+        let staged = self.staged()?;
+        let journal = Journal::begin(&self.paths.journal_dir(), description, &manifests)?;
+        *self.journal.borrow_mut() = Some(journal);
+
+        // Errors are reported as they happen, and everything up to them is kept, so the journal
+        // is only for when the operation is cut short. Cancelling cuts it short on purpose.
+        let result = f();
+        let journal = self.journal.borrow_mut().take().unwrap();
+        match &result {
+            Err(e) if atomic || cancel::is_cancelled(&**e) => {
+                drop(journal);
+                if let Err(rollback) = self.roll_back(&staged) {
+                    return Err(Box::new(ErrorKind::RollbackFailed(
+                        e.to_string(),
+                        rollback.to_string(),
+                    )));
+                }
+            }
+            _ => journal.commit()?,
+        }
+        if result.is_ok() {
+            self.cap_caches();
+        }
+        result
+    }
+
+    /// Prunes the caches down to `cache_max_size`, if there is one. Pruning is only to save
+    /// space, so it failing is warned about rather than failing the change that came before it.
+    fn cap_caches(&self) {
+        let max_size = match self.cache_max_size {
+            Some(max_size) => max_size,
+            None => return,
+        };
+        let caches = cache::caches(&self.paths.http_cache_dir());
+        if let Err(e) = cache::prune(&caches, Some(max_size), None) {
+            self.warn(format!("couldn't prune the caches: {}", e));
+        }
+    }
+
+    /// Removes the downloads in the staging directory that never finished
+    fn remove_partial_downloads(&self) -> Result<(), Box<Error>> {
+        let staging_dir = self.paths.staging_dir();
+        if !staging_dir.exists() {
+            return Ok(());
+        }
+        for path in Self::staged_files(&staging_dir)? {
+            if path.extension().is_some_and(|ext| ext == PARTIAL_EXTENSION) {
+                fs::remove_file(&path)?;
+            }
+        }
+        Self::remove_empty_dirs(&staging_dir);
+        Ok(())
+    }
+
+    /// Whether a file in the plugin directory is on the config's ignore list
+    pub fn is_ignored(&self, file_name: &str) -> bool {
+        self.ignore.iter().any(|pattern| pattern.matches(file_name))
+    }
+
+    /// Records that a JAR is about to be put into the plugin directory
+    fn journal_added(&self, path: &Path) -> Result<(), Box<Error>> {
+        match self.journal.borrow_mut().as_mut() {
+            Some(journal) => journal.record_added(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Removes a JAR from the plugin directory, in a way the journal can undo
+    fn journal_remove(&self, path: &Path) -> Result<(), Box<Error>> {
+        match self.journal.borrow_mut().as_mut() {
+            Some(journal) => journal.remove_file(path),
+            None => Ok(fs::remove_file(path)?),
+        }
+    }
+
+    /// Windows doesn't let files that a running server has open be replaced or deleted. Other
+    /// platforms don't lock files, so this is never the case there.
+    fn is_locked(e: &io::Error) -> bool {
+        // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+        cfg!(windows)
+            && (e.kind() == io::ErrorKind::PermissionDenied
+                || e.raw_os_error() == Some(32)
+                || e.raw_os_error() == Some(33))
+    }
+
+    /// Swaps in any JARs that were left staged because the JARs they replace were in use,
+    /// returning the ones that made it into the plugin directory. Leftovers from downloads that
+    /// never finished are cleaned up too. Frontends should call this before doing anything else.
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn apply_staged(&self) -> Result<Vec<PathBuf>, Box<Error>> {
+        self.transaction("swap in staged downloads", || self.swap_in_staged())
+    }
+
+    /// Does the work of `apply_staged`
+    fn swap_in_staged(&self) -> Result<Vec<PathBuf>, Box<Error>> {
+        let staging_dir = self.paths.staging_dir();
+        if !staging_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut applied = Vec::new();
+        for path in Self::staged_files(&staging_dir)? {
+            if path.extension().is_some_and(|ext| ext == PARTIAL_EXTENSION) {
+                fs::remove_file(&path)?;
+            } else if self.swap_in(&path)? {
+                applied.push(
+                    self.paths
+                        .plugin_dir
+                        .join(path.strip_prefix(&staging_dir).unwrap()),
+                );
+            }
+        }
+
+        Ok(applied)
+    }
+
+    /// Removes a folder if there are only empty folders in it, along with them
+    fn remove_empty_dirs(dir: &Path) {
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                if entry.path().is_dir() {
+                    Self::remove_empty_dirs(&entry.path());
+                }
+            }
+        }
+        fs::remove_dir(dir).ok();
+    }
+
+    /// Lists the files in the staging directory, including those in addons' folders
+    fn staged_files(dir: &Path) -> Result<Vec<PathBuf>, Box<Error>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            match path.is_dir() {
+                true => files.extend(Self::staged_files(&path)?),
+                false => files.push(path),
+            }
+        }
+        Ok(files)
+    }
+
+    /// Lists the JARs that are downloaded, but waiting on the JARs they replace to stop being used
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn staged(&self) -> Result<Vec<PathBuf>, Box<Error>> {
+        let staging_dir = self.paths.staging_dir();
+        if !staging_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut staged = Vec::new();
+        for path in Self::staged_files(&staging_dir)? {
+            if path.extension().is_none_or(|ext| ext != PARTIAL_EXTENSION) {
+                staged.push(path);
+            }
+        }
+
+        Ok(staged)
+    }
+
+    /// Explains why a package is on the server: what in the pkg.yml requires it, which installed
+    /// packages need it, and which change installed it. Nothing is changed, and the package
+    /// doesn't have to be installed, which shows whether installing it would be asked for.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`state::ErrorKind::StateInvalid`](../state/enum.ErrorKind.html#variant.StateInvalid) - the state file is corrupt
+    /// * [`history::ErrorKind::HistoryInvalid`](../history/enum.ErrorKind.html#variant.HistoryInvalid) - the history file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_why(&self, name: &str) -> Result<Explanation, Box<Error>> {
+        let requirements = self
+            .pkg_requirements()?
+            .into_iter()
+            .filter(|r| r.package.eq_ignore_ascii_case(name))
+            .collect::<Vec<Requirement>>();
+        let locked = Lockfile::load(&self.paths.lockfile())?.packages.remove(name);
+        let state = State::load(&self.paths.state_file())?;
+        let installed_by = match &locked {
+            Some(locked) => History::load(&self.paths.history_file())?
+                .events
+                .into_iter()
+                .filter(|event| event.package == name && event.version == locked.version)
+                .rfind(|event| event.action != "remove"),
+            None => None,
+        };
+
+        Ok(Explanation {
+            name: name.to_string(),
+            version: locked.map(|locked| locked.version),
+            reason: state.packages.get(name).map(|package| package.reason),
+            requirements: requirements,
+            chains: Self::dependency_chains(&state, name),
+            installed_by: installed_by,
+        })
+    }
+
+    /// The chains of installed packages that lead down to a package through what they `depend`
+    /// on, starting from explicitly installed packages. Only the shortest chain from each is
+    /// kept.
+    fn dependency_chains(state: &State, name: &str) -> Vec<Vec<String>> {
+        // The packages whose plugins depend on the plugin a package provides
+        let dependents = |name: &str| {
+            state
+                .packages
+                .iter()
+                .filter(|(_, package)| {
+                    package
+                        .depend
+                        .iter()
+                        .any(|dep| state.provider_of(dep) == Some(name))
+                })
+                .map(|(dependent, _)| dependent.clone())
+                .collect::<Vec<String>>()
+        };
+
+        // Walk up from the package, one level at a time
+        let mut chains = Vec::new();
+        let mut seen = vec![name.to_string()];
+        let mut paths = vec![vec![name.to_string()]];
+        while !paths.is_empty() {
+            let mut next = Vec::new();
+            for path in paths {
+                for dependent in dependents(&path[0]) {
+                    if seen.contains(&dependent) {
+                        continue;
+                    }
+                    seen.push(dependent.clone());
+                    let mut longer = vec![dependent.clone()];
+                    longer.extend(path.iter().cloned());
+                    match state.packages[&dependent].reason {
+                        InstallReason::Explicit => chains.push(longer),
+                        InstallReason::Dependency => next.push(longer),
+                    }
+                }
+            }
+            paths = next;
+        }
+        chains
+    }
+
+    /// Lists every locked package, with why it was installed. Packages the state file doesn't
+    /// know about were locked by hand or by an old version of dropper, and count as explicit.
+    ///
+    /// # Errors
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * [`state::ErrorKind::StateInvalid`](../state/enum.ErrorKind.html#variant.StateInvalid) - the state file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_installed(&self) -> Result<Vec<InstalledListing>, Box<Error>> {
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        let state = State::load(&self.paths.state_file())?;
+        let orphans = state.orphans();
+
+        Ok(lockfile
+            .packages
+            .into_iter()
+            .map(|(name, locked)| InstalledListing {
+                reason: state
+                    .packages
+                    .get(&name)
+                    .map_or(InstallReason::Explicit, |package| package.reason),
+                orphaned: orphans.contains(&name),
+                version: locked.version,
+                name: name,
+            })
+            .collect())
+    }
+
+    /// Compares the pkg.yml, the lockfile, the state file and the plugin directory, and reports
+    /// everything that has drifted apart. Nothing is changed.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * [`state::ErrorKind::StateInvalid`](../state/enum.ErrorKind.html#variant.StateInvalid) - the state file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_status(&self) -> Result<StatusReport, Box<Error>> {
+        let requirements = self.pkg_requirements()?;
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        let state = State::load(&self.paths.state_file())?;
+        let mut report = StatusReport::default();
+
+        for requirement in requirements.iter() {
+            if requirement.required_by != PKG_LIST_REQUIRER {
+                continue;
+            }
+            match lockfile.packages.get(&requirement.package) {
+                None => report.not_installed.push(requirement.package.clone()),
+                Some(locked) if !requirement.constraint.matches(&locked.version) => {
+                    report.mismatched.push((
+                        requirement.package.clone(),
+                        format!(
+                            "pkg.yml wants {}, but {} is locked",
+                            requirement.constraint, locked.version
+                        ),
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+
+        for (name, locked) in lockfile.packages.iter() {
+            let mut files = vec![locked.file.clone()];
+            files.extend(locked.extra_files());
+            for file in files {
+                if !self.paths.plugin_dir.join(&file).exists() {
+                    report.missing.push((name.clone(), file));
+                }
+            }
+            if let Some(installed) = state.packages.get(name) {
+                if installed.version != locked.version {
+                    report.mismatched.push((
+                        name.clone(),
+                        format!(
+                            "{} is locked, but {} was installed last",
+                            locked.version, installed.version
+                        ),
+                    ));
+                }
+            }
+        }
+
+        for (name, manual) in self.manual_packages.borrow().iter() {
+            if !self.paths.plugin_dir.join(&manual.file).exists() {
+                report.manual_missing.push((name.clone(), manual.file.clone()));
+            }
+        }
+
+        report.unmanaged = self.unmanaged_files(&lockfile)?;
+
+        report.staged = self
+            .staged()?
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+
+        Ok(report)
+    }
+
+    /// The JARs in the plugin directory that dropper didn't install, that the pkg.yml doesn't
+    /// say are managed by hand, and that aren't ignored, sorted by name
+    fn unmanaged_files(&self, lockfile: &Lockfile) -> Result<Vec<String>, Box<Error>> {
+        let mut unmanaged = Vec::new();
+        if self.paths.plugin_dir.exists() {
+            for entry in fs::read_dir(&self.paths.plugin_dir)? {
+                let path = entry?.path();
+                if path.extension().is_none_or(|ext| ext != "jar") {
+                    continue;
+                }
+                let file = path.file_name().unwrap().to_string_lossy().to_string();
+                if lockfile.owner_of(&file).is_none()
+                    && self.manual_owner_of(&file).is_none()
+                    && !self.is_ignored(&file)
+                {
+                    unmanaged.push(file);
+                }
+            }
+        }
+        unmanaged.sort();
+        Ok(unmanaged)
+    }
+
+    /// The package managed by hand that a JAR in the plugin directory is, if the pkg.yml, as
+    /// `pkg_requirements` last read it, says it's one
+    fn manual_owner_of(&self, file: &str) -> Option<String> {
+        self.manual_packages
+            .borrow()
+            .iter()
+            .find(|(_, manual)| manual.file == file)
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Reads each JAR in the plugin directory that dropper doesn't manage, and isn't ignored,
+    /// and guesses which project it is on each source, to say what `pkg_adopt` could take it
+    /// under management as. The plugin website and Maven Central are searched for the plugin's
+    /// name, and the indexes of the self-hosted repositories the pkg.yml uses are looked
+    /// through. The guess is the project named like the plugin, ignoring case, spaces and
+    /// punctuation, or else the one with the closest name, if any is close; among Maven
+    /// artifacts named alike, it's the one whose group the plugin's main class is in. Searching
+    /// is only a nicety here, so a source that can't be searched is warned about and skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `searcher` - Searches the plugin website
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_unmanaged(
+        &self,
+        searcher: &PluginSearchable,
+    ) -> Result<Vec<UnmanagedJar>, Box<Error>> {
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        // The pkg.yml says which repositories there are to look through
+        self.pkg_requirements()?;
+
+        let mut jars = Vec::new();
+        for file in self.unmanaged_files(&lockfile)? {
+            self.cancel.check()?;
+            let description = PluginDescription::from_jar(&self.paths.plugin_dir.join(&file)).ok();
+            let guesses = match &description {
+                Some(description) => self.guess_projects(description, searcher),
+                None => Vec::new(),
+            };
+            jars.push(UnmanagedJar {
+                file: file,
+                description: description,
+                guesses: guesses,
+            });
+        }
+        Ok(jars)
+    }
+
+    /// Guesses which project a plugin is on each source, for `pkg_unmanaged`
+    fn guess_projects(
+        &self,
+        description: &PluginDescription,
+        searcher: &PluginSearchable,
+    ) -> Vec<(String, PluginListing)> {
+        let mut guesses = Vec::new();
+        let searches = vec![
+            (
+                self.plugin_website.clone(),
+                searcher.search_listings(&description.name, &SearchFilters::default()),
+            ),
+            (
+                "maven".to_string(),
+                maven::search_central(&self.client, &self.credentials, &description.name),
+            ),
+        ];
+        for (source, found) in searches {
+            match found {
+                Ok(listings) => {
+                    if let Some(listing) = Self::likeliest_listing(description, listings) {
+                        guesses.push((source, listing));
+                    }
+                }
+                Err(e) => self.warn(format!("couldn't search {}: {}", source, e)),
+            }
+        }
+
+        for listings in self.repo_listings() {
+            if let Some(listing) = Self::likeliest_listing(description, listings) {
+                guesses.push(("repo".to_string(), listing));
+            }
+        }
+        guesses
+    }
+
+    /// Lists the packages in the index of each self-hosted repository the pkg.yml fetches from,
+    /// linked to the index they're in. Indexes that can't be fetched are warned about.
+    fn repo_listings(&self) -> Vec<Vec<PluginListing>> {
+        let index_urls = self
+            .repo_sources
+            .borrow()
+            .values()
+            .map(|repo| repo.index_url.clone())
+            .collect::<BTreeSet<String>>();
+        let mut listings = Vec::new();
+        for index_url in index_urls {
+            if !self.repo_indexes.borrow().contains_key(&index_url) {
+                match Index::fetch(&self.client, &self.credentials, &index_url) {
+                    Ok(index) => self.repo_indexes.borrow_mut().insert(index_url.clone(), index),
+                    Err(e) => {
+                        self.warn(format!("couldn't look through {}: {}", index_url, e));
+                        continue;
+                    }
+                };
+            }
+            listings.push(
+                self.repo_indexes.borrow()[&index_url]
+                    .packages
+                    .iter()
+                    .map(|(package, metadata)| PluginListing {
+                        name: package.clone(),
+                        title: metadata.display_name.clone().unwrap_or_else(|| package.clone()),
+                        link: index_url.clone(),
+                        downloads: None,
+                        description: metadata.description.clone(),
+                        keywords: metadata.keywords.clone(),
+                    })
+                    .collect(),
+            );
+        }
+        listings
+    }
+
+    /// Searches the indexes of the self-hosted repositories the pkg.yml fetches from, matching
+    /// the packages' names, descriptions and keywords. Each package is linked to the index it's
+    /// in. Indexes that can't be fetched are warned about.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_search_repos(&self, query: &str) -> Result<Vec<PluginListing>, Box<Error>> {
+        // Reading the pkg.yml is what finds out which repositories there are
+        self.pkg_requirements()?;
+        Ok(self
+            .repo_listings()
+            .into_iter()
+            .flatten()
+            .filter(|listing| listing.matches(query))
+            .collect())
+    }
+
+    /// The listing a source most likely has a plugin as, for `guess_projects`
+    fn likeliest_listing(
+        description: &PluginDescription,
+        listings: Vec<PluginListing>,
+    ) -> Option<PluginListing> {
+        let key = |title: &str| {
+            title
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        };
+        let main = description.main.clone().unwrap_or_default();
+        let in_group = |listing: &PluginListing| match listing.name.find(':') {
+            Some(i) => main.starts_with(&format!("{}.", &listing.name[..i])),
+            None => false,
+        };
+
+        let mut named_alike = listings
+            .iter()
+            .filter(|listing| key(&listing.title) == key(&description.name))
+            .collect::<Vec<&PluginListing>>();
+        named_alike.sort_by_key(|listing| !in_group(listing));
+        if let Some(listing) = named_alike.first() {
+            return Some((*listing).clone());
+        }
+
+        let titles = listings
+            .iter()
+            .map(|listing| listing.title.as_str())
+            .collect::<Vec<&str>>();
+        let closest = Self::closest_match(&description.name, &titles)?.to_string();
+        listings.into_iter().find(|listing| listing.title == closest)
+    }
+
+    /// Checks every locked JAR against the lockfile's digests. Only the main JAR of a package
+    /// has digests, so the others just have to be there.
+    ///
+    /// A deep check also opens each package's main JAR and compares the version its plugin.yml
+    /// declares with the locked one, which says what a JAR that was swapped by hand under the
+    /// same name really is. Plugins often add a build number or a commit to their version, so
+    /// the versions only have to agree as [`versions_agree`](#method.versions_agree) says,
+    /// ignoring case and a leading `v`. Addons don't have a plugin.yml, so they're only checked
+    /// against their digests.
+    ///
+    /// The JARs of packages managed by hand are checked against the digests their pkg.yml
+    /// entries give, and with a deep check, against the versions they give.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_verify(&self, deep: bool) -> Result<VerifyReport, Box<Error>> {
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        let mut report = VerifyReport::default();
+
+        for (name, locked) in lockfile.packages.iter() {
+            let path = self.paths.plugin_dir.join(&locked.file);
+            let mut intact = true;
+            for file in locked.extra_files() {
+                if !self.paths.plugin_dir.join(&file).exists() {
+                    report.missing.push((name.clone(), file));
+                    intact = false;
+                }
+            }
+            if !path.is_file() {
+                report.missing.push((name.clone(), locked.file.clone()));
+                continue;
+            }
+            if hash::verify_file(&path, &locked.hashes)?.is_some() {
+                report.modified.push((name.clone(), locked.file.clone()));
+                intact = false;
+            }
+
+            if deep && !addon::is_addon_file(&locked.file) {
+                let declared = match PluginDescription::from_jar(&path) {
+                    Ok(description) => description.version,
+                    Err(e) => {
+                        report.mismatched.push((
+                            name.clone(),
+                            format!("{}'s plugin.yml couldn't be read: {}", locked.file, e),
+                        ));
+                        continue;
+                    }
+                };
+                if !Self::versions_agree(&locked.version, &declared) {
+                    report.mismatched.push((
+                        name.clone(),
+                        format!(
+                            "{} is locked, but {}'s plugin.yml says it's {}",
+                            locked.version, locked.file, declared
+                        ),
+                    ));
+                    continue;
+                }
+            }
+            if intact {
+                report.verified.push((name.clone(), locked.file.clone()));
+            }
+        }
+
+        self.pkg_requirements()?;
+        for (name, manual) in self.manual_packages.borrow().iter() {
+            let path = self.paths.plugin_dir.join(&manual.file);
+            if !path.is_file() {
+                report.manual_missing.push((name.clone(), manual.file.clone()));
+                continue;
+            }
+            if let Some((algorithm, expected, actual)) = hash::verify_file(&path, &manual.hashes)? {
+                report.manual_mismatched.push((
+                    name.clone(),
+                    format!(
+                        "pkg.yml gives {} {} for {}, but it's {}",
+                        algorithm.to_uppercase(),
+                        expected,
+                        manual.file,
+                        actual
+                    ),
+                ));
+                continue;
+            }
+
+            if let (true, Some(version)) = (deep, &manual.version) {
+                let declared = match PluginDescription::from_jar(&path) {
+                    Ok(description) => description.version,
+                    Err(e) => {
+                        report.manual_mismatched.push((
+                            name.clone(),
+                            format!("{}'s plugin.yml couldn't be read: {}", manual.file, e),
+                        ));
+                        continue;
+                    }
+                };
+                if !Self::versions_agree(version, &declared) {
+                    report.manual_mismatched.push((
+                        name.clone(),
+                        format!(
+                            "pkg.yml gives {}, but {}'s plugin.yml says it's {}",
+                            version, manual.file, declared
+                        ),
+                    ));
+                    continue;
+                }
+            }
+            report.manual_verified.push((name.clone(), manual.file.clone()));
+        }
+
+        Ok(report)
+    }
+
+    /// Whether the version a plugin.yml declares is the version it was locked at, for
+    /// `pkg_verify`: whether the shorter of them is in the longer one, as a whole, so that
+    /// `1.1` agrees with `1.1-b42` and `Foo 1.1`, but not with `1.10` or `1.1.1`
+    fn versions_agree(locked: &str, declared: &str) -> bool {
+        let normalize = |version: &str| {
+            let version = version.trim().to_lowercase();
+            match version.starts_with('v') {
+                true => version[1..].to_string(),
+                false => version,
+            }
+        };
+        let (locked, declared) = (normalize(locked), normalize(declared));
+        let (shorter, longer) = match locked.len() <= declared.len() {
+            true => (locked, declared),
+            false => (declared, locked),
+        };
+        if shorter.is_empty() {
+            return false;
+        }
+        // Whatever's either side of the version can't carry on its numbers
+        longer.match_indices(&shorter).any(|(start, _)| {
+            let before = longer[..start].chars().last();
+            let after = longer[start + shorter.len()..].chars().next();
+            !before.is_some_and(|c| c.is_ascii_alphanumeric() || c == '.')
+                && !after.is_some_and(|c| c.is_ascii_digit() || c == '.')
+        })
+    }
+
+    /// Works out which of the changes between two snapshots of the plugin directory weren't
+    /// made by dropper: JARs dropper installs or removes are in the lockfile before and after,
+    /// so they aren't drift. The `drift` hook is run for each JAR that is. Returns `None` if a
+    /// dropper operation is in progress, since it's only done changing the lockfile once it's
+    /// done changing the plugin directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `before` - The JARs that were in the plugin directory
+    /// * `after` - The JARs that are in the plugin directory now
+    ///
+    /// # Errors
+    /// * [`lock::ErrorKind::LockfileInvalid`](../lock/enum.ErrorKind.html#variant.LockfileInvalid) - the lockfile is incorrectly formatted
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_drift(
+        &self,
+        before: &BTreeSet<String>,
+        after: &BTreeSet<String>,
+    ) -> Result<Option<DriftReport>, Box<Error>> {
+        if self.paths.journal_dir().exists() {
+            return Ok(None);
+        }
+
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        let mut report = DriftReport::default();
+        for file in after.difference(before) {
+            if lockfile.owner_of(file).is_none() && !self.is_ignored(file) {
+                report.added.push(file.clone());
+            }
+        }
+        for file in before.difference(after) {
+            if let Some(name) = lockfile.owner_of(file) {
+                report.removed.push((name.to_string(), file.clone()));
+            }
+        }
+
+        for file in report.added.iter() {
+            self.run_drift_hook(file, "added");
+        }
+        for (_, file) in report.removed.iter() {
+            self.run_drift_hook(file, "removed");
+        }
+
+        Ok(Some(report))
+    }
+
+    /// Moves a JAR out of the plugin directory and into the quarantine directory, where the
+    /// server won't load it but it can still be looked at. Returns where it was moved to.
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_quarantine(&self, file: &str) -> Result<PathBuf, Box<Error>> {
+        let quarantine_dir = self.paths.quarantine_dir();
+        fs::create_dir_all(&quarantine_dir)?;
+        let quarantined = quarantine_dir.join(file);
+        fs::rename(self.paths.plugin_dir.join(file), &quarantined)?;
+        Ok(quarantined)
+    }
+
+    /// Takes a JAR that was put into the plugin directory by hand under dropper's management,
+    /// without downloading it again. The package it's a version of is the one the specifier
+    /// names, or the one named like the plugin its plugin.yml describes, and the version is the
+    /// package's version that agrees with the plugin.yml's, as
+    /// [`versions_agree`](#method.versions_agree) says. If the source publishes digests for that
+    /// version, the JAR has to match them. The JAR is renamed like dropper names the JARs it
+    /// installs, and the package is added to the pkg.yml and the lockfile at that version.
+    /// Returns the (name, version) of the package, or `None` if there's no such package.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The JAR, relative to the plugin directory
+    /// * `pkg_specifier` - The package the JAR is a version of, if it isn't named like the plugin.
+    ///   Any version in it is ignored.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::AdoptFailed`](enum.ErrorKind.html#variant.AdoptFailed) - the JAR is already managed, its plugin.yml can't be read, or it isn't a version of the package
+    /// * [`ErrorKind::PkgSpecInvalid`](enum.ErrorKind.html#variant.PkgSpecInvalid) - the package specifier is malformed
+    /// * [`ErrorKind::SourceUnknown`](enum.ErrorKind.html#variant.SourceUnknown) - the package specifier names a source there's no parser for
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_adopt(
+        &self,
+        file: &str,
+        pkg_specifier: Option<&str>,
+    ) -> Result<Option<(String, String)>, Box<Error>> {
+        self.transaction(&format!("adopt {}", file), || self.adopt(file, pkg_specifier))
+    }
+
+    /// Does the work of `pkg_adopt`
+    fn adopt(
+        &self,
+        file: &str,
+        pkg_specifier: Option<&str>,
+    ) -> Result<Option<(String, String)>, Box<Error>> {
+        let failed = |why: String| Box::new(ErrorKind::AdoptFailed(file.to_string(), why));
+        let path = self.paths.plugin_dir.join(file);
+        if !path.is_file() {
+            return Err(failed("it isn't in the plugin directory".to_string()));
+        }
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        if let Some(owner) = lockfile.owner_of(file) {
+            return Err(failed(format!("it's already installed as {}", owner)));
+        }
+        let description = match PluginDescription::from_jar(&path) {
+            Ok(description) => description,
+            Err(e) => return Err(failed(format!("its plugin.yml couldn't be read: {}", e))),
+        };
+
+        // The pkg.yml may say where the package comes from, or that it's managed by hand
+        self.pkg_requirements()?;
+        if let Some(owner) = self.manual_owner_of(file) {
+            return Err(failed(format!("it's managed by hand, as {}", owner)));
+        }
+        let (source, name) = match pkg_specifier {
+            Some(pkg_specifier) => {
+                let (source, name, _) = Self::parse_package_specifier(pkg_specifier.to_string())?;
+                (source, name)
+            }
+            None => (None, description.name.to_lowercase().replace(' ', "-")),
+        };
+        let source = source.unwrap_or_else(|| self.source_of(&name));
+        self.source_parser(&source)?;
+        let (name, project_id, _) = match self.resolve_project_id(&source, &name, false)? {
+            Some(tup) => tup,
+            None => return Ok(None),
+        };
+        self.set_source(&name, Some(&source));
+        if let Some(locked) = lockfile.packages.get(&name) {
+            return Err(failed(format!("{} is already installed, as {}", name, locked.file)));
+        }
+
+        let candidates = match self.package_files(&name)? {
+            Some(files) => files
+                .into_iter()
+                .filter(|candidate| Self::versions_agree(&candidate.version, &description.version))
+                .collect::<Vec<PluginFile>>(),
+            None => return Ok(None),
+        };
+        if candidates.is_empty() {
+            return Err(failed(format!(
+                "its plugin.yml says it's {} {}, which {} has no version of",
+                description.name, description.version, name
+            )));
+        }
+
+        // A version that's published along with its digests has to match them, and one that's
+        // published without any is only taken if none of the others match
+        let mut adopted = None;
+        for candidate in candidates.iter() {
+            let hashes = self.published_hashes(&name, &candidate.link)?;
+            if hashes.is_empty() {
+                adopted = adopted.or(Some((candidate, hashes)));
+            } else if hash::verify_file(&path, &hashes)?.is_none() {
+                adopted = Some((candidate, hashes));
+                break;
+            }
+        }
+        let (candidate, hashes) = match adopted {
+            Some(adopted) => adopted,
+            None => {
+                return Err(failed(format!(
+                    "it doesn't match what {} publishes for {}",
+                    name,
+                    candidates
+                        .iter()
+                        .map(|candidate| candidate.version.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                )))
+            }
+        };
+
+        let target = self.paths.plugin_dir.join(self.installed_file(&name, &candidate.version));
+        if target != path {
+            self.journal_added(&target)?;
+            fs::copy(&path, &target)?;
+            self.journal_remove(&path)?;
+        }
+        let downloaded = Downloaded {
+            algorithms: hashes.keys().cloned().collect(),
+            ..Downloaded::default()
+        };
+        self.record_install(
+            &name,
+            &candidate.version,
+            &candidate.link,
+            InstallReason::Explicit,
+            &downloaded,
+        )?;
+        if let Some(project_id) = project_id {
+            self.record_project_id(&name, &project_id)?;
+        }
+        self.record_history("adopt", &name, &candidate.version, None)?;
+        self.add_to_pkg_list(&name, &candidate.version)?;
+        if self.save_docs {
+            self.save_package_docs(&name, &candidate.version);
+        }
+
+        Ok(Some((name, candidate.version.clone())))
+    }
+
+    /// Makes the plugin directory match the lockfile exactly: locked JARs that are missing or
+    /// don't match their digest are downloaded from their locked URLs, other versions of locked
+    /// packages are removed, and so is every other JAR that isn't on the ignore list or managed
+    /// by hand. Nothing is resolved, so running it again changes nothing.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`ErrorKind::HashMismatch`](enum.ErrorKind.html#variant.HashMismatch) - a download didn't match the lockfile
+    /// * [`ErrorKind::VersionYanked`](enum.ErrorKind.html#variant.VersionYanked) - a locked version's file has been taken down
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_sync(&self) -> Result<SyncReport, Box<Error>> {
+        self.transaction("sync", || self.sync())
+    }
+
+    /// Does the work of `pkg_sync`
+    fn sync(&self) -> Result<SyncReport, Box<Error>> {
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        // The pkg.yml says which JARs are managed by hand, and have to be left where they are
+        self.pkg_requirements()?;
+        let state = State::load(&self.paths.state_file())?;
+        let mut report = SyncReport::default();
+
+        for (name, locked) in lockfile.packages.iter() {
+            let path = self.paths.plugin_dir.join(&locked.file);
+            let present = path.exists();
+            // Only the main JAR has digests, so the others just have to be there
+            let intact = present
+                && hash::verify_file(&path, &locked.hashes)?.is_none()
+                && locked
+                    .extra_files()
+                    .iter()
+                    .all(|file| self.paths.plugin_dir.join(file).exists());
+            let others = self
+                .installed_jars(name)?
+                .into_iter()
+                .filter(|jar| !locked.owns(&self.relative_file(jar)))
+                .collect::<Vec<PathBuf>>();
+            if intact && others.is_empty() {
+                continue;
+            }
+
+            let mut downloaded = Downloaded::default();
+            if !intact {
+                let patterns = Self::locked_patterns(locked);
+                downloaded = self
+                    .download(name, &locked.version, &locked.url, Some(&locked.hashes), &patterns)
+                    .map_err(|e| self.yanked(name, &locked.version, e))?;
+            }
+            for jar in others.iter() {
+                self.journal_remove(jar)?;
+            }
+
+            let installed = state.packages.get(name);
+            let reason = installed.map_or(InstallReason::Explicit, |p| p.reason);
+            downloaded.algorithms = locked.hashes.keys().cloned().collect();
+            downloaded.jars = locked.jars.clone();
+            self.record_install(name, &locked.version, &locked.url, reason, &downloaded)?;
+            match installed {
+                Some(installed) if installed.version != locked.version => self.record_history(
+                    "update",
+                    name,
+                    &locked.version,
+                    Some(&installed.version),
+                )?,
+                Some(_) => {}
+                None => self.record_history("install", name, &locked.version, None)?,
+            }
+
+            match present || !others.is_empty() {
+                true => report.replaced.push(locked.file.clone()),
+                false => report.installed.push(locked.file.clone()),
+            }
+        }
+
+        if self.paths.plugin_dir.exists() {
+            for entry in fs::read_dir(&self.paths.plugin_dir)? {
+                let path = entry?.path();
+                if !path.is_file() || path.extension().is_none_or(|ext| ext != "jar") {
+                    continue;
+                }
+                let file = path.file_name().unwrap().to_string_lossy().to_string();
+                if lockfile.owner_of(&file).is_none()
+                    && self.manual_owner_of(&file).is_none()
+                    && !self.is_ignored(&file)
+                {
+                    self.journal_remove(&path)?;
+                    report.removed.push(file);
+                }
+            }
+        }
+        report.removed.sort();
+
+        Ok(report)
+    }
+
+    /// Packs the lockfile, the pkg.yml, and the file each locked package was downloaded as into a
+    /// bundle at `path`, to set up a server with no internet access from. JARs that are installed
+    /// and still match the lockfile are bundled as they are. Everything else, including the
+    /// distributions JARs were picked out of, is downloaded again and checked against the
+    /// lockfile first, so a bundle only ever holds what the lockfile pins. The server itself isn't
+    /// changed. Returns the bundle's manifest.
+    ///
+    /// # Errors
+    /// * [`bundle::ErrorKind::LockfileMissing`](../bundle/enum.ErrorKind.html#variant.LockfileMissing) - there's no lockfile to bundle
+    /// * [`bundle::ErrorKind::NameUnsupported`](../bundle/enum.ErrorKind.html#variant.NameUnsupported) - `path` doesn't end in `.zip`
+    /// * [`ErrorKind::HashMismatch`](enum.ErrorKind.html#variant.HashMismatch) - a download didn't match the lockfile
+    /// * [`ErrorKind::VersionYanked`](enum.ErrorKind.html#variant.VersionYanked) - a locked version's file has been taken down
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_bundle(&self, path: &Path) -> Result<Manifest, Box<Error>> {
+        // Before anything is downloaded for it
+        bundle::check_name(path)?;
+        let lockfile_path = self.paths.lockfile();
+        if !lockfile_path.exists() {
+            return Err(Box::new(bundle::ErrorKind::LockfileMissing(
+                lockfile_path.display().to_string(),
+            )));
+        }
+        let lockfile = Lockfile::load(&lockfile_path)?;
+
+        let pkg_list = self.paths.pkg_list();
+        let mut downloads = Vec::new();
+        let manifest = (|| {
+            let mut files = BTreeMap::new();
+            for (name, locked) in lockfile.packages.iter() {
+                let (file, downloaded) = self.bundled_file(name, locked)?;
+                if downloaded {
+                    downloads.push(file.clone());
+                }
+                files.insert(name.clone(), (locked.url.clone(), file));
+            }
+            bundle::create(
+                path,
+                &self.server_version,
+                &lockfile_path,
+                match pkg_list.exists() {
+                    true => Some(&pkg_list),
+                    false => None,
+                },
+                &files,
+            )
+        })();
+
+        // What was downloaded again was only ever for the bundle
+        for download in downloads.iter() {
+            fs::remove_file(download)?;
+        }
+        Self::remove_empty_dirs(&self.paths.staging_dir());
+        manifest
+    }
+
+    /// Installs the packages in a bundle that `pkg_bundle` made, checking it against its manifest
+    /// first. The bundle's lockfile and pkg.yml replace the server's, and the plugin directory is
+    /// then synced with the lockfile like `pkg_sync` does, except that the files come out of the
+    /// bundle instead of being downloaded. They're checked against the lockfile all the same,
+    /// and installs are recorded in the state file and the history like any other. Nothing is
+    /// fetched, so a server with no internet access can be set up this way.
+    ///
+    /// # Errors
+    /// * [`bundle::ErrorKind::BundleInvalid`](../bundle/enum.ErrorKind.html#variant.BundleInvalid) - the bundle is corrupt, doesn't have a file for every locked package, or would install one outside the plugin directory
+    /// * [`bundle::ErrorKind::ArtifactCorrupt`](../bundle/enum.ErrorKind.html#variant.ArtifactCorrupt) - a file in the bundle doesn't match the manifest
+    /// * [`ErrorKind::HashMismatch`](enum.ErrorKind.html#variant.HashMismatch) - a file in the bundle didn't match the lockfile
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the bundle's lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_install_bundle(&self, path: &Path) -> Result<SyncReport, Box<Error>> {
+        self.transaction("bundle install", || {
+            let dir = self.paths.bundle_dir();
+            let result = self.install_bundle(path, &dir);
+            self.bundled.borrow_mut().clear();
+            if dir.exists() {
+                fs::remove_dir_all(&dir)?;
+            }
+            result
+        })
+    }
+
+    /// Does the work of `pkg_install_bundle`, unpacking the bundle into `dir`
+    fn install_bundle(&self, path: &Path, dir: &Path) -> Result<SyncReport, Box<Error>> {
+        // Whatever's there is left from an install that was cut short
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        let unpacked = bundle::unpack(path, dir)?;
+        // Bundles come from elsewhere, so their lockfiles are checked before they replace the
+        // server's, which loading them does
+        let lockfile = Lockfile::load(&unpacked.lockfile).map_err(|e| {
+            let outside = match e.downcast_ref::<lock::ErrorKind>() {
+                Some(lock::ErrorKind::FileOutside(_, name, file)) => format!(
+                    "it would install {} as {}, outside the plugins folder",
+                    name, file
+                ),
+                _ => return e,
+            };
+            Box::new(bundle::ErrorKind::BundleInvalid(path.display().to_string(), outside))
+        })?;
+        for (name, locked) in lockfile.packages.iter() {
+            let artifact = unpacked.manifest.artifacts.get(name);
+            let file = match (artifact, unpacked.artifacts.get(name)) {
+                (Some(artifact), Some(file)) if artifact.url == locked.url => file,
+                _ => {
+                    return Err(Box::new(bundle::ErrorKind::BundleInvalid(
+                        path.display().to_string(),
+                        format!("it has no file for the locked version of {}", name),
+                    )))
+                }
+            };
+            let link = Url::from_file_path(fs::canonicalize(file)?).unwrap();
+            self.bundled
+                .borrow_mut()
+                .insert(locked.url.clone(), link.to_string());
+        }
+        if unpacked.manifest.server_version != self.server_version {
+            self.warn(format!(
+                "the bundle was made for {}, but this server is on {}",
+                unpacked.manifest.server_version, self.server_version
+            ));
+        }
+
+        fs::copy(&unpacked.lockfile, self.paths.lockfile())?;
+        if let Some(pkg_list) = &unpacked.pkg_list {
+            fs::copy(pkg_list, self.paths.pkg_list())?;
+        }
+        self.sync()
+    }
+
+    /// The file a locked package was downloaded as, for `pkg_bundle`, and whether it had to be
+    /// downloaded again for it
+    fn bundled_file(
+        &self,
+        name: &str,
+        locked: &LockedPackage,
+    ) -> Result<(PathBuf, bool), Box<Error>> {
+        let installed = self.paths.plugin_dir.join(&locked.file);
+        if locked.jars.is_empty()
+            && installed.is_file()
+            && hash::verify_file(&installed, &locked.hashes)?.is_none()
+        {
+            return Ok((installed, false));
+        }
+
+        let (partial, _) = Self::fetch_partial(
+            &self.downloader(),
+            &self.paths,
+            &locked.file,
+            &locked.url,
+            &locked.hashes,
+            &Self::locked_patterns(locked),
+        )
+        .map_err(|e| self.yanked(name, &locked.version, e))?;
+        let distribution = Self::distribution_partial(&self.paths, &locked.file);
+        match distribution.exists() {
+            true => {
+                fs::remove_file(&partial)?;
+                Ok((distribution, true))
+            }
+            false => Ok((partial, true)),
+        }
+    }
+
+    /// Writes the SHA-256 of every locked JAR in the plugin directory, and of every JAR managed by
+    /// hand, to a SHA256SUMS file at `path`, replacing what was there. JARs that aren't there are
+    /// left out, and warned about. Returns what was written, as (file, SHA-256).
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_write_checksums(&self, path: &Path) -> Result<Vec<(String, String)>, Box<Error>> {
+        let mut sums = Vec::new();
+        for file in self.managed_files()? {
+            let jar = self.paths.plugin_dir.join(&file);
+            if !jar.is_file() {
+                self.warn(format!("{} isn't in the plugin directory, so it was left out", file));
+                continue;
+            }
+            let sha256 = hash::digest_file(&jar, &["sha256"])?.remove("sha256").unwrap();
+            sums.push((file, sha256));
+        }
+        fs::write(path, checksum::format(&sums))?;
+        Ok(sums)
+    }
+
+    /// Checks the JARs listed in a SHA256SUMS file at `path` against their digests, and that
+    /// every locked JAR, and every JAR managed by hand, is listed
+    ///
+    /// # Errors
+    /// * [`checksum::ErrorKind::SumsInvalid`](../checksum/enum.ErrorKind.html#variant.SumsInvalid) - the file is corrupt
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_check_checksums(&self, path: &Path) -> Result<ChecksumReport, Box<Error>> {
+        let sums = checksum::parse(&path.display().to_string(), &fs::read_to_string(path)?)?;
+        let mut report = ChecksumReport::default();
+        for (file, sha256) in sums.iter() {
+            let jar = self.paths.plugin_dir.join(file);
+            if !jar.is_file() {
+                report.missing.push(file.clone());
+                continue;
+            }
+            let mut expected = BTreeMap::new();
+            expected.insert("sha256".to_string(), sha256.clone());
+            match hash::verify_file(&jar, &expected)? {
+                Some(_) => report.failed.push(file.clone()),
+                None => report.matched.push(file.clone()),
+            }
+        }
+        report.unlisted = self
+            .managed_files()?
+            .into_iter()
+            .filter(|file| !sums.iter().any(|(listed, _)| listed == file))
+            .collect();
+        Ok(report)
+    }
+
+    /// Every JAR the lockfile says dropper installed, and every JAR the pkg.yml says is managed by
+    /// hand, relative to the plugin directory, sorted
+    fn managed_files(&self) -> Result<Vec<String>, Box<Error>> {
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        let mut files = Vec::new();
+        for locked in lockfile.packages.values() {
+            files.push(locked.file.clone());
+            files.extend(locked.extra_files());
+        }
+        self.pkg_requirements()?;
+        for manual in self.manual_packages.borrow().values() {
+            files.push(manual.file.clone());
+        }
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+
+    /// Turns a locked download that's gone into a [`ErrorKind::VersionYanked`](enum.ErrorKind.html#variant.VersionYanked),
+    /// along with the nearest version of the package that's still available. Other errors are
+    /// passed through.
+    fn yanked(&self, name: &str, version: &str, e: Box<Error>) -> Box<Error> {
+        match e.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::DownloadGone(_)) => {}
+            _ => return e,
+        }
+
+        // The pkg.yml says where packages with their own source are looked up
+        let files = match self.pkg_requirements() {
+            Ok(_) => self.package_files(name).ok().and_then(|files| self.unblocked(name, files)),
+            Err(_) => None,
+        };
+        let versions = files
+            .unwrap_or_default()
+            .into_iter()
+            .map(|file| file.version)
+            .filter(|v| v != version)
+            .collect::<Vec<String>>();
+        Box::new(ErrorKind::VersionYanked(
+            name.to_string(),
+            version.to_string(),
+            Self::nearest_version(version, &versions),
+        ))
+    }
+
+    /// The version closest to `version`: the oldest one that's newer, or failing that, the
+    /// newest one that's older
+    fn nearest_version(version: &str, candidates: &[String]) -> Option<String> {
+        let target = Version::parse(version)?;
+        let mut numbered = candidates
+            .iter()
+            .filter_map(|candidate| Version::parse(candidate))
+            .collect::<Vec<Version>>();
+        numbered.sort();
+        numbered
+            .iter()
+            .find(|candidate| **candidate > target)
+            .or_else(|| numbered.iter().rev().find(|candidate| **candidate < target))
+            .map(|candidate| candidate.to_string())
+    }
+
+    /// Installs a package into a sandbox instead of the server, so that it can be looked over
+    /// before it's added for real. The sandbox is a server of its own in `sandbox_dir`, set up
+    /// with this server's config and credentials, and with the package's pkg.yml entry if it has
+    /// one, minus its configs. Hooks aren't run for it. The server itself isn't changed, but
+    /// the commands the package would share with the server's plugins are warned about. The
+    /// sandbox is left for the caller to look through or remove. Returns `None` if the package
+    /// can't be found.
+    ///
+    /// # Arguments
+    ///
+    /// * `pkg_specifier` - The package to try, like for `pkg_install`
+    /// * `sandbox_dir` - Where to set up the sandbox, which shouldn't exist yet
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`ErrorKind::CheckFailed`](enum.ErrorKind.html#variant.CheckFailed) - in strict mode, the package isn't compatible with the server
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_try(
+        &self,
+        pkg_specifier: &str,
+        sandbox_dir: &Path,
+    ) -> Result<Option<Trial>, Box<Error>> {
+        let sandbox_paths = ProjectPaths::new(sandbox_dir.to_path_buf(), None);
+        fs::create_dir_all(sandbox_paths.config_root())?;
+        fs::copy(self.paths.config_file(), sandbox_paths.config_file())?;
+        if self.paths.credentials_file().exists() {
+            fs::copy(
+                self.paths.credentials_file(),
+                sandbox_paths.credentials_file(),
+            )?;
+        }
+
+        // The entry says where the package comes from, but its configs are for the server
+        let (_, name, _) = Self::parse_package_specifier(pkg_specifier.to_string())?;
+        let mut entries = Hash::new();
+        let pkg_entries = self.pkg_entries()?;
+        if let Some(key) = Self::pkg_list_key(&pkg_entries, &name) {
+            let mut entry = pkg_entries[&key].clone();
+            if let Yaml::Hash(settings) = &mut entry {
+                settings.remove(&Yaml::from_str("configs"));
+            }
+            entries.insert(key, entry);
+        }
+        File::create(sandbox_paths.pkg_list())?;
+
+        let client = self.client.clone();
+        let mut sandbox = PackageBackend::new(self.package_parser, sandbox_paths, client)?;
+        sandbox.write_pkg_list(entries)?;
+        sandbox.sources = self.sources.clone();
+        sandbox.server_version = self.server_version.clone();
+        sandbox.forced = self.forced.clone();
+        sandbox.allow_blocked = self.allow_blocked;
+        sandbox.allow_downgrade = self.allow_downgrade;
+        sandbox.max_download_rate = self.max_download_rate;
+        sandbox.install_strategy = InstallStrategy::Copy;
+        sandbox.hooks = Hooks::default();
+        sandbox.save_docs = false;
+        sandbox.cancel = self.cancel.clone();
+
+        let (name, version) = match sandbox.pkg_install(pkg_specifier)? {
+            Some(installed) => installed,
+            None => return Ok(None),
+        };
+
+        let lockfile = Lockfile::load(&sandbox.paths.lockfile())?;
+        let server_lockfile = Lockfile::load(&self.paths.lockfile())?;
+        let mut packages = Vec::new();
+        for listing in sandbox.pkg_installed()? {
+            let file = match lockfile.packages.get(&listing.name) {
+                Some(locked) => sandbox.paths.plugin_dir.join(&locked.file),
+                None => continue,
+            };
+            let description = PluginDescription::from_jar(&file).ok();
+            if listing.name == name {
+                if let Some(description) = &description {
+                    self.check_command_collisions(description)?;
+                }
+            }
+            packages.push(TrialPackage {
+                installed: server_lockfile
+                    .packages
+                    .get(&listing.name)
+                    .map(|locked| locked.version.clone()),
+                contents: jar::entries(&file).unwrap_or_default(),
+                description: description,
+                file: file,
+                name: listing.name,
+                version: listing.version,
+                reason: listing.reason,
+            });
+        }
+        packages.sort_by_key(|package| package.name != name);
+
+        Ok(Some(Trial {
+            name: name,
+            version: version,
+            packages: packages,
+            warnings: sandbox.take_warnings(),
+        }))
+    }
+
+    /// Starts a throwaway copy of the server in `sandbox_dir` with every plugin that's installed,
+    /// and reports the packages whose plugins didn't load. See the
+    /// [`smoke`](../smoke/index.html) module for how the copy is set up. The sandbox is left for
+    /// the caller to look through or remove.
+    ///
+    /// # Errors
+    /// * [`smoke::ErrorKind::TestServerMissing`](../smoke/enum.ErrorKind.html#variant.TestServerMissing) - the config doesn't say how to start a test server
+    /// * [`smoke::ErrorKind::StartFailed`](../smoke/enum.ErrorKind.html#variant.StartFailed) - the test server couldn't be started
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_test(&self, sandbox_dir: &Path) -> Result<TestReport, Box<Error>> {
+        let test_server = match &self.test_server {
+            Some(test_server) => test_server,
+            None => return Err(Box::new(smoke::ErrorKind::TestServerMissing)),
+        };
+
+        let owners = self.plugin_owners()?;
+        let run = test_server.run(
+            &self.paths.project_dir,
+            &self.paths.plugin_dir,
+            sandbox_dir,
+            &self.cancel,
+        )?;
+
+        let mut failed = Vec::new();
+        let mut failed_plugins = BTreeSet::new();
+        for problem in run.problems.iter() {
+            let name = match owners.blame(problem) {
+                Some((_, Some(package))) => package,
+                Some((culprit, None)) => culprit,
+                None => continue,
+            };
+            failed_plugins.insert(name.clone());
+            failed.push((
+                name,
+                problem.explanation.as_ref().unwrap_or(&problem.line).clone(),
+            ));
+        }
+
+        let mut not_enabled = Vec::new();
+        if run.outcome == Outcome::Started {
+            let enabled = run
+                .enabled
+                .iter()
+                .map(|plugin| plugin.to_lowercase())
+                .collect::<BTreeSet<String>>();
+            for (plugin, name) in owners.by_plugin.iter() {
+                if !enabled.contains(plugin) && !failed_plugins.contains(name) {
+                    not_enabled.push(name.clone());
+                }
+            }
+        }
+
+        Ok(TestReport {
+            run: run,
+            failed: failed,
+            not_enabled: not_enabled,
+        })
+    }
+
+    /// Reads a server's console output, as in its `logs/latest.log`, for problems with plugins,
+    /// blames them on the packages they're from, and suggests what to do about them. See the
+    /// [`logs`](../logs/index.html) module for the problems that are found. Problems with
+    /// plugins dropper doesn't manage are diagnosed without suggestions, and stack traces that
+    /// can't be blamed on any plugin are left out.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_analyze(&self, output: &[String]) -> Result<Vec<Diagnosis>, Box<Error>> {
+        let owners = self.plugin_owners()?;
+        let mut diagnoses: Vec<Diagnosis> = Vec::new();
+        for problem in logs::problems(output) {
+            let (culprit, package) = match owners.blame(&problem) {
+                Some(blamed) => blamed,
+                None => continue,
+            };
+            let seen = diagnoses.iter_mut().find(|diagnosis| {
+                diagnosis.culprit == culprit
+                    && diagnosis.what == problem.what
+                    && diagnosis.explanation == problem.explanation
+            });
+            match seen {
+                Some(diagnosis) => diagnosis.count += 1,
+                None => diagnoses.push(Diagnosis {
+                    package: package,
+                    culprit: culprit,
+                    what: problem.what,
+                    explanation: problem.explanation,
+                    count: 1,
+                    suggestions: Vec::new(),
+                }),
+            }
+        }
+
+        // Checking for updates is slow, so it's only done when a package is to blame
+        let ages = match diagnoses.iter().any(|diagnosis| diagnosis.package.is_some()) {
+            true => self.pkg_outdated()?,
+            false => Vec::new(),
+        };
+        let requirements = self.pkg_requirements()?;
+        for diagnosis in diagnoses.iter_mut() {
+            let missing = diagnosis
+                .explanation
+                .as_ref()
+                .map_or(Vec::new(), |explanation| logs::missing_dependencies(explanation));
+            for dependency in missing {
+                let managed = requirements.iter().any(|r| r.package == dependency.to_lowercase())
+                    || owners.by_plugin.contains_key(&dependency.to_lowercase());
+                diagnosis.suggestions.push(match managed {
+                    true => format!(
+                        "it needs {}, which is in the pkg.yml but didn't load; \
+                         check that it's installed with `dropper status`",
+                        dependency
+                    ),
+                    false => format!(
+                        "it needs {}, which isn't installed; add it with `dropper add {}`",
+                        dependency,
+                        dependency.to_lowercase()
+                    ),
+                });
+            }
+
+            let age = match &diagnosis.package {
+                Some(package) => ages.iter().find(|age| age.name == *package),
+                None => continue,
+            };
+            if let Some(age) = age {
+                if let Some(reason) = &age.blocked {
+                    diagnosis.suggestions.push(format!(
+                        "version {} is known to be bad ({}); update or remove it",
+                        age.installed, reason
+                    ));
+                }
+                if let (true, Some(newest)) = (age.is_outdated(), &age.newest) {
+                    diagnosis.suggestions.push(format!(
+                        "version {} is out; update to it with `dropper update {}`",
+                        newest, age.name
+                    ));
+                }
+            }
+        }
+        Ok(diagnoses)
+    }
+
+    /// Reads a crash report, or a log with a watchdog's thread dump in it, for the packages whose
+    /// code is in the crash's stacks, and for what dropper changed in the days before the crash.
+    /// Suspects that were changed shortly before the crash come first, and the rest are in the
+    /// order the stacks name them. Returns `None` if the output isn't a crash report and has no
+    /// thread dump.
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - The report or log, a line at a time
+    /// * `time` - When the server crashed, in seconds since the Unix epoch
+    ///
+    /// # Errors
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * [`history::ErrorKind::HistoryInvalid`](../history/enum.ErrorKind.html#variant.HistoryInvalid) - the history file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_crash(
+        &self,
+        output: &[String],
+        time: u64,
+    ) -> Result<Option<CrashReport>, Box<Error>> {
+        let crash = match logs::crash(output) {
+            Some(crash) => crash,
+            None => return Ok(None),
+        };
+        let owners = self.plugin_owners()?;
+
+        let mut suspects: Vec<Suspect> = Vec::new();
+        for frame in crash.frames.iter() {
+            let package = match owners.owner(frame) {
+                Some(package) => package,
+                None => continue,
+            };
+            match suspects.iter_mut().find(|suspect| suspect.package == *package) {
+                Some(suspect) => suspect.frames += 1,
+                None => suspects.push(Suspect {
+                    package: package.clone(),
+                    plugin: owners.plugins.get(package).unwrap_or(package).clone(),
+                    frame: frame.clone(),
+                    frames: 1,
+                    changes: Vec::new(),
+                }),
+            }
+        }
+
+        let mut changes = Vec::new();
+        let since = time.saturating_sub(CRASH_LOOKBACK);
+        for event in History::load(&self.paths.history_file())?.events {
+            if event.time < since || event.time > time {
+                continue;
+            }
+            match suspects.iter_mut().find(|suspect| suspect.package == event.package) {
+                Some(suspect) => suspect.changes.push(event),
+                None => changes.push(event),
+            }
+        }
+        suspects.sort_by_key(|suspect| suspect.changes.is_empty());
+
+        Ok(Some(CrashReport {
+            description: crash.description,
+            time: time,
+            suspects: suspects,
+            changes: changes,
+        }))
+    }
+
+    /// Finds which package each installed plugin is from, by its JAR, its plugin name, and the
+    /// Java package of its main class
+    ///
+    /// # Errors
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    fn plugin_owners(&self) -> Result<PluginOwners, Box<Error>> {
+        let mut owners = PluginOwners {
+            by_file: BTreeMap::new(),
+            by_plugin: BTreeMap::new(),
+            by_class: Vec::new(),
+            plugins: BTreeMap::new(),
+        };
+        for (name, locked) in Lockfile::load(&self.paths.lockfile())?.packages {
+            if addon::is_addon_file(&locked.file) {
+                continue;
+            }
+            let jar = self.paths.plugin_dir.join(&locked.file);
+            if let Ok(description) = PluginDescription::from_jar(&jar) {
+                owners.by_plugin.insert(description.name.to_lowercase(), name.clone());
+                if let Some(main) = &description.main {
+                    if let Some(dot) = main.rfind('.') {
+                        owners.by_class.push((main[..dot + 1].to_string(), name.clone()));
+                    }
+                }
+                owners.plugins.insert(name.clone(), description.name);
+            }
+            owners.by_file.insert(locked.file, name);
+        }
+        // The most specific package wins when one plugin's classes are nested in another's
+        owners.by_class.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        Ok(owners)
+    }
+
+    /// Reads the plugin.yml of an installed package, and finds the JAR it came from
+    ///
+    /// # Arguments
+    /// * `name` - The package's name. A source in front of it is ignored.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgNotInstalled`](enum.ErrorKind.html#variant.PkgNotInstalled) - there's no JAR for the package
+    /// * [`jar::ErrorKind::DescriptionMissing`](../jar/enum.ErrorKind.html#variant.DescriptionMissing) - the JAR has no plugin.yml
+    /// * [`jar::ErrorKind::DescriptionInvalid`](../jar/enum.ErrorKind.html#variant.DescriptionInvalid) - the plugin.yml could not be understood
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_info(&self, name: &str) -> Result<(PathBuf, PluginDescription), Box<Error>> {
+        let (_, name) = Self::split_source(name);
+        let locked = Lockfile::load(&self.paths.lockfile())?
+            .packages
+            .get(name)
+            .map(|locked| self.paths.plugin_dir.join(&locked.file))
+            .filter(|path| path.exists());
+        // JARs installed before there was a lockfile are found by name
+        let jar = match locked {
+            Some(path) => path,
+            None => match self.installed_jars(name)?.into_iter().next() {
+                Some(path) => path,
+                None => return Err(Box::new(ErrorKind::PkgNotInstalled(name.to_string()))),
+            },
+        };
+
+        let description = PluginDescription::from_jar(&jar)?;
+        Ok((jar, description))
+    }
+
+    /// Checks every installed package against the newest version the pkg.yml allows, along with
+    /// how old each of them is. Packages that can't be checked are warned about, and have no
+    /// newest version.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_outdated(&self) -> Result<Vec<PackageAge>, Box<Error>> {
+        let requirements = self.pkg_requirements()?;
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+        let state = State::load(&self.paths.state_file())?;
+
+        self.prefetch(&lockfile.packages.keys().cloned().collect::<Vec<String>>());
+        let mut ages = Vec::new();
+        for (name, locked) in lockfile.packages.iter() {
+            let mut age = PackageAge {
+                name: name.clone(),
+                installed: locked.version.clone(),
+                installed_at: state.packages.get(name).and_then(|p| p.installed_at),
+                installed_released: None,
+                newest: None,
+                newest_released: None,
+                blocked: self
+                    .blocklist
+                    .reason(name, &locked.version)
+                    .map(|reason| reason.to_string()),
+            };
+
+            match self.package_files(name) {
+                Ok(Some(files)) => {
+                    let installed_released = files
+                        .iter()
+                        .find(|file| file.link == locked.url)
+                        .and_then(|file| file.uploaded);
+                    // Blocked versions and other channels aren't anything to update to
+                    let files = self.unblocked(name, Some(files)).unwrap();
+                    let files = Self::in_channel(name, &requirements, files);
+                    age.installed_released = installed_released;
+
+                    let versions = files
+                        .iter()
+                        .map(|file| file.version.clone())
+                        .collect::<Vec<String>>();
+                    match resolver::resolve(name, &requirements, &versions) {
+                        Ok(i) => {
+                            age.newest = Some(files[i].version.clone());
+                            age.newest_released = files[i].uploaded;
+                        }
+                        Err(e) => self.warn(e.to_string()),
+                    }
+                }
+                Ok(None) => self.warn(format!("{} is no longer on {}", name, self.source_of(name))),
+                Err(e) => self.warn(format!("couldn't check {} for updates: {}", name, e)),
+            }
+
+            ages.push(age);
+        }
+
+        Ok(ages)
+    }
+
+    /// Finds the libraries that an installed package depends on, but which aren't present in the
+    /// plugin directory yet. Only libraries from the knowledge base that can be fetched from this
+    /// backend's plugin website are returned; the frontend decides whether to install them.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the installed package, as returned by `pkg_install`
+    /// * `version` - The version of the installed package, as returned by `pkg_install`
+    ///
+    /// # Errors
+    /// * [`jar::ErrorKind::*`](../jar/enum.ErrorKind.html) - the package's JAR has no readable plugin.yml
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_missing_libraries(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Vec<&'static Library>, Box<Error>> {
+        let description = self.installed_description(name, version)?;
+
+        Ok(self.missing_plugins(&description.depend)?
+            .iter()
+            .filter_map(|dep| libraries::find(dep))
+            .filter(|library| library.plugin_website == self.plugin_website)
+            .collect())
+    }
+
+    /// Installs the newest version of a library from the knowledge base, as a dependency. It's
+    /// only recorded in the lockfile, not the pkg.yml, so it's required for as long as the
+    /// packages that `depend` on it are, and `autoremove` takes it out after them.
+    ///
+    /// # Arguments
+    ///
+    /// * `library` - The library to add, usually one returned by `pkg_missing_libraries`
+    pub fn pkg_add_library(&self, library: &Library) -> Result<Option<(String, String)>, Box<Error>> {
+        self.transaction(&format!("add {}", library.package_name), || {
+            self.install(library.package_name, InstallReason::Dependency)
+        })
+    }
+
+    /// Finds the optional integrations (`softdepend`s) of an installed package that aren't present
+    /// in the plugin directory. These are never installed automatically; the frontend should ask
+    /// the user about each one.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the installed package, as returned by `pkg_install`
+    /// * `version` - The version of the installed package, as returned by `pkg_install`
+    ///
+    /// # Errors
+    /// * [`jar::ErrorKind::*`](../jar/enum.ErrorKind.html) - the package's JAR has no readable plugin.yml
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_missing_integrations(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Vec<String>, Box<Error>> {
+        let description = self.installed_description(name, version)?;
+        self.missing_plugins(&description.softdepend)
+    }
+
+    /// Adds the newest version of an optional integration. Integrations are named the way plugins
+    /// name themselves, so the knowledge base is consulted for the package name first, falling
+    /// back on the plugin name itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `plugin_name` - The integration to add, usually one returned by `pkg_missing_integrations`
+    pub fn pkg_add_integration(
+        &self,
+        plugin_name: &str,
+    ) -> Result<Option<(String, String)>, Box<Error>> {
+        // The user chose to install it, so it isn't just a dependency
+        match libraries::find(plugin_name) {
+            Some(library) if library.plugin_website == self.plugin_website => {
+                self.pkg_add(library.package_name)
+            }
+            _ => self.pkg_add(&plugin_name.to_lowercase()),
+        }
+    }
+
+    /// Removes a package from the plugin directory and the pkg.yml. Packages that other installed
+    /// plugins `depend` on are left alone unless `force` is set, since removing them would stop
+    /// those plugins from loading. Returns whether there was anything to remove.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the package to remove
+    /// * `force` - Remove the package even if other plugins depend on it
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgRequired`](enum.ErrorKind.html#variant.PkgRequired) - other plugins depend on the package
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_remove(&self, name: &str, force: bool) -> Result<bool, Box<Error>> {
+        self.transaction(&format!("remove {}", name), || self.remove(name, force))
+    }
+
+    /// Does the work of `pkg_remove`
+    fn remove(&self, name: &str, force: bool) -> Result<bool, Box<Error>> {
+        let jars = self.installed_jars(name)?;
+
+        if !force {
+            let dependents = self.dependents(&jars)?;
+            if !dependents.is_empty() {
+                return Err(Box::new(ErrorKind::PkgRequired(name.to_string(), dependents)));
+            }
+        }
+
+        // Hooks are only told about packages there's a JAR for
+        let version = Lockfile::load(&self.paths.lockfile())?
+            .packages
+            .get(name)
+            .map(|locked| locked.version.clone());
+        if let (Some(version), false) = (&version, jars.is_empty()) {
+            self.run_hook(Hook::PreRemove, name, version, None)?;
+        }
+
+        for jar in jars.iter() {
+            self.journal_remove(jar)?;
+        }
+
+        // The package is taken out of the environment's overlay as well as the base entries, so
+        // neither brings it back
+        let mut hash = self.read_pkg_list()?;
+        let mut listed = Self::remove_entry(&mut hash, name);
+        if self.env.is_some() {
+            let mut overlay = self.pkg_section(&hash)?;
+            if Self::remove_entry(&mut overlay, name) {
+                self.set_pkg_section(&mut hash, overlay);
+                listed = true;
+            }
+        }
+        if listed {
+            self.write_pkg_list(hash)?;
+        }
+
+        let mut state = State::load(&self.paths.state_file())?;
+        if let Some(package) = state.packages.remove(name) {
+            self.remove_owned_files(&package.files)?;
+            state.save(&self.paths.state_file())?;
+        }
+
+        let mut lockfile = Lockfile::load(&self.paths.lockfile())?;
+        if lockfile.packages.remove(name).is_some() {
+            lockfile.save(&self.paths.lockfile())?;
+        }
+        let docs_dir = self.paths.docs_dir(name);
+        if docs_dir.exists() {
+            fs::remove_dir_all(&docs_dir)?;
+        }
+
+        if let (Some(version), false) = (&version, jars.is_empty()) {
+            self.record_history("remove", name, version, None)?;
+            self.run_hook(Hook::PostRemove, name, version, None)?;
+        }
+
+        Ok(listed || !jars.is_empty())
+    }
+
+    /// Removes the files a package put into the plugin directory besides its JAR, along with
+    /// the folders they leave empty. Files that are already gone are skipped.
+    fn remove_owned_files(&self, files: &[String]) -> Result<(), Box<Error>> {
+        for file in files.iter() {
+            let path = self.paths.plugin_dir.join(file);
+            if path.is_file() {
+                self.journal_remove(&path)?;
+            }
+            // Folders that still have the server's own files in them stay put
+            Self::remove_empty_parents(&path, &self.paths.plugin_dir);
+        }
+        Ok(())
+    }
+
+    /// Removes the folders a file was in, up to `root`, for as long as they're empty
+    fn remove_empty_parents(path: &Path, root: &Path) {
+        let mut dir = path.parent();
+        while let Some(parent) = dir {
+            if parent == root || !parent.starts_with(root) || fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+    }
+
+    /// Removes the packages that were only installed as dependencies, once no explicitly installed
+    /// package needs them anymore. Dependencies that unmanaged plugins `depend` on are kept.
+    /// Returns the names of the packages that were removed.
+    ///
+    /// # Errors
+    /// * [`state::ErrorKind::StateInvalid`](../state/enum.ErrorKind.html#variant.StateInvalid) - the state file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_autoremove(&self) -> Result<Vec<String>, Box<Error>> {
+        self.transaction("autoremove", || self.autoremove())
+    }
+
+    /// Does the work of `pkg_autoremove`
+    fn autoremove(&self) -> Result<Vec<String>, Box<Error>> {
+        let state = State::load(&self.paths.state_file())?;
+
+        let mut removed = Vec::new();
+        for name in state.orphans() {
+            match self.pkg_remove(&name, false) {
+                Ok(_) => removed.push(name),
+                Err(e) => match e.downcast_ref::<ErrorKind>() {
+                    Some(ErrorKind::PkgRequired(_, _)) => continue,
+                    _ => return Err(e),
+                },
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Finds the installed plugins that `depend` on any of the given JARs
+    fn dependents(&self, jars: &[PathBuf]) -> Result<Vec<String>, Box<Error>> {
+        // Addons in data folders aren't plugins, whatever's in them
+        let names = jars
+            .iter()
+            .filter(|jar| jar.parent() == Some(self.paths.plugin_dir.as_path()))
+            .filter_map(|jar| PluginDescription::from_jar(jar).ok())
+            .map(|description| description.name)
+            .collect::<Vec<String>>();
+
+        Ok(jar::installed_plugins(&self.paths.plugin_dir)?
+            .into_iter()
+            .filter(|plugin| !names.contains(&plugin.name))
+            .filter(|plugin| {
+                plugin
+                    .depend
+                    .iter()
+                    .any(|dep| names.iter().any(|name| name.eq_ignore_ascii_case(dep)))
+            })
+            .map(|plugin| plugin.name)
+            .collect())
+    }
+
+    /// Finds the versions `pkg_update` would update each of a batch of packages to, without
+    /// downloading anything. Packages that are up to date are left out, and packages that can't
+    /// be checked are warned about and left out too.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`lock::ErrorKind::LockInvalid`](../lock/enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_upgrades(&self, names: &[String]) -> Result<Vec<Upgrade>, Box<Error>> {
+        let requirements = self.pkg_requirements()?;
+        let lockfile = Lockfile::load(&self.paths.lockfile())?;
+
+        let mut upgrades = Vec::new();
+        for name in names {
+            if let Err(e) = self.check_not_manual(name) {
+                self.warn(e.to_string());
+                continue;
+            }
+            let from = match lockfile.packages.get(name) {
+                Some(locked) => locked.version.clone(),
+                None => {
+                    self.warn(ErrorKind::PkgNotInstalled(name.clone()).to_string());
+                    continue;
+                }
+            };
+            let (to, link) = match self.resolve_package(name, &requirements) {
+                Ok(Some(tup)) => tup,
+                Ok(None) => {
+                    self.warn(format!("{} is no longer on {}", name, self.source_of(name)));
+                    continue;
+                }
+                Err(e) => {
+                    self.warn(format!("couldn't check {} for updates: {}", name, e));
+                    continue;
+                }
+            };
+            if to == from {
+                continue;
+            }
+
+            // The file says which channel the version is in, and where its changes are written up
+            let file = self
+                .package_files(name)
+                .ok()
+                .and_then(|files| files)
+                .and_then(|files| files.into_iter().find(|file| file.link == link));
+            upgrades.push(Upgrade {
+                name: name.clone(),
+                channel: match &file {
+                    Some(file) => file.channel,
+                    None => Channel::from_version(&to),
+                },
+                changelog: file.and_then(|file| file.changelog),
+                from: from,
+                to: to,
+            });
+        }
+
+        Ok(upgrades)
+    }
+
+    /// Updates a batch of packages, as `pkg_update` does each of them. Unless `keep_going` is set,
+    /// the batch is all or nothing: the first package that fails stops it, and every package the
+    /// batch updated before then is rolled back, so the server is never left half updated. Hooks
+    /// that had already run for those packages aren't undone. With `keep_going`, each package is
+    /// updated on its own, and the ones that fail are skipped.
+    ///
+    /// # Errors
+    /// * [`cancel::ErrorKind::Cancelled`](../cancel/enum.ErrorKind.html#variant.Cancelled) - the batch was cancelled, and has been rolled back
+    /// * [`ErrorKind::RollbackFailed`](enum.ErrorKind.html#variant.RollbackFailed) - a package failed, and the batch couldn't be rolled back
+    /// * `std::io::ErrorKind::*` - an IO error occured while finishing the batch
+    pub fn pkg_update_batch(
+        &self,
+        names: &[String],
+        keep_going: bool,
+    ) -> Result<BatchUpdate, Box<Error>> {
+        let mut batch = BatchUpdate::default();
+        if keep_going {
+            for name in names {
+                match self.pkg_update(name) {
+                    Ok(Some((old, new))) => batch.updated.push((name.clone(), old, new)),
+                    Ok(None) => batch.up_to_date.push(name.clone()),
+                    Err(e) if cancel::is_cancelled(&*e) => return Err(e),
+                    Err(e) => batch.failed.push((name.clone(), e.to_string())),
+                }
+            }
+            return Ok(batch);
+        }
+
+        let result = self.atomic_transaction(&format!("update {}", names.join(" ")), || {
+            for name in names {
+                match self.update(name) {
+                    Ok(Some((old, new))) => batch.updated.push((name.clone(), old, new)),
+                    Ok(None) => batch.up_to_date.push(name.clone()),
+                    Err(e) => {
+                        if !cancel::is_cancelled(&*e) {
+                            batch.failed.push((name.clone(), e.to_string()));
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+            Ok(())
+        });
+        // The batch was only rolled back if a package failed, and nothing went wrong after that
+        let rolled_back = match &result {
+            Err(e) => match e.downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::RollbackFailed(..)) => false,
+                _ => !cancel::is_cancelled(&**e) && !batch.failed.is_empty(),
+            },
+            Ok(()) => false,
+        };
+        match result {
+            Err(_) if rolled_back => {
+                batch.rolled_back = true;
+                Ok(batch)
+            }
+            Err(e) => Err(e),
+            Ok(()) => Ok(batch),
+        }
+    }
+
+    /// The update function which takes in a package name, checks to see if it's been installed, and
+    /// by default installs the newest version according to the user's pkg.yml. Returns the (old, new) versions, or `None`
+    /// if the package is already up to date.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A string slice that represents the package name that the user wishes to update.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgNotInstalled`](enum.ErrorKind.html#variant.PkgNotInstalled) - the package isn't installed
+    /// * [`ErrorKind::PkgNotFound`](enum.ErrorKind.html#variant.PkgNotFound) - the package is no longer on the plugin website
+    /// * [`ErrorKind::PkgManual`](enum.ErrorKind.html#variant.PkgManual) - the pkg.yml says the package is managed by hand
+    /// * [`resolver::ErrorKind::Conflict`](../resolver/enum.ErrorKind.html#variant.Conflict) - the constraints on the package can't all be met
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn pkg_update(&self, name: &str) -> Result<Option<(String, String)>, Box<Error>> {
+        self.transaction(&format!("update {}", name), || self.update(name))
+    }
+
+    /// Does the work of `pkg_update`
+    fn update(&self, name: &str) -> Result<Option<(String, String)>, Box<Error>> {
+        let requirements = self.pkg_requirements()?;
+        self.check_not_manual(name)?;
+        let old_version = match Lockfile::load(&self.paths.lockfile())?.packages.get(name) {
+            Some(locked) => locked.version.clone(),
+            None => return Err(Box::new(ErrorKind::PkgNotInstalled(name.to_string()))),
+        };
+
+        let (version, link) = match self.resolve_package(name, &requirements)? {
+            Some(tup) => tup,
+            None => return Err(Box::new(ErrorKind::PkgNotFound(name.to_string()))),
+        };
+        if version == old_version {
+            return Ok(None);
+        }
+
+        let reason = State::load(&self.paths.state_file())?
+            .packages
+            .get(name)
+            .map_or(InstallReason::Explicit, |package| package.reason);
+        self.install_resolved(name, &version, &link, reason, Some(&old_version))?;
+
+        Ok(Some((old_version, version)))
+    }
+
+    /// Runs a configured hook for a package. Failing `pre_` hooks stop the operation, while
+    /// failing `post_` hooks are only warned about, since there's nothing left to stop.
+    fn run_hook(
+        &self,
+        hook: Hook,
+        name: &str,
+        version: &str,
+        old_version: Option<&str>,
+    ) -> Result<(), Box<Error>> {
+        let context = HookContext {
+            package: name,
+            version: version,
+            old_version: old_version,
+            drift: None,
+            project_dir: &self.paths.project_dir,
+            plugin_dir: &self.paths.plugin_dir,
+        };
+
+        match self.hooks.run(hook, &context) {
+            Err(e) if !hook.is_pre() => {
+                self.warn(e.to_string());
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Runs the `drift` hook for a JAR that was added or removed behind dropper's back. There's
+    /// nothing for it to stop, so failing is only warned about.
+    fn run_drift_hook(&self, file: &str, change: &str) {
+        let context = HookContext {
+            package: file,
+            version: "",
+            old_version: None,
+            drift: Some(change),
+            project_dir: &self.paths.project_dir,
+            plugin_dir: &self.paths.plugin_dir,
+        };
+
+        if let Err(e) = self.hooks.run(Hook::Drift, &context) {
+            self.warn(e.to_string());
+        }
+    }
+
+    /// Versions in YAML are often unquoted, so they may come through as numbers
+    fn yaml_version(yaml: &Yaml) -> Option<String> {
+        match yaml {
+            Yaml::String(s) | Yaml::Real(s) => Some(s.clone()),
+            Yaml::Integer(i) => Some(i.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Reads the plugin.yml of a package that `pkg_install` has put in the plugin directory
+    fn installed_description(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<PluginDescription, Box<Error>> {
+        PluginDescription::from_jar(&self.installed_jar_path(name, version))
+    }
+
+    /// Where the JAR that was just installed for a package is. If it is still waiting to be
+    /// swapped in, the staged copy is the one we want.
+    fn installed_jar_path(&self, name: &str, version: &str) -> PathBuf {
+        let staged = self
+            .paths
+            .staging_dir()
+            .join(self.installed_file(name, version));
+        match staged.exists() {
+            true => staged,
+            false => self.jar_path(name, version),
+        }
+    }
+
+    /// Filters a list of plugin names down to those that aren't in the plugin directory
+    fn missing_plugins(&self, plugin_names: &[String]) -> Result<Vec<String>, Box<Error>> {
+        let installed = jar::installed_plugins(&self.paths.plugin_dir)?;
+
+        Ok(plugin_names
+            .iter()
+            .filter(|name| {
+                !installed
+                    .iter()
+                    .any(|plugin| plugin.name.eq_ignore_ascii_case(name))
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// Records an install in the state file and the lockfile. Once a package has been installed
+    /// explicitly, it stays that way even if it is later installed again as a dependency. The
+    /// files the download put into the plugin directory are added to those it put there before,
+    /// so they're all removed along with it.
+    fn record_install(
+        &self,
+        name: &str,
+        version: &str,
+        link: &str,
+        reason: InstallReason,
+        downloaded: &Downloaded,
+    ) -> Result<(), Box<Error>> {
+        let mut recorded = hash::RECORDED_ALGORITHMS.to_vec();
+        for algorithm in downloaded.algorithms.iter() {
+            if !recorded.contains(&algorithm.as_str()) {
+                recorded.push(algorithm);
+            }
+        }
+        let mut lockfile = Lockfile::load(&self.paths.lockfile())?;
+        let project_id = lockfile
+            .packages
+            .get(name)
+            .and_then(|locked| locked.project_id.clone());
+        // Reinstalling a downgraded version keeps it downgraded, and moving to a newer one
+        // doesn't
+        let downgraded = match lockfile.packages.get(name) {
+            Some(locked) if locked.version == version => locked.downgraded,
+            Some(locked) => Self::is_older(version, &locked.version),
+            None => false,
+        };
+        lockfile.packages.insert(
+            name.to_string(),
+            LockedPackage {
+                version: version.to_string(),
+                file: self.installed_file(name, version),
+                url: link.to_string(),
+                hashes: hash::digest_file(&self.installed_jar_path(name, version), &recorded)?,
+                project_id: project_id,
+                source: self.package_sources.borrow().get(name).cloned(),
+                downgraded: downgraded,
+                jars: downloaded.jars.clone(),
+            },
+        );
+        lockfile.save(&self.paths.lockfile())?;
+
+        let mut state = State::load(&self.paths.state_file())?;
+        let reason = match state.packages.get(name) {
+            Some(package) if package.reason == InstallReason::Explicit => InstallReason::Explicit,
+            _ => reason,
+        };
+        let mut owned = state
+            .packages
+            .get(name)
+            .map(|package| package.files.clone())
+            .unwrap_or_default();
+        for file in downloaded.files.iter() {
+            if !owned.contains(file) {
+                owned.push(file.clone());
+            }
+        }
+
+        let description = match self.addon_target(name) {
+            Some(_) => None,
+            None => self.installed_description(name, version).ok(),
+        };
+        state.packages.insert(
+            name.to_string(),
+            InstalledPackage {
+                version: version.to_string(),
+                reason: reason,
+                plugin_name: description.as_ref().map(|d| d.name.clone()),
+                depend: description.map(|d| d.depend).unwrap_or_default(),
+                installed_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .ok()
+                    .map(|d| d.as_secs()),
+                files: owned,
+                configs: state
+                    .packages
+                    .get(name)
+                    .map(|package| package.configs.clone())
+                    .unwrap_or_default(),
+            },
+        );
+        state.save(&self.paths.state_file())
+    }
+
+    /// Reads the entries of the pkg.yml, creating it if it doesn't exist yet
+    fn read_pkg_list(&self) -> Result<Hash, Box<Error>> {
+        let pkg_yml = match Self::read_yaml_file(&self.paths.pkg_list())? {
+            Some(yml) => yml,
+            // If we couldn't find the YML file, then we create it and start fresh
+            None => {
+                let mut pkg_file = File::create(self.paths.pkg_list())?;
+                pkg_file.write_all(b"---\n")?;
+                Self::read_yaml_file(&self.paths.pkg_list())?.unwrap()
+            }
+        };
+
+        match pkg_yml.first() {
+            Some(Yaml::Hash(h)) => Ok(h.clone()),
+            Some(Yaml::Null) | None => Ok(Hash::new()),
+            _ => Err(Box::new(ErrorKind::PkgListInvalid)),
+        }
+    }
+
+    /// The pkg.yml's package entries, with the overlay of the environment laid over them, and
+    /// the variables filled in. A missing pkg.yml has no entries.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the pkg.yml is incorrectly formatted
+    /// * [`ErrorKind::EnvUnknown`](enum.ErrorKind.html#variant.EnvUnknown) - the pkg.yml has no overlay for the environment
+    /// * [`template::ErrorKind::VariableMissing`](../template/enum.ErrorKind.html#variant.VariableMissing) - the pkg.yml uses an undefined variable
+    fn pkg_entries(&self) -> Result<Hash, Box<Error>> {
+        let mut entries = Hash::new();
+        for (key, entry) in self.overlaid_pkg_entries()? {
+            entries.insert(key, self.render_yaml(entry)?);
+        }
+        Ok(entries)
+    }
+
+    /// Fills in the variables in every string in a pkg.yml entry
+    fn render_yaml(&self, yaml: Yaml) -> Result<Yaml, Box<Error>> {
+        Ok(match yaml {
+            Yaml::String(s) => Yaml::String(template::render(PKG_LIST_FILE, &s, &self.variables)?),
+            Yaml::Array(items) => Yaml::Array(
+                items
+                    .into_iter()
+                    .map(|item| self.render_yaml(item))
+                    .collect::<Result<Vec<Yaml>, Box<Error>>>()?,
+            ),
+            Yaml::Hash(hash) => {
+                let mut rendered = Hash::new();
+                for (key, value) in hash {
+                    rendered.insert(key, self.render_yaml(value)?);
+                }
+                Yaml::Hash(rendered)
+            }
+            yaml => yaml,
+        })
+    }
+
+    /// The pkg.yml's package entries, with the overlay of the environment laid over them
+    fn overlaid_pkg_entries(&self) -> Result<Hash, Box<Error>> {
+        let mut hash = match Self::read_yaml_file(&self.paths.pkg_list())? {
+            Some(yml) => match yml.first() {
+                Some(Yaml::Hash(h)) => h.clone(),
+                Some(Yaml::Null) | None => Hash::new(),
+                _ => return Err(Box::new(ErrorKind::PkgListInvalid)),
+            },
+            None => Hash::new(),
+        };
+
+        let environments = hash.remove(&Yaml::from_str(ENVIRONMENTS_KEY));
+        let env = match &self.env {
+            Some(env) => env,
+            None => return Ok(hash),
+        };
+        let overlay = match Self::env_overlay(environments.as_ref(), env)? {
+            Some(overlay) => overlay,
+            None => {
+                let envs = Self::env_names(environments.as_ref());
+                let closest = Self::closest_match(
+                    env,
+                    &envs.iter().map(|e| e.as_str()).collect::<Vec<&str>>(),
+                )
+                .map(|e| e.to_string());
+                return Err(Box::new(ErrorKind::EnvUnknown(env.clone(), envs, closest)));
+            }
+        };
+
+        // An overlay entry replaces the package's base entry, even if only one of them names
+        // the package's source
+        for (key, entry) in overlay.iter() {
+            if let Some(name) = key.as_str() {
+                if let Some(old_key) = Self::pkg_list_key(&hash, Self::split_source(name).1) {
+                    hash.remove(&old_key);
+                }
+            }
+            hash.insert(key.clone(), entry.clone());
+        }
+        Ok(hash)
+    }
+
+    /// Finds an environment's overlay among the pkg.yml's `environments`, or `None` if it
+    /// doesn't have one. An empty overlay is still an overlay.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the environments are incorrectly formatted
+    fn env_overlay(environments: Option<&Yaml>, env: &str) -> Result<Option<Hash>, Box<Error>> {
+        let environments = match environments {
+            Some(Yaml::Hash(environments)) => environments,
+            None => return Ok(None),
+            _ => return Err(Box::new(ErrorKind::PkgListInvalid)),
+        };
+        match environments.get(&Yaml::String(env.to_string())) {
+            Some(Yaml::Hash(overlay)) => Ok(Some(overlay.clone())),
+            Some(Yaml::Null) => Ok(Some(Hash::new())),
+            Some(_) => Err(Box::new(ErrorKind::PkgListInvalid)),
+            None => Ok(None),
+        }
+    }
+
+    /// The names of the environments the pkg.yml has overlays for
+    fn env_names(environments: Option<&Yaml>) -> Vec<String> {
+        match environments {
+            Some(Yaml::Hash(environments)) => environments
+                .keys()
+                .filter_map(|env| env.as_str().map(|env| env.to_string()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The part of the pkg.yml that packages are added to: the environment's overlay if there is
+    /// an environment, and the base entries otherwise
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgListInvalid`](enum.ErrorKind.html#variant.PkgListInvalid) - the environments are incorrectly formatted
+    fn pkg_section(&self, hash: &Hash) -> Result<Hash, Box<Error>> {
+        match &self.env {
+            Some(env) => Ok(
+                Self::env_overlay(hash.get(&Yaml::from_str(ENVIRONMENTS_KEY)), env)?
+                    .unwrap_or_else(Hash::new),
+            ),
+            None => Ok(hash.clone()),
+        }
+    }
+
+    /// Puts a section read by `pkg_section` back into the pkg.yml's entries
+    fn set_pkg_section(&self, hash: &mut Hash, section: Hash) {
+        let env = match &self.env {
+            Some(env) => env,
+            None => {
+                *hash = section;
+                return;
+            }
+        };
+
+        let key = Yaml::from_str(ENVIRONMENTS_KEY);
+        if let Some(Yaml::Hash(environments)) = hash.get_mut(&key) {
+            environments.insert(Yaml::String(env.clone()), Yaml::Hash(section));
+            return;
+        }
+        let mut environments = Hash::new();
+        environments.insert(Yaml::String(env.clone()), Yaml::Hash(section));
+        hash.insert(key, Yaml::Hash(environments));
+    }
+
+    /// A pkg.yml entry with its version replaced. Entries with extra settings keep them.
+    fn with_version(entry: Option<&Yaml>, version: &str) -> Yaml {
+        match entry {
+            Some(Yaml::Hash(settings)) => {
+                let mut settings = settings.clone();
+                settings.insert(Yaml::from_str("version"), Yaml::from_str(version));
+                Yaml::Hash(settings)
+            }
+            _ => Yaml::from_str(version),
+        }
+    }
+
+    /// Replaces the version of a package's entry in some pkg.yml entries, keeping its key
+    fn set_entry_version(hash: &mut Hash, name: &str, version: &str) {
+        if let Some(key) = Self::pkg_list_key(hash, name) {
+            let entry = Self::with_version(hash.get(&key), version);
+            hash.insert(key, entry);
+        }
+    }
+
+    /// Takes a package's entry out of some pkg.yml entries. Returns whether it was there.
+    fn remove_entry(hash: &mut Hash, name: &str) -> bool {
+        match Self::pkg_list_key(hash, name) {
+            Some(key) => hash.remove(&key).is_some(),
+            None => false,
+        }
+    }
+
+    /// Writes entries back to the pkg.yml, replacing what was there
+    fn write_pkg_list(&self, hash: Hash) -> Result<(), Box<Error>> {
+        let mut pkg_file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(self.paths.pkg_list())?;
+        let mut tmp_string = String::new();
+        let mut emitter = YamlEmitter::new(&mut tmp_string);
+        emitter.dump(&Yaml::Hash(hash)).unwrap();
+        tmp_string = format!("{}\n", tmp_string);
+        pkg_file.write_all(&tmp_string.into_bytes())?;
+
+        Ok(())
+    }
+
+    /// Finds every JAR in the plugin directory that was installed for a package, or every file in
+    /// its folder if it's an addon
+    fn installed_jars(&self, name: &str) -> Result<Vec<PathBuf>, Box<Error>> {
+        let (dir, extension) = match self.addon_target(name) {
+            Some(target) => (self.paths.plugin_dir.join(target.dir), target.extension),
+            None => (self.paths.plugin_dir.clone(), "jar".to_string()),
+        };
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let prefix = format!("{}{}", name, VERSION_SPLIT_CHAR);
+        let suffix = format!(".{}", extension);
+        let mut jars = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let is_match = match path.file_name().and_then(|f| f.to_str()) {
+                Some(file_name) => file_name.starts_with(&prefix) && file_name.ends_with(&suffix),
+                None => false,
+            };
+            if is_match {
+                jars.push(path);
+            }
+        }
+
+        Ok(jars)
+    }
+
+    /// Where a package's JAR lives once it has been installed
+    fn jar_path(&self, name: &str, version: &str) -> PathBuf {
+        self.paths.plugin_dir.join(self.installed_file(name, version))
+    }
+
+    /// The name a version of a package is installed under, relative to the plugin directory.
+    /// Addons are in their plugin's data folder, and plugins are in the plugin directory itself.
+    fn installed_file(&self, name: &str, version: &str) -> String {
+        match self.addon_target(name) {
+            Some(target) => target.file(&format!("{}{}{}", name, VERSION_SPLIT_CHAR, version)),
+            None => Self::jar_file_name(name, version),
+        }
+    }
+
+    /// Where a package is installed to if it's an addon, going by its pkg.yml entry, or by the
+    /// lockfile if it has left the pkg.yml
+    fn addon_target(&self, name: &str) -> Option<AddonTarget> {
+        if let Ok(hash) = self.pkg_entries() {
+            if let Some(entry) = Self::pkg_list_key(&hash, name).and_then(|key| hash.get(&key)) {
+                return AddonTarget::from_yaml(name, entry).ok().and_then(|target| target);
+            }
+        }
+
+        let lockfile = Lockfile::load(&self.paths.lockfile()).ok()?;
+        let file = Path::new(&lockfile.packages.get(name)?.file).to_path_buf();
+        if !addon::is_addon_file(&file.to_string_lossy()) {
+            return None;
+        }
+        Some(AddonTarget {
+            dir: file.parent()?.to_path_buf(),
+            extension: file.extension()?.to_string_lossy().to_string(),
+        })
+    }
+
+    /// A path in the plugin directory as the lockfile names it, with `/` between folders
+    fn relative_file(&self, path: &Path) -> String {
+        lock::file_name(path.strip_prefix(&self.paths.plugin_dir).unwrap_or(path))
+    }
+
+    fn jar_file_name(name: &str, version: &str) -> String {
+        format!("{}{}{}.jar", name, VERSION_SPLIT_CHAR, version)
+    }
+
+    /// Splits the source off a package name, if it has one. Project IDs marked with
+    /// `PROJECT_ID_PREFIX` aren't mistaken for a source.
+    fn split_source(name: &str) -> (Option<&str>, &str) {
+        let id_marker = PROJECT_ID_PREFIX.trim_end_matches(SOURCE_SPLIT_CHAR);
+        match name.find(SOURCE_SPLIT_CHAR) {
+            Some(i) if &name[..i] != id_marker => (Some(&name[..i]), &name[i + 1..]),
+            _ => (None, name),
+        }
+    }
+
+    /// An internal function to parse out the source, package name and version from a package
+    /// specifier
+    ///
+    /// # Arguments
+    /// * `pkg_specifier` - A string slice that represents the package and version the user wishes
+    ///   to add. It should be in the package specifier format defined above.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PkgSpecInvalid`](enum.ErrorKind.html#variant.PkgSpecInvalid) - the package specifier was invalid
+    ///
+    /// # Non Error Return Value
+    /// A tuple containing an option of source, the package name and an option of version code. If
+    /// there's no source, the package comes from the plugin website; if there's no version, assume
+    /// the newest package is acceptable.
+    fn parse_package_specifier(
+        pkg_specifier: String,
+    ) -> Result<(Option<String>, String, Option<String>), ErrorKind> {
+        let (source, rest) = Self::split_source(&pkg_specifier);
+        let source_re = Regex::new(r"^[a-z][a-z0-9_-]*$").unwrap();
+        // Sources that name packages by owner, like `github:EssentialsX/Essentials`, allow a slash
+        let name_re = match source {
+            Some(source) if source_re.is_match(source) => Regex::new(r"^(id:\d+|\w+(/\w+)?)$").unwrap(),
+            Some(_) => return Err(ErrorKind::PkgSpecInvalid(pkg_specifier)),
+            None => Regex::new(r"^(id:\d+|\w+)$").unwrap(),
+        };
+        let source = source.map(|s| s.to_string());
+        let rest = rest.to_string();
+
+        if rest.contains(VERSION_SPLIT_CHAR) {
+            // A version was specified along with the package
+            let components = rest.split(VERSION_SPLIT_CHAR).collect::<Vec<&str>>();
+            // Anything more than two components means that one too many separators appeared
+            match components.len() {
+                2 => {
+                    if !name_re.is_match(components[0]) {
+                        return Err(ErrorKind::PkgSpecInvalid(pkg_specifier));
+                    }
+
+                    // Channels like `latest-dev` aren't version numbers, but are versions
+                    let is_version = match Constraint::parse(components[1]) {
+                        Constraint::Channel(_) => true,
+                        Constraint::Prefix(prefix) => {
+                            prefix.iter().all(|c| c.parse::<u32>().is_ok())
+                        }
+                        Constraint::Exact(version) => Version::parse(&version).is_some(),
+                        Constraint::Any => false,
+                    };
+                    if !is_version {
+                        return Err(ErrorKind::PkgSpecInvalid(pkg_specifier));
+                    }
+
+                    // At this point, the components are valid and can be passed out
+                    Ok((
+                        source,
+                        components[0].to_string(),
+                        Some(components[1].to_string()),
+                    ))
+                }
+                // More than two components were found
+                _ => Err(ErrorKind::PkgSpecInvalid(pkg_specifier)),
+            }
+        } else {
+            // No version was specified along with the package
+            // Ensure that the package name is just one valid word
+            match name_re.is_match(&rest) {
+                true => Ok((source, rest, None)),
+                false => Err(ErrorKind::PkgSpecInvalid(pkg_specifier)),
+            }
+        }
+    }
+}
+
+/// Runs `f` on every item, on up to `METADATA_THREADS` threads at once, returning the results in
+/// the same order as the items
+fn parallel_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<Option<R>>>());
+
+    crossbeam_utils::thread::scope(|scope| {
+        for _ in 0..METADATA_THREADS.min(items.len()) {
+            scope.spawn(|_| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= items.len() {
+                    break;
+                }
+                let result = f(&items[i]);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    })
+    // A worker only panics if `f` does, which should take the whole program down anyway
+    .unwrap();
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect()
+}
+
+// Only Windows locks JARs that are in use, so that's the only place staging is exercised
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::PluginFile;
+    #[cfg(windows)]
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::{env, process};
+    use zip::write::{FileOptions, ZipWriter};
+
+    /// A server with nothing installed, in a folder of its own under the temp directory
+    fn test_paths(test: &str) -> ProjectPaths {
+        let dir = env::temp_dir().join(format!("dropper-{}-{}", test, process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        let paths = ProjectPaths::new(dir, None);
+        PackageBackend::init(&paths).unwrap();
+        fs::write(
+            paths.config_file(),
+            "plugin_website: bukkit\nserver_version: \"1.16.5\"\n",
+        )
+        .unwrap();
+        fs::create_dir_all(&paths.plugin_dir).unwrap();
+        paths
+    }
+
+    /// A plugin website that's only in memory, so that nothing has to go online. Its JARs are
+    /// written into a folder of their own, and downloaded from there.
+    struct TestParser {
+        dir: PathBuf,
+        packages: BTreeMap<String, Vec<PluginFile>>,
+    }
+
+    impl TestParser {
+        fn new(paths: &ProjectPaths) -> TestParser {
+            TestParser {
+                dir: paths.project_dir.join("website"),
+                packages: BTreeMap::new(),
+            }
+        }
+
+        /// Publishes a version of a package, as a JAR with a plugin.yml that `depend`s on the
+        /// given plugins. Versions are published oldest first.
+        fn publish(&mut self, name: &str, version: &str, depend: &[&str]) -> &mut TestParser {
+            let plugin_yml = format!(
+                "name: {}\nversion: \"{}\"\nmain: test.Main\ndepend: [{}]\n",
+                name,
+                version,
+                depend.join(", ")
+            );
+            self.publish_jar(name, version, &[("plugin.yml", &plugin_yml)])
+        }
+
+        /// Publishes a version of a package as a JAR with the given (path, contents) entries
+        fn publish_jar(
+            &mut self,
+            name: &str,
+            version: &str,
+            entries: &[(&str, &str)],
+        ) -> &mut TestParser {
+            let path = self.dir.join(format!("{}-{}.jar", name, version));
+            test_jar(&path, entries);
+            let link = Url::from_file_path(&path).unwrap().to_string();
+            self.packages
+                .entry(name.to_string())
+                .or_default()
+                .insert(0, PluginFile::new(version.to_string(), link));
+            self
+        }
+    }
+
+    impl PluginFetchable for TestParser {
+        fn enumerate_files(
+            &self,
+            package_name: &str,
+        ) -> Result<Option<Vec<PluginFile>>, Box<Error>> {
+            Ok(self.packages.get(package_name).cloned())
+        }
+    }
+
+    /// Writes a ZIP with the given (path, contents) entries
+    fn test_jar(path: &Path, entries: &[(&str, &str)]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for (name, contents) in entries.iter() {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn test_backend<'a>(parser: &'a TestParser, paths: &ProjectPaths) -> PackageBackend<'a> {
+        PackageBackend::new(parser, paths.clone(), http::build_client().unwrap()).unwrap()
+    }
+
+    /// The (name, version) of each package, in order of name
+    fn sorted(mut packages: Vec<(String, String)>) -> Vec<(String, String)> {
+        packages.sort();
+        packages
+    }
+
+    fn pairs(packages: &[(&str, &str)]) -> Vec<(String, String)> {
+        packages
+            .iter()
+            .map(|(name, version)| (name.to_string(), version.to_string()))
+            .collect()
+    }
+
+    /// Opens a file the way a running server holds its JARs, without sharing it
+    #[cfg(windows)]
+    fn hold_open(path: &Path) -> File {
+        OpenOptions::new()
+            .read(true)
+            .share_mode(0)
+            .open(path)
+            .unwrap()
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn held_jars_are_locked() {
+        let paths = test_paths("held-jars-are-locked");
+        let jar = paths.plugin_dir.join("Foo.jar");
+        fs::write(&jar, b"old").unwrap();
+
+        let held = hold_open(&jar);
+        let e = fs::remove_file(&jar).unwrap_err();
+        assert!(PackageBackend::is_locked(&e));
+        drop(held);
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn staged_jars_wait_for_the_jars_they_replace() {
+        let paths = test_paths("staged-jars-wait");
+        let jar = paths.plugin_dir.join("Foo.jar");
+        fs::write(&jar, b"old").unwrap();
+        let staged = paths.staging_dir().join("Foo.jar");
+        fs::create_dir_all(paths.staging_dir()).unwrap();
+        fs::write(&staged, b"new").unwrap();
+
+        let parser = TestParser::new(&paths);
+        let backend = test_backend(&parser, &paths);
+
+        // The server has the old JAR open, so the new one stays staged
+        let held = hold_open(&jar);
+        assert!(backend.apply_staged().unwrap().is_empty());
+        assert_eq!(backend.staged().unwrap(), vec![staged.clone()]);
+        assert_eq!(fs::read(&jar).unwrap(), b"old");
+        drop(held);
+
+        // Once the server lets go of it, it's swapped in
+        assert_eq!(backend.apply_staged().unwrap(), vec![jar.clone()]);
+        assert!(backend.staged().unwrap().is_empty());
+        assert!(!staged.exists());
+        assert_eq!(fs::read(&jar).unwrap(), b"new");
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn staged_addons_wait_in_their_plugins_folder() {
+        let paths = test_paths("staged-addons-wait");
+        let dir = paths.plugin_dir.join("Foo").join("addons");
+        fs::create_dir_all(&dir).unwrap();
+        let jar = dir.join("Bar.jar");
+        fs::write(&jar, b"old").unwrap();
+        let staged = paths
+            .staging_dir()
+            .join("Foo")
+            .join("addons")
+            .join("Bar.jar");
+        fs::create_dir_all(staged.parent().unwrap()).unwrap();
+        fs::write(&staged, b"new").unwrap();
+
+        let parser = TestParser::new(&paths);
+        let backend = test_backend(&parser, &paths);
+
+        let held = hold_open(&jar);
+        assert!(backend.apply_staged().unwrap().is_empty());
+        drop(held);
+
+        assert_eq!(backend.apply_staged().unwrap(), vec![jar.clone()]);
+        assert_eq!(fs::read(&jar).unwrap(), b"new");
+        // The staging directory's copy of the plugin's folder is cleaned up behind it
+        assert!(!paths.staging_dir().join("Foo").exists());
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn satisfiable_graphs_resolve_to_the_newest_versions_allowed() {
+        let paths = test_paths("satisfiable-graphs");
+        let mut parser = TestParser::new(&paths);
+        for version in &["3.9", "4.4.0", "4.4.2", "4.5.0", "5.0.0"] {
+            parser.publish("protocollib", version, &[]);
+        }
+        parser.publish("first", "1.2", &["ProtocolLib"]);
+        parser.publish("first", "2.0", &["ProtocolLib"]);
+        parser.publish("second", "1.0", &["ProtocolLib"]);
+        fs::write(
+            paths.pkg_list(),
+            "first:\n  version: \"1.*\"\n  requires:\n    protocollib: \"4.4.*\"\n\
+             second:\n  requires:\n    protocollib: \"4.*\"\n",
+        )
+        .unwrap();
+
+        let backend = test_backend(&parser, &paths);
+        let requirements = backend.pkg_requirements().unwrap();
+        let resolved = backend
+            .resolve_all(&requirements)
+            .unwrap()
+            .into_iter()
+            .map(|(name, version, _)| (name, version))
+            .collect::<Vec<(String, String)>>();
+        assert_eq!(
+            sorted(resolved),
+            pairs(&[
+                ("first", "1.2"),
+                ("protocollib", "4.4.2"),
+                ("second", "1.0")
+            ])
+        );
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn conflicting_dependents_are_reported_before_anything_is_installed() {
+        let paths = test_paths("conflicting-dependents");
+        let mut parser = TestParser::new(&paths);
+        parser.publish("protocollib", "4.5.0", &[]);
+        parser.publish("protocollib", "5.0.0", &[]);
+        parser.publish("first", "1.0", &[]);
+        parser.publish("second", "1.0", &[]);
+        fs::write(
+            paths.pkg_list(),
+            "first:\n  requires:\n    protocollib: \"4.*\"\n\
+             second:\n  requires:\n    protocollib: \"5.*\"\n",
+        )
+        .unwrap();
+
+        let backend = test_backend(&parser, &paths);
+        let e = backend.pkg_install_all().unwrap_err();
+        match e.downcast_ref::<resolver::ErrorKind>() {
+            Some(resolver::ErrorKind::Conflict(package, requirements)) => {
+                assert_eq!(package, "protocollib");
+                let by = requirements
+                    .iter()
+                    .map(|r| r.required_by.as_str())
+                    .collect::<Vec<&str>>();
+                assert_eq!(by, vec!["first", "second"]);
+            }
+            _ => panic!("expected a conflict, got {}", e),
+        }
+        assert!(jar::installed_plugins(&paths.plugin_dir)
+            .unwrap()
+            .is_empty());
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn packages_that_require_each_other_are_both_installed() {
+        let paths = test_paths("requirement-cycles");
+        let mut parser = TestParser::new(&paths);
+        parser.publish("first", "1.0", &["second"]);
+        parser.publish("first", "2.0", &["second"]);
+        parser.publish("second", "1.0", &["first"]);
+        fs::write(
+            paths.pkg_list(),
+            "first:\n  requires:\n    second: \"*\"\n\
+             second:\n  requires:\n    first: \"1.*\"\n",
+        )
+        .unwrap();
+
+        let backend = test_backend(&parser, &paths);
+        let installed = backend.pkg_install_all().unwrap();
+        assert_eq!(
+            sorted(installed),
+            pairs(&[("first", "1.0"), ("second", "1.0")])
+        );
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn missing_requirements_are_reported_by_name() {
+        let paths = test_paths("missing-requirements");
+        let mut parser = TestParser::new(&paths);
+        parser.publish("first", "1.0", &["Vault"]);
+        fs::write(paths.pkg_list(), "first:\n  requires:\n    vault: \"*\"\n").unwrap();
+
+        let backend = test_backend(&parser, &paths);
+        let e = backend.pkg_install_all().unwrap_err();
+        match e.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::PkgNotFound(name)) => assert_eq!(name, "vault"),
+            _ => panic!("expected vault to be missing, got {}", e),
+        }
+        assert!(jar::installed_plugins(&paths.plugin_dir)
+            .unwrap()
+            .is_empty());
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn dependencies_stay_required_by_what_depends_on_them() {
+        let paths = test_paths("dependencies-stay-required");
+        let mut parser = TestParser::new(&paths);
+        parser.publish("first", "1.0", &["Vault"]);
+        parser.publish("vault", "1.7.3", &[]);
+        let backend = test_backend(&parser, &paths);
+        backend.pkg_add("first").unwrap();
+        backend.install("vault", InstallReason::Dependency).unwrap();
+
+        let requirements = backend.pkg_requirements().unwrap();
+        let vault = requirements
+            .iter()
+            .find(|r| r.package == "vault")
+            .expect("vault should be required");
+        assert_eq!(vault.required_by, "first");
+        assert_eq!(vault.constraint, Constraint::Any);
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    /// What's in the plugin directory and the manifests, to compare before and after
+    fn server_snapshot(paths: &ProjectPaths) -> BTreeMap<PathBuf, Vec<u8>> {
+        let mut files = BTreeMap::new();
+        let manifests = vec![
+            paths.config_file(),
+            paths.pkg_list(),
+            paths.lockfile(),
+            paths.state_file(),
+            paths.history_file(),
+        ];
+        for path in manifests.into_iter().filter(|path| path.exists()) {
+            files.insert(path.clone(), fs::read(&path).unwrap());
+        }
+        for entry in fs::read_dir(&paths.plugin_dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_file() {
+                files.insert(path.clone(), fs::read(&path).unwrap());
+            }
+        }
+        files
+    }
+
+    /// Installs the given packages from a pkg.yml that allows any version of them, so that they
+    /// can be updated
+    fn install_unpinned(parser: &TestParser, paths: &ProjectPaths, names: &[&str]) {
+        let pkg_list: String = names
+            .iter()
+            .map(|name| format!("{}: \"*\"\n", name))
+            .collect();
+        fs::write(paths.pkg_list(), pkg_list).unwrap();
+        test_backend(parser, paths).pkg_install_all().unwrap();
+    }
+
+    fn locked_version(paths: &ProjectPaths, name: &str) -> String {
+        Lockfile::load(&paths.lockfile()).unwrap().packages[name]
+            .version
+            .clone()
+    }
+
+    #[test]
+    fn failed_atomic_transactions_are_rolled_back() {
+        let paths = test_paths("failed-atomic-transactions");
+        let mut parser = TestParser::new(&paths);
+        parser.publish("vault", "1.7.2", &[]);
+        install_unpinned(&parser, &paths, &["vault"]);
+        parser.publish("vault", "1.7.3", &[]);
+        parser.publish("essentials", "2.18", &[]);
+        let before = server_snapshot(&paths);
+
+        let backend = test_backend(&parser, &paths);
+        let result: Result<(), Box<Error>> = backend.atomic_transaction("update vault", || {
+            backend.update("vault")?;
+            backend.install("essentials", InstallReason::Explicit)?;
+            Err(Box::new(io::Error::other("killed")))
+        });
+        assert_eq!(result.unwrap_err().to_string(), "killed");
+        assert_eq!(server_snapshot(&paths), before);
+        assert_eq!(locked_version(&paths, "vault"), "1.7.2");
+        assert!(!paths.journal_dir().exists());
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn interrupted_operations_are_recovered_on_the_next_run() {
+        let paths = test_paths("interrupted-operations");
+        let mut parser = TestParser::new(&paths);
+        parser.publish("vault", "1.7.2", &[]);
+        install_unpinned(&parser, &paths, &["vault"]);
+        parser.publish("vault", "1.7.3", &[]);
+        let before = server_snapshot(&paths);
+
+        // The journal as it was at the end of the update is put back after it's committed, as if
+        // dropper had been killed before it got the chance to commit it
+        let journal_dir = paths.journal_dir();
+        let copy_dir = paths.project_dir.join("journal-copy");
+        let backend = test_backend(&parser, &paths);
+        backend
+            .transaction("update vault", || {
+                backend.update("vault")?;
+                fs::create_dir(&copy_dir)?;
+                for entry in fs::read_dir(&journal_dir)? {
+                    let path = entry?.path();
+                    fs::copy(&path, copy_dir.join(path.file_name().unwrap()))?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        fs::rename(&copy_dir, &journal_dir).unwrap();
+        assert_eq!(locked_version(&paths, "vault"), "1.7.3");
+
+        let description = PackageBackend::recover(&paths).unwrap();
+        assert_eq!(description, Some("update vault".to_string()));
+        assert_eq!(server_snapshot(&paths), before);
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn partial_downloads_are_removed_and_finished_ones_kept() {
+        let paths = test_paths("partial-downloads");
+        let parser = TestParser::new(&paths);
+        let staging_dir = paths.staging_dir();
+        let partial = staging_dir.join(format!("Vault.jar.{}", PARTIAL_EXTENSION));
+        let nested = staging_dir
+            .join("Essentials")
+            .join(format!("addon.jar.{}", PARTIAL_EXTENSION));
+        let finished = staging_dir.join("WorldEdit.jar");
+        for path in [&partial, &nested, &finished] {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "jar").unwrap();
+        }
+
+        test_backend(&parser, &paths)
+            .remove_partial_downloads()
+            .unwrap();
+        assert!(!partial.exists());
+        assert!(!nested.parent().unwrap().exists());
+        assert!(finished.exists());
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    /// A server with first and second installed at 1.0, and a 1.1 of each where second's
+    /// download is broken
+    fn half_broken_update(test: &str) -> (ProjectPaths, TestParser) {
+        let paths = test_paths(test);
+        let mut parser = TestParser::new(&paths);
+        parser.publish("first", "1.0", &[]);
+        parser.publish("second", "1.0", &[]);
+        install_unpinned(&parser, &paths, &["first", "second"]);
+        parser.publish("first", "1.1", &[]);
+        parser.publish("second", "1.1", &[]);
+        fs::remove_file(parser.dir.join("second-1.1.jar")).unwrap();
+        (paths, parser)
+    }
+
+    #[test]
+    fn batches_with_a_failed_package_are_rolled_back() {
+        let (paths, parser) = half_broken_update("batches-rolled-back");
+        let before = server_snapshot(&paths);
+
+        let backend = test_backend(&parser, &paths);
+        let names = vec!["first".to_string(), "second".to_string()];
+        let batch = backend.pkg_update_batch(&names, false).unwrap();
+        assert!(batch.rolled_back);
+        assert_eq!(batch.updated.len(), 1);
+        assert_eq!(batch.failed.len(), 1);
+        assert_eq!(batch.failed[0].0, "second");
+        assert_eq!(server_snapshot(&paths), before);
+        assert_eq!(locked_version(&paths, "first"), "1.0");
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn batches_that_keep_going_keep_what_succeeded() {
+        let (paths, parser) = half_broken_update("batches-keep-going");
+
+        let backend = test_backend(&parser, &paths);
+        let names = vec!["first".to_string(), "second".to_string()];
+        let batch = backend.pkg_update_batch(&names, true).unwrap();
+        assert!(!batch.rolled_back);
+        assert_eq!(
+            batch.updated,
+            vec![("first".to_string(), "1.0".to_string(), "1.1".to_string())]
+        );
+        assert_eq!(batch.failed[0].0, "second");
+        assert_eq!(locked_version(&paths, "first"), "1.1");
+        assert_eq!(locked_version(&paths, "second"), "1.0");
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    /// A server with first and second installed at 1.0, whose pkg.yml has since dropped second
+    /// and added third, and a plan for bringing it in line after first 1.1 came out
+    fn planned_server(test: &str) -> (ProjectPaths, TestParser, Plan) {
+        let paths = test_paths(test);
+        let mut parser = TestParser::new(&paths);
+        parser.publish("first", "1.0", &[]);
+        parser.publish("second", "1.0", &[]);
+        parser.publish("third", "1.0", &[]);
+        install_unpinned(&parser, &paths, &["first", "second"]);
+        parser.publish("first", "1.1", &[]);
+        fs::write(paths.pkg_list(), "first: \"*\"\nthird: \"*\"\n").unwrap();
+        let plan = test_backend(&parser, &paths).pkg_plan().unwrap();
+        (paths, parser, plan)
+    }
+
+    #[test]
+    fn plans_are_applied_exactly_as_they_were_made() {
+        let (paths, mut parser, plan) = planned_server("plans-applied");
+        let url = |name: &str, version: &str| {
+            let path = parser.dir.join(format!("{}-{}.jar", name, version));
+            Url::from_file_path(path).unwrap().to_string()
+        };
+        let expected = vec![
+            Change::Update {
+                package: "first".to_string(),
+                from: "1.0".to_string(),
+                to: "1.1".to_string(),
+                url: url("first", "1.1"),
+            },
+            Change::Install {
+                package: "third".to_string(),
+                version: "1.0".to_string(),
+                url: url("third", "1.0"),
+                reason: InstallReason::Explicit,
+            },
+            Change::Remove {
+                package: "second".to_string(),
+                version: "1.0".to_string(),
+            },
+        ];
+        assert_eq!(plan.changes, expected);
+
+        // What's newest when the plan is applied doesn't matter, only what was planned
+        parser.publish("first", "1.2", &[]);
+        parser.publish("third", "1.1", &[]);
+        let plan_file = paths.plan_file();
+        plan.save(&plan_file).unwrap();
+        let backend = test_backend(&parser, &paths);
+        let applied = backend.pkg_apply(&Plan::load(&plan_file).unwrap()).unwrap();
+        assert_eq!(applied, expected);
+
+        let lockfile = Lockfile::load(&paths.lockfile()).unwrap();
+        let locked = lockfile
+            .packages
+            .iter()
+            .map(|(name, locked)| (name.clone(), locked.version.clone()))
+            .collect::<Vec<(String, String)>>();
+        assert_eq!(locked, pairs(&[("first", "1.1"), ("third", "1.0")]));
+        let replanned = backend.pkg_plan().unwrap();
+        let printed = replanned
+            .changes
+            .iter()
+            .map(|change| change.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(printed, vec!["~ first 1.1 -> 1.2", "~ third 1.0 -> 1.1"]);
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn stale_plans_are_refused() {
+        let (paths, parser, plan) = planned_server("stale-plans");
+        let backend = test_backend(&parser, &paths);
+        backend.install("third", InstallReason::Explicit).unwrap();
+        let before = server_snapshot(&paths);
+
+        let e = backend.pkg_apply(&plan).unwrap_err();
+        match e.downcast_ref::<plan::ErrorKind>() {
+            Some(plan::ErrorKind::PlanStale) => {}
+            _ => panic!("expected the plan to be stale, got {}", e),
+        }
+        assert_eq!(server_snapshot(&paths), before);
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn bundles_that_would_install_outside_the_plugins_folder_are_refused() {
+        let paths = test_paths("bundles-outside");
+        let parser = TestParser::new(&paths);
+        let made = paths.project_dir.join("made");
+        let lockfile = made.join("dropper.lock");
+        let jar = made.join("evil.jar");
+        test_jar(
+            &jar,
+            &[(
+                "plugin.yml",
+                "name: Evil\nversion: \"1.0\"\nmain: test.Main\n",
+            )],
+        );
+        fs::write(
+            &lockfile,
+            "packages:\n  evil: {version: \"1.0\", file: ../evil.jar, url: x}\n",
+        )
+        .unwrap();
+        let mut files = BTreeMap::new();
+        files.insert("evil".to_string(), ("x".to_string(), jar));
+        let bundle_path = paths.project_dir.join("evil.zip");
+        bundle::create(&bundle_path, "1.16.5", &lockfile, None, &files).unwrap();
+
+        let e = test_backend(&parser, &paths)
+            .pkg_install_bundle(&bundle_path)
+            .unwrap_err();
+        match e.downcast_ref::<bundle::ErrorKind>() {
+            Some(bundle::ErrorKind::BundleInvalid(_, why)) => assert_eq!(
+                why,
+                "it would install evil as ../evil.jar, outside the plugins folder"
+            ),
+            _ => panic!("expected the bundle to be refused, got {}", e),
+        }
+        assert!(!paths.lockfile().exists());
+        assert!(!paths.project_dir.join("evil.jar").exists());
+        assert!(!paths.bundle_dir().exists());
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn configs_arent_rendered_outside_the_plugin_directory() {
+        let paths = test_paths("configs-outside");
+        let mut parser = TestParser::new(&paths);
+        let plugin_yml = "name: ../escaped\nversion: \"1.0\"\nmain: test.Main\n";
+        parser.publish_jar("evil", "1.0", &[("plugin.yml", plugin_yml)]);
+        fs::write(
+            paths.pkg_list(),
+            "evil:\n  version: \"*\"\n  configs:\n    config.yml: config.yml\n",
+        )
+        .unwrap();
+        fs::write(paths.project_dir.join("config.yml"), "evil: true\n").unwrap();
+
+        let e = test_backend(&parser, &paths).pkg_install_all().unwrap_err();
+        match e.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::PluginNameInvalid(name, plugin_name)) => {
+                assert_eq!(
+                    (name.as_str(), plugin_name.as_str()),
+                    ("evil", "../escaped")
+                )
+            }
+            _ => panic!("expected the plugin's name to be refused, got {}", e),
+        }
+        assert!(!paths.project_dir.join("escaped").exists());
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn bundled_configs_are_only_extracted_into_a_data_folder() {
+        let paths = test_paths("bundled-configs");
+        let parser = TestParser::new(&paths);
+        let distribution = paths.project_dir.join("distribution.zip");
+        test_jar(
+            &distribution,
+            &[
+                ("Vault.jar", "jar"),
+                ("Vault/config.yml", "economy: true\n"),
+                ("Vault/lang/en.yml", "hello: hi\n"),
+                ("Other/config.yml", "other: true\n"),
+            ],
+        );
+        let backend = test_backend(&parser, &paths);
+
+        for plugin_name in ["../Vault", "/tmp/Vault", "Vault/..", "", "."] {
+            let e = backend
+                .extract_bundled_configs("vault", &distribution, "Vault.jar", plugin_name)
+                .unwrap_err();
+            match e.downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::PluginNameInvalid(_, invalid)) => assert_eq!(invalid, plugin_name),
+                _ => panic!("expected {:?} to be refused, got {}", plugin_name, e),
+            }
+        }
+        assert!(!paths.project_dir.join("Vault").exists());
+
+        let mut files = backend
+            .extract_bundled_configs("vault", &distribution, "Vault.jar", "Vault")
+            .unwrap();
+        files.sort();
+        assert_eq!(files, vec!["Vault/config.yml", "Vault/lang/en.yml"]);
+        let data_dir = paths.plugin_dir.join("Vault");
+        assert_eq!(
+            fs::read_to_string(data_dir.join("config.yml")).unwrap(),
+            "economy: true\n"
+        );
+        assert!(!paths.plugin_dir.join("Other").exists());
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    /// The name and contents of every file in a plugin directory, with the folders they're in
+    fn plugin_files(plugin_dir: &Path) -> BTreeMap<String, Vec<u8>> {
+        glob::glob(&format!("{}/**/*", plugin_dir.display()))
+            .unwrap()
+            .map(|path| path.unwrap())
+            .filter(|path| path.is_file())
+            .map(|path| {
+                (
+                    lock::file_name(path.strip_prefix(plugin_dir).unwrap()),
+                    fs::read(&path).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn servers_are_set_up_from_bundles_without_going_online() {
+        let made = test_paths("bundle-made");
+        let mut parser = TestParser::new(&made);
+        parser.publish("vault", "1.7.3", &[]);
+        parser.publish("first", "1.0", &["Vault"]);
+        parser.publish("second", "2.0", &[]);
+        install_unpinned(&parser, &made, &["vault", "first", "second"]);
+        let bundle_path = made.project_dir.join("server.zip");
+        let manifest = test_backend(&parser, &made)
+            .pkg_bundle(&bundle_path)
+            .unwrap();
+        assert_eq!(
+            manifest.artifacts.keys().collect::<Vec<&String>>(),
+            vec!["first", "second", "vault"]
+        );
+        // Nothing can be downloaded from here on
+        fs::remove_dir_all(&parser.dir).unwrap();
+
+        let installed = test_paths("bundle-installed");
+        let offline = TestParser::new(&installed);
+        let report = test_backend(&offline, &installed)
+            .pkg_install_bundle(&bundle_path)
+            .unwrap();
+        assert_eq!(report.installed.len(), 3);
+        assert_eq!(
+            fs::read(installed.lockfile()).unwrap(),
+            fs::read(made.lockfile()).unwrap()
+        );
+        assert_eq!(
+            fs::read(installed.pkg_list()).unwrap(),
+            fs::read(made.pkg_list()).unwrap()
+        );
+        assert_eq!(
+            plugin_files(&installed.plugin_dir),
+            plugin_files(&made.plugin_dir)
+        );
+        assert!(!installed.bundle_dir().exists());
+
+        fs::remove_dir_all(&made.project_dir).unwrap();
+        fs::remove_dir_all(&installed.project_dir).unwrap();
+    }
+
+    #[test]
+    fn bundles_can_only_be_written_as_zip_archives() {
+        let paths = test_paths("bundle-names");
+        let mut parser = TestParser::new(&paths);
+        parser.publish("vault", "1.7.3", &[]);
+        install_unpinned(&parser, &paths, &["vault"]);
+        let backend = test_backend(&parser, &paths);
+
+        for name in ["server.tar.zst", "server.tar.gz", "server"] {
+            let path = paths.project_dir.join(name);
+            let e = backend.pkg_bundle(&path).unwrap_err();
+            match e.downcast_ref::<bundle::ErrorKind>() {
+                Some(bundle::ErrorKind::NameUnsupported(_)) => {}
+                _ => panic!("expected {} to be refused, got {}", name, e),
+            }
+            assert!(!path.exists());
+        }
+        backend
+            .pkg_bundle(&paths.project_dir.join("server.ZIP"))
+            .unwrap();
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    type Specifier<'a> = Option<(Option<&'a str>, &'a str, Option<&'a str>)>;
+
+    /// Checks that each specifier parses into its (source, name, version), or is refused if
+    /// that's `None`
+    fn check_specifiers(cases: &[(&str, Specifier)]) {
+        for (spec, expected) in cases.iter() {
+            match (
+                PackageBackend::parse_package_specifier(spec.to_string()),
+                expected,
+            ) {
+                (Ok((source, name, version)), Some(expected)) => assert_eq!(
+                    (source.as_deref(), name.as_str(), version.as_deref()),
+                    *expected,
+                    "{}",
+                    spec
+                ),
+                (Err(ErrorKind::PkgSpecInvalid(invalid)), None) => assert_eq!(invalid, *spec),
+                (parsed, _) => panic!("{} was parsed as {:?}", spec, parsed),
+            }
+        }
+    }
+
+    #[test]
+    fn package_specifiers_can_name_projects_by_id() {
+        check_specifiers(&[
+            ("id:31043", Some((None, "id:31043", None))),
+            ("id:31043@1.7.3", Some((None, "id:31043", Some("1.7.3")))),
+            (
+                "curseforge:id:31043",
+                Some((Some("curseforge"), "id:31043", None)),
+            ),
+            ("id:", None),
+            ("id:vault", None),
+            ("id:31043x", None),
+            ("id:@1.7.3", None),
+        ]);
+    }
+
+    #[test]
+    fn package_specifiers_are_parsed_into_source_name_and_version() {
+        check_specifiers(&[
+            ("vault", Some((None, "vault", None))),
+            ("vault@1.7.3", Some((None, "vault", Some("1.7.3")))),
+            (
+                "vault@latest-dev",
+                Some((None, "vault", Some("latest-dev"))),
+            ),
+            ("spigot:vault", Some((Some("spigot"), "vault", None))),
+            (
+                "spigot:vault@1.7",
+                Some((Some("spigot"), "vault", Some("1.7"))),
+            ),
+            (
+                "github:EssentialsX/Essentials@2.19.0",
+                Some((Some("github"), "EssentialsX/Essentials", Some("2.19.0"))),
+            ),
+            ("@1.0", None),
+            ("foo@", None),
+            (":bar", None),
+            ("github:", None),
+            ("vault@1.7@1.8", None),
+            ("vault@newest", None),
+            ("Spigot:vault", None),
+            ("vault/extra", None),
+            ("github:a/b/c", None),
+            ("spigot:other:vault", None),
+        ]);
+    }
+
+    #[test]
+    fn sources_are_split_off_package_names() {
+        let cases = [
+            ("vault", (None, "vault")),
+            ("spigot:vault", (Some("spigot"), "vault")),
+            ("id:31043", (None, "id:31043")),
+            ("curseforge:id:31043", (Some("curseforge"), "id:31043")),
+            (":bar", (Some(""), "bar")),
+            ("github:", (Some("github"), "")),
+        ];
+        for (name, split) in cases.iter() {
+            assert_eq!(PackageBackend::split_source(name), *split, "{}", name);
+        }
+    }
+
+    #[test]
+    fn blocked_versions_are_only_installed_when_allowed() {
+        let paths = test_paths("blocked-versions");
+        let mut parser = TestParser::new(&paths);
+        parser.publish("vault", "1.7.2", &[]);
+        parser.publish("vault", "1.7.3", &[]);
+        let mut backend = test_backend(&parser, &paths);
+        let config = YamlLoader::load_from_str("vault:\n  \"1.7.3\": steals tokens\n").unwrap();
+        backend.blocklist = Blocklist::from_yaml(&config[0]).unwrap();
+
+        // Asking for a blocked version by name is refused, and leaves nothing behind
+        let e = backend.pkg_add("vault@1.7.3").unwrap_err();
+        match e.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::VersionBlocked(name, version, reason)) => {
+                assert_eq!(
+                    (name.as_str(), version.as_str(), reason.as_str()),
+                    ("vault", "1.7.3", "steals tokens")
+                )
+            }
+            _ => panic!("expected 1.7.3 to be blocked, got {}", e),
+        }
+        assert!(plugin_files(&paths.plugin_dir).is_empty());
+
+        // and the newest version is the newest one that isn't blocked
+        backend.pkg_add("vault").unwrap();
+        assert_eq!(locked_version(&paths, "vault"), "1.7.2");
+        backend.pkg_remove("vault", false).unwrap();
+
+        backend.allow_blocked = true;
+        backend.pkg_add("vault@1.7.3").unwrap();
+        assert_eq!(locked_version(&paths, "vault"), "1.7.3");
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
+    }
+
+    #[test]
+    fn config_templates_use_the_built_in_variables_unless_theyre_defined() {
+        let paths = test_paths("template-variables");
+        let mut parser = TestParser::new(&paths);
+        let plugin_yml = "name: Vault\nversion: \"1.7.3\"\nmain: test.Main\n";
+        parser.publish_jar("vault", "1.7.3", &[("plugin.yml", plugin_yml)]);
+        fs::write(
+            paths.pkg_list(),
+            "vault:\n  version: \"*\"\n  configs:\n    config.yml: vault.yml\n",
+        )
+        .unwrap();
+        fs::write(
+            paths.project_dir.join("vault.yml"),
+            "package: {{ package }}\nversion: {{ version }}\nserver: {{ server_version }}\n\
+             motd: {{ motd }}\n",
+        )
+        .unwrap();
+        fs::write(
+            paths.config_file(),
+            "plugin_website: bukkit\nserver_version: \"1.16.5\"\n\
+             variables:\n  version: from the config\n  motd: from the config\n",
+        )
+        .unwrap();
+        fs::write(paths.vars_file(), "motd: from vars.yml\n").unwrap();
+
+        test_backend(&parser, &paths).pkg_install_all().unwrap();
+        assert_eq!(
+            fs::read_to_string(paths.plugin_dir.join("Vault").join("config.yml")).unwrap(),
+            "package: vault\nversion: from the config\nserver: 1.16.5\nmotd: from vars.yml\n"
+        );
+
+        fs::remove_dir_all(&paths.project_dir).unwrap();
     }
 }
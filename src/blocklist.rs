@@ -0,0 +1,190 @@
+//! Versions of plugins that are known to be dangerous to run, like ones that corrupt worlds or
+//! were published with malware in them. dropper won't install or update to a blocked version
+//! unless it's told to, and `dropper outdated` points out blocked versions that are already
+//! installed.
+//!
+//! A few versions are blocked out of the box, and servers can block more in the config, by
+//! package and version constraint, with the reason for each:
+//!
+//! ```yaml
+//! blocklist:
+//!   someplugin:
+//!     "2.3.1": corrupts chunk data
+//!     "2.4.*": ships a backdoor
+//! ```
+
+use crate::resolver::Constraint;
+use std::error::Error;
+use std::fmt;
+use yaml_rust::Yaml;
+
+/// The versions blocked for every server, as (package, version constraint, reason). Only
+/// versions confirmed to be harmful belong here.
+const BUNDLED: &'static [(&'static str, &'static str, &'static str)] = &[];
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // The config's blocklist isn't a map of packages to versions and reasons. Takes the package
+    // whose entry is wrong, if it's known, as a param.
+    BlocklistInvalid(Option<String>),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::BlocklistInvalid(Some(s)) => format!(
+                    "the blocklist entry for '{}' should map versions to reasons",
+                    s
+                ),
+                ErrorKind::BlocklistInvalid(None) => {
+                    "the blocklist should map packages to versions and reasons".to_string()
+                }
+            }
+        )
+    }
+}
+
+/// A range of versions of a package that shouldn't be installed
+#[derive(Debug, Clone)]
+pub struct Blocked {
+    pub package: String,
+    pub constraint: Constraint,
+    /// Why the versions are blocked
+    pub reason: String,
+}
+
+/// Every blocked version, from the bundled list and the config
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    entries: Vec<Blocked>,
+}
+
+impl Blocklist {
+    /// The bundled blocklist, with the config's `blocklist` added to it
+    ///
+    /// # Errors
+    /// * [`ErrorKind::BlocklistInvalid`](enum.ErrorKind.html#variant.BlocklistInvalid) - the config's blocklist is incorrectly formatted
+    pub fn from_yaml(yaml: &Yaml) -> Result<Blocklist, ErrorKind> {
+        let mut entries = BUNDLED
+            .iter()
+            .map(|(package, version, reason)| Blocked {
+                package: package.to_string(),
+                constraint: Constraint::parse(version),
+                reason: reason.to_string(),
+            })
+            .collect::<Vec<Blocked>>();
+
+        let packages = match yaml {
+            Yaml::Hash(packages) => packages,
+            Yaml::Null | Yaml::BadValue => return Ok(Blocklist { entries: entries }),
+            _ => return Err(ErrorKind::BlocklistInvalid(None)),
+        };
+        for (package, versions) in packages.iter() {
+            let package = match package.as_str() {
+                Some(package) => package.to_string(),
+                None => return Err(ErrorKind::BlocklistInvalid(None)),
+            };
+            let versions = match versions {
+                Yaml::Hash(versions) => versions,
+                _ => return Err(ErrorKind::BlocklistInvalid(Some(package))),
+            };
+            for (version, reason) in versions.iter() {
+                // Unquoted versions like 2.3 are read as numbers
+                let version = match version {
+                    Yaml::String(s) | Yaml::Real(s) => s.clone(),
+                    Yaml::Integer(i) => i.to_string(),
+                    _ => return Err(ErrorKind::BlocklistInvalid(Some(package))),
+                };
+                match reason.as_str() {
+                    Some(reason) => entries.push(Blocked {
+                        package: package.clone(),
+                        constraint: Constraint::parse(&version),
+                        reason: reason.to_string(),
+                    }),
+                    None => return Err(ErrorKind::BlocklistInvalid(Some(package))),
+                }
+            }
+        }
+
+        Ok(Blocklist { entries: entries })
+    }
+
+    /// Why a version of a package is blocked, or `None` if it isn't
+    pub fn reason(&self, package: &str, version: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|blocked| {
+                blocked.package.eq_ignore_ascii_case(package) && blocked.constraint.matches(version)
+            })
+            .map(|blocked| blocked.reason.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    fn blocklist(yaml: &str) -> Result<Blocklist, ErrorKind> {
+        Blocklist::from_yaml(&YamlLoader::load_from_str(yaml).unwrap()[0])
+    }
+
+    #[test]
+    fn config_entries_block_the_versions_they_match() {
+        let blocklist = blocklist(
+            "someplugin:\n  \"2.3.1\": corrupts chunk data\n  \"2.4.*\": ships a backdoor\n  \
+             2.5: leaks memory\n  3: crashes on start\n",
+        )
+        .unwrap();
+        assert_eq!(
+            blocklist.reason("someplugin", "2.3.1"),
+            Some("corrupts chunk data")
+        );
+        assert_eq!(
+            blocklist.reason("SomePlugin", "2.4.7"),
+            Some("ships a backdoor")
+        );
+        assert_eq!(blocklist.reason("someplugin", "2.5"), Some("leaks memory"));
+        assert_eq!(
+            blocklist.reason("someplugin", "3"),
+            Some("crashes on start")
+        );
+        assert_eq!(blocklist.reason("someplugin", "2.3.2"), None);
+        assert_eq!(blocklist.reason("otherplugin", "2.3.1"), None);
+    }
+
+    #[test]
+    fn a_missing_blocklist_only_has_the_bundled_entries() {
+        let blocklist = Blocklist::from_yaml(&Yaml::BadValue).unwrap();
+        assert_eq!(blocklist.entries.len(), BUNDLED.len());
+    }
+
+    #[test]
+    fn malformed_blocklists_are_refused() {
+        let cases = [
+            ("- someplugin\n", None),
+            ("someplugin: \"2.3.1\"\n", Some("someplugin")),
+            (
+                "someplugin:\n  \"2.3.1\": [corrupts, chunks]\n",
+                Some("someplugin"),
+            ),
+            (
+                "someplugin:\n  [2, 3]: corrupts chunk data\n",
+                Some("someplugin"),
+            ),
+        ];
+        for (yaml, package) in cases.iter() {
+            match blocklist(yaml) {
+                Err(ErrorKind::BlocklistInvalid(invalid)) => {
+                    assert_eq!(invalid.as_deref(), *package, "{}", yaml)
+                }
+                Ok(_) => panic!("expected {:?} to be refused", yaml),
+            }
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! This module builds plugins that aren't published anywhere as JARs from their source. A pkg.yml
+//! entry with a `git` repository is cloned at its `ref` (a tag, branch, or commit), built with
+//! Maven or Gradle, and the JAR the build produces is installed like any download:
+//!
+//! ```yaml
+//! someplugin:
+//!   git: https://github.com/someone/someplugin.git
+//!   ref: v1.2.0
+//! ```
+//!
+//! Which build tool to use is worked out from the files in the repository, so `git` and `mvn` or
+//! `gradle` (unless the repository has a Gradle wrapper) need to be installed.
+//!
+//! Builds are cached by the commit they were built from and how they were built, so installing
+//! the same package on another server doesn't compile it again. The cache is shared by every
+//! server on the machine; it lives in `DROPPER_CACHE_DIR` if that's set, or the user's cache
+//! folder otherwise, and `dropper cache prune-builds` clears out old builds.
+
+use glob::glob;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use yaml_rust::Yaml;
+
+/// Download links of packages built from source start with this, so they can be told apart
+const LINK_PREFIX: &'static str = "git+";
+// Separates the repository from the ref in a link
+const REF_SPLIT_CHAR: char = '#';
+
+/// Where the JARs of each build tool end up, relative to the repository, including those of
+/// modules one level down
+const MAVEN_OUTPUTS: [&'static str; 2] = ["target/*.jar", "*/target/*.jar"];
+const GRADLE_OUTPUTS: [&'static str; 2] = ["build/libs/*.jar", "*/build/libs/*.jar"];
+// JARs that builds make alongside the plugin, which are never what should be installed
+const NOT_PLUGINS: [&'static str; 4] = ["-sources.jar", "-javadoc.jar", "-plain.jar", "original-"];
+
+// Overrides where dropper's machine-wide cache lives
+const CACHE_DIR_VAR: &'static str = "DROPPER_CACHE_DIR";
+const BUILD_CACHE_DIR: &'static str = "builds";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A pkg.yml entry's git settings are missing or the wrong type. Takes the package name as
+    // a param.
+    SourceInvalid(String),
+    // There's no pom.xml or Gradle build in the repository. Takes the repository as a param.
+    BuildUnknown(String),
+    // A git or build command failed. Takes the command and how it exited as params.
+    CommandFailed(String, String),
+    // The build finished without producing a JAR. Takes the repository as a param.
+    JarMissing(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::SourceInvalid(s) => {
+                    format!("the git source of '{}' needs a `git` repository and a `ref`", s)
+                }
+                ErrorKind::BuildUnknown(s) => {
+                    format!("{} has neither a pom.xml nor a Gradle build", s)
+                }
+                ErrorKind::CommandFailed(command, status) => {
+                    format!("`{}` failed ({})", command, status)
+                }
+                ErrorKind::JarMissing(s) => format!("building {} didn't produce a JAR", s),
+            }
+        )
+    }
+}
+
+/// The build tools a repository can use
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuildTool {
+    Maven,
+    Gradle,
+}
+
+impl BuildTool {
+    /// Works out which build tool a checked out repository uses
+    fn detect(repo: &Path) -> Option<BuildTool> {
+        if repo.join("pom.xml").exists() {
+            Some(BuildTool::Maven)
+        } else if ["gradlew", "build.gradle", "build.gradle.kts"]
+            .iter()
+            .any(|file| repo.join(file).exists())
+        {
+            Some(BuildTool::Gradle)
+        } else {
+            None
+        }
+    }
+
+    /// The arguments the build tool is run with, leaving the tests out since a server admin
+    /// can't do much about them failing
+    fn args(&self) -> &'static [&'static str] {
+        match self {
+            BuildTool::Maven => &["-B", "-q", "package", "-DskipTests"],
+            BuildTool::Gradle => &["build", "-x", "test"],
+        }
+    }
+
+    /// The command that builds the repository
+    fn command(&self, repo: &Path) -> Command {
+        match self {
+            BuildTool::Maven => {
+                let mut command = Command::new("mvn");
+                command.args(self.args());
+                command
+            }
+            BuildTool::Gradle => {
+                let wrapper = repo.join(match cfg!(windows) {
+                    true => "gradlew.bat",
+                    false => "gradlew",
+                });
+                let mut command = match wrapper.exists() {
+                    true => Command::new(wrapper),
+                    false => Command::new("gradle"),
+                };
+                command.args(self.args());
+                command
+            }
+        }
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        match self {
+            BuildTool::Maven => &MAVEN_OUTPUTS,
+            BuildTool::Gradle => &GRADLE_OUTPUTS,
+        }
+    }
+}
+
+/// A git repository to build a package from
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitSource {
+    /// Where to clone the repository from
+    pub url: String,
+    /// The tag, branch, or commit to build
+    pub reference: String,
+}
+
+impl GitSource {
+    /// Reads the git source out of a pkg.yml entry, or `None` if the entry doesn't have one
+    ///
+    /// # Errors
+    /// * [`ErrorKind::SourceInvalid`](enum.ErrorKind.html#variant.SourceInvalid) - the entry has a `git` key, but not a usable source
+    pub fn from_yaml(name: &str, entry: &Yaml) -> Result<Option<GitSource>, ErrorKind> {
+        match (&entry["git"], &entry["ref"]) {
+            (Yaml::BadValue, _) => Ok(None),
+            (Yaml::String(url), Yaml::String(reference)) => Ok(Some(GitSource {
+                url: url.clone(),
+                reference: reference.clone(),
+            })),
+            _ => Err(ErrorKind::SourceInvalid(name.to_string())),
+        }
+    }
+
+    /// Reads the git source back out of a download link made by `link`
+    pub fn from_link(link: &str) -> Option<GitSource> {
+        if !link.starts_with(LINK_PREFIX) {
+            return None;
+        }
+        let (url, reference) = link[LINK_PREFIX.len()..].rsplit_once(REF_SPLIT_CHAR)?;
+        Some(GitSource {
+            url: url.to_string(),
+            reference: reference.to_string(),
+        })
+    }
+
+    /// The download link to record for the package, which is enough to build it again
+    pub fn link(&self) -> String {
+        format!("{}{}{}{}", LINK_PREFIX, self.url, REF_SPLIT_CHAR, self.reference)
+    }
+
+    /// The version to install the package as. Refs can be branches with slashes in them, which
+    /// can't be part of a file name.
+    pub fn version(&self) -> String {
+        self.reference.replace('/', "-")
+    }
+
+    /// Clones the repository into `work_dir` (replacing whatever is there), builds it, and
+    /// returns the path of the plugin JAR it produced. When a build produces more than one JAR,
+    /// the biggest is taken, since that's the one with its dependencies shaded in.
+    ///
+    /// If `cache` is given, a build of the same commit made the same way is used instead of
+    /// building again, and new builds are added to it.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::CommandFailed`](enum.ErrorKind.html#variant.CommandFailed) - the clone, checkout, or build failed
+    /// * [`ErrorKind::BuildUnknown`](enum.ErrorKind.html#variant.BuildUnknown) - there's no build to run
+    /// * [`ErrorKind::JarMissing`](enum.ErrorKind.html#variant.JarMissing) - the build didn't produce a JAR
+    /// * `std::io::ErrorKind::*` - a command couldn't be started, or an IO error occured
+    pub fn build(&self, work_dir: &Path, cache: Option<&Path>) -> Result<PathBuf, Box<Error>> {
+        if work_dir.exists() {
+            fs::remove_dir_all(work_dir)?;
+        }
+        if let Some(parent) = work_dir.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut clone = Command::new("git");
+        clone
+            .args(["clone", "--quiet", &self.url])
+            .arg(work_dir);
+        Self::run(clone, &format!("git clone {}", self.url))?;
+        // Build tools are run from inside the repository, so relative paths would go wrong
+        let work_dir = &work_dir.canonicalize()?;
+
+        let mut checkout = Command::new("git");
+        checkout
+            .args(["checkout", "--quiet", &self.reference])
+            .current_dir(work_dir);
+        Self::run(checkout, &format!("git checkout {}", self.reference))?;
+
+        let tool = match BuildTool::detect(work_dir) {
+            Some(tool) => tool,
+            None => return Err(Box::new(ErrorKind::BuildUnknown(self.url.clone()))),
+        };
+
+        // Refs like branches move, so builds are cached by the commit they point at
+        let cached = match cache {
+            Some(cache) => {
+                let commit = Self::head_commit(work_dir)?;
+                let config = format!("{:?} {}", tool, tool.args().join(" "));
+                let key = format!("{}-{:x}", commit, md5::compute(config.as_bytes()));
+                Some(cache.join(format!("{}.jar", key)))
+            }
+            None => None,
+        };
+        if let Some(cached) = &cached {
+            if cached.exists() {
+                return Ok(cached.clone());
+            }
+        }
+
+        let mut command = tool.command(work_dir);
+        command.current_dir(work_dir);
+        let shown = format!("{:?}", command);
+        Self::run(command, &shown)?;
+
+        let mut jars = Vec::new();
+        for pattern in tool.outputs() {
+            let pattern = work_dir.join(pattern).display().to_string();
+            for path in glob(&pattern)? {
+                let path = path?;
+                let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+                if !NOT_PLUGINS.iter().any(|suffix| file_name.contains(suffix)) {
+                    jars.push((fs::metadata(&path)?.len(), path));
+                }
+            }
+        }
+
+        let jar = match jars.into_iter().max_by_key(|(size, _)| *size) {
+            Some((_, jar)) => jar,
+            None => return Err(Box::new(ErrorKind::JarMissing(self.url.clone()))),
+        };
+        match cached {
+            Some(cached) => {
+                fs::create_dir_all(cached.parent().unwrap())?;
+                fs::copy(&jar, &cached)?;
+                Ok(cached)
+            }
+            None => Ok(jar),
+        }
+    }
+
+    /// The commit a checked out repository is at
+    fn head_commit(repo: &Path) -> Result<String, Box<Error>> {
+        let output = Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(repo)
+            .output()?;
+        match output.status.success() {
+            true => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+            false => Err(Box::new(ErrorKind::CommandFailed(
+                "git rev-parse HEAD".to_string(),
+                output.status.to_string(),
+            ))),
+        }
+    }
+
+    /// Runs a command to completion, with its output going straight to the terminal
+    fn run(mut command: Command, shown: &str) -> Result<(), Box<Error>> {
+        let status = command.status()?;
+        match status.success() {
+            true => Ok(()),
+            false => Err(Box::new(ErrorKind::CommandFailed(
+                shown.to_string(),
+                status.to_string(),
+            ))),
+        }
+    }
+}
+
+/// Where dropper keeps what's shared by every server on the machine, or `None` if there's
+/// nowhere to put it. This is `DROPPER_CACHE_DIR`, or `dropper` in the user's cache folder.
+pub fn cache_root() -> Option<PathBuf> {
+    match env::var_os(CACHE_DIR_VAR) {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => match (env::var_os("XDG_CACHE_HOME"), env::var_os("LOCALAPPDATA")) {
+            (Some(dir), _) => Some(PathBuf::from(dir).join("dropper")),
+            (None, Some(dir)) if cfg!(windows) => Some(PathBuf::from(dir).join("dropper")),
+            _ => Some(PathBuf::from(env::var_os("HOME")?).join(".cache").join("dropper")),
+        },
+    }
+}
+
+/// Where builds are cached, or `None` if there's nowhere to put them. This is `builds` in the
+/// cache root.
+pub fn build_cache_dir() -> Option<PathBuf> {
+    cache_root().map(|root| root.join(BUILD_CACHE_DIR))
+}
+
+/// Removes the cached builds that were made longer ago than `max_age`, returning the size of
+/// each one that was removed
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn prune_builds(cache: &Path, max_age: Duration) -> io::Result<Vec<(PathBuf, u64)>> {
+    let mut pruned = Vec::new();
+    if !cache.exists() {
+        return Ok(pruned);
+    }
+
+    let now = SystemTime::now();
+    for entry in fs::read_dir(cache)? {
+        let path = entry?.path();
+        let metadata = fs::metadata(&path)?;
+        let age = now
+            .duration_since(metadata.modified()?)
+            .unwrap_or(Duration::from_secs(0));
+        if metadata.is_file() && age >= max_age {
+            fs::remove_file(&path)?;
+            pruned.push((path, metadata.len()));
+        }
+    }
+    Ok(pruned)
+}
@@ -0,0 +1,384 @@
+//! This module packs a server's plugins into a bundle, a single file that a server with no
+//! internet access can be set up from. A bundle holds the lockfile and the pkg.yml, every file
+//! the lockfile points at, as it was downloaded, and a manifest listing those files:
+//!
+//! ```yaml
+//! format: 1
+//! created: 1700000000
+//! server_version: 1.20.4
+//! artifacts:
+//!   worldedit:
+//!     path: artifacts/5d1b4cbd2b3ab8e7a3f2fd4e3c7d7a0ad3f1e4b3a8d2c1c7e6de9f2b1a0c3e4f
+//!     url: "https://dev.bukkit.org/projects/worldedit/files/2597538/download"
+//!     sha256: 5d1b4cbd2b3ab8e7a3f2fd4e3c7d7a0ad3f1e4b3a8d2c1c7e6de9f2b1a0c3e4f
+//! ```
+//!
+//! Files are kept in the bundle under their SHA-256, so packages that share a download share the
+//! file too. The manifest's digests are of the files as they are in the bundle, which for a
+//! distribution is the whole distribution rather than the JAR the lockfile pins.
+//!
+//! A bundle is a ZIP archive, and is named like one, with a `.zip` extension. The JARs in it
+//! are compressed already, so they're stored as they are, and only the lockfile, the pkg.yml and
+//! the manifest are deflated. A bundle is checked against its manifest as it's unpacked, and
+//! installed from like the lockfile is synced, with the bundle's files in place of downloads.
+
+use crate::hash;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// The newest version of the manifest format there is
+pub const FORMAT: i64 = 1;
+/// Where the manifest is in a bundle
+pub const MANIFEST_ENTRY: &'static str = "manifest.yml";
+/// Where the lockfile is in a bundle
+pub const LOCKFILE_ENTRY: &'static str = "dropper.lock";
+/// Where the pkg.yml is in a bundle, if it has one
+pub const PKG_LIST_ENTRY: &'static str = "pkg.yml";
+const ARTIFACTS_DIR: &'static str = "artifacts";
+const EXTENSION: &'static str = "zip";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // There's no lockfile to make a bundle from. Takes its path as a param.
+    LockfileMissing(String),
+    // A bundle would be written under a name that says it's something other than a ZIP archive.
+    // Takes its path as a param.
+    NameUnsupported(String),
+    // A bundle isn't one, or is missing something. Takes its path and what's wrong as params.
+    BundleInvalid(String, String),
+    // A file in a bundle doesn't match the manifest's digest. Takes the bundle's path and the
+    // package the file is for as params.
+    ArtifactCorrupt(String, String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::LockfileMissing(s) => format!(
+                    "there's no lockfile at {}, so there's nothing to bundle; install something first",
+                    s
+                ),
+                ErrorKind::NameUnsupported(s) => format!(
+                    "bundles are ZIP archives, so {} should end in .{}",
+                    s, EXTENSION
+                ),
+                ErrorKind::BundleInvalid(s, why) => format!("{} isn't a usable bundle: {}", s, why),
+                ErrorKind::ArtifactCorrupt(s, name) => format!(
+                    "the file for {} in the bundle {} doesn't match its digest, so the bundle is corrupt",
+                    name, s
+                ),
+            }
+        )
+    }
+}
+
+/// A file that a package was downloaded as, kept in a bundle
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artifact {
+    /// Where the file is in the bundle
+    pub path: String,
+    /// Where the file was downloaded from, which is the package's URL in the lockfile
+    pub url: String,
+    /// The SHA-256 of the file
+    pub sha256: String,
+}
+
+/// What's in a bundle
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    /// When the bundle was made, in seconds since the Unix epoch
+    pub created: u64,
+    /// The server version the packages were locked for
+    pub server_version: String,
+    /// The file each package was downloaded as, keyed by package name
+    pub artifacts: BTreeMap<String, Artifact>,
+}
+
+/// A bundle that's been unpacked and checked against its manifest
+#[derive(Debug, Clone)]
+pub struct Unpacked {
+    pub manifest: Manifest,
+    /// Where the bundle's lockfile was unpacked to
+    pub lockfile: PathBuf,
+    /// Where the bundle's pkg.yml was unpacked to, if it has one
+    pub pkg_list: Option<PathBuf>,
+    /// Where the file for each package was unpacked to, keyed by package name
+    pub artifacts: BTreeMap<String, PathBuf>,
+}
+
+impl Manifest {
+    /// Reads a bundle's manifest
+    ///
+    /// # Errors
+    /// * [`ErrorKind::BundleInvalid`](enum.ErrorKind.html#variant.BundleInvalid) - the manifest is corrupt, or in a newer format
+    fn from_yaml(bundle: &str, contents: &str) -> Result<Manifest, ErrorKind> {
+        let invalid = |why: &str| ErrorKind::BundleInvalid(bundle.to_string(), why.to_string());
+        let doc = YamlLoader::load_from_str(contents)
+            .map_err(|e| invalid(&format!("its manifest is corrupt: {}", e)))?
+            .into_iter()
+            .next()
+            .unwrap_or(Yaml::Null);
+        match &doc["format"] {
+            Yaml::Integer(format) if *format <= FORMAT => {}
+            Yaml::Integer(_) => return Err(invalid("it's in a newer format, so update dropper")),
+            _ => return Err(invalid("its manifest has no format")),
+        }
+        let listed = match &doc["artifacts"] {
+            Yaml::Hash(listed) => listed,
+            _ => return Err(invalid("its manifest should have a map of artifacts")),
+        };
+
+        let mut artifacts = BTreeMap::new();
+        for (name, entry) in listed.iter() {
+            let field = |key: &str| {
+                entry[key]
+                    .as_str()
+                    .map(|s| s.to_string())
+                    .ok_or_else(|| invalid(&format!("an artifact in its manifest has no {}", key)))
+            };
+            let name = name
+                .as_str()
+                .ok_or_else(|| invalid("its manifest has an artifact without a name"))?;
+            artifacts.insert(
+                name.to_string(),
+                Artifact {
+                    path: field("path")?,
+                    url: field("url")?,
+                    sha256: field("sha256")?.to_lowercase(),
+                },
+            );
+        }
+
+        Ok(Manifest {
+            created: doc["created"].as_i64().unwrap_or(0) as u64,
+            server_version: doc["server_version"]
+                .as_str()
+                .map_or(String::new(), |s| s.to_string()),
+            artifacts: artifacts,
+        })
+    }
+
+    fn to_yaml(&self) -> String {
+        let mut artifacts = Hash::new();
+        for (name, artifact) in self.artifacts.iter() {
+            let mut entry = Hash::new();
+            for (key, value) in [
+                ("path", &artifact.path),
+                ("url", &artifact.url),
+                ("sha256", &artifact.sha256),
+            ]
+            .iter()
+            {
+                entry.insert(Yaml::from_str(key), Yaml::String(value.to_string()));
+            }
+            artifacts.insert(Yaml::String(name.clone()), Yaml::Hash(entry));
+        }
+
+        let mut doc = Hash::new();
+        doc.insert(Yaml::from_str("format"), Yaml::Integer(FORMAT));
+        doc.insert(
+            Yaml::from_str("created"),
+            Yaml::Integer(self.created as i64),
+        );
+        doc.insert(
+            Yaml::from_str("server_version"),
+            Yaml::String(self.server_version.clone()),
+        );
+        doc.insert(Yaml::from_str("artifacts"), Yaml::Hash(artifacts));
+
+        let mut tmp_string = String::new();
+        YamlEmitter::new(&mut tmp_string)
+            .dump(&Yaml::Hash(doc))
+            .unwrap();
+        format!("{}\n", tmp_string)
+    }
+}
+
+/// Checks that a bundle can be written to `path`, which has to be named like a ZIP archive
+///
+/// # Errors
+/// * [`ErrorKind::NameUnsupported`](enum.ErrorKind.html#variant.NameUnsupported) - the name doesn't end in `.zip`
+pub fn check_name(path: &Path) -> Result<(), ErrorKind> {
+    match path.extension() {
+        Some(ext) if ext.eq_ignore_ascii_case(EXTENSION) => Ok(()),
+        _ => Err(ErrorKind::NameUnsupported(path.display().to_string())),
+    }
+}
+
+/// Writes a bundle to `path`, replacing whatever was there. It's written next to `path` first,
+/// so that a bundle that was cut short is never left where one was asked for. Returns the
+/// bundle's manifest.
+///
+/// # Arguments
+///
+/// * `server_version` - The server version the packages were locked for
+/// * `lockfile` - The lockfile to bundle
+/// * `pkg_list` - The pkg.yml to bundle, if there is one
+/// * `files` - The file each package was downloaded as, and where it was downloaded from,
+///   keyed by package name
+///
+/// # Errors
+/// * [`ErrorKind::NameUnsupported`](enum.ErrorKind.html#variant.NameUnsupported) - the name doesn't end in `.zip`
+/// * `zip::result::ZipError` - the bundle couldn't be written
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn create(
+    path: &Path,
+    server_version: &str,
+    lockfile: &Path,
+    pkg_list: Option<&Path>,
+    files: &BTreeMap<String, (String, PathBuf)>,
+) -> Result<Manifest, Box<Error>> {
+    check_name(path)?;
+    let mut manifest = Manifest {
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0),
+        server_version: server_version.to_string(),
+        artifacts: BTreeMap::new(),
+    };
+    for (name, (url, file)) in files.iter() {
+        let sha256 = hash::digest_file(file, &["sha256"])?
+            .remove("sha256")
+            .unwrap();
+        manifest.artifacts.insert(
+            name.clone(),
+            Artifact {
+                path: format!("{}/{}", ARTIFACTS_DIR, sha256),
+                url: url.clone(),
+                sha256: sha256,
+            },
+        );
+    }
+
+    let mut partial_name = path.file_name().unwrap_or_default().to_os_string();
+    partial_name.push(".part");
+    let partial = path.with_file_name(partial_name);
+    let written = write_bundle(&partial, &manifest, lockfile, pkg_list, files);
+    if let Err(e) = written {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    fs::rename(&partial, path)?;
+    Ok(manifest)
+}
+
+/// Does the writing for `create`
+fn write_bundle(
+    path: &Path,
+    manifest: &Manifest,
+    lockfile: &Path,
+    pkg_list: Option<&Path>,
+    files: &BTreeMap<String, (String, PathBuf)>,
+) -> Result<(), Box<Error>> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let deflated = FileOptions::default();
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file(MANIFEST_ENTRY, deflated)?;
+    zip.write_all(manifest.to_yaml().as_bytes())?;
+    zip.start_file(LOCKFILE_ENTRY, deflated)?;
+    zip.write_all(&fs::read(lockfile)?)?;
+    if let Some(pkg_list) = pkg_list {
+        zip.start_file(PKG_LIST_ENTRY, deflated)?;
+        zip.write_all(&fs::read(pkg_list)?)?;
+    }
+
+    let mut written = Vec::new();
+    for (name, (_, file)) in files.iter() {
+        let artifact = &manifest.artifacts[name];
+        if written.contains(&artifact.path) {
+            continue;
+        }
+        zip.start_file(artifact.path.as_str(), stored)?;
+        io::copy(&mut File::open(file)?, &mut zip)?;
+        written.push(artifact.path.clone());
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Unpacks a bundle into `dir`, checking every file for a package against the manifest. Files
+/// are unpacked under their digests, so nothing in the bundle can say where they go.
+///
+/// # Errors
+/// * [`ErrorKind::BundleInvalid`](enum.ErrorKind.html#variant.BundleInvalid) - the bundle isn't a ZIP archive, or is missing its manifest, its lockfile or a file the manifest lists
+/// * [`ErrorKind::ArtifactCorrupt`](enum.ErrorKind.html#variant.ArtifactCorrupt) - a file doesn't match the manifest's digest
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn unpack(path: &Path, dir: &Path) -> Result<Unpacked, Box<Error>> {
+    let bundle = path.display().to_string();
+    let invalid = |why: String| Box::new(ErrorKind::BundleInvalid(bundle.clone(), why));
+    let mut archive = ZipArchive::new(File::open(path)?)
+        .map_err(|e| invalid(format!("it isn't a ZIP archive ({})", e)))?;
+
+    let mut contents = String::new();
+    archive
+        .by_name(MANIFEST_ENTRY)
+        .map_err(|_| invalid("it has no manifest".to_string()))?
+        .read_to_string(&mut contents)?;
+    let manifest = Manifest::from_yaml(&bundle, &contents)?;
+
+    fs::create_dir_all(dir.join(ARTIFACTS_DIR))?;
+    let mut extract = |name: &str, dest: &Path| -> Result<bool, Box<Error>> {
+        let mut file = match archive.by_name(name) {
+            Ok(file) => file,
+            Err(_) => return Ok(false),
+        };
+        io::copy(&mut file, &mut File::create(dest)?)?;
+        Ok(true)
+    };
+    let lockfile = dir.join(LOCKFILE_ENTRY);
+    if !extract(LOCKFILE_ENTRY, &lockfile)? {
+        return Err(invalid("it has no lockfile".to_string()));
+    }
+    let pkg_list = dir.join(PKG_LIST_ENTRY);
+    let pkg_list = match extract(PKG_LIST_ENTRY, &pkg_list)? {
+        true => Some(pkg_list),
+        false => None,
+    };
+
+    let mut artifacts = BTreeMap::new();
+    for (name, artifact) in manifest.artifacts.iter() {
+        let is_digest =
+            artifact.sha256.len() == 64 && artifact.sha256.chars().all(|c| c.is_ascii_hexdigit());
+        if !is_digest {
+            return Err(invalid(format!("the digest of {} isn't a SHA-256", name)));
+        }
+        let dest = dir.join(ARTIFACTS_DIR).join(&artifact.sha256);
+        if !extract(&artifact.path, &dest)? {
+            return Err(invalid(format!("it's missing the file for {}", name)));
+        }
+        let mut expected = BTreeMap::new();
+        expected.insert("sha256".to_string(), artifact.sha256.clone());
+        if hash::verify_file(&dest, &expected)?.is_some() {
+            return Err(Box::new(ErrorKind::ArtifactCorrupt(
+                bundle.clone(),
+                name.clone(),
+            )));
+        }
+        artifacts.insert(name.clone(), dest);
+    }
+
+    Ok(Unpacked {
+        manifest: manifest,
+        lockfile: lockfile,
+        pkg_list: pkg_list,
+        artifacts: artifacts,
+    })
+}
@@ -0,0 +1,222 @@
+//! This module manages what dropper keeps cached: the pages it has fetched from plugin websites,
+//! which each server keeps in `.dropper/cache/http`, and the artifacts every server on the
+//! machine shares, which are the builds of packages built from source and the JARs in the content
+//! store (see the [`store`](../store/index.html) module). `dropper cache stats` reports how big
+//! each cache is, and `dropper cache prune` removes what's oldest in them. The config can also
+//! cap how big they get altogether, which is enforced after every change to the server:
+//!
+//! ```yaml
+//! cache_max_size: 2G
+//! ```
+//!
+//! JARs in the store that a plugins folder still links to are never removed, since the server
+//! would lose the plugin, but they still count towards the cap.
+
+use crate::build;
+use crate::http;
+use crate::store;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// One of the caches dropper keeps
+#[derive(Debug, Clone)]
+pub struct Cache {
+    /// What the cache goes by, like `http`
+    pub name: &'static str,
+    pub dir: PathBuf,
+}
+
+/// How big a cache is, as found by `stats`
+#[derive(Debug)]
+pub struct CacheStats {
+    pub cache: Cache,
+    /// How many things are cached, like pages or JARs
+    pub entries: usize,
+    /// How many bytes they take up
+    pub size: u64,
+    /// When the oldest of them was cached
+    pub oldest: Option<SystemTime>,
+    /// How many of them are in use, and can't be pruned
+    pub in_use: usize,
+}
+
+/// What `prune` removed
+#[derive(Debug, Default)]
+pub struct Pruned {
+    /// How many things were removed
+    pub entries: usize,
+    /// How many bytes were freed
+    pub size: u64,
+}
+
+/// Something cached, which may be kept in several files, like a page and its validators
+struct Entry {
+    files: Vec<PathBuf>,
+    size: u64,
+    modified: SystemTime,
+    in_use: bool,
+}
+
+/// The caches a server uses, given where it keeps its pages. Caches with nowhere to be kept
+/// are left out.
+pub fn caches(http_cache_dir: &Path) -> Vec<Cache> {
+    let mut caches = vec![Cache {
+        name: "http",
+        dir: http_cache_dir.to_path_buf(),
+    }];
+    if let Some(dir) = build::build_cache_dir() {
+        caches.push(Cache {
+            name: "builds",
+            dir: dir,
+        });
+    }
+    if let Some(dir) = store::store_dir() {
+        caches.push(Cache {
+            name: "store",
+            dir: dir,
+        });
+    }
+    caches
+}
+
+/// Parses a size like `500M` or `2G` into bytes. Suffixes are powers of 1024, and a plain
+/// number is in bytes.
+pub fn parse_size(size: &str) -> Option<u64> {
+    // Sizes are written like download rates, without the `/s`
+    let size = size.trim().trim_end_matches(['B', 'b']);
+    match size.contains('/') {
+        true => None,
+        false => http::parse_rate(size),
+    }
+}
+
+/// Parses an age like `90d`, `12h` or `30m`. A plain number is in days.
+pub fn parse_age(age: &str) -> Option<Duration> {
+    let age = age.trim();
+    let (number, multiplier) = match age.chars().last()? {
+        'd' => (&age[..age.len() - 1], 24 * 60 * 60),
+        'h' => (&age[..age.len() - 1], 60 * 60),
+        'm' => (&age[..age.len() - 1], 60),
+        _ => (age, 24 * 60 * 60),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .map(|number| Duration::from_secs(number * multiplier))
+}
+
+/// Formats a number of bytes for people, like `1.5 MB`
+pub fn format_size(size: u64) -> String {
+    let units = ["KB", "MB", "GB", "TB"];
+    let mut scaled = size as f64;
+    let mut unit = None;
+    for next in units.iter() {
+        if scaled < 1024.0 {
+            break;
+        }
+        scaled /= 1024.0;
+        unit = Some(next);
+    }
+    match unit {
+        Some(unit) => format!("{:.1} {}", scaled, unit),
+        None => format!("{} bytes", size),
+    }
+}
+
+/// Finds out how big a cache is
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn stats(cache: &Cache) -> io::Result<CacheStats> {
+    let entries = entries(cache)?;
+    Ok(CacheStats {
+        cache: cache.clone(),
+        entries: entries.len(),
+        size: entries.iter().map(|entry| entry.size).sum(),
+        oldest: entries.iter().map(|entry| entry.modified).min(),
+        in_use: entries.iter().filter(|entry| entry.in_use).count(),
+    })
+}
+
+/// Removes what's oldest from the caches, first everything cached longer ago than `max_age`,
+/// and then whatever else it takes to bring them under `max_size` bytes altogether. What's in
+/// use is left alone.
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn prune(
+    caches: &[Cache],
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+) -> io::Result<Pruned> {
+    let mut entries = Vec::new();
+    for cache in caches.iter() {
+        entries.append(&mut self::entries(cache)?);
+    }
+    entries.sort_by_key(|entry| entry.modified);
+
+    let now = SystemTime::now();
+    let mut size = entries.iter().map(|entry| entry.size).sum::<u64>();
+    let mut pruned = Pruned::default();
+    for entry in entries.iter().filter(|entry| !entry.in_use) {
+        let age = now
+            .duration_since(entry.modified)
+            .unwrap_or(Duration::from_secs(0));
+        let too_old = max_age.is_some_and(|max_age| age >= max_age);
+        let too_big = max_size.is_some_and(|max_size| size > max_size);
+        if !too_old && !too_big {
+            continue;
+        }
+        for file in entry.files.iter() {
+            fs::remove_file(file)?;
+        }
+        size -= entry.size;
+        pruned.entries += 1;
+        pruned.size += entry.size;
+    }
+    Ok(pruned)
+}
+
+/// Everything in a cache. Files are grouped by their name without its extension, which keeps a
+/// cached page with its validators.
+fn entries(cache: &Cache) -> io::Result<Vec<Entry>> {
+    if !cache.dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut grouped: BTreeMap<String, Entry> = BTreeMap::new();
+    for dir_entry in fs::read_dir(&cache.dir)? {
+        let path = dir_entry?.path();
+        let metadata = fs::metadata(&path)?;
+        if !metadata.is_file() {
+            continue;
+        }
+        // The store keeps its own files next to its JARs, and JARs being added end in `.part`
+        let in_use = match cache.name {
+            "store" if !store::is_stored_jar(&path) => continue,
+            "store" => store::is_linked(&cache.dir, &path)?,
+            _ => false,
+        };
+
+        let stem = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let modified = metadata.modified()?;
+        let entry = grouped.entry(stem).or_insert_with(|| Entry {
+            files: Vec::new(),
+            size: 0,
+            modified: modified,
+            in_use: false,
+        });
+        entry.files.push(path);
+        entry.size += metadata.len();
+        entry.modified = entry.modified.max(modified);
+        entry.in_use |= in_use;
+    }
+    Ok(grouped.into_values().collect())
+}
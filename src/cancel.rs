@@ -0,0 +1,173 @@
+//! This module lets long operations be cancelled from outside, like by a GUI's cancel button or
+//! a timeout. A `CancelToken` is handed to the package backend and the package parsers, which
+//! check it between requests and while downloading, and give up with `ErrorKind::Cancelled` once
+//! it has been cancelled. The backend rolls back whatever the operation had done so far, so a
+//! cancelled operation leaves the server the way it found it.
+//!
+//! Tokens are cheap to clone, and clones share their state, so one can be kept by whoever cancels
+//! and the others handed out to whatever checks.
+//!
+//! The command line cancels with Ctrl-C: `CancelToken::on_interrupt` gives a token that's
+//! cancelled when dropper is sent SIGINT or SIGTERM, so that being interrupted rolls the
+//! operation back instead of leaving a half-downloaded JAR behind.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::Read;
+#[cfg(target_os = "linux")]
+use std::mem;
+#[cfg(target_os = "linux")]
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Once};
+use std::time::{Duration, Instant};
+
+// Set once dropper has been interrupted
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static CATCH_INTERRUPTS: Once = Once::new();
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // The operation was cancelled before it finished
+    Cancelled,
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::Cancelled => "the operation was cancelled",
+            }
+        )
+    }
+}
+
+/// Says whether an operation should stop
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    /// When the token cancels itself, if it has a timeout
+    deadline: Option<Instant>,
+    /// Whether the token is cancelled by dropper being interrupted
+    on_interrupt: bool,
+}
+
+impl CancelToken {
+    /// A token that's only cancelled by calling `cancel`
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// A token that cancels itself once `timeout` has gone by, if it isn't cancelled before then
+    pub fn with_timeout(timeout: Duration) -> CancelToken {
+        CancelToken {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            deadline: Some(Instant::now() + timeout),
+            on_interrupt: false,
+        }
+    }
+
+    /// A token that's cancelled once dropper is interrupted, by Ctrl-C or SIGTERM. Interrupting
+    /// dropper a second time kills it straight away, for when stopping cleanly is taking too
+    /// long. Interrupts are only caught on Linux; elsewhere they kill dropper like they always
+    /// have, and what was left half done is rolled back the next time it runs.
+    pub fn on_interrupt() -> CancelToken {
+        CATCH_INTERRUPTS.call_once(catch_interrupts);
+        CancelToken {
+            on_interrupt: true,
+            ..CancelToken::default()
+        }
+    }
+
+    /// Cancels the operations checking this token, and every clone of it
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the token has been cancelled, or has timed out
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+            || (self.on_interrupt && interrupted())
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Gives up if the token has been cancelled
+    ///
+    /// # Errors
+    /// * [`ErrorKind::Cancelled`](enum.ErrorKind.html#variant.Cancelled) - the token has been cancelled
+    pub fn check(&self) -> Result<(), ErrorKind> {
+        match self.is_cancelled() {
+            true => Err(ErrorKind::Cancelled),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Whether dropper has been interrupted since it started catching interrupts
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(target_os = "linux")]
+fn catch_interrupts() {
+    extern "C" fn handle(_: libc::c_int) {
+        const MESSAGE: &'static [u8] =
+            b"\nInterrupted; stopping once it's safe to. Interrupt again to stop right away.\n";
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        // Only things that are safe in a signal handler are done here
+        unsafe {
+            libc::write(2, MESSAGE.as_ptr() as *const libc::c_void, MESSAGE.len());
+        }
+    }
+
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // SA_RESETHAND leaves the next interrupt to kill dropper. SA_RESTART is left out, so a
+        // read waiting on the terminal fails with `Interrupted` instead of carrying on, and a
+        // prompt can give up rather than wait on Enter.
+        action.sa_flags = libc::SA_RESETHAND;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGINT, &action, ptr::null_mut());
+        libc::sigaction(libc::SIGTERM, &action, ptr::null_mut());
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn catch_interrupts() {}
+
+/// Whether an error is because an operation was cancelled
+pub fn is_cancelled(e: &(Error + 'static)) -> bool {
+    matches!(e.downcast_ref::<ErrorKind>(), Some(ErrorKind::Cancelled))
+}
+
+/// A reader that stops reading once its token is cancelled, so that a big download can be cut
+/// short rather than having to finish first
+pub struct Cancellable<R> {
+    inner: R,
+    token: CancelToken,
+}
+
+impl<R: Read> Cancellable<R> {
+    pub fn new(inner: R, token: CancelToken) -> Cancellable<R> {
+        Cancellable {
+            inner: inner,
+            token: token,
+        }
+    }
+}
+
+impl<R: Read> Read for Cancellable<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Not `Interrupted`, which copying would take as a reason to try again
+        self.token.check().map_err(io::Error::other)?;
+        self.inner.read(buf)
+    }
+}
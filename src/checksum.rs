@@ -0,0 +1,180 @@
+//! This module reads and writes `SHA256SUMS` files, listing the SHA-256 of every JAR dropper
+//! manages, for admins who check what's on a server some other way than through dropper. The
+//! file is written like `sha256sum` writes it, one JAR per line, sorted by name:
+//!
+//! ```text
+//! 943c846f8d540ba7d00e8ef44405c941b1a2b5ed918bf3bda4c985c1f0c78f6c  foo@1.1.jar
+//! 5d1b4cbd2b3ab8e7a3f2fd4e3c7d7a0ad3f1e4b3a8d2c1c7e6de9f2b1a0c3e4f  worldedit@6.1.9.jar
+//! ```
+//!
+//! The names are relative to the plugin directory, so `sha256sum -c` can check the file from
+//! there too. Nothing in it changes unless a JAR does, so it can be signed, like with
+//! `gpg --clearsign`, and compared with a copy kept elsewhere. The armor of a clearsigned file
+//! is skipped when it's read, though the signature is left for gpg to check.
+
+use std::error::Error;
+use std::fmt;
+
+/// The name the file is written under by default
+pub const SUMS_FILE: &'static str = "SHA256SUMS";
+const SIGNED_HEADER: &'static str = "-----BEGIN PGP SIGNED MESSAGE-----";
+const SIGNATURE_HEADER: &'static str = "-----BEGIN PGP SIGNATURE-----";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A line of a SHA256SUMS file isn't a digest and a name. Takes the file and the line number
+    // as params.
+    SumsInvalid(String, usize),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::SumsInvalid(s, line) =>
+                    format!("line {} of {} isn't a SHA-256 and a file name", line, s),
+            }
+        )
+    }
+}
+
+/// Writes out digests, given as (file, SHA-256), in the order they're given
+pub fn format(sums: &[(String, String)]) -> String {
+    sums.iter()
+        .map(|(file, sha256)| format!("{}  {}\n", sha256, file))
+        .collect()
+}
+
+/// Reads the digests out of a SHA256SUMS file, as (file, SHA-256), in the order they're listed.
+/// Names marked as binary, with a `*` in front of them, are read the same as the rest.
+///
+/// # Arguments
+///
+/// * `file` - What to call the file in errors, usually its path
+/// * `contents` - What's in the file
+///
+/// # Errors
+/// * [`ErrorKind::SumsInvalid`](enum.ErrorKind.html#variant.SumsInvalid) - a line isn't a digest and a name
+pub fn parse(file: &str, contents: &str) -> Result<Vec<(String, String)>, ErrorKind> {
+    let mut sums = Vec::new();
+    let mut lines = contents.lines().enumerate().peekable();
+    // A clearsigned file has a header, and then a blank line, before what was signed
+    if lines
+        .peek()
+        .is_some_and(|(_, line)| line.trim() == SIGNED_HEADER)
+    {
+        for (_, line) in lines.by_ref() {
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+    }
+
+    for (number, line) in lines {
+        if line.trim() == SIGNATURE_HEADER {
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = || ErrorKind::SumsInvalid(file.to_string(), number + 1);
+        let mut parts = line.splitn(2, ' ');
+        let sha256 = parts.next().unwrap_or("");
+        let name = parts.next().ok_or_else(invalid)?;
+        let name = name
+            .strip_prefix(' ')
+            .or_else(|| name.strip_prefix('*'))
+            .ok_or_else(invalid)?;
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) || name.is_empty() {
+            return Err(invalid());
+        }
+        sums.push((name.to_string(), sha256.to_lowercase()));
+    }
+    Ok(sums)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VAULT: &str = "0d1e4c8a6c2a3f3be6e977e3b3d2fa1b9a8c7e5d4f3a2b1c0d9e8f7a6b5c4d3e";
+    const ESSENTIALS: &str = "9f8e7d6c5b4a39281706f5e4d3c2b1a0ffeeddccbbaa99887766554433221100";
+
+    #[test]
+    fn written_sums_are_read_back_the_same() {
+        let sums = vec![
+            ("Vault.jar".to_string(), VAULT.to_string()),
+            (
+                "addons/Essentials Chat.jar".to_string(),
+                ESSENTIALS.to_string(),
+            ),
+        ];
+        let written = format(&sums);
+        assert_eq!(
+            written,
+            format!(
+                "{}  Vault.jar\n{}  addons/Essentials Chat.jar\n",
+                VAULT, ESSENTIALS
+            )
+        );
+        assert_eq!(parse("SHA256SUMS", &written).unwrap(), sums);
+    }
+
+    #[test]
+    fn binary_names_are_read_like_the_rest() {
+        let contents = format!(
+            "{} *Vault.jar\n\n{}  Essentials.jar\n",
+            VAULT.to_uppercase(),
+            ESSENTIALS
+        );
+        assert_eq!(
+            parse("SHA256SUMS", &contents).unwrap(),
+            [
+                ("Vault.jar".to_string(), VAULT.to_string()),
+                ("Essentials.jar".to_string(), ESSENTIALS.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_what_was_signed_is_read_from_clearsigned_files() {
+        let contents = format!(
+            "{}\nHash: SHA256\n\n{}  Vault.jar\n{}\n\niQEzBAEBCAAdFiEE\n\
+             -----END PGP SIGNATURE-----\n",
+            SIGNED_HEADER, VAULT, SIGNATURE_HEADER
+        );
+        assert_eq!(
+            parse("SHA256SUMS.asc", &contents).unwrap(),
+            [("Vault.jar".to_string(), VAULT.to_string())]
+        );
+    }
+
+    #[test]
+    fn malformed_lines_are_refused() {
+        let short = &VAULT[..63];
+        let cases = [
+            format!("{}  Vault.jar\nnot a digest\n", VAULT),
+            format!("{}  Vault.jar\n{}  Essentials.jar\n", VAULT, short),
+            format!("{}  Vault.jar\n{}\n", VAULT, ESSENTIALS),
+            format!("{}  Vault.jar\n{}  \n", VAULT, ESSENTIALS),
+            format!("{}  Vault.jar\n{} Essentials.jar\n", VAULT, ESSENTIALS),
+            format!(
+                "{}  Vault.jar\n{}  Essentials.jar\n",
+                VAULT,
+                ESSENTIALS.replace('f', "g")
+            ),
+        ];
+        for contents in cases.iter() {
+            match parse("SHA256SUMS", contents) {
+                Err(ErrorKind::SumsInvalid(file, line)) => {
+                    assert_eq!((file.as_str(), line), ("SHA256SUMS", 2), "{}", contents)
+                }
+                Ok(sums) => panic!("expected {:?} to be refused, got {:?}", contents, sums),
+            }
+        }
+    }
+}
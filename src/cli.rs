@@ -1,2 +1,3537 @@
-/// TODO: Implement the command line interface.
-/// The CLI will take the hook functions from the backend module, and give the user a way to interface with them.
+//! This module is the command line interface. It takes the hook functions from the backend
+//! module, and gives the user a way to interface with them.
+//!
+//! Each subcommand is handled by a function of the same name, which returns any error it runs
+//! into back up to `run` to be reported.
+//!
+//! The commands that scripts are likely to read, like `list`, `outdated` and `status`, take
+//! `--porcelain`, which prints one record per line with its fields separated by tabs, and nothing
+//! else. Unknown fields are `-`. Records only ever gain fields at the end, so scripts written
+//! against one minor release keep working with the next.
+
+use crate::auth;
+use crate::auth::Storage;
+use crate::backend;
+use crate::backend::{
+    Check, Compatibility, ConfigReport, InstalledListing, PackageAge, PackageBackend, ProjectPaths,
+    StatusReport, UnmanagedJar, VersionListing,
+};
+use crate::build;
+use crate::cache;
+use crate::cancel;
+use crate::cancel::CancelToken;
+use crate::complete;
+use crate::format;
+use crate::format::FormatStatus;
+use crate::history::Event;
+use crate::http;
+use crate::lock::Lockfile;
+use crate::maven;
+use crate::modrinth::ModrinthBrowser;
+use crate::parser;
+use crate::parser::{
+    BukkitHTMLPluginParser, Channel, ListingSort, PluginBrowsable, PluginFetchable, PluginListing,
+    PluginSearchable, SearchFilters,
+};
+use crate::plan::Plan;
+use crate::repo;
+use crate::smoke::Outcome;
+use crate::state::{InstallReason, State};
+use crate::watch;
+use crate::watch::Watcher;
+use clap::{App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
+use reqwest::Client;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Parses the command line arguments and runs the requested subcommand
+pub fn run() {
+    let matches = app().subcommand(complete_subcommand()).get_matches();
+    if let (_, Some(m)) = matches.subcommand() {
+        if let Err(e) = trace_http(m) {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+
+    let result = match matches.subcommand() {
+        ("init", Some(m)) => init(&project_paths(m)),
+        ("add", Some(m)) => add(m),
+        ("install", Some(m)) => install(m),
+        ("adopt", Some(m)) => adopt(m),
+        ("update", Some(m)) => update(m),
+        ("remove", Some(m)) => remove(m),
+        ("autoremove", Some(m)) => autoremove(m),
+        ("list", Some(m)) => list(m),
+        ("why", Some(m)) => why(m),
+        ("docs", Some(m)) => docs(m),
+        ("outdated", Some(m)) => outdated(m),
+        ("versions", Some(m)) => versions(m),
+        ("plan", Some(m)) => plan(m),
+        ("apply", Some(m)) => apply(m),
+        ("sync", Some(m)) => sync(m),
+        ("verify", Some(m)) => verify(m),
+        ("status", Some(m)) => status(m),
+        ("unmanaged", Some(m)) => unmanaged(m),
+        ("watch", Some(m)) => watch(m),
+        ("info", Some(m)) => info(m),
+        ("try", Some(m)) => try_package(m),
+        ("search", Some(m)) => search(m),
+        ("browse", Some(m)) => browse(m),
+        ("top", Some(m)) => top(m),
+        ("compat", Some(m)) => compat(m),
+        ("migrate", Some(m)) => migrate(m),
+        ("doctor", Some(m)) => doctor(m),
+        ("test", Some(m)) => test(m),
+        ("analyze", Some(m)) => analyze(m),
+        ("crash", Some(m)) => crash(m),
+        ("cache", Some(m)) => cache(m),
+        ("auth", Some(m)) => auth(m),
+        ("repo", Some(m)) => repo(m),
+        ("bundle", Some(m)) => bundle(m),
+        ("checksum", Some(m)) => checksum(m),
+        ("completions", Some(m)) => completions(m),
+        ("__complete", Some(m)) => complete(m),
+        _ => unreachable!(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        // Like a shell reports a command killed by SIGINT
+        process::exit(match cancel::interrupted() {
+            true => 130,
+            false => 1,
+        });
+    }
+}
+
+/// Starts tracing HTTP requests, if `--trace-http` or `--trace-http-log` asked to
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - the log file couldn't be opened
+fn trace_http(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    if let Some(path) = matches.value_of("trace-http-log") {
+        let log = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        http::trace_to(Box::new(log));
+    } else if matches.is_present("trace-http") {
+        http::trace_to(Box::new(io::stderr()));
+    }
+    Ok(())
+}
+
+/// The command line interface, which `completions` also writes completion scripts for
+fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new("dropper")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("A Minecraft server package manager")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("project-dir")
+                .long("project-dir")
+                .value_name("DIR")
+                .help("The server root to manage, instead of the current directory")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("target-dir")
+                .long("target-dir")
+                .value_name("DIR")
+                .help("Where to install plugins, instead of the server's plugins folder. A relative DIR is in the server root.")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("max-download-rate")
+                .long("max-download-rate")
+                .value_name("RATE")
+                .help("The fastest to download at, e.g. 500K or 2M a second, instead of the config's max_download_rate")
+                .takes_value(true)
+                .validator(|rate| match http::parse_rate(&rate) {
+                    Some(_) => Ok(()),
+                    None => Err(format!("'{}' isn't a download rate", rate)),
+                })
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("env")
+                .long("env")
+                .value_name("ENV")
+                .help("Lays the pkg.yml's overlay for this environment, e.g. dev, over the rest of it")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("trace-http")
+                .long("trace-http")
+                .help("Logs every HTTP request dropper makes to stderr, with its status, timing, and whether the cache was used")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("trace-http-log")
+                .long("trace-http-log")
+                .value_name("FILE")
+                .help("Logs every HTTP request like --trace-http, but appends them to FILE instead")
+                .takes_value(true)
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("init")
+                .about("Sets up dropper in the current server directory"),
+        )
+        .subcommand(
+            SubCommand::with_name("add")
+                .about("Installs packages and adds them to pkg.yml")
+                .arg(
+                    Arg::with_name("PACKAGE")
+                        .help("The packages to add, e.g. WorldEdit@6.1.9 or bukkit:vault")
+                        .required_unless("from-file")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("from-file")
+                        .long("from-file")
+                        .value_name("FILE")
+                        .help("Also adds the packages listed in FILE, one per line. Use - to read them from stdin")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("with-optional")
+                        .long("with-optional")
+                        .help("Installs every optional integration without asking"),
+                )
+                .arg(force_arg())
+                .arg(allow_blocked_arg())
+                .arg(allow_downgrade_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("install")
+                .about("Installs packages without adding them to pkg.yml")
+                .arg(
+                    Arg::with_name("PACKAGE")
+                        .help("The packages to install, e.g. WorldEdit@6.1.9 or bukkit:vault. Installs everything in pkg.yml if left out")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("from-lock")
+                        .long("from-lock")
+                        .value_name("LOCKFILE")
+                        .help("Installs exactly what LOCKFILE pins. Works on servers that aren't set up with dropper")
+                        .takes_value(true)
+                        .conflicts_with("PACKAGE"),
+                )
+                .arg(force_arg())
+                .arg(allow_blocked_arg())
+                .arg(allow_downgrade_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("adopt")
+                .about("Takes a JAR that was put into the plugins folder by hand under dropper's management, adding it to pkg.yml and the lockfile without downloading it again")
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("The JAR, relative to the plugins folder")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("PACKAGE")
+                        .help("The package the JAR is a version of, e.g. worldedit or bukkit:vault. Found from the plugin's name if left out"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("update")
+                .about("Updates packages to the newest versions pkg.yml allows")
+                .arg(
+                    Arg::with_name("PACKAGE")
+                        .help("The names of the packages to update. Updates everything installed but downgraded packages if left out")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("keep-going")
+                        .long("keep-going")
+                        .help("Updates the rest of the packages when one fails, rather than rolling back the ones already updated"),
+                )
+                .arg(
+                    Arg::with_name("interactive")
+                        .long("interactive")
+                        .short("i")
+                        .help("Shows each update that's waiting, with its channel and changelog, and asks whether to make it before anything is downloaded"),
+                )
+                .arg(force_arg())
+                .arg(allow_blocked_arg())
+                .arg(no_restart_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("remove")
+                .about("Uninstalls packages and removes them from pkg.yml")
+                .arg(
+                    Arg::with_name("PACKAGE")
+                        .help("The names of the packages to remove")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Removes packages even if other plugins depend on them"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("autoremove")
+                .about("Removes dependencies that nothing needs anymore"),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("Lists the installed packages, and whether each was asked for or pulled in as a dependency")
+                .arg(
+                    Arg::with_name("explicit")
+                        .long("explicit")
+                        .conflicts_with("dependencies")
+                        .help("Only lists the packages that were asked for"),
+                )
+                .arg(
+                    Arg::with_name("dependencies")
+                        .long("dependencies")
+                        .help("Only lists the packages that were pulled in as dependencies"),
+                )
+                .arg(porcelain_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("why")
+                .about("Explains why a package is installed: what requires it, and what installed it")
+                .arg(
+                    Arg::with_name("PACKAGE")
+                        .help("The package to explain")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("docs")
+                .about("Writes up the installed plugins as Markdown, with the docs saved for them, for handing the server over")
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("FILE")
+                        .help("Writes the write-up to a file instead of printing it")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("outdated")
+                .about("Shows which packages have newer versions, and how old every package is")
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .help("Shows packages that are up to date too"),
+                )
+                .arg(porcelain_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("versions")
+                .about("Lists every version of a package, with its channel, server versions and release date")
+                .arg(
+                    Arg::with_name("PACKAGE")
+                        .help("The package, e.g. worldedit or bukkit:vault. Only the versions worldedit@6.* covers are listed if a version is given")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("compatible")
+                        .long("compatible")
+                        .help("Only lists the versions for the server version"),
+                )
+                .arg(
+                    Arg::with_name("allowed")
+                        .long("allowed")
+                        .help("Only lists the versions the pkg.yml allows"),
+                )
+                .arg(porcelain_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("plan")
+                .about("Works out what installing pkg.yml would change, and saves it as a plan")
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .value_name("FILE")
+                        .help("Where to save the plan, instead of dropper.plan in the server root")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("apply")
+                .about("Makes exactly the changes in a plan saved by `dropper plan`")
+                .arg(
+                    Arg::with_name("PLAN")
+                        .help("The plan to apply, instead of dropper.plan in the server root"),
+                )
+                .arg(force_arg())
+                .arg(allow_blocked_arg())
+                .arg(no_restart_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("sync")
+                .about("Makes the plugins folder match the lockfile exactly, removing unmanaged JARs"),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Checks every locked JAR in the plugins folder against the lockfile's digests, and every JAR managed by hand against the pkg.yml")
+                .arg(
+                    Arg::with_name("deep")
+                        .long("deep")
+                        .help("Also checks that the version in each JAR's plugin.yml is the locked one"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("status")
+                .about("Reports where the plugins folder has drifted from pkg.yml and the lockfile")
+                .arg(
+                    Arg::with_name("configs")
+                        .long("configs")
+                        .help("Reports the rendered configs that were changed on the server since they were rendered instead"),
+                )
+                .arg(porcelain_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("unmanaged")
+                .about("Lists the JARs in the plugins folder dropper doesn't manage, with the plugin in each and the project it's most likely published as on each source")
+                .arg(porcelain_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("Keeps watching the plugins folder, reporting JARs that are added or removed outside dropper")
+                .arg(
+                    Arg::with_name("interval")
+                        .long("interval")
+                        .value_name("SECONDS")
+                        .help("How often to look at the plugins folder when it can't be watched for changes")
+                        .takes_value(true)
+                        .default_value("60")
+                        .validator(|seconds| match seconds.parse::<u64>() {
+                            Ok(seconds) if seconds > 0 => Ok(()),
+                            _ => Err(format!("'{}' isn't a number of seconds", seconds)),
+                        }),
+                )
+                .arg(
+                    Arg::with_name("quarantine")
+                        .long("quarantine")
+                        .help("Moves JARs that turn up into .dropper/quarantine, so the server doesn't load them"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Shows what an installed package's plugin.yml says about it, or what its source says about a package that isn't installed")
+                .arg(
+                    Arg::with_name("PACKAGE")
+                        .help("The name of the package")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("detailed")
+                        .long("detailed")
+                        .help("Also lists the commands and permissions the plugin registers"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("try")
+                .about("Installs a package into a throwaway sandbox instead of the server, and reports what it brought with it")
+                .arg(
+                    Arg::with_name("PACKAGE")
+                        .help("The package to try, e.g. WorldEdit@6.1.9 or bukkit:vault")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("keep")
+                        .long("keep")
+                        .help("Keeps the sandbox afterwards, to look through it"),
+                )
+                .arg(force_arg())
+                .arg(allow_blocked_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("search")
+                .about("Searches the plugin website for packages")
+                .arg(
+                    Arg::with_name("QUERY")
+                        .help("What to search for, e.g. holographic displays")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("category")
+                        .long("category")
+                        .value_name("CATEGORY")
+                        .help("Only shows plugins in this category, e.g. admin-tools")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("mc")
+                        .long("mc")
+                        .value_name("VERSION")
+                        .help("Only shows plugins with builds for this server version, e.g. 1.12")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("all-sources")
+                        .long("all-sources")
+                        .help("Also searches Maven Central and the pkg.yml's self-hosted repositories, merging plugins found on several. Filtered searches only search the plugin website"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("browse")
+                .about("Lists the most popular plugins in a category")
+                .arg(
+                    Arg::with_name("CATEGORY")
+                        .help("The category to browse, e.g. admin-tools")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("mc")
+                        .long("mc")
+                        .value_name("VERSION")
+                        .help("Only shows plugins with builds for this server version, e.g. 1.12")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("top")
+                .about("Lists the most downloaded plugins with builds for this server")
+                .arg(
+                    Arg::with_name("trending")
+                        .long("trending")
+                        .help("Lists the most popular plugins lately, rather than of all time"),
+                )
+                .arg(
+                    Arg::with_name("source")
+                        .long("source")
+                        .value_name("SOURCE")
+                        .help("The plugin website to list plugins from")
+                        .takes_value(true)
+                        .possible_values(&["bukkit", "modrinth"])
+                        .default_value("bukkit"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compat")
+                .about("Shows which server versions every package in pkg.yml has builds for")
+                .arg(
+                    Arg::with_name("versions")
+                        .long("versions")
+                        .value_name("VERSIONS")
+                        .help("The server versions to check, e.g. 1.11,1.12")
+                        .takes_value(true)
+                        .use_delimiter(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Moves the server to another version, updating every package to a build for it")
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("VERSION")
+                        .help("The server version to move to, e.g. 1.13")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .help("Moves the server without asking first"),
+                )
+                .arg(force_arg())
+                .arg(allow_blocked_arg())
+                .arg(no_restart_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about("Checks that dropper's own files are in formats this version of dropper knows")
+                .arg(
+                    Arg::with_name("fix")
+                        .long("fix")
+                        .help("Upgrades files in older formats, keeping them as they were next to them"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("test")
+                .about("Starts a throwaway copy of the server with the installed plugins, and reports the ones that fail to load")
+                .arg(
+                    Arg::with_name("keep")
+                        .long("keep")
+                        .help("Keeps the copy afterwards, to look through it"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("analyze")
+                .about("Reads a server log for plugins that threw exceptions or failed to load, and suggests what to do about them")
+                .arg(
+                    Arg::with_name("LOG")
+                        .help("The log to read, like logs/latest.log, or - to read stdin")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("crash")
+                .about("Reads a crash report for the installed plugins in its stacks, and what dropper changed shortly before it")
+                .arg(
+                    Arg::with_name("REPORT")
+                        .help("The crash report, or a log with a watchdog's thread dump in it, or - to read stdin. Defaults to the newest crash report."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("auth")
+                .about("Manages the tokens sources need, which are kept in the keychain where there is one")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("login")
+                        .about("Sets the token for a source, reading it from stdin")
+                        .arg(
+                            Arg::with_name("SOURCE")
+                                .help("The source, like github or curseforge, or the host the token is for")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("logout")
+                        .about("Forgets the token for a source")
+                        .arg(
+                            Arg::with_name("SOURCE")
+                                .help("The source, like github or curseforge, or the host the token is for")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("cache")
+                .about("Manages what dropper keeps cached")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("prune-builds")
+                        .about("Removes old builds of packages built from source, which every server on this machine shares")
+                        .arg(
+                            Arg::with_name("older-than")
+                                .long("older-than")
+                                .value_name("DAYS")
+                                .help("Only removes builds made more than this many days ago")
+                                .takes_value(true)
+                                .default_value("30")
+                                .validator(|days| match days.parse::<u64>() {
+                                    Ok(_) => Ok(()),
+                                    Err(_) => Err(format!("'{}' isn't a number of days", days)),
+                                }),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("stats")
+                        .about("Shows how big each cache is"),
+                )
+                .subcommand(
+                    SubCommand::with_name("prune")
+                        .about("Removes the oldest of what's cached: fetched pages, builds, and JARs in the content store that nothing links to")
+                        .arg(
+                            Arg::with_name("max-size")
+                                .long("max-size")
+                                .value_name("SIZE")
+                                .help("Removes the oldest of what's cached until the caches take up no more than this, like 2G")
+                                .takes_value(true)
+                                .required_unless("older-than")
+                                .validator(|size| match cache::parse_size(&size) {
+                                    Some(_) => Ok(()),
+                                    None => {
+                                        Err(format!("'{}' isn't a size, like 500M or 2G", size))
+                                    }
+                                }),
+                        )
+                        .arg(
+                            Arg::with_name("older-than")
+                                .long("older-than")
+                                .value_name("AGE")
+                                .help("Removes what was cached longer ago than this, like 90d or 12h")
+                                .takes_value(true)
+                                .validator(|age| match cache::parse_age(&age) {
+                                    Some(_) => Ok(()),
+                                    None => {
+                                        Err(format!("'{}' isn't an age, like 90d or 12h", age))
+                                    }
+                                }),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("repo")
+                .about("Publishes plugins to a self-hosted repository, a folder to serve from any static host")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("add-file")
+                        .about("Copies plugin JARs into the repository and lists them in its index")
+                        .arg(
+                            Arg::with_name("JAR")
+                                .help("The plugin JARs to publish")
+                                .required(true)
+                                .multiple(true),
+                        )
+                        .arg(repo_dir_arg())
+                        .arg(
+                            Arg::with_name("package")
+                                .long("package")
+                                .value_name("NAME")
+                                .help("The name to list the JARs under, instead of their plugin's name")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("channel")
+                                .long("channel")
+                                .help("The channel to publish to, instead of the one the version says")
+                                .takes_value(true)
+                                .possible_values(&["release", "beta", "dev"]),
+                        )
+                        .arg(
+                            Arg::with_name("game-version")
+                                .long("game-version")
+                                .value_name("VERSION")
+                                .help("A server version the JARs are for, if they aren't for every one")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("index")
+                        .about("Brings the repository's index up to date with the JARs in it")
+                        .arg(repo_dir_arg()),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bundle")
+                .about("Packs the locked plugins into a single file, to set up a server with no internet access from")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("create")
+                        .about("Bundles the lockfile, the pkg.yml, and every file the lockfile points at, checked against its digests")
+                        .arg(
+                            Arg::with_name("BUNDLE")
+                                .help("Where to write the bundle, which is a ZIP archive, so its name has to end in .zip")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("install")
+                        .about("Sets the server up from a bundle without going online, replacing its lockfile and pkg.yml and syncing the plugins folder with them")
+                        .arg(
+                            Arg::with_name("BUNDLE")
+                                .help("The bundle to install")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("checksum")
+                .about("Lists the SHA-256 of every JAR dropper manages in a SHA256SUMS file, to check the server against some other way")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("write")
+                        .about("Writes the SHA256SUMS file, replacing what was there")
+                        .arg(checksum_file_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("check")
+                        .about("Checks the JARs against the SHA256SUMS file, and that every JAR dropper manages is in it")
+                        .arg(checksum_file_arg()),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("completions")
+                .about("Prints a script that sets up tab completion for a shell")
+                .arg(
+                    Arg::with_name("SHELL")
+                        .help("The shell to complete in")
+                        .required(true)
+                        .possible_values(&Shell::variants()),
+                ),
+        )
+}
+
+/// The folder a self-hosted repository is kept in
+fn repo_dir_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("dir")
+        .long("dir")
+        .value_name("DIR")
+        .help("The repository folder")
+        .takes_value(true)
+        .default_value(".")
+}
+
+/// The SHA256SUMS file to write or check, which is in the plugin directory unless it's given
+fn checksum_file_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("file")
+        .long("file")
+        .value_name("FILE")
+        .help("The SHA256SUMS file, instead of the one in the plugins folder")
+        .takes_value(true)
+}
+
+/// What the completion scripts run to suggest package names and versions. It's left out of
+/// `app`, since clap can't write bash completion for subcommands whose names have `__` in them.
+fn complete_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("__complete")
+        .setting(AppSettings::Hidden)
+        .arg(
+            Arg::with_name("KIND")
+                .required(true)
+                .possible_values(&["pkg"]),
+        )
+        .arg(Arg::with_name("PREFIX"))
+}
+
+/// The `--force` flag of commands that install packages, which lets them through strict mode's
+/// compatibility checks. Without a value, every check is overridden.
+fn force_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("force-checks")
+        .long("force")
+        .value_name("CHECKS")
+        .help("Installs packages that fail the given strict mode checks (api-version, game-version, fallback), or any of them if none are given")
+        .takes_value(true)
+        .min_values(0)
+        .require_equals(true)
+        .use_delimiter(true)
+        .possible_values(&["api-version", "game-version", "fallback"])
+}
+
+/// The `--allow-blocked` flag of commands that install packages, which lets them install
+/// versions on the blocklist
+fn allow_blocked_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("allow-blocked")
+        .long("allow-blocked")
+        .help("Installs versions on the blocklist rather than refusing them")
+}
+
+/// The `--allow-downgrade` flag of commands that install packages, which lets them replace a
+/// package with an older version
+fn allow_downgrade_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("allow-downgrade")
+        .long("allow-downgrade")
+        .help("Installs versions older than the installed ones, which `dropper update` then leaves alone")
+}
+
+/// The `--no-restart` flag of commands that update packages, which keeps them from restarting
+/// the server afterwards
+fn no_restart_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("no-restart")
+        .long("no-restart")
+        .help("Doesn't restart the server afterwards, even if the config says to")
+}
+
+/// The `--porcelain` flag of commands that scripts read the output of
+fn porcelain_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("porcelain")
+        .long("porcelain")
+        .help("Prints one tab-separated record per line, in a format that won't change between minor releases")
+}
+
+/// Restarts the server the way the config says to, if anything changed and `--no-restart`
+/// wasn't given
+fn restart_after(
+    backend: &PackageBackend,
+    matches: &ArgMatches,
+    changed: bool,
+) -> Result<(), Box<Error>> {
+    let restart = match &backend.restart {
+        Some(restart) if changed && !matches.is_present("no-restart") => restart,
+        _ => return Ok(()),
+    };
+
+    match restart.delay {
+        0 => println!("Restarting the server"),
+        delay => println!("Restarting the server in {} seconds", delay),
+    }
+    restart.run(&backend.client, &backend.credentials)
+}
+
+/// Works out which checks `--force` overrides
+fn forced_checks(matches: &ArgMatches) -> Vec<Check> {
+    match matches.values_of("force-checks") {
+        Some(names) => {
+            let checks = names.filter_map(Check::from_name).collect::<Vec<Check>>();
+            match checks.is_empty() {
+                true => Check::ALL.to_vec(),
+                false => checks,
+            }
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Works out which server is being managed from the `--project-dir` and `--target-dir` flags
+fn project_paths(matches: &ArgMatches) -> ProjectPaths {
+    let project_dir = PathBuf::from(matches.value_of("project-dir").unwrap_or("."));
+    // Like everything else, a relative target dir is in the server root
+    let plugin_dir = matches
+        .value_of("target-dir")
+        .map(|dir| project_dir.join(dir));
+    ProjectPaths::new(project_dir, plugin_dir)
+}
+
+/// Builds a backend from the config of the server being managed, and hands it to `f`
+fn with_backend<F>(matches: &ArgMatches, f: F) -> Result<(), Box<Error>>
+where
+    F: FnOnce(&PackageBackend) -> Result<(), Box<Error>>,
+{
+    // Porcelain output is only records, so anything else goes to stderr
+    let porcelain = matches.is_present("porcelain");
+    let notice = |message: String| match porcelain {
+        true => eprintln!("{}", message),
+        false => println!("{}", message),
+    };
+
+    let paths = project_paths(matches);
+    if let Some(description) = PackageBackend::recover(&paths)? {
+        notice(format!(
+            "The last operation (dropper {}) didn't finish, so it has been rolled back",
+            description
+        ));
+    }
+
+    let server_version = match PackageBackend::configured_server_version(&paths) {
+        Ok(v) => v,
+        Err(e) => {
+            if let Some(backend::ErrorKind::ConfigMissing) = e.downcast_ref::<backend::ErrorKind>() {
+                eprintln!("dropper doesn't seem to be set up here; try running `dropper init`");
+            }
+            return Err(e);
+        }
+    };
+
+    let client = http::build_client()?;
+    let parser = bukkit_parser(&paths, server_version, client.clone())?;
+    let mut backend = PackageBackend::new(&parser, paths, client)?;
+    backend.cancel = CancelToken::on_interrupt();
+    backend.forced = forced_checks(matches);
+    backend.allow_blocked = matches.is_present("allow-blocked");
+    backend.allow_downgrade = matches.is_present("allow-downgrade");
+    backend.env = matches.value_of("env").map(|env| env.to_string());
+    if let Some(rate) = matches.value_of("max-download-rate") {
+        backend.max_download_rate = http::parse_rate(rate);
+    }
+
+    for path in backend.apply_staged()? {
+        notice(format!("Swapped in {}, which was waiting on the server", path.display()));
+    }
+
+    let result = f(&backend);
+
+    for warning in backend.take_warnings() {
+        eprintln!("warning: {}", warning);
+    }
+
+    for path in backend.staged()? {
+        notice(format!(
+            "{} is downloaded, but the JAR it replaces is in use. Stop the server and run dropper again to swap it in.",
+            path.file_name().unwrap().to_string_lossy()
+        ));
+    }
+
+    result
+}
+
+fn bukkit_parser(
+    paths: &ProjectPaths,
+    server_version: String,
+    client: Client,
+) -> Result<BukkitHTMLPluginParser, Box<Error>> {
+    Ok(BukkitHTMLPluginParser::builder()
+        .game_version(server_version)
+        .client(client)
+        .credentials(PackageBackend::configured_credentials(paths)?)
+        .cache_dir(paths.http_cache_dir())
+        .cancel(CancelToken::on_interrupt())
+        .build()
+        // Only the game version is required, and it's always given
+        .unwrap())
+}
+
+/// Asks the user a yes or no question. Anything other than a yes, including there being nobody
+/// on the other end of stdin, is taken as a no.
+fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+
+    // Being interrupted, even while waiting on the answer, is a no
+    match read_line() {
+        Ok(_) if cancel::interrupted() => false,
+        Ok(answer) => matches!(answer.trim().to_lowercase().as_ref(), "y" | "yes"),
+        Err(_) => false,
+    }
+}
+
+/// Reads a line from stdin. Unlike `Stdin::read_line`, this gives up when dropper is interrupted
+/// while waiting on it instead of trying again.
+///
+/// # Errors
+/// * `std::io::ErrorKind::Interrupted` - dropper was interrupted while waiting on the line
+/// * `std::io::ErrorKind::*` - stdin couldn't be read, or the line isn't UTF-8
+fn read_line() -> io::Result<String> {
+    if cancel::interrupted() {
+        return Err(io::ErrorKind::Interrupted.into());
+    }
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let mut line = Vec::new();
+    loop {
+        let (read, done) = {
+            let available = stdin.fill_buf()?;
+            match available.iter().position(|&byte| byte == b'\n') {
+                Some(end) => {
+                    line.extend_from_slice(&available[..=end]);
+                    (end + 1, true)
+                }
+                None => {
+                    line.extend_from_slice(available);
+                    (available.len(), available.is_empty())
+                }
+            }
+        };
+        stdin.consume(read);
+        if done {
+            break;
+        }
+    }
+    String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn init(paths: &ProjectPaths) -> Result<(), Box<Error>> {
+    if PackageBackend::validate(paths).is_ok()
+        && !confirm("dropper is already set up here. Wipe its config and start over?")
+    {
+        return Ok(());
+    }
+
+    PackageBackend::init(paths)?;
+    println!(
+        "dropper is ready to go! Take a look at {}",
+        paths.config_file().display()
+    );
+    Ok(())
+}
+
+fn add(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let with_optional = matches.is_present("with-optional");
+
+    let mut pkgs = match matches.values_of("PACKAGE") {
+        Some(pkgs) => pkgs.map(|pkg| pkg.to_string()).collect(),
+        None => Vec::new(),
+    };
+    let from_file = matches.value_of("from-file");
+    if let Some(file) = from_file {
+        pkgs.extend(read_package_list(file)?);
+    }
+
+    with_backend(matches, |backend| {
+        let mut added = Vec::new();
+        let mut not_found = Vec::new();
+        let mut failed = Vec::new();
+
+        for pkg in pkgs.iter() {
+            let (name, version) = match backend.pkg_add(pkg) {
+                Ok(Some(tup)) => tup,
+                Ok(None) => {
+                    report_not_found(backend, pkg);
+                    not_found.push(pkg.clone());
+                    continue;
+                }
+                // One bad line in a list shouldn't stop the rest of it
+                Err(e) if from_file.is_some() => {
+                    println!("Error while trying to add {}: {}", pkg, e);
+                    failed.push(format!("{} ({})", pkg, e));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            println!("Package {} installed @ version {}!", name, version);
+            added.push(format!("{} {}", name, version));
+
+            // Libraries are required to run, so there's no point in asking
+            for library in backend.pkg_missing_libraries(&name, &version)? {
+                println!("{} needs {}, installing it too", name, library.name);
+                report_install(backend, library.name, backend.pkg_add_library(library));
+            }
+
+            for integration in backend.pkg_missing_integrations(&name, &version)? {
+                let question = format!("{} integrates with {}. Install it?", name, integration);
+                if with_optional || confirm(&question) {
+                    report_install(
+                        backend,
+                        &integration,
+                        backend.pkg_add_integration(&integration),
+                    );
+                }
+            }
+        }
+
+        if from_file.is_some() {
+            println!();
+            println!(
+                "{} added, {} not found, {} failed",
+                added.len(),
+                not_found.len(),
+                failed.len()
+            );
+            print_section("Not found:", &not_found);
+            print_section("Failed:", &failed);
+        }
+
+        Ok(())
+    })
+}
+
+/// Reads package specifiers from a file, or from stdin if `path` is `-`. Each line holds one
+/// specifier; blank lines and lines starting with `#` are skipped.
+fn read_package_list(path: &str) -> Result<Vec<String>, Box<Error>> {
+    let contents = match path {
+        "-" => {
+            let mut contents = String::new();
+            io::stdin().read_to_string(&mut contents)?;
+            contents
+        }
+        _ => fs::read_to_string(path)?,
+    };
+
+    Ok(contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect())
+}
+
+fn install(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    // Deploying a lockfile doesn't need a backend, since there may be no config to make one from
+    if let Some(lockfile) = matches.value_of("from-lock") {
+        let installed = PackageBackend::install_from_lock(
+            &PathBuf::from(lockfile),
+            &project_paths(matches),
+            &http::build_client()?,
+            matches.value_of("max-download-rate").and_then(http::parse_rate),
+            &CancelToken::on_interrupt(),
+        )?;
+        if installed.is_empty() {
+            println!("Everything in {} is already installed", lockfile);
+        }
+        for (name, version) in installed {
+            println!("Package {} installed @ version {}!", name, version);
+        }
+        return Ok(());
+    }
+
+    with_backend(matches, |backend| {
+        match matches.values_of("PACKAGE") {
+            Some(pkgs) => {
+                for pkg in pkgs {
+                    report_install(backend, pkg, backend.pkg_install(pkg));
+                }
+            }
+            None => {
+                for (name, version) in backend.pkg_install_all()? {
+                    println!("Package {} installed @ version {}!", name, version);
+                }
+            }
+        }
+
+        Ok(())
+    })
+}
+
+fn adopt(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let file = matches.value_of("FILE").unwrap();
+    let pkg = matches.value_of("PACKAGE");
+    with_backend(matches, |backend| {
+        match backend.pkg_adopt(file, pkg)? {
+            Some((name, version)) => println!("Adopted {} as {} @ version {}", file, name, version),
+            None => match pkg {
+                Some(pkg) => report_not_found(backend, pkg),
+                None => println!(
+                    "Could not find a package named like the plugin in {}; give the package it's a version of too",
+                    file
+                ),
+            },
+        }
+        Ok(())
+    })
+}
+
+fn update(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    with_backend(matches, |backend| {
+        // Downgraded packages are only updated when they're asked for by name
+        let names = match matches.values_of("PACKAGE") {
+            Some(pkgs) => pkgs.map(|pkg| pkg.to_string()).collect(),
+            None => {
+                let mut names = Vec::new();
+                for (name, locked) in Lockfile::load(&backend.paths.lockfile())?.packages {
+                    match locked.downgraded {
+                        true => println!(
+                            "Not updating {}, which was downgraded to {} (run `dropper update {}` to update it)",
+                            name, locked.version, name
+                        ),
+                        false => names.push(name),
+                    }
+                }
+                names
+            }
+        };
+
+        backend.prefetch(&names);
+        let names = match matches.is_present("interactive") {
+            true => pick_upgrades(backend, &names)?,
+            false => names,
+        };
+        if names.is_empty() {
+            return Ok(());
+        }
+        let batch = backend.pkg_update_batch(&names, matches.is_present("keep-going"))?;
+        for (name, why) in batch.failed.iter() {
+            println!("Error while trying to update {}: {}", name, why);
+        }
+        if batch.rolled_back {
+            if !batch.updated.is_empty() {
+                println!(
+                    "Rolled back {}, so that nothing is half updated (use --keep-going to keep them)",
+                    batch
+                        .updated
+                        .iter()
+                        .map(|(name, old, _)| format!("{} to {}", name, old))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                );
+            }
+            // Failing the command, so scripts don't take it for a successful update
+            let (name, _) = &batch.failed[0];
+            return Err(Box::new(backend::ErrorKind::BatchRolledBack(name.clone())));
+        }
+        for (name, old, new) in batch.updated.iter() {
+            println!("Updated {} from {} to {}", name, old, new);
+        }
+        for name in batch.up_to_date.iter() {
+            println!("{} is up to date", name);
+        }
+
+        // The restart only goes ahead if every update went through
+        restart_after(
+            backend,
+            matches,
+            !batch.updated.is_empty() && batch.failed.is_empty(),
+        )
+    })
+}
+
+/// Shows the user each update that's waiting for the packages, and returns the ones they want
+/// made
+fn pick_upgrades(backend: &PackageBackend, names: &[String]) -> Result<Vec<String>, Box<Error>> {
+    let upgrades = backend.pkg_upgrades(names)?;
+    if upgrades.is_empty() {
+        println!("Everything is up to date");
+        return Ok(Vec::new());
+    }
+
+    let mut picked = Vec::new();
+    for upgrade in upgrades {
+        println!(
+            "{} {} -> {} ({})",
+            upgrade.name,
+            upgrade.from,
+            upgrade.to,
+            upgrade.channel.name()
+        );
+        if let Some(changelog) = &upgrade.changelog {
+            println!("  Changelog: {}", changelog);
+        }
+        match confirm(&format!("Update {}?", upgrade.name)) {
+            true => picked.push(upgrade.name),
+            false => println!("Skipping {}", upgrade.name),
+        }
+    }
+    if picked.is_empty() {
+        println!("Nothing to update");
+    }
+    Ok(picked)
+}
+
+fn remove(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let force = matches.is_present("force");
+
+    with_backend(matches, |backend| {
+        for pkg in matches.values_of("PACKAGE").unwrap() {
+            match backend.pkg_remove(pkg, force) {
+                Ok(true) => println!("Removed {}", pkg),
+                Ok(false) => println!("{} isn't installed", pkg),
+                Err(e) => match e.downcast_ref::<backend::ErrorKind>() {
+                    Some(backend::ErrorKind::PkgRequired(_, _)) => {
+                        println!("Not removing {}: {} (use --force to remove it anyway)", pkg, e)
+                    }
+                    _ => println!("Error while trying to remove {}: {}", pkg, e),
+                },
+            }
+        }
+
+        // What was removed may have been the last thing to need some dependencies
+        let orphans = backend
+            .pkg_installed()?
+            .into_iter()
+            .filter(|package| package.orphaned)
+            .map(|package| package.name)
+            .collect::<Vec<String>>();
+        if !orphans.is_empty() {
+            println!(
+                "Nothing needs {} anymore; `dropper autoremove` removes them",
+                orphans.join(", ")
+            );
+        }
+
+        Ok(())
+    })
+}
+
+fn autoremove(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    with_backend(matches, |backend| {
+        let removed = backend.pkg_autoremove()?;
+        match removed.len() {
+            0 => println!("Nothing to remove"),
+            _ => {
+                for name in removed {
+                    println!("Removed {}", name);
+                }
+            }
+        }
+
+        Ok(())
+    })
+}
+
+fn list(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    with_backend(matches, |backend| {
+        let packages = backend
+            .pkg_installed()?
+            .into_iter()
+            .filter(|package| match package.reason {
+                InstallReason::Explicit => !matches.is_present("dependencies"),
+                InstallReason::Dependency => !matches.is_present("explicit"),
+            })
+            .collect::<Vec<InstalledListing>>();
+        if matches.is_present("porcelain") {
+            for record in installed_records(&packages) {
+                println!("{}", record);
+            }
+            return Ok(());
+        }
+        if packages.is_empty() {
+            println!("No packages to list");
+            return Ok(());
+        }
+
+        let width = packages.iter().map(|p| p.name.len()).max().unwrap_or(0);
+        for package in packages {
+            println!(
+                "{:width$}  {:12}  {}{}",
+                package.name,
+                package.version,
+                package.reason.as_str(),
+                match package.orphaned {
+                    true => " (nothing needs it anymore)",
+                    false => "",
+                },
+                width = width
+            );
+        }
+
+        Ok(())
+    })
+}
+
+fn why(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let name = matches.value_of("PACKAGE").unwrap();
+
+    with_backend(matches, |backend| {
+        let explanation = backend.pkg_why(name)?;
+        match (&explanation.version, explanation.reason) {
+            (Some(version), Some(reason)) => {
+                println!("{} {} is installed ({})", name, version, reason.as_str())
+            }
+            (Some(version), None) => println!("{} {} is locked", name, version),
+            (None, _) => println!("{} isn't installed", name),
+        }
+
+        for requirement in explanation.requirements.iter() {
+            println!(
+                "{} requires {} {}",
+                requirement.required_by, name, requirement.constraint
+            );
+        }
+        for chain in explanation.chains.iter() {
+            println!("Needed through {}", chain.join(" -> "));
+        }
+        if explanation.requirements.is_empty() && explanation.chains.is_empty() {
+            println!("Nothing requires {}", name);
+        }
+
+        if let Some(event) = &explanation.installed_by {
+            println!(
+                "{}{} {}",
+                match event.action.as_str() {
+                    "install" => "Installed",
+                    "update" => "Updated",
+                    "downgrade" => "Downgraded",
+                    action => action,
+                },
+                match &event.from {
+                    Some(from) => format!(" from {}", from),
+                    None => String::new(),
+                },
+                ago(Some(event.time))
+            );
+        }
+
+        Ok(())
+    })
+}
+
+fn docs(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    with_backend(matches, |backend| {
+        let markdown = backend.pkg_handover()?;
+        match matches.value_of("output") {
+            Some(path) => {
+                fs::write(path, markdown)?;
+                println!("Wrote {}", path);
+            }
+            None => print!("{}", markdown),
+        }
+        if !backend.save_docs {
+            eprintln!(
+                "Set save_docs in the config to save plugins' descriptions as they're installed"
+            );
+        }
+
+        Ok(())
+    })
+}
+
+/// How long ago a time was, in days, like `3 days ago`
+fn ago(time: Option<u64>) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    match time {
+        Some(time) => match now.saturating_sub(time) / (60 * 60 * 24) {
+            0 => "today".to_string(),
+            1 => "1 day ago".to_string(),
+            days => format!("{} days ago", days),
+        },
+        None => "at an unknown time".to_string(),
+    }
+}
+
+fn outdated(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let all = matches.is_present("all");
+
+    with_backend(matches, |backend| {
+        let ages = backend
+            .pkg_outdated()?
+            .into_iter()
+            .filter(|age| all || age.is_outdated() || age.blocked.is_some())
+            .collect::<Vec<PackageAge>>();
+        if matches.is_present("porcelain") {
+            for record in age_records(&ages) {
+                println!("{}", record);
+            }
+            return Ok(());
+        }
+        if ages.is_empty() {
+            println!("Everything is up to date");
+            return Ok(());
+        }
+
+        let mut rows = vec![vec![
+            "Package".to_string(),
+            "Installed".to_string(),
+            "Newest".to_string(),
+            "Age".to_string(),
+        ]];
+        for age in ages.iter() {
+            let description = match age.is_outdated() {
+                true => format!(
+                    "installed {}, newest released {}",
+                    ago(age.installed_at),
+                    ago(age.newest_released)
+                ),
+                false => format!(
+                    "installed {}, released {}",
+                    ago(age.installed_at),
+                    ago(age.installed_released)
+                ),
+            };
+            rows.push(vec![
+                age.name.clone(),
+                age.installed.clone(),
+                age.newest.clone().unwrap_or("?".to_string()),
+                description,
+            ]);
+        }
+        print_table(&rows);
+
+        let blocked = ages
+            .iter()
+            .filter_map(|age| age.blocked.as_ref().map(|reason| (age, reason)))
+            .collect::<Vec<(&PackageAge, &String)>>();
+        if !blocked.is_empty() {
+            println!();
+        }
+        for (age, reason) in blocked {
+            println!(
+                "! {} {} is on the blocklist ({}); update or remove it",
+                age.name, age.installed, reason
+            );
+        }
+
+        Ok(())
+    })
+}
+
+fn versions(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let pkg = matches.value_of("PACKAGE").unwrap();
+
+    with_backend(matches, |backend| {
+        let listings = match backend.pkg_versions(pkg)? {
+            Some(listings) => listings
+                .into_iter()
+                .filter(|listing| listing.compatible || !matches.is_present("compatible"))
+                .filter(|listing| listing.allowed || !matches.is_present("allowed"))
+                .collect::<Vec<VersionListing>>(),
+            None => {
+                report_not_found(backend, pkg);
+                return Ok(());
+            }
+        };
+        if matches.is_present("porcelain") {
+            for record in version_records(&listings) {
+                println!("{}", record);
+            }
+            return Ok(());
+        }
+        if listings.is_empty() {
+            println!("No versions of {} to list", pkg);
+            return Ok(());
+        }
+
+        let mut rows = vec![vec![
+            "Version".to_string(),
+            "Channel".to_string(),
+            "Server versions".to_string(),
+            "Released".to_string(),
+            String::new(),
+        ]];
+        for listing in listings.iter() {
+            let mut notes = Vec::new();
+            if listing.installed {
+                notes.push("installed".to_string());
+            }
+            if !listing.compatible {
+                notes.push("not for this server".to_string());
+            }
+            if !listing.allowed {
+                notes.push("pkg.yml doesn't allow it".to_string());
+            }
+            if let Some(reason) = &listing.blocked {
+                notes.push(format!("blocked: {}", reason));
+            }
+            rows.push(vec![
+                listing.file.version.clone(),
+                listing.file.channel.name().to_string(),
+                match listing.file.game_versions.is_empty() {
+                    true => "?".to_string(),
+                    false => listing.file.game_versions.join(", "),
+                },
+                listing
+                    .file
+                    .uploaded
+                    .map_or("?".to_string(), |time| ago(Some(time))),
+                notes.join("; "),
+            ]);
+        }
+        print_table(&rows);
+
+        Ok(())
+    })
+}
+
+fn plan(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let out = match matches.value_of("out") {
+        Some(out) => PathBuf::from(out),
+        None => project_paths(matches).plan_file(),
+    };
+
+    with_backend(matches, |backend| {
+        let plan = backend.pkg_plan()?;
+        plan.save(&out)?;
+
+        if plan.changes.is_empty() {
+            println!("Nothing to do; the server matches pkg.yml");
+            return Ok(());
+        }
+        for change in plan.changes.iter() {
+            println!("{}", change);
+        }
+        println!();
+        println!(
+            "Saved to {}. Run `dropper apply` to make these changes.",
+            out.display()
+        );
+
+        Ok(())
+    })
+}
+
+fn apply(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let path = match matches.value_of("PLAN") {
+        Some(path) => PathBuf::from(path),
+        None => project_paths(matches).plan_file(),
+    };
+    let plan = Plan::load(&path)?;
+
+    with_backend(matches, |backend| {
+        let applied = backend.pkg_apply(&plan)?;
+        if applied.is_empty() {
+            println!("Nothing to do");
+        }
+        for change in applied.iter() {
+            println!("{}", change);
+        }
+
+        restart_after(backend, matches, !applied.is_empty())
+    })
+}
+
+fn sync(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    with_backend(matches, |backend| {
+        let report = backend.pkg_sync()?;
+        if report.is_empty() {
+            println!("The plugins folder already matches the lockfile");
+        }
+        for file in report.installed.iter() {
+            println!("+ {}", file);
+        }
+        for file in report.replaced.iter() {
+            println!("~ {}", file);
+        }
+        for file in report.removed.iter() {
+            println!("- {}", file);
+        }
+
+        Ok(())
+    })
+}
+
+fn verify(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let mut clean = true;
+    with_backend(matches, |backend| {
+        let report = backend.pkg_verify(matches.is_present("deep"))?;
+        for (name, file) in report.missing.iter() {
+            println!("{} is missing {}", name, file);
+        }
+        for (name, file) in report.modified.iter() {
+            println!("{}'s {} doesn't match the lockfile", name, file);
+        }
+        for (name, explanation) in report.mismatched.iter() {
+            println!("{}: {}", name, explanation);
+        }
+        for (name, file) in report.manual_missing.iter() {
+            println!("{} is managed by hand, but isn't in the plugins folder as {}", name, file);
+        }
+        for (name, explanation) in report.manual_mismatched.iter() {
+            println!("{}: {}", name, explanation);
+        }
+        clean = report.is_clean();
+        if clean {
+            println!("Every locked JAR matches the lockfile");
+            if !report.manual_verified.is_empty() {
+                println!("Every JAR managed by hand matches the pkg.yml");
+            }
+        }
+        Ok(())
+    })?;
+
+    if !clean {
+        return Err(Box::new(backend::ErrorKind::VerifyFailed));
+    }
+    Ok(())
+}
+
+fn status(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    with_backend(matches, |backend| {
+        if matches.is_present("configs") {
+            let report = backend.pkg_config_drift()?;
+            if matches.is_present("porcelain") {
+                for record in config_records(&report) {
+                    println!("{}", record);
+                }
+                return Ok(());
+            }
+            if report.is_clean() {
+                println!("Every rendered config is as dropper left it");
+                return Ok(());
+            }
+
+            let describe = |configs: &[(String, String)]| {
+                configs
+                    .iter()
+                    .map(|(name, file)| format!("{} ({})", file, name))
+                    .collect::<Vec<String>>()
+            };
+            print_section("Changed since they were rendered:", &describe(&report.modified));
+            print_section("Deleted since they were rendered:", &describe(&report.deleted));
+            return Ok(());
+        }
+
+        let report = backend.pkg_status()?;
+        if matches.is_present("porcelain") {
+            for record in status_records(&report) {
+                println!("{}", record);
+            }
+            return Ok(());
+        }
+        if report.is_clean() {
+            println!("Everything is in order");
+            return Ok(());
+        }
+
+        print_section("In pkg.yml, but not installed:", &report.not_installed);
+        print_section(
+            "Locked, but missing from the plugins folder:",
+            &report
+                .missing
+                .iter()
+                .map(|(name, file)| format!("{} ({})", name, file))
+                .collect::<Vec<String>>(),
+        );
+        print_section(
+            "Managed by hand, but missing from the plugins folder:",
+            &report
+                .manual_missing
+                .iter()
+                .map(|(name, file)| format!("{} ({})", name, file))
+                .collect::<Vec<String>>(),
+        );
+        print_section(
+            "Version mismatches:",
+            &report
+                .mismatched
+                .iter()
+                .map(|(name, why)| format!("{}: {}", name, why))
+                .collect::<Vec<String>>(),
+        );
+        print_section("Not managed by dropper:", &report.unmanaged);
+        print_section("Waiting to be swapped in:", &report.staged);
+
+        Ok(())
+    })
+}
+
+fn unmanaged(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    with_backend(matches, |backend| {
+        let searcher = bukkit_parser(
+            &backend.paths,
+            backend.server_version.clone(),
+            backend.client.clone(),
+        )?;
+        let jars = backend.pkg_unmanaged(&searcher)?;
+        if matches.is_present("porcelain") {
+            for record in unmanaged_records(&jars) {
+                println!("{}", record);
+            }
+            return Ok(());
+        }
+        if jars.is_empty() {
+            println!("Every JAR in the plugins folder is managed by dropper or ignored");
+            return Ok(());
+        }
+
+        for jar in jars.iter() {
+            match &jar.description {
+                Some(description) => {
+                    println!("{} ({} {})", jar.file, description.name, description.version)
+                }
+                None => println!("{} (no plugin.yml that could be read)", jar.file),
+            }
+            if jar.description.is_some() && jar.guesses.is_empty() {
+                println!("  Not found on any source");
+            }
+            for (source, listing) in jar.guesses.iter() {
+                match source.as_str() {
+                    // Maven artifacts and repository packages are added by pkg.yml entries, not a
+                    // package specifier
+                    "maven" => println!("  maven: {} ({})", listing.name, listing.link),
+                    "repo" => println!("  repo: {} (in {})", listing.name, listing.link),
+                    _ => println!("  {}:{} ({})", source, listing.name, listing.link),
+                }
+            }
+        }
+        println!();
+        println!("Take one under management with `dropper adopt <FILE> <PACKAGE>`, or add it to the config's ignore list to leave it be");
+        Ok(())
+    })
+}
+
+fn watch(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let interval = Duration::from_secs(matches.value_of("interval").unwrap().parse().unwrap());
+
+    with_backend(matches, |backend| {
+        let plugin_dir = &backend.paths.plugin_dir;
+        fs::create_dir_all(plugin_dir)?;
+        let watcher = Watcher::new(plugin_dir, interval)?;
+        let mut known = watch::snapshot(plugin_dir)?;
+        println!("Watching {}", plugin_dir.display());
+
+        loop {
+            watcher.wait()?;
+            // Being interrupted is how watching is stopped
+            if backend.cancel.is_cancelled() {
+                return Ok(());
+            }
+            let jars = watch::snapshot(plugin_dir)?;
+            // Changes made during an operation are looked at once it's done
+            let drift = match backend.pkg_drift(&known, &jars)? {
+                Some(drift) => drift,
+                None => continue,
+            };
+            known = jars;
+
+            for file in drift.added.iter() {
+                match matches.is_present("quarantine") {
+                    true => {
+                        let quarantined = backend.pkg_quarantine(file)?;
+                        known.remove(file);
+                        println!(
+                            "{} was added outside dropper, and has been moved to {}",
+                            file,
+                            quarantined.display()
+                        );
+                    }
+                    false => println!(
+                        "{} was added outside dropper; `dropper sync` will remove it, unless it's on the ignore list",
+                        file
+                    ),
+                }
+            }
+            for (name, file) in drift.removed.iter() {
+                println!(
+                    "{} ({}) was removed outside dropper; `dropper sync` will put it back",
+                    file, name
+                );
+            }
+            for warning in backend.take_warnings() {
+                eprintln!("warning: {}", warning);
+            }
+            io::stdout().flush()?;
+        }
+    })
+}
+
+/// Prints a heading with a list under it, unless there's nothing in the list
+fn print_section(heading: &str, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+
+    println!("{}", heading);
+    for item in items {
+        println!("  {}", item);
+    }
+}
+
+fn info(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let name = matches.value_of("PACKAGE").unwrap();
+
+    with_backend(matches, |backend| {
+        let (jar, description) = match backend.pkg_info(name) {
+            Err(e) => match e.downcast_ref::<backend::ErrorKind>() {
+                Some(backend::ErrorKind::PkgNotInstalled(_)) => return remote_info(backend, name),
+                _ => return Err(e),
+            },
+            Ok(info) => info,
+        };
+        println!("{} {}", description.name, description.version);
+        println!("File: {}", jar.display());
+        // Saved docs say more than a plugin.yml does, without having to go online
+        if let Some(docs) = backend.pkg_docs(name) {
+            if !docs.authors.is_empty() {
+                println!("Authors: {}", docs.authors.join(", "));
+            }
+            if let Some(text) = &docs.description {
+                println!("{}", text);
+            }
+        }
+        if let Some(api_version) = &description.api_version {
+            println!("API version: {}", api_version);
+        }
+        if !description.depend.is_empty() {
+            println!("Depends on: {}", description.depend.join(", "));
+        }
+        if !description.softdepend.is_empty() {
+            println!("Integrates with: {}", description.softdepend.join(", "));
+        }
+        if !matches.is_present("detailed") {
+            return Ok(());
+        }
+
+        let mut commands = Vec::new();
+        for command in description.commands.iter() {
+            let mut line = format!("/{}", command.name);
+            if !command.aliases.is_empty() {
+                line.push_str(&format!(" (aliases: {})", command.aliases.join(", ")));
+            }
+            if let Some(text) = &command.description {
+                line.push_str(&format!(" - {}", text));
+            }
+            commands.push(line);
+            if let Some(usage) = &command.usage {
+                commands.push(format!("  usage: {}", usage));
+            }
+            if let Some(permission) = &command.permission {
+                commands.push(format!("  needs: {}", permission));
+            }
+        }
+
+        let mut permissions = Vec::new();
+        for permission in description.permissions.iter() {
+            // Bukkit gives undeclared defaults to ops
+            let mut line = format!(
+                "{} (default: {})",
+                permission.name,
+                permission.default.as_ref().map_or("op", |d| d.as_str())
+            );
+            if let Some(text) = &permission.description {
+                line.push_str(&format!(" - {}", text));
+            }
+            permissions.push(line);
+            for (child, granted) in permission.children.iter() {
+                permissions.push(format!(
+                    "  {} {}",
+                    match granted {
+                        true => "grants",
+                        false => "revokes",
+                    },
+                    child
+                ));
+            }
+        }
+
+        if commands.is_empty() && permissions.is_empty() {
+            println!("\n{} registers no commands or permissions", description.name);
+            return Ok(());
+        }
+        if !commands.is_empty() {
+            println!();
+            print_section("Commands:", &commands);
+        }
+        if !permissions.is_empty() {
+            println!();
+            print_section("Permissions:", &permissions);
+        }
+
+        Ok(())
+    })
+}
+
+/// Shows what a package's source says about it, for `info` on packages that aren't installed
+fn remote_info(backend: &PackageBackend, name: &str) -> Result<(), Box<Error>> {
+    let metadata = match backend.pkg_metadata(name)? {
+        Some(metadata) => metadata,
+        None => return Err(Box::new(backend::ErrorKind::PkgNotFound(name.to_string()))),
+    };
+
+    println!(
+        "{} (not installed)",
+        metadata.display_name.as_ref().unwrap_or(&metadata.slug)
+    );
+    if let Some(id) = &metadata.id {
+        println!("Project ID: {}", id);
+    }
+    if !metadata.authors.is_empty() {
+        println!("Authors: {}", metadata.authors.join(", "));
+    }
+    if let Some(description) = &metadata.description {
+        println!("{}", description);
+    }
+    match metadata.versions.first() {
+        Some(newest) => println!(
+            "Newest version: {} ({}), out of {}",
+            newest.version,
+            newest.channel.name(),
+            metadata.versions.len()
+        ),
+        None => println!("There are no versions for this server"),
+    }
+
+    Ok(())
+}
+
+fn try_package(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let pkg = matches.value_of("PACKAGE").unwrap();
+    let sandbox = std::env::temp_dir().join(format!("dropper-try-{}", process::id()));
+    if sandbox.exists() {
+        fs::remove_dir_all(&sandbox)?;
+    }
+
+    let result = with_backend(matches, |backend| {
+        let trial = match backend.pkg_try(pkg, &sandbox)? {
+            Some(trial) => trial,
+            None => {
+                report_not_found(backend, pkg);
+                return Ok(());
+            }
+        };
+        for warning in trial.warnings.iter() {
+            eprintln!("warning: {}", warning);
+        }
+
+        for package in trial.packages.iter() {
+            println!();
+            println!("{} {} ({})", package.name, package.version, package.reason.as_str());
+            if let Some(installed) = &package.installed {
+                println!("  The server has {} installed", installed);
+            }
+            match &package.description {
+                Some(description) => {
+                    println!("  Plugin: {} {}", description.name, description.version);
+                    if let Some(api_version) = &description.api_version {
+                        println!("  API version: {}", api_version);
+                    }
+                    if !description.depend.is_empty() {
+                        println!("  Depends on: {}", description.depend.join(", "));
+                    }
+                    if !description.softdepend.is_empty() {
+                        println!("  Integrates with: {}", description.softdepend.join(", "));
+                    }
+                    if !description.commands.is_empty() {
+                        let commands = description
+                            .commands
+                            .iter()
+                            .map(|command| format!("/{}", command.name))
+                            .collect::<Vec<String>>();
+                        println!("  Commands: {}", commands.join(", "));
+                    }
+                }
+                None => println!("  Not a plugin of its own"),
+            }
+
+            // Classes are too many to list, but the resources say what the plugin ships with
+            let (classes, resources): (Vec<&String>, Vec<&String>) = package
+                .contents
+                .iter()
+                .partition(|file| file.ends_with(".class"));
+            println!(
+                "  Contents: {} {}, {} of them classes",
+                package.contents.len(),
+                match package.contents.len() {
+                    1 => "file",
+                    _ => "files",
+                },
+                classes.len()
+            );
+            if !resources.is_empty() {
+                let resources = resources
+                    .iter()
+                    .map(|file| file.as_str())
+                    .collect::<Vec<&str>>();
+                println!("  Resources: {}", resources.join(", "));
+            }
+        }
+
+        println!();
+        println!(
+            "Nothing on the server was changed. Run `dropper add {}` to install {} {} for real.",
+            pkg, trial.name, trial.version
+        );
+        Ok(())
+    });
+
+    // A download that didn't match its digest is kept in the sandbox to be looked into
+    let quarantined = ProjectPaths::new(sandbox.clone(), None).quarantine_dir();
+    let keep = matches.is_present("keep") || (result.is_err() && quarantined.exists());
+    match keep && sandbox.exists() {
+        true => println!("The sandbox is kept at {}", sandbox.display()),
+        false => {
+            if sandbox.exists() {
+                fs::remove_dir_all(&sandbox)?;
+            }
+        }
+    }
+    result
+}
+
+fn search(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    // Multi-word searches don't need to be quoted
+    let query = matches
+        .values_of("QUERY")
+        .unwrap()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    let paths = project_paths(matches);
+    let parser = bukkit_parser(
+        &paths,
+        PackageBackend::configured_server_version(&paths)?,
+        http::build_client()?,
+    )?;
+
+    let filters = SearchFilters {
+        category: matches.value_of("category").map(|s| s.to_string()),
+        game_version: matches.value_of("mc").map(|s| s.to_string()),
+    };
+
+    if matches.is_present("all-sources") {
+        return search_all_sources(matches, &parser, &query, &filters);
+    }
+
+    let mut results = parser.search_listings(&query, &filters)?;
+    results.sort_by(|a, b| a.name.cmp(&b.name));
+    remember_names(&paths, results.iter().map(|l| l.name.clone()).collect());
+
+    match results.len() {
+        0 => println!("Nothing matched '{}'", query),
+        _ => {
+            for listing in results {
+                println!("{} -> {}", listing.name, listing.link);
+                if let Some(description) = &listing.description {
+                    println!("    {}", snippet(description));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// How much of what a plugin says it does is shown next to it
+const SNIPPET_CHARS: usize = 72;
+
+/// The start of what a plugin says it does, short enough to go on one line with its name
+fn snippet(description: &str) -> String {
+    let mut snippet = description
+        .chars()
+        .take(SNIPPET_CHARS)
+        .collect::<String>();
+    if description.chars().count() > SNIPPET_CHARS {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+/// Searches every source at once, and prints what they found as one table, with what each
+/// plugin does. A source that can't be searched is warned about, rather than failing the search.
+fn search_all_sources(
+    matches: &ArgMatches,
+    parser: &BukkitHTMLPluginParser,
+    query: &str,
+    filters: &SearchFilters,
+) -> Result<(), Box<Error>> {
+    let paths = &project_paths(matches);
+    let client = http::build_client()?;
+    let credentials = PackageBackend::configured_credentials(paths)?;
+    let (website, central) = crossbeam_utils::thread::scope(|scope| {
+        let website = scope.spawn(|_| {
+            parser
+                .search_listings(query, filters)
+                .map_err(|e| e.to_string())
+        });
+        // Maven Central can't be filtered by category or server version
+        let central = match filters.is_empty() {
+            true => Some(scope.spawn(|_| {
+                maven::search_central(&client, &credentials, query).map_err(|e| e.to_string())
+            })),
+            false => None,
+        };
+        (
+            website.join().unwrap(),
+            central.map(|central| central.join().unwrap()),
+        )
+    })
+    .unwrap();
+
+    // Only the pkg.yml knows which self-hosted repositories there are, and they're searched here
+    // rather than by a website, so they can't be filtered either
+    let mut repos = None;
+    if filters.is_empty() {
+        with_backend(matches, |backend| {
+            repos = Some(backend.pkg_search_repos(query).map_err(|e| e.to_string()));
+            Ok(())
+        })?;
+    }
+
+    let mut results = Vec::new();
+    for (source, found) in [("bukkit", Some(website)), ("maven", central), ("repo", repos)] {
+        match found {
+            Some(Ok(listings)) => results.push((source.to_string(), listings)),
+            Some(Err(e)) => eprintln!("warning: couldn't search {}: {}", source, e),
+            None => {}
+        }
+    }
+    remember_names(
+        paths,
+        results
+            .iter()
+            .filter(|(source, _)| source == "bukkit")
+            .flat_map(|(_, listings)| listings.iter().map(|l| l.name.clone()))
+            .collect(),
+    );
+
+    let merged = parser::merge_listings(results);
+    if merged.is_empty() {
+        println!("Nothing matched '{}'", query);
+        return Ok(());
+    }
+
+    let mut rows = vec![vec![
+        "Name".to_string(),
+        "Downloads".to_string(),
+        "Found on".to_string(),
+        "Description".to_string(),
+    ]];
+    for hit in merged.iter() {
+        rows.push(vec![
+            hit.title.clone(),
+            match hit.downloads() {
+                Some(downloads) => downloads.to_string(),
+                None => "?".to_string(),
+            },
+            hit.sources
+                .iter()
+                .map(|(source, listing)| match source.as_str() {
+                    // Maven artifacts are added by their coordinates, and packages in a
+                    // repository by a pkg.yml entry, not a package specifier
+                    "maven" | "repo" => format!("{} ({})", source, listing.name),
+                    _ => format!("{}:{}", source, listing.name),
+                })
+                .collect::<Vec<String>>()
+                .join(", "),
+            hit.description().map_or(String::new(), snippet),
+        ]);
+    }
+    print_table(&rows);
+
+    Ok(())
+}
+
+fn browse(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let category = matches.value_of("CATEGORY").unwrap();
+    let paths = project_paths(matches);
+    let parser = bukkit_parser(
+        &paths,
+        PackageBackend::configured_server_version(&paths)?,
+        http::build_client()?,
+    )?;
+
+    let filters = SearchFilters {
+        category: Some(category.to_string()),
+        game_version: matches.value_of("mc").map(|s| s.to_string()),
+    };
+    let listings = parser.browse(&filters, ListingSort::Popularity)?;
+    remember_names(&paths, listings.iter().map(|l| l.name.clone()).collect());
+
+    match listings.len() {
+        0 => println!("There's nothing in '{}'", category),
+        _ => print_listings(&listings),
+    }
+
+    Ok(())
+}
+
+fn top(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let paths = project_paths(matches);
+    let server_version = PackageBackend::configured_server_version(&paths)?;
+    let client = http::build_client()?;
+    let modrinth = matches.value_of("source") == Some("modrinth");
+    let browser: Box<PluginBrowsable> = match modrinth {
+        true => Box::new(ModrinthBrowser::new(
+            client,
+            PackageBackend::configured_credentials(&paths)?,
+        )),
+        false => Box::new(bukkit_parser(&paths, server_version.clone(), client)?),
+    };
+
+    let filters = SearchFilters {
+        category: None,
+        game_version: Some(server_version.clone()),
+    };
+    let sort = match matches.is_present("trending") {
+        true => ListingSort::Popularity,
+        false => ListingSort::Downloads,
+    };
+    let listings = browser.browse(&filters, sort)?;
+    // Packages are installed from Bukkit by name, so only its names are completed
+    if !modrinth {
+        remember_names(&paths, listings.iter().map(|l| l.name.clone()).collect());
+    }
+
+    match listings.len() {
+        0 => println!("There are no plugins with builds for {} yet", server_version),
+        _ => print_listings(&listings),
+    }
+    if modrinth && !listings.is_empty() {
+        println!("\nThese are Modrinth's names for the plugins, whose pages are at https://modrinth.com/plugin/<package>");
+    }
+
+    Ok(())
+}
+
+/// Prints plugins from a listing as a table, with the name to install each by
+fn print_listings(listings: &[PluginListing]) {
+    let mut rows = vec![vec![
+        "Package".to_string(),
+        "Name".to_string(),
+        "Downloads".to_string(),
+    ]];
+    for listing in listings {
+        rows.push(vec![
+            listing.name.clone(),
+            listing.title.clone(),
+            match listing.downloads {
+                Some(downloads) => downloads.to_string(),
+                None => "?".to_string(),
+            },
+        ]);
+    }
+    print_table(&rows);
+}
+
+fn compat(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let versions = matches.values_of("versions").unwrap().collect::<Vec<&str>>();
+
+    with_backend(matches, |backend| {
+        // One column of cells per server version
+        let mut columns = Vec::new();
+        for version in versions.iter() {
+            let parser =
+                bukkit_parser(&backend.paths, version.to_string(), backend.client.clone())?;
+            columns.push(backend.pkg_compatibility(&parser)?);
+        }
+
+        let names = match columns.first() {
+            Some(column) => column.iter().map(|(name, _)| name.clone()).collect(),
+            None => Vec::new(),
+        };
+        if names.is_empty() {
+            println!("There are no packages in pkg.yml to check");
+            return Ok(());
+        }
+
+        let mut rows = vec![versions.iter().map(|v| v.to_string()).collect::<Vec<String>>()];
+        rows[0].insert(0, "Package".to_string());
+        for (i, name) in names.iter().enumerate() {
+            let mut row = vec![name.clone()];
+            for column in columns.iter() {
+                row.push(match &column[i].1 {
+                    Compatibility::Compatible(version) => version.clone(),
+                    Compatibility::Incompatible => "-".to_string(),
+                    Compatibility::Unknown(_) => "?".to_string(),
+                });
+            }
+            rows.push(row);
+        }
+        print_table(&rows);
+
+        let ready = versions
+            .iter()
+            .zip(columns.iter())
+            .filter(|(_, column)| {
+                column
+                    .iter()
+                    .all(|(_, c)| matches!(c, Compatibility::Compatible(_)))
+            })
+            .map(|(version, _)| *version)
+            .collect::<Vec<&str>>();
+        println!();
+        match ready.len() {
+            0 => println!("None of those versions have builds for every package yet"),
+            _ => println!("Every package has a build for: {}", ready.join(", ")),
+        }
+
+        // Explain the question marks, once per version
+        for (version, column) in versions.iter().zip(columns.iter()) {
+            if let Some((_, Compatibility::Unknown(why))) = column
+                .iter()
+                .find(|(_, c)| matches!(c, Compatibility::Unknown(_)))
+            {
+                println!("? {}: {}", version, why);
+            }
+        }
+
+        Ok(())
+    })
+}
+
+fn migrate(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let server_version = matches.value_of("to").unwrap().to_string();
+
+    with_backend(matches, |current| {
+        if current.server_version == server_version {
+            println!("The server is already on {}", server_version);
+            return Ok(());
+        }
+
+        // Packages are looked up the way they will be once the server has moved
+        let client = current.client.clone();
+        let parser = bukkit_parser(&current.paths, server_version.clone(), client.clone())?;
+        let mut backend = PackageBackend::new(&parser, current.paths.clone(), client)?;
+        backend.server_version = server_version.clone();
+        backend.cancel = current.cancel.clone();
+        backend.forced = current.forced.clone();
+        backend.allow_blocked = current.allow_blocked;
+        backend.env = current.env.clone();
+        backend.max_download_rate = current.max_download_rate;
+
+        let result = migrate_with(&backend, matches);
+        for warning in backend.take_warnings() {
+            eprintln!("warning: {}", warning);
+        }
+        result
+    })
+}
+
+/// Does the work of `migrate`, with a backend for the server version being moved to
+fn migrate_with(backend: &PackageBackend, matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let server_version = &backend.server_version;
+    let migration = backend.pkg_migration()?;
+    print_section(
+        &format!("Has a build for {}:", server_version),
+        &migration
+            .packages
+            .iter()
+            .map(|(name, version)| format!("{} {}", name, version))
+            .collect::<Vec<String>>(),
+    );
+    print_section(
+        "Needs its constraint in pkg.yml moved:",
+        &migration
+            .constraints
+            .iter()
+            .map(|(name, old, new)| format!("{}: {} -> {}", name, old, new))
+            .collect::<Vec<String>>(),
+    );
+    print_section(
+        "Blocking the move:",
+        &migration
+            .blockers
+            .iter()
+            .map(|(name, why)| format!("{}: {}", name, why))
+            .collect::<Vec<String>>(),
+    );
+
+    if migration.is_blocked() {
+        return Err(Box::new(backend::ErrorKind::MigrationBlocked(
+            server_version.clone(),
+            migration.blockers.iter().map(|(name, _)| name.clone()).collect(),
+        )));
+    }
+    let question = format!("Move the server to {}?", server_version);
+    if !matches.is_present("yes") && !confirm(&question) {
+        return Ok(());
+    }
+
+    let installed = backend.pkg_migrate(&migration)?;
+    for (name, version) in installed.iter() {
+        println!("Installed {} {}", name, version);
+    }
+    println!("The server is now on {}", server_version);
+
+    restart_after(backend, matches, !installed.is_empty())
+}
+
+fn doctor(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let paths = project_paths(matches);
+    let fix = matches.is_present("fix");
+
+    let lockfile = paths.lockfile();
+    let state_file = paths.state_file();
+    let healthy = [
+        check_format(&lockfile, Lockfile::format_status(&lockfile)?, fix, || {
+            Lockfile::upgrade(&lockfile)
+        })?,
+        check_format(&state_file, State::format_status(&state_file)?, fix, || {
+            State::upgrade(&state_file)
+        })?,
+    ]
+    .iter()
+    .all(|healthy| *healthy);
+
+    if !healthy {
+        return Err(Box::new(format::ErrorKind::FormatsUnhealthy));
+    }
+    println!("Everything is in order");
+    Ok(())
+}
+
+/// Reports the format of one of dropper's files, upgrading it if it's outdated and `fix` is set.
+/// Returns whether the file is usable as it is.
+fn check_format<F>(
+    path: &Path,
+    status: FormatStatus,
+    fix: bool,
+    upgrade: F,
+) -> Result<bool, Box<Error>>
+where
+    F: FnOnce() -> Result<Option<i64>, Box<Error>>,
+{
+    let path = path.display();
+    match status {
+        FormatStatus::Missing | FormatStatus::Current => Ok(true),
+        FormatStatus::Outdated(version) if fix => {
+            upgrade()?;
+            println!("Upgraded {} from format {}", path, version);
+            Ok(true)
+        }
+        FormatStatus::Outdated(version) => {
+            println!(
+                "{} is in the older format {}; run `dropper doctor --fix` to upgrade it",
+                path, version
+            );
+            Ok(false)
+        }
+        FormatStatus::TooNew(version) => {
+            println!(
+                "{} is in format {}, which only a newer dropper knows; upgrade dropper to use it",
+                path, version
+            );
+            Ok(false)
+        }
+        FormatStatus::Invalid => {
+            println!("{} is corrupt", path);
+            Ok(false)
+        }
+    }
+}
+
+fn test(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let sandbox = std::env::temp_dir().join(format!("dropper-test-{}", process::id()));
+    if sandbox.exists() {
+        fs::remove_dir_all(&sandbox)?;
+    }
+
+    let mut clean = true;
+    let result = with_backend(matches, |backend| {
+        if backend.test_server.is_some() {
+            println!("Starting a test server in {}", sandbox.display());
+            io::stdout().flush()?;
+        }
+        let report = backend.pkg_test(&sandbox)?;
+        clean = report.is_clean();
+
+        match report.run.outcome {
+            Outcome::Started => println!("The test server started"),
+            Outcome::Exited(Some(code)) => println!(
+                "The test server stopped with exit code {} before it finished starting",
+                code
+            ),
+            Outcome::Exited(None) => {
+                println!("The test server was killed before it finished starting")
+            }
+            Outcome::TimedOut => println!("The test server didn't finish starting in time"),
+        }
+        print_section(
+            "Failed to load:",
+            &report
+                .failed
+                .iter()
+                .map(|(name, why)| format!("{}: {}", name, why))
+                .collect::<Vec<String>>(),
+        );
+        print_section("Not enabled, without saying why:", &report.not_enabled);
+        if clean {
+            println!("Every plugin loaded");
+        } else {
+            println!("The server's output is in {}", report.run.log.display());
+        }
+        Ok(())
+    });
+
+    // A copy that went wrong is kept, for its log
+    match (matches.is_present("keep") || !clean) && sandbox.exists() {
+        true => println!("The copy is kept at {}", sandbox.display()),
+        false => {
+            if sandbox.exists() {
+                fs::remove_dir_all(&sandbox)?;
+            }
+        }
+    }
+    result?;
+    if !clean {
+        return Err(Box::new(backend::ErrorKind::TestFailed));
+    }
+    Ok(())
+}
+
+fn analyze(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    // Logs aren't always valid UTF-8, since plugins print whatever they like
+    let contents = match matches.value_of("LOG").unwrap() {
+        "-" => {
+            let mut contents = Vec::new();
+            io::stdin().read_to_end(&mut contents)?;
+            contents
+        }
+        path => fs::read(path)?,
+    };
+    let output = String::from_utf8_lossy(&contents)
+        .lines()
+        .map(|line| line.to_string())
+        .collect::<Vec<String>>();
+
+    with_backend(matches, |backend| {
+        let diagnoses = backend.pkg_analyze(&output)?;
+        if diagnoses.is_empty() {
+            println!("Nothing in the log points at a plugin");
+            return Ok(());
+        }
+
+        for (i, diagnosis) in diagnoses.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            let culprit = match &diagnosis.package {
+                Some(package) if *package != diagnosis.culprit => {
+                    format!("{} (package {})", diagnosis.culprit, package)
+                }
+                _ => diagnosis.culprit.clone(),
+            };
+            match diagnosis.count {
+                1 => println!("{} {}", culprit, diagnosis.what),
+                count => println!("{} {}, {} times", culprit, diagnosis.what, count),
+            }
+            if let Some(explanation) = &diagnosis.explanation {
+                println!("  {}", explanation);
+            }
+            if diagnosis.package.is_none() {
+                println!("  dropper doesn't manage it");
+            }
+            for suggestion in diagnosis.suggestions.iter() {
+                println!("  - {}", suggestion);
+            }
+        }
+        Ok(())
+    })
+}
+
+fn crash(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    with_backend(matches, |backend| {
+        let path = match matches.value_of("REPORT") {
+            Some(path) => PathBuf::from(path),
+            None => match newest_file(&backend.paths.crash_reports_dir())? {
+                Some(path) => path,
+                None => {
+                    println!("The server hasn't written any crash reports");
+                    return Ok(());
+                }
+            },
+        };
+
+        // The report is written as the server crashes, so it's from when it was last changed
+        let (contents, time) = match path.to_str() {
+            Some("-") => {
+                let mut contents = Vec::new();
+                io::stdin().read_to_end(&mut contents)?;
+                (contents, SystemTime::now())
+            }
+            _ => (fs::read(&path)?, fs::metadata(&path)?.modified()?),
+        };
+        let time = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let output = String::from_utf8_lossy(&contents)
+            .lines()
+            .map(|line| line.to_string())
+            .collect::<Vec<String>>();
+
+        let report = match backend.pkg_crash(&output, time)? {
+            Some(report) => report,
+            None => {
+                println!(
+                    "{} isn't a crash report, and has no thread dump in it",
+                    path.display()
+                );
+                return Ok(());
+            }
+        };
+
+        println!("{}: {}", path.display(), report.description);
+        let change = |event: &Event| {
+            format!(
+                "{} {} {} before the crash",
+                match event.action.as_str() {
+                    "install" => "installed",
+                    "update" => "updated",
+                    "downgrade" => "downgraded",
+                    "remove" => "removed",
+                    action => action,
+                },
+                match &event.from {
+                    Some(from) => format!("from {} to {}", from, event.version),
+                    None => format!("at {}", event.version),
+                },
+                span(report.time.saturating_sub(event.time))
+            )
+        };
+
+        if report.suspects.is_empty() {
+            println!("None of the installed packages are in the crash's stacks");
+        } else {
+            println!();
+            println!("Suspects:");
+        }
+        for suspect in report.suspects.iter() {
+            let name = match suspect.plugin == suspect.package {
+                true => suspect.package.clone(),
+                false => format!("{} (package {})", suspect.plugin, suspect.package),
+            };
+            match suspect.frames {
+                1 => println!("  {}, at {}", name, suspect.frame),
+                frames => println!("  {}, at {} and {} more", name, suspect.frame, frames - 1),
+            }
+            for event in suspect.changes.iter() {
+                println!("    {} was {}", suspect.plugin, change(event));
+            }
+        }
+        if !report.changes.is_empty() {
+            println!();
+        }
+        print_section(
+            "Also changed shortly before the crash:",
+            &report
+                .changes
+                .iter()
+                .map(|event| format!("{} was {}", event.package, change(event)))
+                .collect::<Vec<String>>(),
+        );
+        Ok(())
+    })
+}
+
+/// The file in a folder that was changed last, or `None` if there's nothing in it
+fn newest_file(dir: &Path) -> io::Result<Option<PathBuf>> {
+    if !dir.exists() {
+        return Ok(None);
+    }
+    let mut newest = None;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        if newest.as_ref().is_none_or(|(time, _)| modified > *time) {
+            newest = Some((modified, entry.path()));
+        }
+    }
+    Ok(newest.map(|(_, path)| path))
+}
+
+/// A number of seconds, in the largest unit that fits, like "2 hours"
+fn span(seconds: u64) -> String {
+    let (count, unit) = match seconds {
+        0..=59 => (seconds, "second"),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    match count {
+        1 => format!("1 {}", unit),
+        count => format!("{} {}s", count, unit),
+    }
+}
+
+fn auth(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    match matches.subcommand() {
+        ("login", Some(m)) => auth_login(m),
+        ("logout", Some(m)) => auth_logout(m),
+        _ => unreachable!(),
+    }
+}
+
+fn auth_login(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let source = matches.value_of("SOURCE").unwrap();
+    let (host, _) = auth::host_of(source)?;
+    let paths = project_paths(matches);
+
+    let token = read_secret(&format!("Paste the token for {}, then press Enter: ", host))?;
+    if token.is_empty() {
+        return Err(Box::new(auth::ErrorKind::TokenEmpty));
+    }
+    match auth::login(&paths.credentials_file(), source, &token)? {
+        Storage::Keychain => println!("The token for {} is in the keychain", host),
+        Storage::CredentialsFile(why) => println!(
+            "The token for {} is in {}, which only you can read, since {}",
+            host,
+            paths.credentials_file().display(),
+            why
+        ),
+    }
+    Ok(())
+}
+
+fn auth_logout(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let source = matches.value_of("SOURCE").unwrap();
+    let (host, _) = auth::host_of(source)?;
+    match auth::logout(&project_paths(matches).credentials_file(), source)? {
+        true => println!("Forgot the token for {}", host),
+        false => println!("There's no token for {}", host),
+    }
+    Ok(())
+}
+
+/// Reads a line from stdin without echoing it, if stdin is a terminal. The prompt goes to
+/// stderr, so that it isn't mixed into output that's piped somewhere. Being interrupted while
+/// waiting on it gives [`cancel::ErrorKind::Cancelled`](../cancel/enum.ErrorKind.html#variant.Cancelled).
+fn read_secret(prompt: &str) -> Result<String, Box<Error>> {
+    // Interrupts are caught while the token is typed, so that echo is turned back on
+    let cancel = CancelToken::on_interrupt();
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    // stty fails when stdin isn't a terminal, which is fine, since there's nothing to echo to
+    let stty = |setting| {
+        process::Command::new("stty")
+            .arg(setting)
+            .stdin(process::Stdio::inherit())
+            .stderr(process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    };
+    let hidden = stty("-echo");
+    let result = read_line();
+    if hidden {
+        stty("echo");
+        eprintln!();
+    }
+    match result {
+        Ok(_) if cancel.is_cancelled() => Err(Box::new(cancel::ErrorKind::Cancelled)),
+        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+            Err(Box::new(cancel::ErrorKind::Cancelled))
+        }
+        Ok(line) => Ok(line.trim().to_string()),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+fn cache(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    match matches.subcommand() {
+        ("prune-builds", Some(m)) => prune_builds(m),
+        ("stats", Some(m)) => cache_stats(m),
+        ("prune", Some(m)) => cache_prune(m),
+        _ => unreachable!(),
+    }
+}
+
+fn prune_builds(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let days = matches.value_of("older-than").unwrap().parse::<u64>()?;
+    let cache = match build::build_cache_dir() {
+        Some(cache) => cache,
+        None => {
+            println!("There's no build cache on this machine");
+            return Ok(());
+        }
+    };
+
+    let pruned = build::prune_builds(&cache, Duration::from_secs(days * 24 * 60 * 60))?;
+    let size = pruned.iter().map(|(_, size)| size).sum::<u64>();
+    match pruned.len() {
+        0 => println!("There are no builds older than {} days to remove", days),
+        n => println!(
+            "Removed {} cached build{}, freeing {:.1} MB",
+            n,
+            if n == 1 { "" } else { "s" },
+            size as f64 / (1024.0 * 1024.0)
+        ),
+    }
+    Ok(())
+}
+
+fn cache_stats(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let paths = project_paths(matches);
+    let mut rows = vec![vec![
+        "Cache".to_string(),
+        "Entries".to_string(),
+        "Size".to_string(),
+        "Oldest".to_string(),
+        "Folder".to_string(),
+    ]];
+    let mut total = 0;
+    for cache in cache::caches(&paths.http_cache_dir()) {
+        let stats = cache::stats(&cache)?;
+        total += stats.size;
+        let entries = match stats.in_use {
+            0 => stats.entries.to_string(),
+            in_use => format!("{} ({} in use)", stats.entries, in_use),
+        };
+        let oldest = stats.oldest.map(|oldest| {
+            oldest
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+        rows.push(vec![
+            cache.name.to_string(),
+            entries,
+            cache::format_size(stats.size),
+            match oldest {
+                Some(_) => ago(oldest),
+                None => "-".to_string(),
+            },
+            cache.dir.display().to_string(),
+        ]);
+    }
+    print_table(&rows);
+    println!();
+    println!("{} altogether", cache::format_size(total));
+    Ok(())
+}
+
+fn cache_prune(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let paths = project_paths(matches);
+    let max_size = matches.value_of("max-size").and_then(cache::parse_size);
+    let max_age = matches.value_of("older-than").and_then(cache::parse_age);
+
+    let pruned = cache::prune(&cache::caches(&paths.http_cache_dir()), max_size, max_age)?;
+    match pruned.entries {
+        0 => println!("There's nothing cached to remove"),
+        n => println!(
+            "Removed {} cached item{}, freeing {}",
+            n,
+            if n == 1 { "" } else { "s" },
+            cache::format_size(pruned.size)
+        ),
+    }
+    Ok(())
+}
+
+fn repo(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    match matches.subcommand() {
+        ("add-file", Some(m)) => repo_add_file(m),
+        ("index", Some(m)) => repo_index(m),
+        _ => unreachable!(),
+    }
+}
+
+fn repo_add_file(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let dir = Path::new(matches.value_of("dir").unwrap());
+    let channel = matches.value_of("channel").and_then(Channel::from_name);
+    let game_versions = matches
+        .values_of("game-version")
+        .map_or(Vec::new(), |versions| versions.map(|v| v.to_string()).collect());
+
+    for jar in matches.values_of("JAR").unwrap() {
+        let published = repo::add_file(
+            dir,
+            Path::new(jar),
+            matches.value_of("package"),
+            channel,
+            &game_versions,
+        )
+        .map_err(|e| format!("couldn't publish {}: {}", jar, e))?;
+        println!(
+            "{} {} @ version {} as {}",
+            match published.replaced {
+                true => "Replaced",
+                false => "Published",
+            },
+            published.package,
+            published.version,
+            published.path
+        );
+    }
+    Ok(())
+}
+
+fn repo_index(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let dir = Path::new(matches.value_of("dir").unwrap());
+    let reindexed = repo::reindex(dir)?;
+    for (package, version) in reindexed.added.iter() {
+        println!("Added {} @ version {}", package, version);
+    }
+    for (package, version) in reindexed.removed.iter() {
+        println!("Removed {} @ version {}, whose JAR is gone", package, version);
+    }
+    for (file, why) in reindexed.skipped.iter() {
+        eprintln!("warning: skipped {}: {}", file, why);
+    }
+    if reindexed.added.is_empty() && reindexed.removed.is_empty() {
+        println!("The index of {} is up to date", dir.display());
+    }
+    Ok(())
+}
+
+fn bundle(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    match matches.subcommand() {
+        ("create", Some(m)) => bundle_create(m),
+        ("install", Some(m)) => bundle_install(m),
+        _ => unreachable!(),
+    }
+}
+
+fn bundle_create(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let path = Path::new(matches.value_of("BUNDLE").unwrap());
+    with_backend(matches, |backend| {
+        let manifest = backend.pkg_bundle(path)?;
+        for (name, artifact) in manifest.artifacts.iter() {
+            println!("Bundled {} from {}", name, artifact.url);
+        }
+        println!(
+            "Wrote the bundle for {} to {}",
+            manifest.server_version,
+            path.display()
+        );
+        Ok(())
+    })
+}
+
+fn bundle_install(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let path = Path::new(matches.value_of("BUNDLE").unwrap());
+    with_backend(matches, |backend| {
+        let report = backend.pkg_install_bundle(path)?;
+        if report.is_empty() {
+            println!("The plugins folder already matches the bundle");
+        }
+        for file in report.installed.iter() {
+            println!("+ {}", file);
+        }
+        for file in report.replaced.iter() {
+            println!("~ {}", file);
+        }
+        for file in report.removed.iter() {
+            println!("- {}", file);
+        }
+        Ok(())
+    })
+}
+
+fn checksum(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    match matches.subcommand() {
+        ("write", Some(m)) => checksum_write(m),
+        ("check", Some(m)) => checksum_check(m),
+        _ => unreachable!(),
+    }
+}
+
+/// The SHA256SUMS file that `--file` names, or the one in the plugin directory
+fn checksums_file(matches: &ArgMatches) -> PathBuf {
+    matches
+        .value_of("file")
+        .map_or(project_paths(matches).checksums_file(), PathBuf::from)
+}
+
+fn checksum_write(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let path = checksums_file(matches);
+    with_backend(matches, |backend| {
+        let sums = backend.pkg_write_checksums(&path)?;
+        for (file, _) in sums.iter() {
+            println!("Listed {}", file);
+        }
+        println!("Wrote {}", path.display());
+        Ok(())
+    })
+}
+
+fn checksum_check(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let path = checksums_file(matches);
+    let mut clean = true;
+    with_backend(matches, |backend| {
+        let report = backend.pkg_check_checksums(&path)?;
+        for file in report.matched.iter() {
+            println!("{}: OK", file);
+        }
+        for file in report.failed.iter() {
+            println!("{}: FAILED", file);
+        }
+        for file in report.missing.iter() {
+            println!("{}: missing", file);
+        }
+        for file in report.unlisted.iter() {
+            println!("{}: not listed", file);
+        }
+        clean = report.is_clean();
+        Ok(())
+    })?;
+
+    if !clean {
+        return Err(Box::new(backend::ErrorKind::ChecksumsFailed(
+            path.display().to_string(),
+        )));
+    }
+    Ok(())
+}
+
+fn completions(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let shell = matches.value_of("SHELL").unwrap().parse::<Shell>()?;
+    let mut stdout = io::stdout();
+    app().gen_completions_to("dropper", shell, &mut stdout);
+
+    // clap only completes subcommands and flags, so bash is also set up to ask for package names
+    if let Shell::Bash = shell {
+        stdout.write_all(BASH_PACKAGE_COMPLETION.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Wraps clap's bash completion, so that the commands that take packages complete them with
+/// `dropper __complete`. bash splits words at `@` and `:`, so the suggestions have everything up
+/// to the last of those taken off again.
+const BASH_PACKAGE_COMPLETION: &'static str = r#"
+_dropper_packages() {
+    local word="${COMP_LINE:0:COMP_POINT}"
+    word="${word##* }"
+    case "${COMP_WORDS[1]}" in
+        add|install|update|remove|info)
+            if [[ "$word" != -* ]]; then
+                local IFS=$'\n'
+                local broken="${word%"${word##*[@:]}"}"
+                COMPREPLY=( $(dropper __complete pkg "$word" 2>/dev/null) )
+                COMPREPLY=( "${COMPREPLY[@]#"$broken"}" )
+                return 0
+            fi
+            ;;
+    esac
+    _dropper "$@"
+}
+
+complete -F _dropper_packages -o bashdefault -o default dropper
+"#;
+
+fn complete(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let prefix = matches.value_of("PREFIX").unwrap_or("");
+    let paths = project_paths(matches);
+
+    // Nothing that goes wrong is reported, since it would end up in the middle of the command
+    // line. It just means fewer suggestions.
+    let candidates = match prefix.find('@') {
+        Some(i) => version_candidates(&paths, &prefix[..i]),
+        None => package_candidates(&paths),
+    };
+    for candidate in complete::matching(candidates, prefix) {
+        println!("{}", candidate);
+    }
+    Ok(())
+}
+
+/// The packages in the pkg.yml and the lockfile, and the ones searches have turned up
+fn package_candidates(paths: &ProjectPaths) -> Vec<String> {
+    let mut names = complete::remembered(&paths.names_file());
+    if let Ok(lockfile) = Lockfile::load(&paths.lockfile()) {
+        names.extend(lockfile.packages.keys().cloned());
+    }
+
+    let server_version = match PackageBackend::configured_server_version(paths) {
+        Ok(v) => v,
+        Err(_) => return names,
+    };
+    let parser = BukkitHTMLPluginParser::builder()
+        .game_version(server_version)
+        .build()
+        .unwrap();
+    if let Ok(backend) = PackageBackend::new(&parser, paths.clone(), Client::new()) {
+        if let Ok(requirements) = backend.pkg_requirements() {
+            names.extend(requirements.into_iter().map(|r| r.package));
+        }
+    }
+    names
+}
+
+/// The versions of a package in its cached files page, and the version that's locked, as
+/// `name@version`
+fn version_candidates(paths: &ProjectPaths, name: &str) -> Vec<String> {
+    let mut versions = Vec::new();
+    if let Ok(lockfile) = Lockfile::load(&paths.lockfile()) {
+        if let Some(locked) = lockfile.packages.get(name) {
+            versions.push(locked.version.clone());
+        }
+    }
+
+    if let Ok(server_version) = PackageBackend::configured_server_version(paths) {
+        let parser = BukkitHTMLPluginParser::builder()
+            .game_version(server_version)
+            .cache_dir(paths.http_cache_dir())
+            .cache_only(true)
+            .build()
+            .unwrap();
+        if let Ok(Some(files)) = parser.enumerate_files(name) {
+            versions.extend(files.into_iter().map(|file| file.version));
+        }
+    }
+
+    versions
+        .into_iter()
+        .map(|version| format!("{}@{}", name, version))
+        .collect()
+}
+
+/// Keeps the names a search turned up, so that they can be completed later. Completion is only
+/// a nicety, so failing to keep them isn't an error.
+fn remember_names(paths: &ProjectPaths, names: Vec<String>) {
+    complete::remember(&paths.names_file(), &names).ok();
+}
+
+/// Prints rows of cells with each column padded to line up
+fn print_table(rows: &[Vec<String>]) {
+    let mut widths = Vec::new();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            if i >= widths.len() {
+                widths.push(0);
+            }
+            widths[i] = std::cmp::max(widths[i], cell.len());
+        }
+    }
+
+    for row in rows {
+        let cells = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<String>>();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+/// Writes a record of `--porcelain` output. Tabs and line breaks in fields are turned into
+/// spaces so that they can't split the record, and empty fields are written as `-`.
+fn record(fields: &[&str]) -> String {
+    fields
+        .iter()
+        .map(|field| match field.is_empty() {
+            true => "-".to_string(),
+            false => field.replace(['\t', '\n', '\r'], " "),
+        })
+        .collect::<Vec<String>>()
+        .join("\t")
+}
+
+/// The `--porcelain` records of `list`: name, version, why it was installed, and whether it's
+/// orphaned
+fn installed_records(packages: &[InstalledListing]) -> Vec<String> {
+    packages
+        .iter()
+        .map(|package| {
+            record(&[
+                &package.name,
+                &package.version,
+                package.reason.as_str(),
+                &package.orphaned.to_string(),
+            ])
+        })
+        .collect()
+}
+
+/// The `--porcelain` records of `outdated`: name, installed version, newest allowed version,
+/// whether it's outdated, and why the installed version is blocked
+fn age_records(ages: &[PackageAge]) -> Vec<String> {
+    ages.iter()
+        .map(|age| {
+            record(&[
+                &age.name,
+                &age.installed,
+                age.newest.as_ref().map_or("-", |newest| newest),
+                &age.is_outdated().to_string(),
+                age.blocked.as_ref().map_or("-", |reason| reason),
+            ])
+        })
+        .collect()
+}
+
+/// The `--porcelain` records of `unmanaged`: an `unmanaged` record with each JAR's file and its
+/// plugin's name and version, followed by a `guess` record with the file, source, name and link
+/// of each project it might be
+fn unmanaged_records(jars: &[UnmanagedJar]) -> Vec<String> {
+    let mut records = Vec::new();
+    for jar in jars.iter() {
+        records.push(match &jar.description {
+            Some(description) => record(&[
+                "unmanaged",
+                &jar.file,
+                &description.name,
+                &description.version,
+            ]),
+            None => record(&["unmanaged", &jar.file, "", ""]),
+        });
+        for (source, listing) in jar.guesses.iter() {
+            records.push(record(&[
+                "guess",
+                &jar.file,
+                source,
+                &listing.name,
+                &listing.link,
+            ]));
+        }
+    }
+    records
+}
+
+/// The `--porcelain` records of `versions`: version, channel, server versions, when it was
+/// uploaded, and whether it's compatible, allowed, installed, and why it's blocked
+fn version_records(listings: &[VersionListing]) -> Vec<String> {
+    listings
+        .iter()
+        .map(|listing| {
+            let uploaded = listing.file.uploaded.map(|time| time.to_string());
+            record(&[
+                &listing.file.version,
+                listing.file.channel.name(),
+                &listing.file.game_versions.join(","),
+                uploaded.as_ref().map_or("-", |time| time),
+                &listing.compatible.to_string(),
+                &listing.allowed.to_string(),
+                &listing.installed.to_string(),
+                listing.blocked.as_ref().map_or("-", |reason| reason),
+            ])
+        })
+        .collect()
+}
+
+/// The `--porcelain` records of `status`, each starting with what kind of problem it is
+fn status_records(report: &StatusReport) -> Vec<String> {
+    let mut records = Vec::new();
+    for name in report.not_installed.iter() {
+        records.push(record(&["not-installed", name]));
+    }
+    for (name, file) in report.missing.iter() {
+        records.push(record(&["missing", name, file]));
+    }
+    for (name, file) in report.manual_missing.iter() {
+        records.push(record(&["manual-missing", name, file]));
+    }
+    for (name, why) in report.mismatched.iter() {
+        records.push(record(&["mismatched", name, why]));
+    }
+    for file in report.unmanaged.iter() {
+        records.push(record(&["unmanaged", file]));
+    }
+    for file in report.staged.iter() {
+        records.push(record(&["staged", file]));
+    }
+    records
+}
+
+/// The `--porcelain` records of `status --configs`: whether the config was modified or deleted,
+/// its package, and its file
+fn config_records(report: &ConfigReport) -> Vec<String> {
+    let mut records = Vec::new();
+    for (name, file) in report.modified.iter() {
+        records.push(record(&["modified", name, file]));
+    }
+    for (name, file) in report.deleted.iter() {
+        records.push(record(&["deleted", name, file]));
+    }
+    records
+}
+
+/// Prints the outcome of installing a single package. Used for packages that are installed as
+/// part of a bigger operation, where one failure shouldn't stop the rest.
+fn report_install(
+    backend: &PackageBackend,
+    pkg: &str,
+    result: Result<Option<(String, String)>, Box<Error>>,
+) {
+    match result {
+        Ok(Some((name, version))) => println!("Package {} installed @ version {}!", name, version),
+        Ok(None) => report_not_found(backend, pkg),
+        Err(e) => println!("Error while trying to add {}: {}", pkg, e),
+    }
+}
+
+/// Says that a package couldn't be found, and which one might have been meant
+fn report_not_found(backend: &PackageBackend, pkg: &str) {
+    let client = backend.client.clone();
+    let suggestion = bukkit_parser(&backend.paths, backend.server_version.clone(), client)
+        .ok()
+        .and_then(|parser| backend.pkg_suggest(pkg, &parser));
+    match suggestion {
+        Some(suggestion) => println!("Could not find {} (did you mean {}?)", pkg, suggestion),
+        None => println!("Could not find {}", pkg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jar::PluginDescription;
+    use crate::parser::PluginFile;
+
+    fn fields(record: &str) -> Vec<&str> {
+        record.split('\t').collect()
+    }
+
+    #[test]
+    fn records_cant_be_split_by_their_fields() {
+        let record = record(&["two\twords", "two\nlines", "windows\r\nline"]);
+        assert_eq!(record, "two words\ttwo lines\twindows  line");
+        assert_eq!(fields(&record).len(), 3);
+        assert!(!record.contains('\n'));
+    }
+
+    #[test]
+    fn empty_fields_are_written_as_dashes() {
+        assert_eq!(record(&["", "vault", ""]), "-\tvault\t-");
+    }
+
+    #[test]
+    fn list_records_are_name_version_reason_orphaned() {
+        let packages = [InstalledListing {
+            name: "vault".to_string(),
+            version: "1.7.3".to_string(),
+            reason: InstallReason::Dependency,
+            orphaned: true,
+        }];
+        assert_eq!(
+            installed_records(&packages),
+            ["vault\t1.7.3\tdependency\ttrue"]
+        );
+    }
+
+    #[test]
+    fn outdated_records_are_name_installed_newest_outdated_blocked() {
+        let age = |newest: Option<&str>, blocked: Option<&str>| PackageAge {
+            name: "worldedit".to_string(),
+            installed: "7.2.0".to_string(),
+            installed_at: Some(1),
+            installed_released: Some(2),
+            newest: newest.map(|newest| newest.to_string()),
+            newest_released: Some(3),
+            blocked: blocked.map(|reason| reason.to_string()),
+        };
+        let ages = [
+            age(Some("7.3.0"), None),
+            age(None, Some("corrupts\tworlds")),
+        ];
+        assert_eq!(
+            age_records(&ages),
+            [
+                "worldedit\t7.2.0\t7.3.0\ttrue\t-",
+                "worldedit\t7.2.0\t-\tfalse\tcorrupts worlds",
+            ]
+        );
+    }
+
+    #[test]
+    fn versions_records_are_version_channel_servers_uploaded_and_flags() {
+        let file = |version: &str, uploaded: Option<u64>, game_versions: &[&str]| PluginFile {
+            uploaded: uploaded,
+            channel: Channel::Beta,
+            game_versions: game_versions.iter().map(|v| v.to_string()).collect(),
+            ..PluginFile::new(version.to_string(), "https://example.com".to_string())
+        };
+        let listings = [
+            VersionListing {
+                file: file("2.0-beta", Some(1600000000), &["1.16", "1.17"]),
+                compatible: true,
+                allowed: false,
+                blocked: None,
+                installed: false,
+            },
+            VersionListing {
+                file: file("1.9", None, &[]),
+                compatible: false,
+                allowed: true,
+                blocked: Some("leaks memory".to_string()),
+                installed: true,
+            },
+        ];
+        assert_eq!(
+            version_records(&listings),
+            [
+                "2.0-beta\tbeta\t1.16,1.17\t1600000000\ttrue\tfalse\tfalse\t-",
+                "1.9\tbeta\t-\t-\tfalse\ttrue\ttrue\tleaks memory",
+            ]
+        );
+    }
+
+    #[test]
+    fn unmanaged_records_are_followed_by_their_guesses() {
+        let description = PluginDescription {
+            name: "Vault".to_string(),
+            version: "1.7.3".to_string(),
+            main: None,
+            description: None,
+            authors: Vec::new(),
+            depend: Vec::new(),
+            softdepend: Vec::new(),
+            api_version: None,
+            commands: Vec::new(),
+            permissions: Vec::new(),
+        };
+        let listing = PluginListing {
+            name: "vault".to_string(),
+            title: "Vault".to_string(),
+            link: "https://example.com/vault".to_string(),
+            downloads: None,
+            description: None,
+            keywords: Vec::new(),
+        };
+        let jars = [
+            UnmanagedJar {
+                file: "Vault.jar".to_string(),
+                description: Some(description),
+                guesses: vec![("bukkit".to_string(), listing)],
+            },
+            UnmanagedJar {
+                file: "Broken.jar".to_string(),
+                description: None,
+                guesses: Vec::new(),
+            },
+        ];
+        assert_eq!(
+            unmanaged_records(&jars),
+            [
+                "unmanaged\tVault.jar\tVault\t1.7.3",
+                "guess\tVault.jar\tbukkit\tvault\thttps://example.com/vault",
+                "unmanaged\tBroken.jar\t-\t-",
+            ]
+        );
+    }
+
+    #[test]
+    fn status_records_start_with_their_kind() {
+        let pair = |name: &str, other: &str| (name.to_string(), other.to_string());
+        let report = StatusReport {
+            not_installed: vec!["essentials".to_string()],
+            missing: vec![pair("vault", "Vault.jar")],
+            manual_missing: vec![pair("citizens", "Citizens.jar")],
+            unmanaged: vec!["Stray.jar".to_string()],
+            mismatched: vec![pair("worldedit", "locked at 7.2.0")],
+            staged: vec!["WorldGuard.jar".to_string()],
+        };
+        assert_eq!(
+            status_records(&report),
+            [
+                "not-installed\tessentials",
+                "missing\tvault\tVault.jar",
+                "manual-missing\tcitizens\tCitizens.jar",
+                "mismatched\tworldedit\tlocked at 7.2.0",
+                "unmanaged\tStray.jar",
+                "staged\tWorldGuard.jar",
+            ]
+        );
+    }
+
+    #[test]
+    fn config_records_are_change_package_file() {
+        let report = ConfigReport {
+            modified: vec![("vault".to_string(), "Vault/config.yml".to_string())],
+            deleted: vec![("essentials".to_string(), "Essentials/kits.yml".to_string())],
+        };
+        assert_eq!(
+            config_records(&report),
+            [
+                "modified\tvault\tVault/config.yml",
+                "deleted\tessentials\tEssentials/kits.yml",
+            ]
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! This module backs shell completion of package names and versions. Completing has to be quick
+//! and can't go online, so it only suggests what dropper already knows about: the packages in
+//! the pkg.yml and the lockfile, the names that searches have turned up, and the versions on
+//! projects' files pages that are in the HTTP cache.
+//!
+//! Shells ask for suggestions with `dropper __complete pkg <prefix>`, which prints one per line.
+//! A prefix with an `@` in it, like `worldedit@6.`, is completed to versions of that package.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+/// How many names from searches are remembered. The oldest ones are forgotten first.
+const MAX_REMEMBERED: usize = 1000;
+
+/// The names that searches have turned up, oldest first. A file that can't be read is treated as
+/// empty, since completion shouldn't ever fail.
+pub fn remembered(path: &Path) -> Vec<String> {
+    let docs = fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| YamlLoader::load_from_str(&contents).ok());
+    match docs.as_ref().and_then(|docs| docs.first()) {
+        Some(Yaml::Array(names)) => names
+            .iter()
+            .filter_map(|name| name.as_str().map(|name| name.to_string()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Adds names that a search turned up to the remembered ones
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn remember(path: &Path, names: &[String]) -> io::Result<()> {
+    let mut remembered = remembered(path)
+        .into_iter()
+        .filter(|name| !names.contains(name))
+        .collect::<Vec<String>>();
+    remembered.extend(names.iter().cloned());
+    if remembered.len() > MAX_REMEMBERED {
+        remembered.drain(..remembered.len() - MAX_REMEMBERED);
+    }
+
+    let yaml = Yaml::Array(remembered.into_iter().map(Yaml::String).collect());
+    let mut tmp_string = String::new();
+    YamlEmitter::new(&mut tmp_string).dump(&yaml).unwrap();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format!("{}\n", tmp_string))
+}
+
+/// The candidates that start with `prefix`, sorted, without duplicates. Package names are
+/// matched regardless of case.
+pub fn matching<I>(candidates: I, prefix: &str) -> Vec<String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let prefix = prefix.to_lowercase();
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.to_lowercase().starts_with(&prefix))
+        .collect::<BTreeSet<String>>()
+        .into_iter()
+        .collect()
+}
@@ -0,0 +1,93 @@
+//! This module installs packages straight from a URL, for files that aren't published anywhere
+//! dropper can look up versions, like scripts shared on a forum. There's nothing to resolve, so
+//! the pkg.yml entry has to pin its version, which takes the place of `{version}` in the URL. The
+//! entry can give the file's digests too, keyed by algorithm like in the lockfile, and the
+//! download is checked against them:
+//!
+//! ```yaml
+//! my-script:
+//!   url: "https://example.com/scripts/my-script-{version}.sk"
+//!   version: "1.2"
+//!   sha256: 5d1b4cbd2b3ab8e7a3f2fd4e3c7d7a0ad3f1e4b3a8d2c1c7e6de9f2b1a0c3e4f
+//! ```
+
+use crate::hash;
+use crate::resolver::Constraint;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use yaml_rust::Yaml;
+
+// Where the version goes in the URL
+const VERSION_PLACEHOLDER: &'static str = "{version}";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A pkg.yml entry's `url` isn't a string, or the entry doesn't pin an exact version. Takes
+    // the package name as a param.
+    SourceInvalid(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::SourceInvalid(s) => format!(
+                    "'{}' is installed from a url, so it needs an exact version, like \"1.2\"",
+                    s
+                ),
+            }
+        )
+    }
+}
+
+/// A file to install a package from
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectSource {
+    /// The URL, with the version in place of `{version}`
+    pub url: String,
+    pub version: String,
+    /// The digests the pkg.yml gives for the file, keyed by algorithm
+    pub hashes: BTreeMap<String, String>,
+}
+
+impl DirectSource {
+    /// Reads the URL out of a pkg.yml entry, or `None` if the entry doesn't have one
+    ///
+    /// # Errors
+    /// * [`ErrorKind::SourceInvalid`](enum.ErrorKind.html#variant.SourceInvalid) - the entry has a `url` key, but not a usable source
+    pub fn from_yaml(name: &str, entry: &Yaml) -> Result<Option<DirectSource>, ErrorKind> {
+        let invalid = || ErrorKind::SourceInvalid(name.to_string());
+        let url = match &entry["url"] {
+            Yaml::BadValue => return Ok(None),
+            Yaml::String(url) => url,
+            _ => return Err(invalid()),
+        };
+        let version = match &entry["version"] {
+            Yaml::String(version) | Yaml::Real(version) => version.clone(),
+            Yaml::Integer(version) => version.to_string(),
+            _ => return Err(invalid()),
+        };
+        match Constraint::parse(&version) {
+            Constraint::Exact(_) => {}
+            _ => return Err(invalid()),
+        }
+
+        Ok(Some(DirectSource {
+            url: url.replace(VERSION_PLACEHOLDER, &version),
+            version: version,
+            hashes: hash::ALGORITHMS
+                .iter()
+                .filter_map(|algorithm| {
+                    entry[*algorithm]
+                        .as_str()
+                        .map(|digest| (algorithm.to_string(), digest.to_lowercase()))
+                })
+                .collect(),
+        }))
+    }
+}
@@ -0,0 +1,105 @@
+//! This module keeps what packages' sources say about them in `.dropper/docs/<package>/`, so that
+//! `dropper info` can describe installed packages without going online, and so that whoever
+//! takes over a server can be handed a write-up of everything on it. Saving docs is turned on
+//! with `save_docs: true` in the config, and they are saved whenever a package is installed or
+//! updated.
+//!
+//! Each package gets a `docs.yml`, which dropper reads back, and a `README.md` with the same
+//! text for people to read:
+//!
+//! ```yaml
+//! name: WorldEdit
+//! version: 6.1.9
+//! authors:
+//!   - sk89q
+//! description: In-game map editor
+//! ```
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+const DOCS_FILE: &'static str = "docs.yml";
+const README_FILE: &'static str = "README.md";
+
+/// What a package's source said about a version of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Docs {
+    /// The name the source shows for the package
+    pub name: String,
+    /// The version that was installed when the docs were saved
+    pub version: String,
+    pub authors: Vec<String>,
+    pub description: Option<String>,
+}
+
+impl Docs {
+    /// Reads the docs saved in a package's docs folder, or `None` if there aren't any or they
+    /// can't be read. Docs are only ever nice to have.
+    pub fn load(dir: &Path) -> Option<Docs> {
+        let contents = fs::read_to_string(dir.join(DOCS_FILE)).ok()?;
+        let docs = YamlLoader::load_from_str(&contents).ok()?;
+        let doc = docs.first()?;
+
+        Some(Docs {
+            name: doc["name"].as_str()?.to_string(),
+            version: doc["version"].as_str()?.to_string(),
+            authors: doc["authors"]
+                .as_vec()
+                .map(|authors| {
+                    authors
+                        .iter()
+                        .filter_map(|author| author.as_str().map(|s| s.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            description: doc["description"].as_str().map(|s| s.to_string()),
+        })
+    }
+
+    /// Saves the docs into a package's docs folder, replacing any that were there
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn save(&self, dir: &Path) -> Result<(), Box<Error>> {
+        let mut hash = Hash::new();
+        hash.insert(Yaml::from_str("name"), Yaml::String(self.name.clone()));
+        hash.insert(
+            Yaml::from_str("version"),
+            Yaml::String(self.version.clone()),
+        );
+        hash.insert(
+            Yaml::from_str("authors"),
+            Yaml::Array(self.authors.iter().cloned().map(Yaml::String).collect()),
+        );
+        if let Some(description) = &self.description {
+            hash.insert(
+                Yaml::from_str("description"),
+                Yaml::String(description.clone()),
+            );
+        }
+
+        let mut tmp_string = String::new();
+        YamlEmitter::new(&mut tmp_string)
+            .dump(&Yaml::Hash(hash))
+            .unwrap();
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(DOCS_FILE), format!("{}\n", tmp_string))?;
+        fs::write(dir.join(README_FILE), self.to_markdown(2))?;
+        Ok(())
+    }
+
+    /// The docs as Markdown, with the package's name as a heading of the given level
+    pub fn to_markdown(&self, level: usize) -> String {
+        let mut markdown = format!("{} {} {}\n\n", "#".repeat(level), self.name, self.version);
+        if !self.authors.is_empty() {
+            markdown.push_str(&format!("By {}\n\n", self.authors.join(", ")));
+        }
+        if let Some(description) = &self.description {
+            markdown.push_str(&format!("{}\n\n", description));
+        }
+        markdown
+    }
+}
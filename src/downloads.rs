@@ -0,0 +1,165 @@
+//! This module fetches packages from download APIs laid out like GeyserMC's, which serve every
+//! build of each version of a project, with a download for each platform the build runs on. A
+//! pkg.yml entry with `downloads` gives the project's URL in the API, and the `download` to
+//! install, which is the Spigot one if it doesn't say:
+//!
+//! ```yaml
+//! geyser:
+//!   downloads: https://download.geysermc.org/v2/projects/geyser
+//!   download: spigot
+//! ```
+//!
+//! Every build is a version, numbered after the version it's a build of, like `2.2.0-build.436`,
+//! so `2.2.*` takes the newest build of any 2.2 version. Only the builds of the project's newest
+//! versions are listed. Builds in the `experimental` channel are dev builds, and the others are
+//! releases. The API lists the SHA-256 of each download, which downloads are checked against.
+
+use crate::http;
+use crate::http::Credentials;
+use crate::parser;
+use crate::parser::{Channel, PluginFile};
+use reqwest::Client;
+use std::error::Error;
+use std::fmt;
+use url::Url;
+use yaml_rust::Yaml;
+
+const DEFAULT_DOWNLOAD: &'static str = "spigot";
+// How many of the project's newest versions to list the builds of
+const MAX_VERSIONS: usize = 10;
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A pkg.yml entry's `downloads` isn't a URL, or its `download` isn't a string. Takes the
+    // package name as a param.
+    SourceInvalid(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::SourceInvalid(s) => format!(
+                    "the downloads of '{}' should be the URL of a project in a download API, and its download a name like spigot",
+                    s
+                ),
+            }
+        )
+    }
+}
+
+/// A project in a download API to install a package from
+#[derive(Debug, Clone)]
+pub struct DownloadsSource {
+    /// The project's URL in the API, like `https://download.geysermc.org/v2/projects/geyser`
+    pub project_url: String,
+    /// Which of each build's downloads to install, like `spigot` or `velocity`
+    pub download: String,
+}
+
+impl DownloadsSource {
+    /// Reads the download API project out of a pkg.yml entry, or `None` if the entry doesn't
+    /// have one
+    ///
+    /// # Errors
+    /// * [`ErrorKind::SourceInvalid`](enum.ErrorKind.html#variant.SourceInvalid) - the entry has a `downloads` key, but not a usable source
+    pub fn from_yaml(name: &str, entry: &Yaml) -> Result<Option<DownloadsSource>, ErrorKind> {
+        let invalid = || ErrorKind::SourceInvalid(name.to_string());
+        let project_url = match &entry["downloads"] {
+            Yaml::BadValue => return Ok(None),
+            Yaml::String(url) if Url::parse(url).is_ok() => url.trim_end_matches('/').to_string(),
+            _ => return Err(invalid()),
+        };
+        let download = match &entry["download"] {
+            Yaml::BadValue => DEFAULT_DOWNLOAD.to_string(),
+            Yaml::String(download) if !download.is_empty() => download.to_lowercase(),
+            _ => return Err(invalid()),
+        };
+
+        Ok(Some(DownloadsSource {
+            project_url: project_url,
+            download: download,
+        }))
+    }
+
+    /// Lists the builds of the project's newest versions that have the download, newest first,
+    /// or `None` if there's no such project
+    ///
+    /// # Errors
+    /// * [`http::ErrorKind::ApiFailed`](../http/enum.ErrorKind.html#variant.ApiFailed) - the API couldn't be used
+    /// * `reqwest::Error` - the request couldn't be made
+    pub fn enumerate_files(
+        &self,
+        client: &Client,
+        credentials: &Credentials,
+    ) -> Result<Option<Vec<PluginFile>>, Box<Error>> {
+        let unexpected = |url: &str| {
+            Box::new(http::ErrorKind::ApiFailed(
+                url.to_string(),
+                "something that isn't a list of builds".to_string(),
+            ))
+        };
+        let project = match http::get_json(client, credentials, &self.project_url)? {
+            Some(project) => project,
+            None => return Ok(None),
+        };
+        // The API lists versions oldest first
+        let versions = match &project["versions"] {
+            Yaml::Array(versions) => versions
+                .iter()
+                .rev()
+                .filter_map(|version| version.as_str())
+                .take(MAX_VERSIONS)
+                .collect::<Vec<&str>>(),
+            _ => return Err(unexpected(&self.project_url)),
+        };
+
+        let mut files = Vec::new();
+        for version in versions {
+            let url = format!("{}/versions/{}/builds", self.project_url, version);
+            let builds = match http::get_json(client, credentials, &url)? {
+                Some(listed) => match &listed["builds"] {
+                    Yaml::Array(builds) => builds.clone(),
+                    _ => return Err(unexpected(&url)),
+                },
+                None => continue,
+            };
+            // and builds oldest first too
+            for build in builds.iter().rev() {
+                if let Some(file) = self.file_of(version, build) {
+                    files.push(file);
+                }
+            }
+        }
+        Ok(Some(files))
+    }
+
+    /// The file of one of a version's builds, if the build has the download
+    fn file_of(&self, version: &str, build: &Yaml) -> Option<PluginFile> {
+        let number = build["build"].as_i64()?;
+        let download = &build["downloads"][self.download.as_str()];
+        if download.is_badvalue() {
+            return None;
+        }
+        let link = format!(
+            "{}/versions/{}/builds/{}/downloads/{}",
+            self.project_url, version, number, self.download
+        );
+        let mut file = PluginFile::new(format!("{}-build.{}", version, number), link);
+
+        file.channel = match build["channel"].as_str() {
+            Some("experimental") => Channel::Dev,
+            _ => Channel::Release,
+        };
+        file.uploaded = build["time"].as_str().and_then(parser::parse_timestamp);
+        if let Some(sha256) = download["sha256"].as_str() {
+            file.hashes
+                .insert("sha256".to_string(), sha256.to_lowercase());
+        }
+        Some(file)
+    }
+}
@@ -0,0 +1,215 @@
+//! This module records the responses to the HTTP requests dropper makes, and plays them back, so
+//! that the parsers and the backend can be tested against real websites without going online.
+//! It's for developers, so it's only built with the `fixtures` feature, and it's turned on with
+//! the `DROPPER_FIXTURES` environment variable, which says whether to record or replay, and
+//! where the fixtures are kept:
+//!
+//! ```sh
+//! DROPPER_FIXTURES=record:fixtures/worldedit dropper add worldedit
+//! DROPPER_FIXTURES=replay:fixtures/worldedit dropper add worldedit
+//! ```
+//!
+//! Each response is kept as a `<key>.yml`, with its status, its headers and the URL it ended up
+//! at after redirects, next to a `<key>.body`, where the key is the MD5 of the request's method
+//! and URL. A request that's made more than once keeps the last response it got. Replaying a
+//! request that was never recorded is an error, rather than going online.
+//!
+//! The parser's tests replay the pages in `fixtures/bukkit`, so they're run with
+//! `cargo test --features fixtures`.
+
+use reqwest::{RequestBuilder, Response};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+const FIXTURES_VAR: &'static str = "DROPPER_FIXTURES";
+/// The header replayed responses carry the URL they ended up at in, since a response can't be
+/// made with a URL of its own
+pub const URL_HEADER: &'static str = "x-dropper-fixture-url";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // `DROPPER_FIXTURES` isn't `record:<dir>` or `replay:<dir>`. Takes its value as a param.
+    ModeInvalid(String),
+    // A request being replayed was never recorded. Takes the method and the URL as params.
+    FixtureMissing(String, String),
+    // A fixture couldn't be understood. Takes its path as a param.
+    FixtureInvalid(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::ModeInvalid(s) => format!(
+                    "{} should be record:<dir> or replay:<dir>, not '{}'",
+                    FIXTURES_VAR, s
+                ),
+                ErrorKind::FixtureMissing(method, url) => {
+                    format!("there's no recorded response to {} {}", method, url)
+                }
+                ErrorKind::FixtureInvalid(s) => format!("the fixture {} is corrupt", s),
+            }
+        )
+    }
+}
+
+/// What `DROPPER_FIXTURES` asks for
+enum Mode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// A response, as it's kept in a fixture
+struct Recorded {
+    status: u16,
+    headers: Vec<(String, String)>,
+    url: String,
+    body: Vec<u8>,
+}
+
+/// Sends a request, recording its response or playing it back if `DROPPER_FIXTURES` says to.
+/// Requests whose bodies can't be read ahead of sending them are always sent.
+///
+/// # Errors
+/// * [`ErrorKind::ModeInvalid`](enum.ErrorKind.html#variant.ModeInvalid) - `DROPPER_FIXTURES` is set to something else
+/// * [`ErrorKind::FixtureMissing`](enum.ErrorKind.html#variant.FixtureMissing) - the request was never recorded
+/// * [`ErrorKind::FixtureInvalid`](enum.ErrorKind.html#variant.FixtureInvalid) - the request's fixture is corrupt
+/// * `reqwest::Error` - the request couldn't be made
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn send(request: RequestBuilder) -> Result<Response, Box<Error>> {
+    let mode = match mode()? {
+        Some(mode) => mode,
+        None => return Ok(request.send()?),
+    };
+    let built = match request.try_clone() {
+        Some(clone) => clone.build()?,
+        None => return Ok(request.send()?),
+    };
+    let method = built.method().to_string();
+    let url = built.url().to_string();
+    let key = format!("{:x}", md5::compute(format!("{} {}", method, url)));
+
+    let recorded = match mode {
+        Mode::Record(dir) => {
+            let mut response = request.send()?;
+            let mut body = Vec::new();
+            response.copy_to(&mut body)?;
+            let recorded = Recorded {
+                status: response.status().as_u16(),
+                headers: response
+                    .headers()
+                    .iter()
+                    .filter_map(|(name, value)| {
+                        value
+                            .to_str()
+                            .ok()
+                            .map(|value| (name.to_string(), value.to_string()))
+                    })
+                    .collect(),
+                url: response.url().to_string(),
+                body: body,
+            };
+            save(&dir, &key, &recorded)?;
+            recorded
+        }
+        Mode::Replay(dir) => match load(&dir, &key)? {
+            Some(recorded) => recorded,
+            None => return Err(Box::new(ErrorKind::FixtureMissing(method, url))),
+        },
+    };
+
+    // Recorded responses are played back too, so that recording behaves like replaying
+    let mut response = ::http::Response::builder();
+    response.status(recorded.status);
+    for (name, value) in recorded.headers.iter() {
+        response.header(name.as_str(), value.as_str());
+    }
+    response.header(URL_HEADER, recorded.url.as_str());
+    Ok(Response::from(response.body(recorded.body)?))
+}
+
+/// Reads `DROPPER_FIXTURES`, or `None` if it isn't set
+fn mode() -> Result<Option<Mode>, ErrorKind> {
+    let value = match env::var(FIXTURES_VAR) {
+        Ok(value) => value,
+        Err(_) => return Ok(None),
+    };
+    let mut parts = value.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some("record"), Some(dir)) if !dir.is_empty() => Ok(Some(Mode::Record(dir.into()))),
+        (Some("replay"), Some(dir)) if !dir.is_empty() => Ok(Some(Mode::Replay(dir.into()))),
+        _ => Err(ErrorKind::ModeInvalid(value.clone())),
+    }
+}
+
+/// Writes a response into the fixtures directory
+fn save(dir: &Path, key: &str, recorded: &Recorded) -> Result<(), Box<Error>> {
+    let mut headers = Hash::new();
+    for (name, value) in recorded.headers.iter() {
+        let values = headers
+            .entry(Yaml::String(name.clone()))
+            .or_insert_with(|| Yaml::Array(Vec::new()));
+        if let Yaml::Array(values) = values {
+            values.push(Yaml::String(value.clone()));
+        }
+    }
+    let mut doc = Hash::new();
+    doc.insert(
+        Yaml::from_str("status"),
+        Yaml::Integer(recorded.status as i64),
+    );
+    doc.insert(Yaml::from_str("url"), Yaml::String(recorded.url.clone()));
+    doc.insert(Yaml::from_str("headers"), Yaml::Hash(headers));
+
+    let mut tmp_string = String::new();
+    YamlEmitter::new(&mut tmp_string)
+        .dump(&Yaml::Hash(doc))
+        .unwrap();
+    fs::create_dir_all(dir)?;
+    fs::write(
+        dir.join(format!("{}.yml", key)),
+        format!("{}\n", tmp_string),
+    )?;
+    fs::write(dir.join(format!("{}.body", key)), &recorded.body)?;
+    Ok(())
+}
+
+/// Reads a response out of the fixtures directory, or `None` if it was never recorded
+fn load(dir: &Path, key: &str) -> Result<Option<Recorded>, Box<Error>> {
+    let path = dir.join(format!("{}.yml", key));
+    if !path.exists() {
+        return Ok(None);
+    }
+    let invalid = || ErrorKind::FixtureInvalid(path.display().to_string());
+    let docs = YamlLoader::load_from_str(&fs::read_to_string(&path)?).map_err(|_| invalid())?;
+    let doc = docs.first().ok_or_else(invalid)?;
+
+    let mut headers = Vec::new();
+    if let Yaml::Hash(entries) = &doc["headers"] {
+        for (name, values) in entries.iter() {
+            let name = name.as_str().ok_or_else(invalid)?;
+            for value in values.as_vec().ok_or_else(invalid)? {
+                headers.push((
+                    name.to_string(),
+                    value.as_str().ok_or_else(invalid)?.to_string(),
+                ));
+            }
+        }
+    }
+
+    Ok(Some(Recorded {
+        status: doc["status"].as_i64().ok_or_else(invalid)? as u16,
+        headers: headers,
+        url: doc["url"].as_str().ok_or_else(invalid)?.to_string(),
+        body: fs::read(dir.join(format!("{}.body", key)))?,
+    }))
+}
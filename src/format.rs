@@ -0,0 +1,328 @@
+//! This module versions the formats of the files dropper writes for itself, like the lockfile and
+//! the state file, so that a newer dropper can still read what an older one wrote. Each file has
+//! a `format` key with the version of the format it was written in:
+//!
+//! ```yaml
+//! format: 1
+//! packages: {}
+//! ```
+//!
+//! Files are migrated up to the newest format as they are read, one version at a time, and are
+//! saved in it. Files from before formats were versioned don't have a `format`, and are format 0.
+//! Files written by a newer dropper are refused rather than misread. Files can also be upgraded
+//! on disk, in which case the file as it was is kept next to it, named after its old format, like
+//! `state.yml.format-0`. `dropper doctor` reports the files that aren't in the newest format.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlLoader};
+
+const FORMAT_KEY: &'static str = "format";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A file is in a newer format than this version of dropper knows. Takes the file, its format
+    // version, and the newest version dropper knows as params.
+    FormatTooNew(String, i64, i64),
+    // A file's format version isn't a version. Takes the file as a param.
+    FormatInvalid(String),
+    // Some of dropper's files aren't in the newest format, and weren't upgraded, or can't be
+    FormatsUnhealthy,
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::FormatTooNew(file, version, newest) => format!(
+                    "{} is in format {}, but this version of dropper only knows up to format {}; \
+                     upgrade dropper to use it",
+                    file, version, newest
+                ),
+                ErrorKind::FormatInvalid(s) => format!("{} has an invalid format version", s),
+                ErrorKind::FormatsUnhealthy => {
+                    "some of dropper's files aren't in the newest format".to_string()
+                }
+            }
+        )
+    }
+}
+
+/// A step that upgrades a file from one format version to the next, given its top level
+pub type Migration = fn(&mut Hash);
+
+/// How the format of a file on disk compares to the newest one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormatStatus {
+    /// There's no file
+    Missing,
+    /// The file is in the newest format
+    Current,
+    /// The file is in an older format, and is migrated as it's read. Takes its version as a
+    /// param.
+    Outdated(i64),
+    /// The file was written by a newer dropper. Takes its version as a param.
+    TooNew(i64),
+    /// The file isn't YAML, or its format version isn't a version
+    Invalid,
+}
+
+/// Checks the format of a file on disk
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn file_status(path: &Path, migrations: &[Migration]) -> io::Result<FormatStatus> {
+    if !path.exists() {
+        return Ok(FormatStatus::Missing);
+    }
+    let doc = match YamlLoader::load_from_str(&fs::read_to_string(path)?) {
+        Ok(docs) => match docs.into_iter().next() {
+            Some(Yaml::Hash(doc)) => doc,
+            _ => Hash::new(),
+        },
+        Err(_) => return Ok(FormatStatus::Invalid),
+    };
+
+    let newest = migrations.len() as i64;
+    Ok(match version_of(&path.display().to_string(), &doc) {
+        Ok(version) if version == newest => FormatStatus::Current,
+        Ok(version) if version < newest => FormatStatus::Outdated(version),
+        Ok(version) => FormatStatus::TooNew(version),
+        Err(_) => FormatStatus::Invalid,
+    })
+}
+
+/// Where a file is kept as it was when it's upgraded from a format
+pub fn backup_path(path: &Path, version: i64) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".format-{}", version));
+    path.with_file_name(name)
+}
+
+/// The format a file is written in, given its top level
+///
+/// # Errors
+/// * [`ErrorKind::FormatInvalid`](enum.ErrorKind.html#variant.FormatInvalid) - the format version isn't a version
+pub fn version_of(file: &str, doc: &Hash) -> Result<i64, ErrorKind> {
+    match doc.get(&Yaml::from_str(FORMAT_KEY)) {
+        None => Ok(0),
+        Some(Yaml::Integer(version)) if *version >= 0 => Ok(*version),
+        Some(_) => Err(ErrorKind::FormatInvalid(file.to_string())),
+    }
+}
+
+/// Upgrades a file to the newest format in place. The migration at each index moves a file from
+/// that format version to the next one, so the newest version is the number of migrations.
+/// Returns the version the file was in.
+///
+/// # Arguments
+///
+/// * `file` - What to call the file in errors, usually its path
+/// * `doc` - The top level of the file
+/// * `migrations` - Every migration the file's format has had, oldest first
+///
+/// # Errors
+/// * [`ErrorKind::FormatTooNew`](enum.ErrorKind.html#variant.FormatTooNew) - the file was written by a newer dropper
+/// * [`ErrorKind::FormatInvalid`](enum.ErrorKind.html#variant.FormatInvalid) - the format version isn't a version
+pub fn migrate(file: &str, doc: &mut Hash, migrations: &[Migration]) -> Result<i64, ErrorKind> {
+    let version = version_of(file, doc)?;
+    let newest = migrations.len() as i64;
+    if version > newest {
+        return Err(ErrorKind::FormatTooNew(file.to_string(), version, newest));
+    }
+
+    for migration in migrations.iter().skip(version as usize) {
+        migration(doc);
+    }
+    stamp(doc, migrations);
+    Ok(version)
+}
+
+/// Marks the top level of a file as being in the newest format, ahead of everything else in it
+pub fn stamp(doc: &mut Hash, migrations: &[Migration]) {
+    let mut stamped = Hash::new();
+    stamped.insert(
+        Yaml::from_str(FORMAT_KEY),
+        Yaml::Integer(migrations.len() as i64),
+    );
+    for (key, value) in doc.iter() {
+        if key.as_str() != Some(FORMAT_KEY) {
+            stamped.insert(key.clone(), value.clone());
+        }
+    }
+    *doc = stamped;
+}
+
+/// Turns the given fields of every entry under a key into strings, for fields that hand-edited
+/// or older files may have written as numbers, like versions
+pub fn stringify_fields(doc: &mut Hash, key: &str, fields: &[&str]) {
+    let entries = match doc.get_mut(&Yaml::from_str(key)) {
+        Some(Yaml::Hash(entries)) => entries,
+        _ => return,
+    };
+    for (_, entry) in entries.iter_mut() {
+        let entry = match entry {
+            Yaml::Hash(entry) => entry,
+            _ => continue,
+        };
+        // Fields are changed where they are, since inserting them again would move them last
+        for field in fields {
+            if let Some(value) = entry.get_mut(&Yaml::from_str(field)) {
+                let stringified = match value {
+                    Yaml::Real(s) => s.clone(),
+                    Yaml::Integer(i) => i.to_string(),
+                    _ => continue,
+                };
+                *value = Yaml::String(stringified);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn add_first(doc: &mut Hash) {
+        doc.insert(Yaml::from_str("first"), Yaml::Boolean(true));
+    }
+
+    fn add_second(doc: &mut Hash) {
+        doc.insert(Yaml::from_str("second"), Yaml::Boolean(true));
+    }
+
+    const TEST_MIGRATIONS: &[Migration] = &[add_first, add_second];
+
+    fn doc(yaml: &str) -> Hash {
+        match YamlLoader::load_from_str(yaml).unwrap().remove(0) {
+            Yaml::Hash(doc) => doc,
+            _ => panic!("{:?} isn't a map", yaml),
+        }
+    }
+
+    #[test]
+    fn files_without_a_format_are_format_zero() {
+        assert_eq!(version_of("test.yml", &doc("packages: {}")).unwrap(), 0);
+        assert_eq!(version_of("test.yml", &doc("format: 2")).unwrap(), 2);
+    }
+
+    #[test]
+    fn format_versions_that_arent_versions_are_invalid() {
+        for yaml in ["format: one", "format: -1", "format: 1.5"] {
+            match version_of("test.yml", &doc(yaml)) {
+                Err(ErrorKind::FormatInvalid(file)) => assert_eq!(file, "test.yml"),
+                result => panic!("expected {:?} to be invalid, got {:?}", yaml, result),
+            }
+        }
+    }
+
+    #[test]
+    fn only_the_migrations_after_a_files_format_are_run() {
+        let mut unversioned = doc("packages: {}");
+        assert_eq!(
+            migrate("test.yml", &mut unversioned, TEST_MIGRATIONS).unwrap(),
+            0
+        );
+        assert_eq!(
+            unversioned,
+            doc("format: 2\npackages: {}\nfirst: true\nsecond: true")
+        );
+
+        let mut outdated = doc("format: 1\npackages: {}");
+        assert_eq!(
+            migrate("test.yml", &mut outdated, TEST_MIGRATIONS).unwrap(),
+            1
+        );
+        assert_eq!(outdated, doc("format: 2\npackages: {}\nsecond: true"));
+
+        let mut current = doc("packages: {}\nformat: 2");
+        assert_eq!(
+            migrate("test.yml", &mut current, TEST_MIGRATIONS).unwrap(),
+            2
+        );
+        assert_eq!(current, doc("format: 2\npackages: {}"));
+        // The format goes first, wherever it was
+        assert_eq!(current.keys().next(), Some(&Yaml::from_str("format")));
+    }
+
+    #[test]
+    fn files_from_a_newer_dropper_are_refused() {
+        let mut too_new = doc("format: 3\npackages: {}");
+        match migrate("test.yml", &mut too_new, TEST_MIGRATIONS) {
+            Err(ErrorKind::FormatTooNew(file, version, newest)) => {
+                assert_eq!((file.as_str(), version, newest), ("test.yml", 3, 2))
+            }
+            result => panic!("expected the file to be too new, got {:?}", result),
+        }
+        assert_eq!(too_new, doc("format: 3\npackages: {}"));
+    }
+
+    #[test]
+    fn numbers_are_turned_into_strings_and_everything_else_is_left_alone() {
+        let mut numbers = doc(concat!(
+            "packages:\n",
+            "  vault: {version: 1.7, project_id: 33184, installed_at: 1538352000}\n",
+            "  worldedit: {version: \"7.2\", project_id: ~}\n",
+            "  broken: 1.0\n",
+            "other: {vault: {version: 1.7}}\n",
+        ));
+        stringify_fields(&mut numbers, "packages", &["version", "project_id"]);
+        assert_eq!(
+            numbers,
+            doc(concat!(
+                "packages:\n",
+                "  vault: {version: \"1.7\", project_id: \"33184\", installed_at: 1538352000}\n",
+                "  worldedit: {version: \"7.2\", project_id: ~}\n",
+                "  broken: 1.0\n",
+                "other: {vault: {version: 1.7}}\n",
+            ))
+        );
+    }
+
+    #[test]
+    fn outdated_files_are_backed_up_next_to_themselves() {
+        let path = Path::new("server").join(".dropper").join("state.yml");
+        assert_eq!(
+            backup_path(&path, 0),
+            Path::new("server")
+                .join(".dropper")
+                .join("state.yml.format-0")
+        );
+    }
+
+    #[test]
+    fn files_on_disk_report_their_format() {
+        let path = env::temp_dir().join(format!("dropper-format-{}.yml", process::id()));
+        assert_eq!(
+            file_status(&path, TEST_MIGRATIONS).unwrap(),
+            FormatStatus::Missing
+        );
+        let cases = [
+            ("format: 2\npackages: {}", FormatStatus::Current),
+            ("packages: {}", FormatStatus::Outdated(0)),
+            ("format: 1", FormatStatus::Outdated(1)),
+            ("format: 3", FormatStatus::TooNew(3)),
+            ("format: latest", FormatStatus::Invalid),
+            ("packages: [", FormatStatus::Invalid),
+        ];
+        for (contents, status) in cases.iter() {
+            fs::write(&path, contents).unwrap();
+            assert_eq!(
+                file_status(&path, TEST_MIGRATIONS).unwrap(),
+                *status,
+                "{}",
+                contents
+            );
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+//! This module fetches packages from the releases of GitHub repositories, which is where many
+//! plugins publish their builds. A pkg.yml entry with a `github` repository installs the JAR
+//! attached to each of its releases. Releases that have more than one JAR attached, like a
+//! plugin's along with its addons', need an `asset` pattern to pick which to install:
+//!
+//! ```yaml
+//! essentialsx:
+//!   github: EssentialsX/Essentials
+//!   asset: "EssentialsX-*.jar"
+//! ```
+//!
+//! A release's version is its tag, without a leading `v`. Releases marked as pre-releases are
+//! betas unless their version says they're dev builds, and drafts are skipped. GitHub lists the
+//! SHA-256 of newer assets, which downloads are checked against. Only the newest hundred
+//! releases are listed. A token saved with `dropper auth login github` is sent with every
+//! request, so rate limits are higher and private repositories can be read.
+
+use crate::http;
+use crate::http::Credentials;
+use crate::parser;
+use crate::parser::{Channel, PluginFile};
+use glob::Pattern;
+use reqwest::Client;
+use std::error::Error;
+use std::fmt;
+use yaml_rust::Yaml;
+
+// Where the releases of a repository are listed, newest first, where `{}` replaces the
+// repository
+const RELEASES_URL: &'static str = "https://api.github.com/repos/{}/releases?per_page=100";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A pkg.yml entry's `github` isn't an owner/repository, or its `asset` isn't a pattern.
+    // Takes the package name and what's wrong as params.
+    SourceInvalid(String, String),
+    // A release has more than one JAR attached, and nothing says which to install. Takes the
+    // repository, the release's tag, and the JARs as params.
+    AssetAmbiguous(String, String, Vec<String>),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::SourceInvalid(s, why) => {
+                    format!("the github repository of '{}' is invalid: {}", s, why)
+                }
+                ErrorKind::AssetAmbiguous(repository, tag, assets) => format!(
+                    "the {} release of {} has several JARs attached ({}); pick which to install with `asset`",
+                    tag,
+                    repository,
+                    assets.join(", ")
+                ),
+            }
+        )
+    }
+}
+
+/// The releases of a GitHub repository to install a package from
+#[derive(Debug, Clone)]
+pub struct GithubSource {
+    /// The repository, as `owner/name`
+    pub repository: String,
+    /// Which of the JARs attached to a release to install, if they have several
+    pub asset: Option<Pattern>,
+}
+
+impl GithubSource {
+    /// Reads the repository out of a pkg.yml entry, or `None` if the entry doesn't have one
+    ///
+    /// # Errors
+    /// * [`ErrorKind::SourceInvalid`](enum.ErrorKind.html#variant.SourceInvalid) - the entry has a `github` key, but not a usable source
+    pub fn from_yaml(name: &str, entry: &Yaml) -> Result<Option<GithubSource>, ErrorKind> {
+        let invalid = |why: &str| ErrorKind::SourceInvalid(name.to_string(), why.to_string());
+        let repository = match &entry["github"] {
+            Yaml::BadValue => return Ok(None),
+            Yaml::String(repository) => repository.trim_matches('/'),
+            _ => return Err(invalid("it should be a string")),
+        };
+        let parts = repository.split('/').collect::<Vec<&str>>();
+        if parts.len() != 2 || parts.iter().any(|part| part.is_empty()) {
+            return Err(invalid("it should look like owner/repository"));
+        }
+        let asset = match &entry["asset"] {
+            Yaml::BadValue => None,
+            Yaml::String(asset) => Some(
+                Pattern::new(asset)
+                    .map_err(|e| invalid(&format!("the asset isn't a pattern: {}", e)))?,
+            ),
+            _ => return Err(invalid("the asset should be a pattern")),
+        };
+
+        Ok(Some(GithubSource {
+            repository: repository.to_string(),
+            asset: asset,
+        }))
+    }
+
+    /// Lists the releases of the repository, newest first, or `None` if there's no such
+    /// repository. Releases without a JAR to install are left out.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::AssetAmbiguous`](enum.ErrorKind.html#variant.AssetAmbiguous) - a release has several JARs, and there's no `asset` to pick one
+    /// * [`http::ErrorKind::ApiFailed`](../http/enum.ErrorKind.html#variant.ApiFailed) - GitHub's API couldn't be used
+    /// * `reqwest::Error` - the request couldn't be made
+    pub fn enumerate_files(
+        &self,
+        client: &Client,
+        credentials: &Credentials,
+    ) -> Result<Option<Vec<PluginFile>>, Box<Error>> {
+        let url = RELEASES_URL.replace("{}", &self.repository);
+        let releases = match http::get_json(client, credentials, &url)? {
+            Some(Yaml::Array(releases)) => releases,
+            Some(_) => {
+                return Err(Box::new(http::ErrorKind::ApiFailed(
+                    url,
+                    "something that isn't a list of releases".to_string(),
+                )))
+            }
+            None => return Ok(None),
+        };
+
+        let mut files = Vec::new();
+        for release in releases.iter() {
+            let tag = match release["tag_name"].as_str() {
+                Some(tag) if release["draft"].as_bool() != Some(true) => tag,
+                _ => continue,
+            };
+            let assets = match &release["assets"] {
+                Yaml::Array(assets) => assets
+                    .iter()
+                    .filter(|asset| {
+                        asset["name"]
+                            .as_str()
+                            .is_some_and(|name| match &self.asset {
+                                Some(pattern) => pattern.matches(name),
+                                None => name.to_lowercase().ends_with(".jar"),
+                            })
+                    })
+                    .collect::<Vec<&Yaml>>(),
+                _ => Vec::new(),
+            };
+            let asset = match assets.len() {
+                0 => continue,
+                1 => assets[0],
+                _ => {
+                    return Err(Box::new(ErrorKind::AssetAmbiguous(
+                        self.repository.clone(),
+                        tag.to_string(),
+                        assets
+                            .iter()
+                            .filter_map(|asset| asset["name"].as_str())
+                            .map(|name| name.to_string())
+                            .collect(),
+                    )))
+                }
+            };
+            let link = match asset["browser_download_url"].as_str() {
+                Some(link) => link,
+                None => continue,
+            };
+
+            let version = tag.trim_start_matches(['v', 'V']);
+            let mut file = PluginFile::new(version.to_string(), link.to_string());
+            if release["prerelease"].as_bool() == Some(true) && file.channel == Channel::Release {
+                file.channel = Channel::Beta;
+            }
+            file.changelog = release["html_url"].as_str().map(|page| page.to_string());
+            file.uploaded = release["published_at"]
+                .as_str()
+                .and_then(parser::parse_timestamp);
+            // Digests are written like `sha256:<hex>`
+            if let Some((algorithm, digest)) = asset["digest"]
+                .as_str()
+                .and_then(|digest| digest.find(':').map(|i| (&digest[..i], &digest[i + 1..])))
+            {
+                file.hashes
+                    .insert(algorithm.to_lowercase(), digest.to_lowercase());
+            }
+            files.push(file);
+        }
+        Ok(Some(files))
+    }
+}
@@ -0,0 +1,178 @@
+//! This module fetches packages from Hangar, PaperMC's plugin repository. A pkg.yml entry with a
+//! `hangar` project installs its versions for the `platform` it names, or for Paper if it doesn't:
+//!
+//! ```yaml
+//! viaversion:
+//!   hangar: ViaVersion
+//! ```
+//!
+//! The project is its slug, or `owner/slug`. Hangar publishes versions to channels, like
+//! Release and Snapshot. Release versions are releases, and the others are dev builds unless
+//! they're named like betas. Hangar lists the SHA-256 of each file, which downloads are checked
+//! against, and the server versions each one is for, which can be ranges like `1.8-1.21`. Only
+//! projects given as `owner/slug` have their versions' changelogs linked to. Files
+//! that Hangar only links to somewhere else are downloaded from there.
+
+use crate::http;
+use crate::http::Credentials;
+use crate::parser;
+use crate::parser::{Channel, PluginFile};
+use reqwest::Client;
+use std::error::Error;
+use std::fmt;
+use url::form_urlencoded;
+use yaml_rust::Yaml;
+
+// Where the versions of a project are listed, newest first, a page at a time
+const VERSIONS_URL: &'static str = "https://hangar.papermc.io/api/v1/projects";
+// Where projects' pages are, by `owner/slug`
+const PAGE_URL: &'static str = "https://hangar.papermc.io";
+const DEFAULT_PLATFORM: &'static str = "PAPER";
+// The most versions Hangar lists on a page, and how many pages to read
+const PAGE_SIZE: usize = 25;
+const MAX_PAGES: usize = 4;
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A pkg.yml entry's `hangar` or `platform` isn't a string. Takes the package name as a
+    // param.
+    SourceInvalid(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::SourceInvalid(s) => format!(
+                    "the hangar project of '{}' should be a slug, and its platform a name like PAPER",
+                    s
+                ),
+            }
+        )
+    }
+}
+
+/// A project on Hangar to install a package from
+#[derive(Debug, Clone)]
+pub struct HangarSource {
+    /// The project's slug, or `owner/slug`
+    pub project: String,
+    /// Which platform's files to install, like `PAPER` or `VELOCITY`
+    pub platform: String,
+}
+
+impl HangarSource {
+    /// Reads the Hangar project out of a pkg.yml entry, or `None` if the entry doesn't have one
+    ///
+    /// # Errors
+    /// * [`ErrorKind::SourceInvalid`](enum.ErrorKind.html#variant.SourceInvalid) - the entry has a `hangar` key, but not a usable source
+    pub fn from_yaml(name: &str, entry: &Yaml) -> Result<Option<HangarSource>, ErrorKind> {
+        let invalid = || ErrorKind::SourceInvalid(name.to_string());
+        let project = match &entry["hangar"] {
+            Yaml::BadValue => return Ok(None),
+            Yaml::String(project) if !project.trim_matches('/').is_empty() => {
+                project.trim_matches('/').to_string()
+            }
+            _ => return Err(invalid()),
+        };
+        let platform = match &entry["platform"] {
+            Yaml::BadValue => DEFAULT_PLATFORM.to_string(),
+            Yaml::String(platform) => platform.to_uppercase(),
+            _ => return Err(invalid()),
+        };
+
+        Ok(Some(HangarSource {
+            project: project,
+            platform: platform,
+        }))
+    }
+
+    /// Lists the project's versions that have a file for the platform, newest first, or `None`
+    /// if there's no such project. Only the newest hundred versions are listed.
+    ///
+    /// # Errors
+    /// * [`http::ErrorKind::ApiFailed`](../http/enum.ErrorKind.html#variant.ApiFailed) - Hangar's API couldn't be used
+    /// * `reqwest::Error` - the request couldn't be made
+    pub fn enumerate_files(
+        &self,
+        client: &Client,
+        credentials: &Credentials,
+    ) -> Result<Option<Vec<PluginFile>>, Box<Error>> {
+        let mut files = Vec::new();
+        for page in 0..MAX_PAGES {
+            let url = format!(
+                "{}/{}/versions?limit={}&offset={}&platform={}",
+                VERSIONS_URL,
+                self.project,
+                PAGE_SIZE,
+                page * PAGE_SIZE,
+                form_urlencoded::byte_serialize(self.platform.as_bytes()).collect::<String>()
+            );
+            let listed = match http::get_json(client, credentials, &url)? {
+                Some(listed) => listed,
+                None => return Ok(None),
+            };
+            let versions = match &listed["result"] {
+                Yaml::Array(versions) => versions,
+                _ => {
+                    return Err(Box::new(http::ErrorKind::ApiFailed(
+                        url,
+                        "something that isn't a list of versions".to_string(),
+                    )))
+                }
+            };
+
+            files.extend(versions.iter().filter_map(|version| self.file_of(version)));
+            let count = listed["pagination"]["count"].as_i64().unwrap_or(0) as usize;
+            if versions.len() < PAGE_SIZE || (page + 1) * PAGE_SIZE >= count {
+                break;
+            }
+        }
+        Ok(Some(files))
+    }
+
+    /// The file of one of the versions Hangar lists, if it has one for the platform
+    fn file_of(&self, version: &Yaml) -> Option<PluginFile> {
+        let download = &version["downloads"][self.platform.as_str()];
+        let link = download["downloadUrl"]
+            .as_str()
+            .or_else(|| download["externalUrl"].as_str())?;
+        let mut file = PluginFile::new(version["name"].as_str()?.to_string(), link.to_string());
+
+        let channel = version["channel"]["name"].as_str().unwrap_or("Release");
+        file.channel = match Channel::from_name(channel) {
+            Some(channel) => channel,
+            None => match Channel::from_version(channel) {
+                Channel::Release => Channel::Dev,
+                channel => channel,
+            },
+        };
+        // Version pages can only be linked to when the project's owner is known
+        if self.project.contains('/') {
+            file.changelog = Some(format!(
+                "{}/{}/versions/{}",
+                PAGE_URL, self.project, file.version
+            ));
+        }
+        file.uploaded = version["createdAt"]
+            .as_str()
+            .and_then(parser::parse_timestamp);
+        if let Yaml::Array(game_versions) = &version["platformDependencies"][self.platform.as_str()]
+        {
+            file.game_versions = game_versions
+                .iter()
+                .filter_map(|game_version| game_version.as_str())
+                .map(|game_version| game_version.to_string())
+                .collect();
+        }
+        if let Some(sha256) = download["fileInfo"]["sha256Hash"].as_str() {
+            file.hashes
+                .insert("sha256".to_string(), sha256.to_lowercase());
+        }
+        Some(file)
+    }
+}
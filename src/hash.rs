@@ -0,0 +1,127 @@
+//! This module computes digests of downloaded files, so that they can be checked against the
+//! digests that plugin websites publish, and against the ones the lockfile recorded.
+//!
+//! Websites publish different kinds of digest (Bukkit publishes MD5s, others publish SHA-1s or
+//! SHA-512s), so digests are kept by the name of the algorithm they were made with. dropper
+//! always records an MD5 and a SHA-256 of its own.
+
+use openssl::sha::{Sha1, Sha256, Sha512};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+/// The algorithms dropper can check digests made with, by the names they go by in the lockfile
+pub const ALGORITHMS: [&'static str; 4] = ["md5", "sha1", "sha256", "sha512"];
+
+/// The algorithms dropper records digests with for every download
+pub const RECORDED_ALGORITHMS: [&'static str; 2] = ["md5", "sha256"];
+
+/// A digest being computed with one of the algorithms
+enum Hasher {
+    Md5(md5::Context),
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn new(algorithm: &str) -> Option<Hasher> {
+        match algorithm {
+            "md5" => Some(Hasher::Md5(md5::Context::new())),
+            "sha1" => Some(Hasher::Sha1(Sha1::new())),
+            "sha256" => Some(Hasher::Sha256(Sha256::new())),
+            "sha512" => Some(Hasher::Sha512(Sha512::new())),
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(context) => context.consume(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    /// The digest as a lowercase hex string
+    fn finish(self) -> String {
+        match self {
+            Hasher::Md5(context) => format!("{:x}", context.compute()),
+            Hasher::Sha1(hasher) => to_hex(&hasher.finish()),
+            Hasher::Sha256(hasher) => to_hex(&hasher.finish()),
+            Hasher::Sha512(hasher) => to_hex(&hasher.finish()),
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Computes the MD5 of a file as a lowercase hex string. The file is read in chunks, since
+/// plugin JARs can get fairly large.
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn md5_file(path: &Path) -> io::Result<String> {
+    let mut digests = digest_file(path, &["md5"])?;
+    Ok(digests.remove("md5").unwrap())
+}
+
+/// Computes the digests of a file with each of the given algorithms, as lowercase hex strings
+/// keyed by algorithm. The file is only read once however many there are. Algorithms dropper
+/// doesn't know are left out.
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn digest_file(path: &Path, algorithms: &[&str]) -> io::Result<BTreeMap<String, String>> {
+    let mut hashers = Vec::new();
+    for algorithm in algorithms.iter() {
+        if let Some(hasher) = Hasher::new(algorithm) {
+            hashers.push((algorithm.to_string(), hasher));
+        }
+    }
+
+    let mut file = File::open(path)?;
+    let mut buffer = [0; 8192];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            n => {
+                for (_, hasher) in hashers.iter_mut() {
+                    hasher.update(&buffer[..n]);
+                }
+            }
+        }
+    }
+
+    Ok(hashers
+        .into_iter()
+        .map(|(algorithm, hasher)| (algorithm, hasher.finish()))
+        .collect())
+}
+
+/// Checks a file against digests keyed by algorithm, returning the first one it doesn't match
+/// as (algorithm, expected, actual). Digests made with algorithms dropper doesn't know can't be
+/// checked, and are skipped.
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn verify_file(
+    path: &Path,
+    expected: &BTreeMap<String, String>,
+) -> io::Result<Option<(String, String, String)>> {
+    let algorithms = expected.keys().map(|a| a.as_str()).collect::<Vec<&str>>();
+    let actual = digest_file(path, &algorithms)?;
+
+    for (algorithm, digest) in actual {
+        let expected = &expected[&algorithm];
+        if !expected.eq_ignore_ascii_case(&digest) {
+            return Ok(Some((algorithm, expected.clone(), digest)));
+        }
+    }
+    Ok(None)
+}
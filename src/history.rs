@@ -0,0 +1,188 @@
+//! This module keeps a history of the changes dropper has made to the server, in
+//! `.dropper/history.yml`, so that admins can look back at what changed when (for example, when
+//! a server starts misbehaving):
+//!
+//! ```yaml
+//! events:
+//!   - time: 1538352000
+//!     action: update
+//!     package: worldedit
+//!     version: 6.1.9
+//!     from: 6.1.7
+//!     forced:
+//!       - api-version
+//! ```
+//!
+//! `forced` lists the strict mode checks that were overridden to make the change.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // The history file couldn't be understood. Takes the path as a param.
+    HistoryInvalid(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::HistoryInvalid(s) => format!("the history file {} is corrupt", s),
+            }
+        )
+    }
+}
+
+/// A single change to the server
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// When the change was made, in seconds since the Unix epoch
+    pub time: u64,
+    /// What was done: `install`, `update` or `remove`
+    pub action: String,
+    pub package: String,
+    pub version: String,
+    /// For updates, the version that was replaced
+    pub from: Option<String>,
+    /// The strict mode checks that were overridden
+    pub forced: Vec<String>,
+}
+
+impl Event {
+    /// Creates an event for a change that is being made now
+    pub fn now(action: &str, package: &str, version: &str) -> Event {
+        Event {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            action: action.to_string(),
+            package: package.to_string(),
+            version: version.to_string(),
+            from: None,
+            forced: Vec::new(),
+        }
+    }
+}
+
+/// Every change dropper has made, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub events: Vec<Event>,
+}
+
+impl History {
+    /// Reads the history file. A missing history file means nothing has happened yet.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::HistoryInvalid`](enum.ErrorKind.html#variant.HistoryInvalid) - the history file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn load(path: &Path) -> Result<History, Box<Error>> {
+        if !path.exists() {
+            return Ok(History::default());
+        }
+
+        let invalid = || ErrorKind::HistoryInvalid(path.display().to_string());
+        let docs = YamlLoader::load_from_str(&fs::read_to_string(path)?).map_err(|_| invalid())?;
+        let doc = match docs.first() {
+            Some(doc) => doc,
+            None => return Ok(History::default()),
+        };
+
+        let mut events = Vec::new();
+        if let Yaml::Array(items) = &doc["events"] {
+            for item in items {
+                let field = |key: &str| {
+                    item[key]
+                        .as_str()
+                        .map(|s| s.to_string())
+                        .ok_or_else(invalid)
+                };
+                events.push(Event {
+                    time: item["time"].as_i64().ok_or_else(invalid)? as u64,
+                    action: field("action")?,
+                    package: field("package")?,
+                    version: field("version")?,
+                    from: item["from"].as_str().map(|s| s.to_string()),
+                    forced: match &item["forced"] {
+                        Yaml::Array(checks) => checks
+                            .iter()
+                            .filter_map(|check| check.as_str().map(|s| s.to_string()))
+                            .collect(),
+                        _ => Vec::new(),
+                    },
+                });
+            }
+        }
+
+        Ok(History { events: events })
+    }
+
+    /// Writes the history file, replacing what was there
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn save(&self, path: &Path) -> Result<(), Box<Error>> {
+        let mut items = Vec::new();
+        for event in self.events.iter() {
+            let mut item = Hash::new();
+            item.insert(Yaml::from_str("time"), Yaml::Integer(event.time as i64));
+            for (key, value) in [
+                ("action", &event.action),
+                ("package", &event.package),
+                ("version", &event.version),
+            ]
+            .iter()
+            {
+                item.insert(Yaml::from_str(key), Yaml::String(value.to_string()));
+            }
+            if let Some(from) = &event.from {
+                item.insert(Yaml::from_str("from"), Yaml::String(from.clone()));
+            }
+            if !event.forced.is_empty() {
+                item.insert(
+                    Yaml::from_str("forced"),
+                    Yaml::Array(
+                        event
+                            .forced
+                            .iter()
+                            .map(|check| Yaml::String(check.clone()))
+                            .collect(),
+                    ),
+                );
+            }
+            items.push(Yaml::Hash(item));
+        }
+
+        let mut doc = Hash::new();
+        doc.insert(Yaml::from_str("events"), Yaml::Array(items));
+
+        let mut tmp_string = String::new();
+        YamlEmitter::new(&mut tmp_string)
+            .dump(&Yaml::Hash(doc))
+            .unwrap();
+        fs::write(path, format!("{}\n", tmp_string))?;
+        Ok(())
+    }
+
+    /// Adds an event to the end of the history file
+    ///
+    /// # Errors
+    /// * [`ErrorKind::HistoryInvalid`](enum.ErrorKind.html#variant.HistoryInvalid) - the history file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn append(path: &Path, event: Event) -> Result<(), Box<Error>> {
+        let mut history = History::load(path)?;
+        history.events.push(event);
+        history.save(path)
+    }
+}
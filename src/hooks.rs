@@ -0,0 +1,192 @@
+//! This module runs the hooks configured in the config file. A hook is a shell command that runs
+//! before or after dropper changes a package, so that admins can wire in restarts, announcements,
+//! backups or whatever else their server needs:
+//!
+//! ```yaml
+//! hooks:
+//!   pre_update: ./backup.sh "$DROPPER_PACKAGE"
+//!   post_install: ./announce.sh "$DROPPER_PACKAGE $DROPPER_VERSION was installed"
+//!   post_remove: ./announce.sh "$DROPPER_PACKAGE was removed"
+//! ```
+//!
+//! Hooks run from the server root, and are told about the package through environment variables:
+//! * `DROPPER_HOOK` - the hook being run, e.g. `post_install`
+//! * `DROPPER_PACKAGE` - the name of the package
+//! * `DROPPER_VERSION` - the version being installed, updated to, or removed
+//! * `DROPPER_OLD_VERSION` - for updates, the version being replaced
+//! * `DROPPER_PLUGIN_DIR` - where the package's JAR lives
+//!
+//! The `drift` hook is different: `dropper watch` runs it when a JAR is added to or removed from
+//! the plugins folder by something other than dropper. `DROPPER_PACKAGE` is then the JAR's file
+//! name, `DROPPER_VERSION` is empty, and `DROPPER_DRIFT` says whether it was `added` or `removed`.
+//!
+//! A `pre_` hook that fails stops the operation before anything is changed; a `post_` hook that
+//! fails only earns a warning, since the change has already been made by then.
+
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+use yaml_rust::Yaml;
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // The config has a hook dropper doesn't know about, or one that isn't a command. Takes the
+    // hook's key as a param.
+    HookInvalid(String),
+    // A hook exited unsuccessfully. Takes the hook's key and how it exited as params.
+    HookFailed(String, String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::HookInvalid(s) => format!("'{}' is not a valid hook", s),
+                ErrorKind::HookFailed(s, status) => format!("the {} hook failed ({})", s, status),
+            }
+        )
+    }
+}
+
+/// The points in an operation that a hook can run at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hook {
+    PreInstall,
+    PostInstall,
+    PreUpdate,
+    PostUpdate,
+    PreRemove,
+    PostRemove,
+    /// A JAR was added or removed behind dropper's back
+    Drift,
+}
+
+impl Hook {
+    const ALL: [Hook; 7] = [
+        Hook::PreInstall,
+        Hook::PostInstall,
+        Hook::PreUpdate,
+        Hook::PostUpdate,
+        Hook::PreRemove,
+        Hook::PostRemove,
+        Hook::Drift,
+    ];
+
+    /// The key the hook is configured under
+    pub fn key(&self) -> &'static str {
+        match self {
+            Hook::PreInstall => "pre_install",
+            Hook::PostInstall => "post_install",
+            Hook::PreUpdate => "pre_update",
+            Hook::PostUpdate => "post_update",
+            Hook::PreRemove => "pre_remove",
+            Hook::PostRemove => "post_remove",
+            Hook::Drift => "drift",
+        }
+    }
+
+    /// Whether the hook runs before the change is made, and so can stop it
+    pub fn is_pre(&self) -> bool {
+        matches!(self, Hook::PreInstall | Hook::PreUpdate | Hook::PreRemove)
+    }
+}
+
+/// What a hook is being run for
+pub struct HookContext<'a> {
+    pub package: &'a str,
+    pub version: &'a str,
+    /// The version being replaced, for updates
+    pub old_version: Option<&'a str>,
+    /// For the drift hook, whether the JAR was added or removed
+    pub drift: Option<&'a str>,
+    pub project_dir: &'a Path,
+    pub plugin_dir: &'a Path,
+}
+
+/// The hooks from the config file
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    commands: Vec<(Hook, String)>,
+}
+
+impl Hooks {
+    /// Reads the hooks out of the config's `hooks` key. No key means no hooks.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::HookInvalid`](enum.ErrorKind.html#variant.HookInvalid) - a hook is unknown, or isn't a command
+    pub fn from_yaml(yaml: &Yaml) -> Result<Hooks, ErrorKind> {
+        let entries = match yaml {
+            Yaml::Hash(entries) => entries,
+            Yaml::Null | Yaml::BadValue => return Ok(Hooks::default()),
+            _ => return Err(ErrorKind::HookInvalid("hooks".to_string())),
+        };
+
+        let mut commands = Vec::new();
+        for (key, command) in entries.iter() {
+            let key = key.as_str().unwrap_or("");
+            let hook = match Hook::ALL.iter().find(|hook| hook.key() == key) {
+                Some(hook) => *hook,
+                None => return Err(ErrorKind::HookInvalid(key.to_string())),
+            };
+            match command.as_str() {
+                Some(command) => commands.push((hook, command.to_string())),
+                None => return Err(ErrorKind::HookInvalid(key.to_string())),
+            }
+        }
+
+        Ok(Hooks { commands: commands })
+    }
+
+    /// Runs the command configured for `hook`, if there is one, waiting for it to finish. Its
+    /// output goes straight to the terminal.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::HookFailed`](enum.ErrorKind.html#variant.HookFailed) - the command exited unsuccessfully
+    /// * `std::io::ErrorKind::*` - the command couldn't be started
+    pub fn run(&self, hook: Hook, context: &HookContext) -> Result<(), Box<Error>> {
+        let command = match self.commands.iter().find(|(h, _)| *h == hook) {
+            Some((_, command)) => command,
+            None => return Ok(()),
+        };
+
+        let mut shell = match cfg!(windows) {
+            true => {
+                let mut shell = Command::new("cmd");
+                shell.arg("/C");
+                shell
+            }
+            false => {
+                let mut shell = Command::new("sh");
+                shell.arg("-c");
+                shell
+            }
+        };
+        shell
+            .arg(command)
+            .current_dir(context.project_dir)
+            .env("DROPPER_HOOK", hook.key())
+            .env("DROPPER_PACKAGE", context.package)
+            .env("DROPPER_VERSION", context.version)
+            .env("DROPPER_PLUGIN_DIR", context.plugin_dir);
+        if let Some(old_version) = context.old_version {
+            shell.env("DROPPER_OLD_VERSION", old_version);
+        }
+        if let Some(drift) = context.drift {
+            shell.env("DROPPER_DRIFT", drift);
+        }
+
+        let status = shell.status()?;
+        match status.success() {
+            true => Ok(()),
+            false => Err(Box::new(ErrorKind::HookFailed(
+                hook.key().to_string(),
+                status.to_string(),
+            ))),
+        }
+    }
+}
@@ -0,0 +1,759 @@
+//! This module sets up the HTTP client that the parsers and the backend's downloader share.
+//! Building one client and handing it around means connections are pooled and kept alive between
+//! requests, which adds up quickly when an operation touches a lot of packages.
+//!
+//! Pages that are fetched over and over, like the files page of every installed project on each
+//! `dropper outdated`, can be kept in a cache directory. Cached pages are revalidated with the
+//! `ETag` and `Last-Modified` the website sent, so a page that hasn't changed only costs a
+//! `304 Not Modified`.
+//!
+//! Hosts that sit behind a login can be sent extra headers and cookies with every request, from
+//! the config's `auth` key or the credentials file (see `Credentials`).
+//!
+//! Every request can be traced with `--trace-http`, which logs its method, URL, status and how
+//! long it took, whether a cached page was used, and the start of the pages fetched, for working
+//! out why a website isn't giving dropper what it expects.
+
+use crate::auth;
+use crate::s3::S3Config;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE, COOKIE,
+    ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, LOCATION, SERVER, USER_AGENT,
+};
+use reqwest::{Client, RedirectPolicy, RequestBuilder, Response, StatusCode, Url};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use url::percent_encoding::percent_decode;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+// How many idle connections to keep around for each host
+const MAX_IDLE_PER_HOST: usize = 8;
+// How many redirects to follow by hand, when tracing a download or while there are
+// credentials to send, which is as many as reqwest follows
+const MAX_REDIRECTS: usize = 10;
+// Strings in the pages Cloudflare serves to clients it wants to check are human
+const CLOUDFLARE_CHALLENGE_MARKERS: [&'static str; 3] =
+    ["cf-browser-verification", "challenge-platform", "Just a moment..."];
+// How much of a body to trace
+const TRACED_BODY_CHARS: usize = 200;
+
+// Where requests are traced to, if they are
+static TRACE: Mutex<Option<Box<Write + Send>>> = Mutex::new(None);
+// The client that redirects are followed by hand with, once it's been built
+static NO_REDIRECT_CLIENT: Mutex<Option<Client>> = Mutex::new(None);
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A host's credentials aren't headers and cookies that can be sent. Takes the host as a
+    // param.
+    CredentialsInvalid(String),
+    // A host's token is meant to be in the keychain, but isn't. Takes the host as a param.
+    TokenMissing(String),
+    // An API didn't answer with JSON. Takes the URL and what it answered instead as params.
+    ApiFailed(String, String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::CredentialsInvalid(s) => format!(
+                    "the credentials for {} should be `headers` and `cookies`, each a map of names to values",
+                    s
+                ),
+                ErrorKind::TokenMissing(s) => format!(
+                    "the token for {} isn't in the keychain; set it again with `dropper auth login {}`",
+                    s, s
+                ),
+                ErrorKind::ApiFailed(url, why) => format!("the API at {} answered {}", url, why),
+            }
+        )
+    }
+}
+
+/// Extra headers to send to hosts that downloads need to be logged in to, keyed by host. A host
+/// also covers its subdomains. They're written like this, in the config under `auth`, or at the
+/// top of the credentials file:
+///
+/// ```yaml
+/// dev.bukkit.org:
+///   headers:
+///     Authorization: Bearer 0123456789abcdef
+///   cookies:
+///     session: 0123456789abcdef
+/// ```
+///
+/// A host can also have a token, which is sent in a header of its own (see the
+/// [`auth`](../auth/index.html) module). reqwest would send every header but a few standard ones
+/// on to wherever a request is redirected, so requests that carry credentials have their
+/// redirects followed one at a time, and each hop is only sent its own host's. That way they
+/// don't leak to wherever a download ends up, like the CDN behind a website's download links.
+///
+/// Requests for `s3://` links are sent to the bucket's storage instead, signed with the keys
+/// the config has for it (see the [`s3`](../s3/index.html) module).
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    hosts: BTreeMap<String, HeaderMap>,
+    s3: S3Config,
+}
+
+impl Credentials {
+    /// Reads credentials written like the above. Nothing at all means there are none.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::CredentialsInvalid`](enum.ErrorKind.html#variant.CredentialsInvalid) - a host's credentials can't be sent
+    pub fn from_yaml(yaml: &Yaml) -> Result<Credentials, ErrorKind> {
+        let entries = match yaml {
+            Yaml::Hash(entries) => entries,
+            Yaml::Null | Yaml::BadValue => return Ok(Credentials::default()),
+            _ => return Err(ErrorKind::CredentialsInvalid("auth".to_string())),
+        };
+
+        let mut hosts = BTreeMap::new();
+        for (host, entry) in entries.iter() {
+            let host = match host.as_str() {
+                Some(host) => host.to_lowercase(),
+                None => return Err(ErrorKind::CredentialsInvalid("auth".to_string())),
+            };
+            let invalid = || ErrorKind::CredentialsInvalid(host.clone());
+
+            let mut headers = HeaderMap::new();
+            match &entry["headers"] {
+                Yaml::Hash(values) => {
+                    for (name, value) in values.iter() {
+                        let name = name
+                            .as_str()
+                            .and_then(|name| HeaderName::from_bytes(name.as_bytes()).ok());
+                        let value = Self::yaml_to_string(value)
+                            .and_then(|value| HeaderValue::from_str(&value).ok());
+                        match (name, value) {
+                            (Some(name), Some(value)) => headers.insert(name, value),
+                            _ => return Err(invalid()),
+                        };
+                    }
+                }
+                Yaml::BadValue => {}
+                _ => return Err(invalid()),
+            }
+
+            match &entry["cookies"] {
+                Yaml::Hash(values) => {
+                    let mut cookies = Vec::new();
+                    for (name, value) in values.iter() {
+                        match (name.as_str(), Self::yaml_to_string(value)) {
+                            (Some(name), Some(value)) => cookies.push(format!("{}={}", name, value)),
+                            _ => return Err(invalid()),
+                        }
+                    }
+                    match HeaderValue::from_str(&cookies.join("; ")) {
+                        Ok(value) => headers.insert(COOKIE, value),
+                        Err(_) => return Err(invalid()),
+                    };
+                }
+                Yaml::BadValue => {}
+                _ => return Err(invalid()),
+            }
+
+            if let Some(token) = Self::yaml_to_string(&entry["token"]) {
+                let token = match token.as_str() {
+                    auth::IN_KEYCHAIN => auth::keychain_get(&host)
+                        .ok_or_else(|| ErrorKind::TokenMissing(host.clone()))?,
+                    _ => token,
+                };
+                let header = match &entry["header"] {
+                    Yaml::String(header) => header.clone(),
+                    Yaml::BadValue => AUTHORIZATION.to_string(),
+                    _ => return Err(invalid()),
+                };
+                let name = HeaderName::from_bytes(header.as_bytes()).map_err(|_| invalid())?;
+                let value = HeaderValue::from_str(&auth::header_value(&header, &token))
+                    .map_err(|_| invalid())?;
+                headers.insert(name, value);
+            }
+
+            hosts.insert(host, headers);
+        }
+
+        Ok(Credentials {
+            hosts: hosts,
+            s3: S3Config::default(),
+        })
+    }
+
+    /// Secrets that happen to be numbers are still sent as they were written
+    fn yaml_to_string(yaml: &Yaml) -> Option<String> {
+        match yaml {
+            Yaml::String(s) => Some(s.clone()),
+            Yaml::Integer(i) => Some(i.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Adds another set of credentials to these, replacing the credentials of any host both
+    /// have
+    pub fn extend(&mut self, other: Credentials) {
+        self.hosts.extend(other.hosts);
+    }
+
+    /// Sets where buckets are, and the keys to sign requests to them with
+    pub fn set_s3(&mut self, s3: S3Config) {
+        self.s3 = s3;
+    }
+
+    /// The headers to send with a request to a URL, going by its host. When both a host and its
+    /// parent domain have credentials, the host's own are used.
+    fn headers_for(&self, url: &str) -> Option<&HeaderMap> {
+        let host = url::Url::parse(url).ok()?.host_str()?.to_lowercase();
+        self.hosts
+            .iter()
+            .filter(|(name, _)| host == **name || host.ends_with(&format!(".{}", name)))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, headers)| headers)
+    }
+
+    /// Sends a GET request to a URL, with the credentials of its host attached, following its
+    /// redirects
+    ///
+    /// # Errors
+    /// * `reqwest::Error` - the request couldn't be made
+    pub fn fetch(&self, client: &Client, url: &str) -> Result<Response, Box<Error>> {
+        self.fetch_with(client, url, |request| request)
+    }
+
+    /// Like `fetch`, with headers of the caller's own added to the request by `extra`
+    ///
+    /// # Errors
+    /// * `reqwest::Error` - the request couldn't be made
+    pub fn fetch_with<F>(
+        &self,
+        client: &Client,
+        url: &str,
+        extra: F,
+    ) -> Result<Response, Box<Error>>
+    where
+        F: Fn(RequestBuilder) -> RequestBuilder,
+    {
+        let mut next = url.to_string();
+        let mut hops = 0;
+        loop {
+            // Without credentials to keep to their host, the client can follow the rest itself
+            if self.headers_for(&next).is_none() {
+                return send(extra(self.get(client, &next)));
+            }
+            let response = send(extra(self.get(&no_redirect_client()?, &next)))?;
+            match redirect_location(&response) {
+                Some(location) if hops < MAX_REDIRECTS => next = location.to_string(),
+                _ => return Ok(response),
+            }
+            hops += 1;
+        }
+    }
+
+    /// Starts a GET request to a URL, with the credentials of its host attached. Its redirects
+    /// are followed by the client, so this is only for clients that don't follow them.
+    fn get(&self, client: &Client, url: &str) -> RequestBuilder {
+        if let Some(https_url) = self.s3.https_url(url) {
+            let request = client.get(&https_url);
+            return match self.s3.sign(&https_url, SystemTime::now()) {
+                Some(headers) => request.headers(headers),
+                None => request,
+            };
+        }
+        self.attach(client.get(url), url)
+    }
+
+    /// Starts a POST request to a URL, with the credentials of its host attached
+    pub fn post(&self, client: &Client, url: &str) -> RequestBuilder {
+        self.attach(client.post(url), url)
+    }
+
+    fn attach(&self, request: RequestBuilder, url: &str) -> RequestBuilder {
+        match self.headers_for(url) {
+            Some(headers) => request.headers(headers.clone()),
+            None => request,
+        }
+    }
+}
+
+/// Builds the client for the rest of the program to use. Clones of a client share its connection
+/// pool, so clone the one this returns rather than building another.
+///
+/// # Errors
+/// * `reqwest::Error` - the TLS backend could not be initialized
+pub fn build_client() -> reqwest::Result<Client> {
+    Client::builder()
+        .default_headers(default_headers())
+        .max_idle_per_host(MAX_IDLE_PER_HOST)
+        .build()
+}
+
+/// Sends a request. With the `fixtures` feature, the response may be recorded or played back
+/// instead; see the [`fixtures`](../fixtures/index.html) module.
+///
+/// # Errors
+/// * `reqwest::Error` - the request couldn't be made
+pub fn send(request: RequestBuilder) -> Result<Response, Box<Error>> {
+    if !tracing() {
+        return send_untraced(request);
+    }
+
+    let description = request
+        .try_clone()
+        .and_then(|clone| clone.build().ok())
+        .map(|built| format!("{} {}", built.method(), built.url()))
+        .unwrap_or("a streamed request".to_string());
+
+    let start = Instant::now();
+    let result = send_untraced(request);
+    let elapsed = start.elapsed().as_millis();
+    match &result {
+        Ok(response) => trace(format!(
+            "{} -> {} ({} ms)",
+            description,
+            response.status(),
+            elapsed
+        )),
+        Err(e) => trace(format!("{} -> failed: {} ({} ms)", description, e, elapsed)),
+    }
+    result
+}
+
+fn send_untraced(request: RequestBuilder) -> Result<Response, Box<Error>> {
+    #[cfg(feature = "fixtures")]
+    return crate::fixtures::send(request);
+    #[cfg(not(feature = "fixtures"))]
+    Ok(request.send()?)
+}
+
+/// Starts tracing every request to `out`, for `--trace-http`
+pub fn trace_to(out: Box<Write + Send>) {
+    *TRACE.lock().unwrap() = Some(out);
+}
+
+/// Whether requests are being traced
+fn tracing() -> bool {
+    TRACE.lock().unwrap().is_some()
+}
+
+/// Writes a line to the trace, if requests are being traced. A trace that can't be written to
+/// isn't worth failing over.
+fn trace(line: String) {
+    if let Some(out) = TRACE.lock().unwrap().as_mut() {
+        writeln!(out, "http: {}", line).ok();
+    }
+}
+
+/// The start of a body, on one line
+fn preview(body: &str) -> String {
+    let mut preview = body
+        .chars()
+        .take(TRACED_BODY_CHARS)
+        .map(|c| match c {
+            '\n' | '\r' | '\t' => ' ',
+            c => c,
+        })
+        .collect::<String>();
+    if body.chars().count() > TRACED_BODY_CHARS {
+        preview.push_str("...");
+    }
+    preview
+}
+
+/// The URL a response ended up at after redirects
+pub fn response_url(response: &Response) -> Url {
+    #[cfg(feature = "fixtures")]
+    {
+        let replayed = response
+            .headers()
+            .get(crate::fixtures::URL_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|url| Url::parse(url).ok());
+        if let Some(url) = replayed {
+            return url;
+        }
+    }
+    response.url().clone()
+}
+
+/// Fetches a page, returning its status and body. If `cache_dir` is given, the page is kept
+/// there, and later fetches only download it again if it has changed.
+///
+/// # Errors
+/// * `reqwest::Error` - the request couldn't be made
+/// * `std::io::ErrorKind::*` - an IO error occured while using the cache
+pub fn get_page(
+    client: &Client,
+    credentials: &Credentials,
+    url: &str,
+    cache_dir: Option<&Path>,
+) -> Result<(StatusCode, String), Box<Error>> {
+    let cache_dir = match cache_dir {
+        Some(dir) => dir,
+        None => {
+            let mut response = credentials.fetch(client, url)?;
+            let body = response.text()?;
+            trace(format!("  body: {}", preview(&body)));
+            return Ok((response.status(), body));
+        }
+    };
+
+    let (body_path, validators_path) = cached_page_paths(cache_dir, url);
+    let validators = match body_path.exists() {
+        true => read_validators(&validators_path),
+        false => Yaml::Null,
+    };
+    let mut response = credentials.fetch_with(client, url, |mut request| {
+        if let Some(etag) = validators["etag"].as_str() {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = validators["last_modified"].as_str() {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    })?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        trace("  cache hit: the cached page hasn't changed".to_string());
+        return Ok((StatusCode::OK, fs::read_to_string(&body_path)?));
+    }
+
+    let status = response.status();
+    let body = response.text()?;
+    match body_path.exists() {
+        true => trace("  cache miss: the cached page has changed".to_string()),
+        false => trace("  cache miss: the page isn't cached".to_string()),
+    }
+    trace(format!("  body: {}", preview(&body)));
+    if status.is_success() {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| Yaml::String(value.to_string()))
+        };
+        let mut validators = Hash::new();
+        if let Some(etag) = header(ETAG) {
+            validators.insert(Yaml::from_str("etag"), etag);
+        }
+        if let Some(last_modified) = header(LAST_MODIFIED) {
+            validators.insert(Yaml::from_str("last_modified"), last_modified);
+        }
+
+        // A page without validators can't be revalidated, so there's no point keeping it
+        if !validators.is_empty() {
+            validators.insert(Yaml::from_str("url"), Yaml::String(url.to_string()));
+            fs::create_dir_all(cache_dir)?;
+            fs::write(&body_path, &body)?;
+            let mut tmp_string = String::new();
+            YamlEmitter::new(&mut tmp_string)
+                .dump(&Yaml::Hash(validators))
+                .unwrap();
+            fs::write(&validators_path, format!("{}\n", tmp_string))?;
+        }
+    }
+
+    Ok((status, body))
+}
+
+/// Fetches a document from a JSON API, read as YAML, which JSON is all but a subset of. Returns
+/// `None` if there's nothing at the URL.
+///
+/// # Errors
+/// * [`ErrorKind::ApiFailed`](enum.ErrorKind.html#variant.ApiFailed) - the API answered with an error, or with something that isn't JSON
+/// * `reqwest::Error` - the request couldn't be made
+pub fn get_json(
+    client: &Client,
+    credentials: &Credentials,
+    url: &str,
+) -> Result<Option<Yaml>, Box<Error>> {
+    let mut response = credentials.fetch(client, url)?;
+    let failed = |why: String| Box::new(ErrorKind::ApiFailed(url.to_string(), why));
+    match response.status() {
+        StatusCode::NOT_FOUND => return Ok(None),
+        status if !status.is_success() => return Err(failed(status.to_string())),
+        _ => {}
+    }
+
+    let body = response.text()?;
+    trace(format!("  body: {}", preview(&body)));
+    match YamlLoader::load_from_str(&body) {
+        Ok(mut docs) if !docs.is_empty() => Ok(Some(docs.remove(0))),
+        _ => Err(failed("something that isn't JSON".to_string())),
+    }
+}
+
+/// The body of a page kept by `get_page`, without asking the website whether it has changed, or
+/// `None` if the page isn't cached
+pub fn cached_page(cache_dir: &Path, url: &str) -> Option<String> {
+    let page = fs::read_to_string(cached_page_paths(cache_dir, url).0).ok();
+    let outcome = match page {
+        Some(_) => "hit",
+        None => "miss",
+    };
+    trace(format!(
+        "GET {} -> cache {}, without asking the website",
+        url, outcome
+    ));
+    page
+}
+
+/// Where a cached page's body and validators are kept. Files are named after the MD5 of the URL,
+/// since URLs can't be file names.
+fn cached_page_paths(cache_dir: &Path, url: &str) -> (PathBuf, PathBuf) {
+    let key = format!("{:x}", md5::compute(url.as_bytes()));
+    (
+        cache_dir.join(format!("{}.html", key)),
+        cache_dir.join(format!("{}.yml", key)),
+    )
+}
+
+/// Reads the validators of a cached page. A cache that can't be read just isn't used.
+fn read_validators(path: &Path) -> Yaml {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| YamlLoader::load_from_str(&contents).ok())
+        .and_then(|docs| docs.into_iter().next())
+        .unwrap_or(Yaml::Null)
+}
+
+/// The headers every request is sent with
+fn default_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        USER_AGENT,
+        HeaderValue::from_static(concat!("dropper/", env!("CARGO_PKG_VERSION"))),
+    );
+    headers
+}
+
+/// Where a request went: every URL it was redirected through, with the status each answered,
+/// ending with the one that answered for real
+#[derive(Debug, Clone, Default)]
+pub struct RedirectTrace {
+    pub hops: Vec<(String, StatusCode)>,
+    /// Whether the last answer was Cloudflare checking that the client is a person
+    pub challenged: bool,
+}
+
+impl fmt::Display for RedirectTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines = self
+            .hops
+            .iter()
+            .map(|(url, status)| format!("  {} -> {}", url, status))
+            .collect::<Vec<String>>();
+        match self.hops.last() {
+            _ if self.challenged => lines.push(
+                "  that was a Cloudflare challenge, so the website is probably blocking this host"
+                    .to_string(),
+            ),
+            Some((_, StatusCode::NOT_FOUND)) | Some((_, StatusCode::GONE)) => lines
+                .push("  the file or its project has probably been removed".to_string()),
+            _ => {}
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Follows a URL's redirects one at a time, to find out where a request that went wrong ended
+/// up. This makes requests of its own, so it's only for diagnosing failures. Each hop is sent
+/// the credentials of its own host.
+///
+/// # Errors
+/// * `reqwest::Error` - a request couldn't be made at all
+pub fn trace_redirects(url: &str, credentials: &Credentials) -> reqwest::Result<RedirectTrace> {
+    let client = no_redirect_client()?;
+
+    let mut trace = RedirectTrace::default();
+    let mut next = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let mut response = credentials.get(&client, &next).send()?;
+        let status = response.status();
+        self::trace(format!("GET {} -> {} (tracing redirects)", next, status));
+        trace.hops.push((next.clone(), status));
+
+        match redirect_location(&response) {
+            Some(location) => next = location.to_string(),
+            _ => {
+                trace.challenged = is_challenge(&mut response);
+                break;
+            }
+        }
+    }
+
+    Ok(trace)
+}
+
+/// Where a response redirects to, if it's a redirect
+fn redirect_location(response: &Response) -> Option<Url> {
+    if !response.status().is_redirection() {
+        return None;
+    }
+    response
+        .headers()
+        .get(LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|location| response_url(response).join(location).ok())
+}
+
+/// A client that doesn't follow redirects, sharing its connection pool with every other use of
+/// it
+fn no_redirect_client() -> reqwest::Result<Client> {
+    let mut client = NO_REDIRECT_CLIENT.lock().unwrap();
+    if client.is_none() {
+        *client = Some(
+            Client::builder()
+                .default_headers(default_headers())
+                .max_idle_per_host(MAX_IDLE_PER_HOST)
+                .redirect(RedirectPolicy::none())
+                .build()?,
+        );
+    }
+    Ok(client.clone().unwrap())
+}
+
+/// Whether a response is a Cloudflare challenge rather than what was asked for
+fn is_challenge(response: &mut Response) -> bool {
+    if response.headers().contains_key("cf-mitigated") {
+        return true;
+    }
+    let from_cloudflare = response
+        .headers()
+        .get(SERVER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|server| server.eq_ignore_ascii_case("cloudflare"));
+    match response.status() {
+        StatusCode::FORBIDDEN | StatusCode::SERVICE_UNAVAILABLE if from_cloudflare => {
+            let body = response.text().unwrap_or_default();
+            CLOUDFLARE_CHALLENGE_MARKERS
+                .iter()
+                .any(|marker| body.contains(marker))
+        }
+        _ => false,
+    }
+}
+
+/// The name of the file a response holds: the one its `Content-Disposition` gives, or else the
+/// last segment of the URL it ended up at after redirects
+pub fn file_name(response: &Response) -> Option<String> {
+    let disposition = response
+        .headers()
+        .get(CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(disposition_file_name);
+    if disposition.is_some() {
+        return disposition;
+    }
+
+    response_url(response)
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| percent_decode(segment.as_bytes()).decode_utf8_lossy().to_string())
+}
+
+/// Pulls the file name out of a `Content-Disposition` header value, preferring the encoded
+/// `filename*` parameter over the plain `filename` one, as RFC 6266 asks
+fn disposition_file_name(value: &str) -> Option<String> {
+    let mut plain = None;
+    for param in value.split(';').map(|param| param.trim()) {
+        let mut parts = param.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim().to_lowercase();
+        let value = match parts.next() {
+            Some(value) => value.trim(),
+            None => continue,
+        };
+        match key.as_str() {
+            // `filename*=UTF-8''name.jar`; the charset is UTF-8 in practice
+            "filename*" => {
+                if let Some((_, encoded)) = value.split_once("''") {
+                    return Some(percent_decode(encoded.as_bytes()).decode_utf8_lossy().to_string());
+                }
+            }
+            "filename" => plain = Some(value.trim_matches('"').to_string()),
+            _ => {}
+        }
+    }
+
+    plain.filter(|name| !name.is_empty())
+}
+
+/// Whether a response says it holds a web page, which is what blocked downloads usually get
+/// instead of the file
+pub fn is_html(response: &Response) -> bool {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            let value = value.to_lowercase();
+            value.starts_with("text/html") || value.starts_with("application/xhtml")
+        })
+}
+
+/// A reader that reads no faster than a set number of bytes per second, so that downloads don't
+/// crowd out a running server's players
+pub struct Throttled<R> {
+    inner: R,
+    bytes_per_second: u64,
+    started: Instant,
+    read: u64,
+}
+
+impl<R: Read> Throttled<R> {
+    pub fn new(inner: R, bytes_per_second: u64) -> Throttled<R> {
+        Throttled {
+            inner: inner,
+            bytes_per_second: bytes_per_second.max(1),
+            started: Instant::now(),
+            read: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for Throttled<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Reading a tenth of a second's worth at a time keeps the rate smooth
+        let chunk = ((self.bytes_per_second / 10).max(1) as usize).min(buf.len());
+        let n = self.inner.read(&mut buf[..chunk])?;
+        self.read += n as u64;
+
+        // Wait until the bytes read so far are within the rate
+        let due = Duration::from_millis(self.read * 1000 / self.bytes_per_second);
+        let elapsed = self.started.elapsed();
+        if due > elapsed {
+            thread::sleep(due - elapsed);
+        }
+        Ok(n)
+    }
+}
+
+/// Parses a download rate like `500K` or `2M` into bytes per second. Suffixes are powers of
+/// 1024, and a plain number is in bytes.
+pub fn parse_rate(rate: &str) -> Option<u64> {
+    let rate = rate.trim().trim_end_matches("/s");
+    let (number, multiplier) = match rate.chars().last()?.to_ascii_uppercase() {
+        'K' => (&rate[..rate.len() - 1], 1024.0),
+        'M' => (&rate[..rate.len() - 1], 1024.0 * 1024.0),
+        'G' => (&rate[..rate.len() - 1], 1024.0 * 1024.0 * 1024.0),
+        _ => (rate, 1.0),
+    };
+
+    match number.trim().parse::<f64>() {
+        Ok(number) if number > 0.0 => Some((number * multiplier) as u64),
+        _ => None,
+    }
+}
@@ -0,0 +1,616 @@
+//! This module inspects plugin JARs. Every Bukkit plugin ships a `plugin.yml` at the root of its
+//! JAR which describes the plugin's name and version, as well as the other plugins it needs in
+//! order to run. Reading it lets the backend reason about plugins after they have been downloaded,
+//! whether or not dropper was the one that put them there. It also lists the commands and
+//! permissions a plugin registers, so they can be set up before the server first loads it.
+//!
+//! Some projects ship a ZIP instead, with the plugin's JAR in it and often a folder of default
+//! configs next to the JAR. Those are called distributions here, and the JAR and configs can be
+//! taken out of them.
+
+use glob::Pattern;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use yaml_rust::{Yaml, YamlLoader};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+const PLUGIN_DESCRIPTION_FILE: &'static str = "plugin.yml";
+// Paper plugins can describe themselves here instead, with the same name and version keys
+const PAPER_DESCRIPTION_FILE: &'static str = "paper-plugin.yml";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // The JAR does not contain a plugin.yml, so it probably isn't a plugin. Takes the JAR path
+    // as a param.
+    DescriptionMissing(String),
+    // The plugin.yml could not be parsed, or is missing a key every plugin must have. Takes the
+    // JAR path as a param.
+    DescriptionInvalid(String),
+    // The JAR isn't a ZIP archive that can be read to the end, so it's probably truncated.
+    // Takes the JAR path as a param.
+    ArchiveInvalid(String),
+    // A distribution has more than one plugin JAR in it, so it isn't clear which to install.
+    // Takes the archive path and the JARs as params.
+    DistributionAmbiguous(String, Vec<String>),
+    // None of the JARs in a distribution match the patterns the pkg.yml picks them with. Takes
+    // the archive path and the patterns as params.
+    DistributionUnmatched(String, Vec<String>),
+    // Some of the extra JARs picked out of a distribution have the same name in different
+    // folders, so they'd be installed over each other. Takes the archive path and the JARs as
+    // params.
+    DistributionClash(String, Vec<String>),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::DescriptionMissing(s) => format!("{} has no plugin.yml in it", s),
+                ErrorKind::DescriptionInvalid(s) => format!("the plugin.yml in {} is invalid", s),
+                ErrorKind::ArchiveInvalid(s) => format!("{} is truncated or corrupt", s),
+                ErrorKind::DistributionAmbiguous(s, jars) =>
+                    format!("{} has several plugin JARs in it: {}", s, jars.join(", ")),
+                ErrorKind::DistributionUnmatched(s, patterns) =>
+                    format!("none of the JARs in {} match {}", s, patterns.join(", ")),
+                ErrorKind::DistributionClash(s, jars) => format!(
+                    "{} has JARs with the same name in different folders, which would be installed over each other: {}",
+                    s,
+                    jars.join(", ")
+                ),
+            }
+        )
+    }
+}
+
+/// The parts of a plugin's `plugin.yml` that dropper cares about
+#[derive(Debug, Clone)]
+pub struct PluginDescription {
+    /// The name the plugin registers itself under. Other plugins refer to it by this name.
+    pub name: String,
+    pub version: String,
+    /// The plugin's main class, which tells its classes apart from other plugins'
+    pub main: Option<String>,
+    pub description: Option<String>,
+    /// Who wrote the plugin, from its `author` and `authors`
+    pub authors: Vec<String>,
+    /// Plugins that must be present for this one to load
+    pub depend: Vec<String>,
+    /// Plugins this one integrates with if they are present
+    pub softdepend: Vec<String>,
+    /// The newest server API the plugin was built against, e.g. `1.13`. Older plugins leave this out.
+    pub api_version: Option<String>,
+    /// The commands the plugin registers, in the order plugin.yml lists them
+    pub commands: Vec<PluginCommand>,
+    /// The permissions the plugin declares, in the order plugin.yml lists them
+    pub permissions: Vec<PluginPermission>,
+}
+
+/// A command declared in a plugin's `plugin.yml`
+#[derive(Debug, Clone)]
+pub struct PluginCommand {
+    /// The command's name, without the leading slash
+    pub name: String,
+    pub description: Option<String>,
+    pub usage: Option<String>,
+    /// Other names the command can be run by
+    pub aliases: Vec<String>,
+    /// The permission needed to run the command, if it needs one
+    pub permission: Option<String>,
+}
+
+/// A permission declared in a plugin's `plugin.yml`
+#[derive(Debug, Clone)]
+pub struct PluginPermission {
+    pub name: String,
+    pub description: Option<String>,
+    /// Who has the permission unless told otherwise: `true`, `false`, `op` or `not op`. Bukkit
+    /// gives it to ops if this is left out.
+    pub default: Option<String>,
+    /// The permissions this one sets, and whether it grants or revokes each of them
+    pub children: Vec<(String, bool)>,
+}
+
+impl PluginDescription {
+    /// Reads the `plugin.yml` out of the JAR at `path`, or the `paper-plugin.yml` if there's no
+    /// plugin.yml
+    ///
+    /// # Errors
+    /// * [`ErrorKind::DescriptionMissing`](enum.ErrorKind.html#variant.DescriptionMissing) - the JAR has no plugin.yml
+    /// * [`ErrorKind::DescriptionInvalid`](enum.ErrorKind.html#variant.DescriptionInvalid) - the plugin.yml could not be understood
+    /// * `zip::result::ZipError` - the file is not a valid JAR
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn from_jar(path: &Path) -> Result<PluginDescription, Box<Error>> {
+        let path_name = path.display().to_string();
+        let mut archive = ZipArchive::new(File::open(path)?)?;
+
+        let mut contents = None;
+        for name in [PLUGIN_DESCRIPTION_FILE, PAPER_DESCRIPTION_FILE].iter() {
+            match archive.by_name(name) {
+                Ok(mut file) => {
+                    let mut text = String::new();
+                    file.read_to_string(&mut text)?;
+                    contents = Some(text);
+                    break;
+                }
+                Err(ZipError::FileNotFound) => continue,
+                Err(e) => return Err(Box::new(e)),
+            }
+        }
+        let contents = match contents {
+            Some(contents) => contents,
+            None => return Err(Box::new(ErrorKind::DescriptionMissing(path_name))),
+        };
+
+        let docs = match YamlLoader::load_from_str(&contents) {
+            Ok(docs) => docs,
+            Err(_e) => return Err(Box::new(ErrorKind::DescriptionInvalid(path_name))),
+        };
+        let doc = match docs.first() {
+            Some(doc) => doc,
+            None => return Err(Box::new(ErrorKind::DescriptionInvalid(path_name))),
+        };
+
+        match (yaml_to_string(&doc["name"]), yaml_to_string(&doc["version"])) {
+            (Some(name), Some(version)) => Ok(PluginDescription {
+                name: name,
+                version: version,
+                main: yaml_to_string(&doc["main"]),
+                description: yaml_to_string(&doc["description"]),
+                authors: yaml_to_list(&doc["author"])
+                    .into_iter()
+                    .chain(yaml_to_list(&doc["authors"]))
+                    .collect(),
+                depend: yaml_to_list(&doc["depend"]),
+                softdepend: yaml_to_list(&doc["softdepend"]),
+                api_version: yaml_to_string(&doc["api-version"]),
+                commands: yaml_to_commands(&doc["commands"]),
+                permissions: yaml_to_permissions(&doc["permissions"]),
+            }),
+            _ => Err(Box::new(ErrorKind::DescriptionInvalid(path_name))),
+        }
+    }
+}
+
+/// Reads the description of every plugin JAR in `dir`. Files that aren't plugins are skipped, and
+/// a missing directory simply has no plugins in it.
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured while listing the directory
+pub fn installed_plugins(dir: &Path) -> Result<Vec<PluginDescription>, Box<Error>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut plugins = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "jar") {
+            // A JAR we can't read isn't ours to worry about here
+            if let Ok(description) = PluginDescription::from_jar(&path) {
+                plugins.push(description);
+            }
+        }
+    }
+
+    Ok(plugins)
+}
+
+/// Lists the files in the JAR at `path`, leaving out folders
+///
+/// # Errors
+/// * `zip::result::ZipError` - the file is not a valid JAR
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn entries(path: &Path) -> Result<Vec<String>, Box<Error>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut names = Vec::new();
+    for i in 0..archive.len() {
+        let name = archive.by_index(i)?.name().to_string();
+        if !name.ends_with('/') {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/// Checks that the file at `path` is a whole plugin JAR: a ZIP archive that can be read, with a
+/// plugin.yml or paper-plugin.yml in it that isn't corrupt. This catches error pages, truncated
+/// downloads, and the wrong asset being downloaded, like a project's source code.
+///
+/// # Errors
+/// * [`ErrorKind::ArchiveInvalid`](enum.ErrorKind.html#variant.ArchiveInvalid) - the file isn't a readable ZIP archive
+/// * [`ErrorKind::DescriptionMissing`](enum.ErrorKind.html#variant.DescriptionMissing) - the JAR has no plugin.yml, so it isn't a plugin
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn validate(path: &Path) -> Result<(), Box<Error>> {
+    let path_name = path.display().to_string();
+    let invalid = || -> Box<Error> { Box::new(ErrorKind::ArchiveInvalid(path_name.clone())) };
+    if !is_zip(path)? {
+        return Err(invalid());
+    }
+    // The central directory is at the end of the archive, so a truncated one can't be opened
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(|_| invalid())?;
+
+    for name in [PLUGIN_DESCRIPTION_FILE, PAPER_DESCRIPTION_FILE].iter() {
+        match archive.by_name(name) {
+            // Reading the file through checks it against its CRC
+            Ok(mut file) => {
+                return io::copy(&mut file, &mut io::sink())
+                    .map(|_| ())
+                    .map_err(|_| invalid())
+            }
+            Err(ZipError::FileNotFound) => continue,
+            Err(_) => return Err(invalid()),
+        }
+    }
+    Err(Box::new(ErrorKind::DescriptionMissing(path_name)))
+}
+
+/// Whether the file at `path` starts like a ZIP archive, as every JAR does. This is much
+/// cheaper than reading the archive, and is enough to tell a JAR from an error page.
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn is_zip(path: &Path) -> Result<bool, Box<Error>> {
+    let mut magic = [0u8; 4];
+    let mut file = File::open(path)?;
+    let mut read = 0;
+    while read < magic.len() {
+        match file.read(&mut magic[read..])? {
+            0 => return Ok(false),
+            n => read += n,
+        }
+    }
+
+    // A local file header, or the end of an empty archive
+    Ok(magic == *b"PK\x03\x04" || magic == *b"PK\x05\x06")
+}
+
+/// Finds the JARs to install out of a distribution, returning their paths inside the archive.
+/// With no `patterns`, that's the one plugin JAR in it. Otherwise it's every JAR whose path
+/// matches one of the patterns, in the order of the patterns, so the first is the main one.
+/// The others are installed by their file name, so no two of them can share one.
+/// Returns `None` if the file isn't a distribution: if it can't be read as a ZIP, if it's a
+/// plugin JAR itself, or if there's no plugin JAR in it and no patterns were given.
+///
+/// # Errors
+/// * [`ErrorKind::DistributionAmbiguous`](enum.ErrorKind.html#variant.DistributionAmbiguous) - there are no patterns, and there's more than one plugin JAR in the distribution
+/// * [`ErrorKind::DistributionUnmatched`](enum.ErrorKind.html#variant.DistributionUnmatched) - none of the JARs in the distribution match the patterns
+/// * [`ErrorKind::DistributionClash`](enum.ErrorKind.html#variant.DistributionClash) - two of the JARs after the main one have the same file name
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn distribution_jars(
+    path: &Path,
+    patterns: &[Pattern],
+) -> Result<Option<Vec<String>>, Box<Error>> {
+    let mut archive = match ZipArchive::new(File::open(path)?) {
+        Ok(archive) => archive,
+        Err(_) => return Ok(None),
+    };
+    if has_description(&mut archive) {
+        return Ok(None);
+    }
+
+    let mut names = archive
+        .file_names()
+        .filter(|name| name.to_lowercase().ends_with(".jar"))
+        .map(|name| name.to_string())
+        .collect::<Vec<String>>();
+    names.sort();
+
+    if !patterns.is_empty() {
+        let mut jars = Vec::new();
+        for pattern in patterns.iter() {
+            let mut matched = names
+                .iter()
+                .filter(|name| pattern.matches(name) && !jars.contains(*name))
+                .cloned()
+                .collect::<Vec<String>>();
+            jars.append(&mut matched);
+        }
+        if jars.is_empty() {
+            return Err(Box::new(ErrorKind::DistributionUnmatched(
+                path.display().to_string(),
+                patterns.iter().map(|p| p.to_string()).collect(),
+            )));
+        }
+        // Names are compared without case, since plugin directories can be on filesystems that
+        // don't tell them apart
+        let file_name = |jar: &String| {
+            Path::new(jar)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_lowercase())
+        };
+        let clashing = jars
+            .iter()
+            .skip(1)
+            .filter(|jar| {
+                jars.iter()
+                    .skip(1)
+                    .filter(|other| file_name(other) == file_name(jar))
+                    .count()
+                    > 1
+            })
+            .cloned()
+            .collect::<Vec<String>>();
+        return match clashing.is_empty() {
+            true => Ok(Some(jars)),
+            false => Err(Box::new(ErrorKind::DistributionClash(
+                path.display().to_string(),
+                clashing,
+            ))),
+        };
+    }
+
+    let mut jars = Vec::new();
+    for name in names {
+        // Archives inside archives can't be read in place, so each JAR is read into memory
+        let mut contents = Vec::new();
+        archive.by_name(&name)?.read_to_end(&mut contents)?;
+        if let Ok(mut jar) = ZipArchive::new(Cursor::new(contents)) {
+            if has_description(&mut jar) {
+                jars.push(name);
+            }
+        }
+    }
+
+    match jars.len() {
+        0 => Ok(None),
+        1 => Ok(Some(jars)),
+        _ => Err(Box::new(ErrorKind::DistributionAmbiguous(
+            path.display().to_string(),
+            jars,
+        ))),
+    }
+}
+
+/// Copies a file out of a ZIP archive
+///
+/// # Errors
+/// * `zip::result::ZipError` - the archive is invalid, or the file isn't in it
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn extract_file(path: &Path, name: &str, dest: &Path) -> Result<(), Box<Error>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut file = archive.by_name(name)?;
+    io::copy(&mut file, &mut File::create(dest)?)?;
+    Ok(())
+}
+
+/// Copies the default configs out of a distribution into the plugin's data folder. They are
+/// expected in a folder next to the JAR that is named after the plugin, like the data folder on
+/// the server is. Files that are already in the data folder are left alone, since the server's
+/// own configs matter more than the defaults. Returns the files that were copied, relative to
+/// the data folder.
+///
+/// # Arguments
+///
+/// * `path` - The distribution
+/// * `jar` - The path of the plugin JAR inside the distribution
+/// * `plugin_name` - The name the plugin registers itself under
+/// * `data_dir` - The plugin's data folder
+///
+/// # Errors
+/// * `zip::result::ZipError` - the distribution is invalid
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn extract_data_folder(
+    path: &Path,
+    jar: &str,
+    plugin_name: &str,
+    data_dir: &Path,
+) -> Result<Vec<PathBuf>, Box<Error>> {
+    let folder = match Path::new(jar).parent() {
+        Some(parent) => parent.join(plugin_name),
+        None => PathBuf::from(plugin_name),
+    };
+    // Plugins don't always name the folder with the same case as themselves
+    let folder = folder
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+        .collect::<Vec<String>>();
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    let mut extracted = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        // Names that would leave the archive are skipped, so they can't leave the data folder
+        let name = match file.enclosed_name() {
+            Some(name) if file.is_file() => name.to_path_buf(),
+            _ => continue,
+        };
+        let components = name
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        let in_folder = components.len() > folder.len()
+            && components
+                .iter()
+                .zip(folder.iter())
+                .all(|(c, f)| c.to_lowercase() == *f);
+        if !in_folder {
+            continue;
+        }
+
+        let relative = components[folder.len()..].iter().collect::<PathBuf>();
+        let dest = data_dir.join(&relative);
+        if dest.exists() {
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut file, &mut File::create(&dest)?)?;
+        extracted.push(relative);
+    }
+
+    Ok(extracted)
+}
+
+/// Whether a plugin JAR has a plugin.yml or paper-plugin.yml in it
+fn has_description<R: Read + io::Seek>(archive: &mut ZipArchive<R>) -> bool {
+    [PLUGIN_DESCRIPTION_FILE, PAPER_DESCRIPTION_FILE]
+        .iter()
+        .any(|name| archive.by_name(name).is_ok())
+}
+
+/// plugin.yml is hand-written, so scalars like `version: 1.0` come through as numbers
+fn yaml_to_string(yaml: &Yaml) -> Option<String> {
+    match yaml {
+        Yaml::String(s) | Yaml::Real(s) => Some(s.clone()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        _ => None,
+    }
+}
+
+/// Dependency keys are usually lists, but a lone name is accepted by Bukkit too
+fn yaml_to_list(yaml: &Yaml) -> Vec<String> {
+    match yaml {
+        Yaml::Array(items) => items.iter().filter_map(yaml_to_string).collect(),
+        other => yaml_to_string(other).into_iter().collect(),
+    }
+}
+
+/// Reads the `commands` section, which maps each command's name to its details. A command
+/// with no details at all is still a command.
+fn yaml_to_commands(yaml: &Yaml) -> Vec<PluginCommand> {
+    let commands = match yaml {
+        Yaml::Hash(commands) => commands,
+        _ => return Vec::new(),
+    };
+
+    commands
+        .iter()
+        .filter_map(|(name, details)| {
+            yaml_to_string(name).map(|name| PluginCommand {
+                name: name,
+                description: yaml_to_string(&details["description"]),
+                usage: yaml_to_string(&details["usage"]),
+                aliases: yaml_to_list(&details["aliases"]),
+                permission: yaml_to_string(&details["permission"]),
+            })
+        })
+        .collect()
+}
+
+/// Reads the `permissions` section, which maps each permission's name to its details
+fn yaml_to_permissions(yaml: &Yaml) -> Vec<PluginPermission> {
+    let permissions = match yaml {
+        Yaml::Hash(permissions) => permissions,
+        _ => return Vec::new(),
+    };
+
+    permissions
+        .iter()
+        .filter_map(|(name, details)| {
+            let default = match &details["default"] {
+                Yaml::Boolean(b) => Some(b.to_string()),
+                other => yaml_to_string(other),
+            };
+            // Children are usually a map of permissions to whether they're granted, but a list
+            // of permissions that are all granted works too
+            let children = match &details["children"] {
+                Yaml::Hash(children) => children
+                    .iter()
+                    .filter_map(|(child, granted)| {
+                        yaml_to_string(child).map(|child| (child, granted.as_bool().unwrap_or(true)))
+                    })
+                    .collect(),
+                other => yaml_to_list(other)
+                    .into_iter()
+                    .map(|child| (child, true))
+                    .collect(),
+            };
+            yaml_to_string(name).map(|name| PluginPermission {
+                name: name,
+                description: yaml_to_string(&details["description"]),
+                default: default,
+                children: children,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Write;
+    use std::process;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn test_zip(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for (name, contents) in entries.iter() {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn test_distribution(name: &str, jars: &[&str]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("dropper-jar-{}-{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let jar = dir.join("plugin.jar");
+        test_zip(
+            &jar,
+            &[(PLUGIN_DESCRIPTION_FILE, b"name: Chat\nversion: 1.0\n")],
+        );
+        let contents = fs::read(&jar).unwrap();
+        let distribution = dir.join("distribution.zip");
+        test_zip(
+            &distribution,
+            &jars
+                .iter()
+                .map(|name| (*name, contents.as_slice()))
+                .collect::<Vec<(&str, &[u8])>>(),
+        );
+        distribution
+    }
+
+    fn patterns(patterns: &[&str]) -> Vec<Pattern> {
+        patterns.iter().map(|p| Pattern::new(p).unwrap()).collect()
+    }
+
+    #[test]
+    fn extra_jars_with_the_same_name_are_refused() {
+        let distribution = test_distribution(
+            "clash",
+            &[
+                "Chat.jar",
+                "bukkit/addons/Chat.jar",
+                "spigot/addons/chat.jar",
+            ],
+        );
+        let e = distribution_jars(&distribution, &patterns(&["Chat.jar", "*/addons/*.jar"]))
+            .unwrap_err();
+        match e.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::DistributionClash(_, jars)) => {
+                assert_eq!(jars, &["bukkit/addons/Chat.jar", "spigot/addons/chat.jar"])
+            }
+            _ => panic!("expected the JARs to clash, got {}", e),
+        }
+
+        fs::remove_dir_all(distribution.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn the_main_jar_can_share_its_name_with_an_extra_one() {
+        let distribution = test_distribution(
+            "main-clash",
+            &["Chat.jar", "addons/Chat.jar", "addons/Bot.jar"],
+        );
+        let jars = distribution_jars(&distribution, &patterns(&["Chat.jar", "addons/*.jar"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(jars, ["Chat.jar", "addons/Bot.jar", "addons/Chat.jar"]);
+
+        fs::remove_dir_all(distribution.parent().unwrap()).unwrap();
+    }
+}
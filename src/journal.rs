@@ -0,0 +1,392 @@
+//! This module keeps a journal of what an operation is doing to the server, so that if dropper is
+//! killed halfway through, the next run can put things back the way they were instead of leaving
+//! a half updated server behind.
+//!
+//! When an operation begins, the manifests it may touch are backed up into the journal directory.
+//! Every JAR that gets put into the plugin directory is recorded before it is moved there, and
+//! JARs that get removed are moved into the journal directory rather than deleted. Once the
+//! operation is done, the journal is thrown away. If a journal is still around when dropper
+//! starts, the operation it belongs to never finished, and rolling it back is a matter of
+//! restoring the manifests, deleting the added JARs and moving the removed ones back.
+
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+const JOURNAL_FILE: &'static str = "journal.yml";
+
+/// An operation in progress
+pub struct Journal {
+    dir: PathBuf,
+    description: String,
+    /// Each manifest, and where it was backed up to (or `None` if it didn't exist yet)
+    manifests: Vec<(PathBuf, Option<PathBuf>)>,
+    /// JARs that were put into the plugin directory
+    added: Vec<PathBuf>,
+    /// JARs that were removed, and where they were moved to
+    removed: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Journal {
+    /// Starts journaling an operation, backing up the given manifests first
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Where to keep the journal. It shouldn't exist yet.
+    /// * `description` - What the operation is, e.g. `add worldedit`
+    /// * `manifests` - The files the operation may change
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn begin(dir: &Path, description: &str, manifests: &[PathBuf]) -> Result<Journal, Box<Error>> {
+        fs::create_dir_all(dir)?;
+
+        let mut backups = Vec::new();
+        for (i, manifest) in manifests.iter().enumerate() {
+            match manifest.exists() {
+                true => {
+                    let backup = dir.join(format!("manifest-{}", i));
+                    fs::copy(manifest, &backup)?;
+                    backups.push((manifest.clone(), Some(backup)));
+                }
+                false => backups.push((manifest.clone(), None)),
+            }
+        }
+
+        let journal = Journal {
+            dir: dir.to_path_buf(),
+            description: description.to_string(),
+            manifests: backups,
+            added: Vec::new(),
+            removed: Vec::new(),
+        };
+        journal.save()?;
+        Ok(journal)
+    }
+
+    /// Records that a JAR is about to be put into the plugin directory. This has to happen before
+    /// the JAR is moved there, so a crash in between is still rolled back.
+    pub fn record_added(&mut self, path: &Path) -> Result<(), Box<Error>> {
+        self.added.push(path.to_path_buf());
+        self.save()
+    }
+
+    /// Removes a JAR by moving it into the journal directory, where it can be restored from
+    pub fn remove_file(&mut self, path: &Path) -> Result<(), Box<Error>> {
+        let backup = self.dir.join(format!("removed-{}", self.removed.len()));
+        self.removed.push((path.to_path_buf(), backup.clone()));
+        self.save()?;
+        move_file(path, &backup)?;
+        Ok(())
+    }
+
+    /// Finishes the operation, throwing the journal and its backups away
+    pub fn commit(self) -> Result<(), Box<Error>> {
+        fs::remove_dir_all(&self.dir)?;
+        Ok(())
+    }
+
+    /// Rolls back the operation that left a journal in `dir`, if any, and returns its
+    /// description. Rolling back is safe to repeat, in case dropper is killed during this too.
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn recover(dir: &Path) -> Result<Option<String>, Box<Error>> {
+        let journal = match Journal::load(dir)? {
+            Some(journal) => journal,
+            None => {
+                // The operation crashed before it got anywhere
+                if dir.exists() {
+                    fs::remove_dir_all(dir)?;
+                }
+                return Ok(None);
+            }
+        };
+
+        for path in journal.added.iter() {
+            if let Err(e) = fs::remove_file(path) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    return Err(Box::new(e));
+                }
+            }
+        }
+        for (path, backup) in journal.removed.iter() {
+            if backup.exists() {
+                move_file(backup, path)?;
+            }
+        }
+        for (manifest, backup) in journal.manifests.iter() {
+            match backup {
+                Some(backup) => {
+                    fs::copy(backup, manifest)?;
+                }
+                None => {
+                    if manifest.exists() {
+                        fs::remove_file(manifest)?;
+                    }
+                }
+            }
+        }
+
+        let description = journal.description.clone();
+        journal.commit()?;
+        Ok(Some(description))
+    }
+
+    fn save(&self) -> Result<(), Box<Error>> {
+        let path_yaml = |path: &Path| Yaml::String(path.to_string_lossy().to_string());
+        let pair_yaml = |path: &Path, backup: Option<&PathBuf>| {
+            let mut entry = Hash::new();
+            entry.insert(Yaml::from_str("path"), path_yaml(path));
+            entry.insert(
+                Yaml::from_str("backup"),
+                match backup {
+                    Some(backup) => path_yaml(backup),
+                    None => Yaml::Null,
+                },
+            );
+            Yaml::Hash(entry)
+        };
+
+        let mut doc = Hash::new();
+        doc.insert(
+            Yaml::from_str("description"),
+            Yaml::String(self.description.clone()),
+        );
+        doc.insert(
+            Yaml::from_str("manifests"),
+            Yaml::Array(
+                self.manifests
+                    .iter()
+                    .map(|(path, backup)| pair_yaml(path, backup.as_ref()))
+                    .collect(),
+            ),
+        );
+        doc.insert(
+            Yaml::from_str("added"),
+            Yaml::Array(self.added.iter().map(|path| path_yaml(path)).collect()),
+        );
+        doc.insert(
+            Yaml::from_str("removed"),
+            Yaml::Array(
+                self.removed
+                    .iter()
+                    .map(|(path, backup)| pair_yaml(path, Some(backup)))
+                    .collect(),
+            ),
+        );
+
+        let mut tmp_string = String::new();
+        YamlEmitter::new(&mut tmp_string)
+            .dump(&Yaml::Hash(doc))
+            .unwrap();
+
+        // Write the new journal next to the old one and swap it in, so there's always a whole
+        // journal on disk
+        let path = self.dir.join(JOURNAL_FILE);
+        let tmp_path = self.dir.join(format!("{}.tmp", JOURNAL_FILE));
+        fs::write(&tmp_path, format!("{}\n", tmp_string))?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    fn load(dir: &Path) -> Result<Option<Journal>, Box<Error>> {
+        let path = dir.join(JOURNAL_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let docs = match YamlLoader::load_from_str(&fs::read_to_string(&path)?) {
+            Ok(docs) => docs,
+            Err(_) => return Ok(None),
+        };
+        let doc = match docs.first() {
+            Some(doc) => doc,
+            None => return Ok(None),
+        };
+
+        let to_path = |yaml: &Yaml| yaml.as_str().map(PathBuf::from);
+        let pairs = |yaml: &Yaml| match yaml {
+            Yaml::Array(items) => items
+                .iter()
+                .filter_map(|item| to_path(&item["path"]).map(|path| (path, to_path(&item["backup"]))))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(Some(Journal {
+            dir: dir.to_path_buf(),
+            description: doc["description"].as_str().unwrap_or("").to_string(),
+            manifests: pairs(&doc["manifests"]),
+            added: match &doc["added"] {
+                Yaml::Array(items) => items.iter().filter_map(to_path).collect(),
+                _ => Vec::new(),
+            },
+            removed: pairs(&doc["removed"])
+                .into_iter()
+                .filter_map(|(path, backup)| backup.map(|backup| (path, backup)))
+                .collect(),
+        }))
+    }
+}
+
+/// Moves a file, falling back on copying it when the plugin directory is on another filesystem
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            fs::copy(from, to)?;
+            if let Err(e) = fs::remove_file(from) {
+                fs::remove_file(to)?;
+                return Err(e);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::{env, process};
+
+    fn test_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("dropper-journal-{}-{}", test, process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join("plugins")).unwrap();
+        dir
+    }
+
+    /// Every file under `dir` and what's in it, leaving out the journal itself
+    fn snapshot(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        let mut files = BTreeMap::new();
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(current) = dirs.pop() {
+            for entry in fs::read_dir(&current).unwrap() {
+                let path = entry.unwrap().path();
+                if path.file_name().unwrap() == "journal" {
+                    continue;
+                }
+                match path.is_dir() {
+                    true => dirs.push(path),
+                    false => {
+                        let contents = fs::read(&path).unwrap();
+                        files.insert(path.strip_prefix(dir).unwrap().to_path_buf(), contents);
+                    }
+                }
+            }
+        }
+        files
+    }
+
+    /// Journals an install that gets cut short after adding a JAR, replacing another and
+    /// extracting a config for it, the same way `PackageBackend` would
+    fn interrupted_install(dir: &Path, manifests: &[PathBuf]) {
+        let plugins = dir.join("plugins");
+        let mut journal = Journal::begin(&dir.join("journal"), "add worldedit", manifests).unwrap();
+
+        let added = plugins.join("worldedit.jar");
+        journal.record_added(&added).unwrap();
+        fs::write(&added, "worldedit 7.2").unwrap();
+
+        let replaced = plugins.join("vault.jar");
+        journal.remove_file(&replaced).unwrap();
+        journal.record_added(&replaced).unwrap();
+        fs::write(&replaced, "vault 1.8").unwrap();
+
+        let config = plugins.join("WorldEdit").join("config.yml");
+        fs::create_dir_all(config.parent().unwrap()).unwrap();
+        journal.record_added(&config).unwrap();
+        fs::write(&config, "wand-item: minecraft:wooden_axe").unwrap();
+
+        fs::write(&manifests[0], "packages: [vault, worldedit]").unwrap();
+        fs::write(&manifests[1], "worldedit: 7.2").unwrap();
+        // Dropper is killed here, before the journal is committed
+    }
+
+    #[test]
+    fn recovering_an_interrupted_install_restores_the_directory_exactly() {
+        let dir = test_dir("install");
+        fs::write(dir.join("plugins").join("vault.jar"), "vault 1.7").unwrap();
+        fs::write(dir.join("plugins").join("essentials.jar"), "essentials").unwrap();
+        fs::write(dir.join("pkg.yml"), "packages: [vault]").unwrap();
+        let manifests = vec![dir.join("pkg.yml"), dir.join("pkg.lock")];
+        let before = snapshot(&dir);
+
+        interrupted_install(&dir, &manifests);
+        assert_ne!(snapshot(&dir), before);
+
+        let description = Journal::recover(&dir.join("journal")).unwrap();
+        assert_eq!(description, Some("add worldedit".to_string()));
+        // The extracted config is gone, but the directory it was extracted into is left behind
+        fs::remove_dir(dir.join("plugins").join("WorldEdit")).unwrap();
+        assert_eq!(snapshot(&dir), before);
+        assert!(!dir.join("journal").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recovering_twice_is_the_same_as_recovering_once() {
+        let dir = test_dir("twice");
+        fs::write(dir.join("plugins").join("vault.jar"), "vault 1.7").unwrap();
+        let manifests = vec![dir.join("pkg.yml"), dir.join("pkg.lock")];
+        let before = snapshot(&dir);
+
+        interrupted_install(&dir, &manifests);
+        // A copy of the journal, as if dropper were killed partway through recovering
+        let journal_dir = dir.join("journal");
+        let copy_dir = dir.join("journal-copy");
+        fs::create_dir(&copy_dir).unwrap();
+        for entry in fs::read_dir(&journal_dir).unwrap() {
+            let path = entry.unwrap().path();
+            fs::copy(&path, copy_dir.join(path.file_name().unwrap())).unwrap();
+        }
+
+        Journal::recover(&journal_dir).unwrap();
+        fs::rename(&copy_dir, &journal_dir).unwrap();
+        assert_eq!(
+            Journal::recover(&journal_dir).unwrap(),
+            Some("add worldedit".to_string())
+        );
+        fs::remove_dir(dir.join("plugins").join("WorldEdit")).unwrap();
+        assert_eq!(snapshot(&dir), before);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn committed_operations_are_not_rolled_back() {
+        let dir = test_dir("commit");
+        let manifests = vec![dir.join("pkg.yml")];
+        let mut journal = Journal::begin(&dir.join("journal"), "add vault", &manifests).unwrap();
+        let added = dir.join("plugins").join("vault.jar");
+        journal.record_added(&added).unwrap();
+        fs::write(&added, "vault 1.7").unwrap();
+        journal.commit().unwrap();
+
+        assert_eq!(Journal::recover(&dir.join("journal")).unwrap(), None);
+        assert!(added.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn journals_that_were_never_written_are_thrown_away() {
+        let dir = test_dir("unwritten");
+        let journal_dir = dir.join("journal");
+        fs::create_dir(&journal_dir).unwrap();
+        fs::write(journal_dir.join("manifest-0"), "half copied").unwrap();
+
+        assert_eq!(Journal::recover(&journal_dir).unwrap(), None);
+        assert!(!journal_dir.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
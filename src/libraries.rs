@@ -0,0 +1,55 @@
+//! A small built-in knowledge base of common library plugins. Plenty of plugins lean on the same
+//! handful of libraries (Vault, ProtocolLib, PlaceholderAPI, etc.) without bundling them, and
+//! leave it to the server owner to track them down. When an installed plugin `depend`s on one of
+//! the libraries listed here, the backend knows where to fetch it from.
+
+/// A library plugin that dropper knows how to fetch
+pub struct Library {
+    /// The name the library registers in its `plugin.yml`, which is what dependents list
+    pub name: &'static str,
+    /// The plugin website the library is best fetched from
+    pub plugin_website: &'static str,
+    /// The name of the package on that website
+    pub package_name: &'static str,
+}
+
+pub const LIBRARIES: &'static [Library] = &[
+    Library {
+        name: "Vault",
+        plugin_website: "bukkit",
+        package_name: "vault",
+    },
+    Library {
+        name: "ProtocolLib",
+        plugin_website: "bukkit",
+        package_name: "protocollib",
+    },
+    Library {
+        name: "PlaceholderAPI",
+        plugin_website: "bukkit",
+        package_name: "placeholderapi",
+    },
+    Library {
+        name: "WorldEdit",
+        plugin_website: "bukkit",
+        package_name: "worldedit",
+    },
+    Library {
+        name: "WorldGuard",
+        plugin_website: "bukkit",
+        package_name: "worldguard",
+    },
+    Library {
+        name: "Citizens",
+        plugin_website: "bukkit",
+        package_name: "citizens",
+    },
+];
+
+/// Looks up a library by the name plugins use to depend on it. Bukkit is lenient about the
+/// casing of dependency names, so this is too.
+pub fn find(plugin_name: &str) -> Option<&'static Library> {
+    LIBRARIES
+        .iter()
+        .find(|library| library.name.eq_ignore_ascii_case(plugin_name))
+}
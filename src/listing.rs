@@ -0,0 +1,174 @@
+//! This module fetches packages from plain directory listings, like the ones nginx's `autoindex`
+//! and Apache serve, for builds that are just copied to a web server. A pkg.yml entry with a
+//! `listing` reads the files linked from the page at that URL, and every file whose name matches
+//! the entry's `pattern` is a version of the package:
+//!
+//! ```yaml
+//! ourplugin:
+//!   listing: https://builds.example.com/ourplugin/
+//!   pattern: 'OurPlugin-(?P<version>[\d.]+)\.jar'
+//!   version: "1.4.*"
+//! ```
+//!
+//! The version is what the pattern's `version` group matched, or its first group if it doesn't
+//! have one named that. Without a pattern, a file is a version if its name ends in one, after a
+//! dash or an underscore, like `OurPlugin-1.4.2.jar`. The pattern has to match the whole name.
+//! Listings don't say anything else about the files, but checksum files published alongside
+//! them, like `OurPlugin-1.4.2.jar.sha256`, are checked against like Maven's are.
+
+use crate::http;
+use crate::http::Credentials;
+use crate::parser::PluginFile;
+use crate::repo;
+use regex::Regex;
+use reqwest::{Client, StatusCode};
+use scraper::{Html, Selector};
+use std::error::Error;
+use std::fmt;
+use url::percent_encoding::percent_decode;
+use url::Url;
+use yaml_rust::Yaml;
+
+// What a file name has to look like to be a version, when the pkg.yml doesn't say
+const DEFAULT_PATTERN: &'static str = r"(?i).*[-_]v?(?P<version>\d[\w.+-]*?)\.(?:jar|zip)";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A pkg.yml entry's `listing` isn't a URL, or its `pattern` isn't a regex with a group in it.
+    // Takes the package name and what's wrong as params.
+    SourceInvalid(String, String),
+    // A directory listing couldn't be fetched. Takes its URL and status as params.
+    ListingUnavailable(String, StatusCode),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::SourceInvalid(s, why) => {
+                    format!("the listing of '{}' is invalid: {}", s, why)
+                }
+                ErrorKind::ListingUnavailable(url, status) => {
+                    format!(
+                        "couldn't fetch the directory listing at {}: {}",
+                        url, status
+                    )
+                }
+            }
+        )
+    }
+}
+
+/// A directory listing to install a package from
+#[derive(Debug, Clone)]
+pub struct ListingSource {
+    /// The URL of the listing
+    pub url: String,
+    /// What the names of the files that are versions look like
+    pub pattern: Regex,
+}
+
+impl ListingSource {
+    /// Reads the directory listing out of a pkg.yml entry, or `None` if the entry doesn't have
+    /// one
+    ///
+    /// # Errors
+    /// * [`ErrorKind::SourceInvalid`](enum.ErrorKind.html#variant.SourceInvalid) - the entry has a `listing` key, but not a usable source
+    pub fn from_yaml(name: &str, entry: &Yaml) -> Result<Option<ListingSource>, ErrorKind> {
+        let invalid = |why: &str| ErrorKind::SourceInvalid(name.to_string(), why.to_string());
+        let url = match &entry["listing"] {
+            Yaml::BadValue => return Ok(None),
+            Yaml::String(url) if Url::parse(url).is_ok() => url.clone(),
+            _ => return Err(invalid("the listing should be a URL")),
+        };
+        let pattern = match &entry["pattern"] {
+            Yaml::BadValue => DEFAULT_PATTERN,
+            Yaml::String(pattern) => pattern,
+            _ => return Err(invalid("the pattern should be a string")),
+        };
+        // Anchored, so that the pattern matches the whole name
+        let pattern = Regex::new(&format!("^(?:{})$", pattern))
+            .map_err(|e| invalid(&format!("the pattern isn't a regex: {}", e)))?;
+        if pattern.captures_len() < 2 {
+            return Err(invalid("the pattern needs a group for the version"));
+        }
+
+        // Links in a listing are relative to the folder, which only a trailing slash makes it
+        Ok(Some(ListingSource {
+            url: match url.ends_with('/') {
+                true => url,
+                false => format!("{}/", url),
+            },
+            pattern: pattern,
+        }))
+    }
+
+    /// The version a file is, going by its name, or `None` if it isn't one
+    pub fn version_of(&self, file_name: &str) -> Option<String> {
+        let captures = self.pattern.captures(file_name)?;
+        captures
+            .name("version")
+            .or_else(|| captures.get(1))
+            .map(|version| version.as_str().to_string())
+    }
+
+    /// Lists the versions in the directory listing, newest first, or `None` if there's no
+    /// listing there
+    ///
+    /// # Errors
+    /// * [`ErrorKind::ListingUnavailable`](enum.ErrorKind.html#variant.ListingUnavailable) - the listing couldn't be fetched
+    /// * `reqwest::Error` - the request couldn't be made
+    pub fn enumerate_files(
+        &self,
+        client: &Client,
+        credentials: &Credentials,
+    ) -> Result<Option<Vec<PluginFile>>, Box<Error>> {
+        let mut response = credentials.fetch(client, &self.url)?;
+        match response.status() {
+            StatusCode::NOT_FOUND => return Ok(None),
+            status if !status.is_success() => {
+                return Err(Box::new(ErrorKind::ListingUnavailable(
+                    self.url.clone(),
+                    status,
+                )))
+            }
+            _ => {}
+        }
+
+        let base = http::response_url(&response);
+        let document = Html::parse_document(&response.text()?);
+        let selector = Selector::parse("a[href]").unwrap();
+        let mut files: Vec<PluginFile> = Vec::new();
+        for element in document.select(&selector) {
+            let href = element.value().attr("href").unwrap_or("");
+            // Folders, and the links that sort the listing, aren't files
+            if href.ends_with('/') || href.starts_with('?') || href.starts_with('#') {
+                continue;
+            }
+            let link = match base.join(href) {
+                Ok(link) => link,
+                Err(_) => continue,
+            };
+            let file_name = match link
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+            {
+                Some(file_name) => percent_decode(file_name.as_bytes())
+                    .decode_utf8_lossy()
+                    .to_string(),
+                None => continue,
+            };
+            if let Some(version) = self.version_of(&file_name) {
+                // Listings can link to a file more than once, by its name and by its icon
+                if !files.iter().any(|file| file.version == version) {
+                    files.push(PluginFile::new(version, link.to_string()));
+                }
+            }
+        }
+        Ok(Some(repo::newest_first(files)))
+    }
+}
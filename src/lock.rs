@@ -0,0 +1,477 @@
+//! This module reads and writes the lockfile, `dropper.lock`. Where the pkg.yml says what versions
+//! the user will accept, the lockfile pins down exactly what was installed: the version, the file
+//! it was installed as, where it came from, and its digests. It lives next to the pkg.yml so that
+//! the two can be shared together to reproduce a server.
+//!
+//! ```yaml
+//! format: 1
+//! packages:
+//!   worldedit:
+//!     version: 6.1.9
+//!     file: worldedit@6.1.9.jar
+//!     url: "https://dev.bukkit.org/projects/worldedit/files/2597538/download"
+//!     md5: 1b5e0d1ab3d56d2bd7a7e0ab3a6efcbd
+//!     sha256: 5d1b4cbd2b3ab8e7a3f2fd4e3c7d7a0ad3f1e4b3a8d2c1c7e6de9f2b1a0c3e4f
+//!     project_id: "31043"
+//! ```
+//!
+//! Each digest is keyed by the algorithm it was made with. There's always an MD5 and a SHA-256,
+//! along with whatever else the plugin website published.
+//!
+//! The project ID is only known for packages that were installed by it, but once it's known it
+//! is kept, since unlike the name it never changes. Packages that don't come from the plugin
+//! website also have a `source`.
+//!
+//! Packages installed out of a distribution with several JARs in it also have `jars`, the paths
+//! of the JARs inside the distribution that were picked. The first is installed as `file`, and the
+//! others next to it under names from [`extra_jar_name`](fn.extra_jar_name.html). The digests
+//! are only of `file`.
+//!
+//! The lockfile's `format` is the version of the format it's in; see the
+//! [`format`](../format/index.html) module.
+
+use crate::format;
+use crate::format::FormatStatus;
+use crate::hash;
+use crate::version::Version;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+// Every change to the lockfile's format, oldest first
+const MIGRATIONS: &'static [format::Migration] = &[from_unversioned];
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // The lockfile couldn't be understood. Takes the path as a param.
+    LockInvalid(String),
+    // A locked file isn't inside the plugin directory. Takes the path, the package name and the
+    // file as params.
+    FileOutside(String, String, String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::LockInvalid(s) => format!("the lockfile {} is corrupt", s),
+                ErrorKind::FileOutside(s, name, file) => format!(
+                    "the lockfile {} has {} installed as {}, which isn't inside the plugins folder",
+                    s, name, file
+                ),
+            }
+        )
+    }
+}
+
+/// Exactly what was installed for a package
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockedPackage {
+    pub version: String,
+    /// The name of the JAR in the plugin directory
+    pub file: String,
+    /// Where the JAR was downloaded from
+    pub url: String,
+    /// The digests of the JAR as it was downloaded, keyed by algorithm
+    pub hashes: BTreeMap<String, String>,
+    /// The plugin website's numeric ID for the project, if it is known
+    pub project_id: Option<String>,
+    /// The source the package was installed from, if it isn't the plugin website
+    pub source: Option<String>,
+    /// Whether the package was moved back to an older version, which updating everything
+    /// leaves alone
+    pub downgraded: bool,
+    /// Where the JARs that were installed are inside the distribution they came from, if they
+    /// were picked out of one with several
+    pub jars: Vec<String>,
+}
+
+impl LockedPackage {
+    /// The locked version, split into its parts, if it's numbered
+    pub fn parsed_version(&self) -> Option<Version> {
+        Version::parse(&self.version)
+    }
+
+    /// Whether the locked version is newer than `version`. Versions that aren't numbered can't
+    /// be told apart, so neither is newer.
+    pub fn is_newer_than(&self, version: &str) -> bool {
+        match (self.parsed_version(), Version::parse(version)) {
+            (Some(locked), Some(version)) => locked > version,
+            _ => false,
+        }
+    }
+
+    /// The names of the JARs in the plugin directory that were installed besides `file`
+    pub fn extra_files(&self) -> Vec<String> {
+        self.jars
+            .iter()
+            .skip(1)
+            .map(|jar| extra_jar_name(&self.file, jar))
+            .collect()
+    }
+
+    /// Whether a file in the plugin directory was installed for this package
+    pub fn owns(&self, file: &str) -> bool {
+        self.file == file || self.extra_files().iter().any(|extra| extra == file)
+    }
+}
+
+/// The name a JAR picked out of a distribution is installed under, when it isn't the package's
+/// main JAR. It starts like the main JAR's name, so that it's removed along with it, and ends
+/// with the JAR's own name: `essentialsx@2.18.jar` and `addons/EssentialsXChat.jar` make
+/// `essentialsx@2.18+EssentialsXChat.jar`.
+pub fn extra_jar_name(file: &str, jar: &str) -> String {
+    let stem = Path::new(jar)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!("{}+{}.jar", file.trim_end_matches(".jar"), stem)
+}
+
+/// A path relative to the plugin directory as the lockfile names it, with `/` between folders
+/// on every platform, so lockfiles can be moved between servers
+pub fn file_name(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// Every locked package, keyed by package name
+#[derive(Debug, Clone, Default)]
+pub struct Lockfile {
+    pub packages: BTreeMap<String, LockedPackage>,
+}
+
+impl Lockfile {
+    /// Reads a lockfile. A missing lockfile has nothing locked in it. Lockfiles are passed
+    /// around with the pkg.yml, so one whose files would be written outside the plugin directory
+    /// isn't read at all.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::LockInvalid`](enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * [`ErrorKind::FileOutside`](enum.ErrorKind.html#variant.FileOutside) - a locked file isn't inside the plugin directory
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn load(path: &Path) -> Result<Lockfile, Box<Error>> {
+        if !path.exists() {
+            return Ok(Lockfile::default());
+        }
+
+        let invalid = || ErrorKind::LockInvalid(path.display().to_string());
+        let docs = YamlLoader::load_from_str(&fs::read_to_string(path)?).map_err(|_| invalid())?;
+        let mut doc = match docs.into_iter().next() {
+            Some(Yaml::Hash(doc)) => doc,
+            Some(_) => Hash::new(),
+            None => return Ok(Lockfile::default()),
+        };
+        format::migrate(&path.display().to_string(), &mut doc, MIGRATIONS)?;
+        let doc = Yaml::Hash(doc);
+
+        let mut packages = BTreeMap::new();
+        if let Yaml::Hash(entries) = &doc["packages"] {
+            for (name, entry) in entries.iter() {
+                let field = |key: &str| {
+                    entry[key]
+                        .as_str()
+                        .map(|s| s.to_string())
+                        .ok_or_else(invalid)
+                };
+                let name = name.as_str().ok_or_else(invalid)?.to_string();
+                let file = field("file")?;
+                if !is_inside(&file) {
+                    return Err(Box::new(ErrorKind::FileOutside(
+                        path.display().to_string(),
+                        name,
+                        file,
+                    )));
+                }
+                packages.insert(
+                    name,
+                    LockedPackage {
+                        version: field("version")?,
+                        file: file,
+                        url: field("url")?,
+                        hashes: hash::ALGORITHMS
+                            .iter()
+                            .filter_map(|algorithm| {
+                                entry[*algorithm]
+                                    .as_str()
+                                    .map(|digest| (algorithm.to_string(), digest.to_string()))
+                            })
+                            .collect(),
+                        project_id: entry["project_id"].as_str().map(|s| s.to_string()),
+                        source: entry["source"].as_str().map(|s| s.to_string()),
+                        downgraded: entry["downgraded"].as_bool().unwrap_or(false),
+                        jars: match &entry["jars"] {
+                            Yaml::Array(jars) => jars
+                                .iter()
+                                .filter_map(|jar| jar.as_str().map(|s| s.to_string()))
+                                .collect(),
+                            _ => Vec::new(),
+                        },
+                    },
+                );
+            }
+        }
+
+        Ok(Lockfile { packages: packages })
+    }
+    /// Checks whether the lockfile on disk is in the newest format
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn format_status(path: &Path) -> io::Result<FormatStatus> {
+        format::file_status(path, MIGRATIONS)
+    }
+
+    /// Upgrades the lockfile on disk to the newest format, keeping it as it was next to it.
+    /// Returns the format it was in, or `None` if it didn't need upgrading.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::LockInvalid`](enum.ErrorKind.html#variant.LockInvalid) - the lockfile is corrupt
+    /// * [`format::ErrorKind::FormatTooNew`](../format/enum.ErrorKind.html#variant.FormatTooNew) - the lockfile was written by a newer dropper
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn upgrade(path: &Path) -> Result<Option<i64>, Box<Error>> {
+        let version = match Self::format_status(path)? {
+            FormatStatus::Outdated(version) => version,
+            FormatStatus::TooNew(version) => {
+                return Err(Box::new(format::ErrorKind::FormatTooNew(
+                    path.display().to_string(),
+                    version,
+                    MIGRATIONS.len() as i64,
+                )))
+            }
+            _ => return Ok(None),
+        };
+        let upgraded = Lockfile::load(path)?;
+        fs::copy(path, format::backup_path(path, version))?;
+        upgraded.save(path)?;
+        Ok(Some(version))
+    }
+
+    /// Writes the lockfile, replacing what was there
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn save(&self, path: &Path) -> Result<(), Box<Error>> {
+        let mut entries = Hash::new();
+        for (name, package) in self.packages.iter() {
+            let mut entry = Hash::new();
+            for (key, value) in [
+                ("version", &package.version),
+                ("file", &package.file),
+                ("url", &package.url),
+            ]
+            .iter()
+            {
+                entry.insert(Yaml::from_str(key), Yaml::String(value.to_string()));
+            }
+            for (algorithm, digest) in package.hashes.iter() {
+                entry.insert(Yaml::String(algorithm.clone()), Yaml::String(digest.clone()));
+            }
+            if let Some(project_id) = &package.project_id {
+                entry.insert(
+                    Yaml::from_str("project_id"),
+                    Yaml::String(project_id.clone()),
+                );
+            }
+            if let Some(source) = &package.source {
+                entry.insert(Yaml::from_str("source"), Yaml::String(source.clone()));
+            }
+            if package.downgraded {
+                entry.insert(Yaml::from_str("downgraded"), Yaml::Boolean(true));
+            }
+            if !package.jars.is_empty() {
+                entry.insert(
+                    Yaml::from_str("jars"),
+                    Yaml::Array(package.jars.iter().cloned().map(Yaml::String).collect()),
+                );
+            }
+            entries.insert(Yaml::String(name.clone()), Yaml::Hash(entry));
+        }
+
+        let mut doc = Hash::new();
+        format::stamp(&mut doc, MIGRATIONS);
+        doc.insert(Yaml::from_str("packages"), Yaml::Hash(entries));
+
+        let mut tmp_string = String::new();
+        YamlEmitter::new(&mut tmp_string)
+            .dump(&Yaml::Hash(doc))
+            .unwrap();
+        fs::write(path, format!("{}\n", tmp_string))?;
+        Ok(())
+    }
+
+    /// Finds the package that a file in the plugin directory was locked for
+    pub fn owner_of(&self, file: &str) -> Option<&str> {
+        self.packages
+            .iter()
+            .find(|(_, package)| package.owns(file))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Whether a locked file stays inside the plugin directory: JARs are right in it, and addons in
+/// the folders of the plugins they belong to
+fn is_inside(file: &str) -> bool {
+    let path = Path::new(file);
+    path.components().count() > 0 && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Lockfiles from before the format was versioned may have had versions and project IDs edited
+/// in by hand, written as numbers
+fn from_unversioned(doc: &mut Hash) {
+    format::stringify_fields(doc, "packages", &["version", "project_id"]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::{env, process};
+
+    const UNVERSIONED: &str = include_str!("../fixtures/formats/unversioned.lock");
+
+    fn lock_path(test: &str, contents: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("dropper-lock-{}-{}", test, process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dropper.lock");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn unversioned_lockfiles_are_migrated_as_theyre_read() {
+        let path = lock_path("unversioned", UNVERSIONED);
+        assert_eq!(
+            Lockfile::format_status(&path).unwrap(),
+            FormatStatus::Outdated(0)
+        );
+
+        let lockfile = Lockfile::load(&path).unwrap();
+        let vault = &lockfile.packages["vault"];
+        assert_eq!(vault.version, "1.7");
+        assert_eq!(vault.project_id, Some("33184".to_string()));
+        assert_eq!(vault.file, "vault@1.7.jar");
+        assert_eq!(vault.hashes["md5"], "7f3b9c2a1d4e5f60718293a4b5c6d7e8");
+        let worldedit = &lockfile.packages["worldedit"];
+        assert_eq!(worldedit.version, "7");
+        assert_eq!(worldedit.project_id, None);
+        // Reading a lockfile doesn't change it
+        assert_eq!(fs::read_to_string(&path).unwrap(), UNVERSIONED);
+
+        lockfile.save(&path).unwrap();
+        assert_eq!(
+            Lockfile::format_status(&path).unwrap(),
+            FormatStatus::Current
+        );
+        assert_eq!(
+            Lockfile::load(&path).unwrap().packages["vault"].version,
+            "1.7"
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn lockfiles_from_a_newer_dropper_are_refused() {
+        let path = lock_path("too-new", "format: 2\npackages: {}\n");
+        let e = Lockfile::load(&path).unwrap_err();
+        match e.downcast_ref::<format::ErrorKind>() {
+            Some(format::ErrorKind::FormatTooNew(_, 2, 1)) => {}
+            _ => panic!("expected the lockfile to be too new, got {}", e),
+        }
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn upgrading_keeps_the_old_lockfile_next_to_the_new_one() {
+        let path = lock_path("upgrade", UNVERSIONED);
+        let backup = format::backup_path(&path, 0);
+
+        assert_eq!(Lockfile::upgrade(&path).unwrap(), Some(0));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), UNVERSIONED);
+        assert_eq!(
+            Lockfile::format_status(&path).unwrap(),
+            FormatStatus::Current
+        );
+        let upgraded = fs::read_to_string(&path).unwrap();
+        assert!(upgraded.starts_with("---\nformat: 1\n"), "{}", upgraded);
+        assert_eq!(
+            Lockfile::load(&path).unwrap().packages["vault"].version,
+            "1.7"
+        );
+
+        // Files that are already upgraded are left alone
+        fs::remove_file(&backup).unwrap();
+        assert_eq!(Lockfile::upgrade(&path).unwrap(), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), upgraded);
+        assert!(!backup.exists());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn lockfiles_from_a_newer_dropper_arent_upgraded() {
+        let contents = "format: 2\npackages: {}\n";
+        let path = lock_path("upgrade-too-new", contents);
+        let e = Lockfile::upgrade(&path).unwrap_err();
+        match e.downcast_ref::<format::ErrorKind>() {
+            Some(format::ErrorKind::FormatTooNew(_, 2, 1)) => {}
+            _ => panic!("expected the lockfile to be too new, got {}", e),
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+        assert!(!format::backup_path(&path, 2).exists());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    fn lockfile_with_file(file: &str) -> String {
+        format!(
+            "format: 1\npackages:\n  vault:\n    version: \"1.7\"\n    file: \"{}\"\n    url: x\n",
+            file
+        )
+    }
+
+    #[test]
+    fn files_outside_the_plugin_directory_are_refused() {
+        let path = lock_path("outside", "");
+        for file in ["../x.jar", "/abs.jar", "a/../../b.jar", "./x.jar", ""] {
+            fs::write(&path, lockfile_with_file(file)).unwrap();
+            let e = Lockfile::load(&path).unwrap_err();
+            match e.downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::FileOutside(_, name, outside)) => {
+                    assert_eq!((name.as_str(), outside.as_str()), ("vault", file))
+                }
+                _ => panic!("expected {:?} to be refused, got {}", file, e),
+            }
+        }
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn jars_and_addons_in_the_plugin_directory_are_allowed() {
+        let path = lock_path("inside", "");
+        for file in ["Vault.jar", "Plugin/addon.jar", "Plugin/addons/nested.jar"] {
+            fs::write(&path, lockfile_with_file(file)).unwrap();
+            let lockfile = Lockfile::load(&path).unwrap();
+            assert_eq!(lockfile.packages["vault"].file, file);
+        }
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
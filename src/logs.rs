@@ -0,0 +1,208 @@
+//! This module reads a server's console output, as in its `logs/latest.log`, for the problems it
+//! reports with plugins: plugins that couldn't be loaded, enabled or disabled, exceptions thrown
+//! by their event handlers, tasks and commands, and stack traces that are left to say what threw
+//! them. `dropper test` reads a test server's output with it, and `dropper analyze` reads the
+//! real server's log. It also reads the crash reports a server writes to `crash-reports/`, and
+//! the thread dumps its watchdog logs when it stops responding, for `dropper crash`.
+
+use regex::Regex;
+
+// How many lines after a problem to look for the exception that explains it
+const EXPLANATION_LINES: usize = 3;
+
+/// A problem with a plugin, as reported in a server's console output
+#[derive(Debug, Clone)]
+pub struct Problem {
+    /// The plugin's JAR, if it couldn't be loaded at all
+    pub file: Option<String>,
+    /// The plugin's name, if the output names it
+    pub plugin: Option<String>,
+    /// What went wrong, like "couldn't be enabled"
+    pub what: String,
+    /// The line of the output that reported it
+    pub line: String,
+    /// The exception that explained it, if there was one right after
+    pub explanation: Option<String>,
+    /// The classes and methods the exception was thrown through, innermost first
+    pub frames: Vec<String>,
+}
+
+/// What a crash report, or a watchdog's thread dump, says about a crash
+#[derive(Debug, Clone)]
+pub struct Crash {
+    /// What the report says happened, like "Ticking entity" or "the server stopped responding"
+    pub description: String,
+    /// The classes and methods in the stacks the report has, in the order they appear
+    pub frames: Vec<String>,
+}
+
+/// Finds the problems with plugins in a server's console output
+pub fn problems(output: &[String]) -> Vec<Problem> {
+    // Each pattern captures the plugin, or the JAR if it's the file that's named
+    let patterns = [
+        (
+            r"Could not load '(?:[^']*[/\\])?([^'/\\]+)'",
+            "couldn't be loaded",
+        ),
+        (
+            r"Error occurred while enabling (\S+)",
+            "couldn't be enabled",
+        ),
+        (
+            r"Error occurred while disabling (\S+)",
+            "couldn't be disabled",
+        ),
+        (
+            r"Could not pass event \S+ to (\S+)",
+            "threw an exception handling an event",
+        ),
+        (
+            r"Task #\d+ for (\S+) v\S* generated an exception",
+            "threw an exception in a scheduled task",
+        ),
+        (
+            r"Unhandled exception executing command '[^']*' in plugin (\S+)",
+            "threw an exception running a command",
+        ),
+    ]
+    .iter()
+    .map(|(pattern, what)| (Regex::new(pattern).unwrap(), *what))
+    .collect::<Vec<(Regex, &str)>>();
+    // Stack traces that go through the logger have its timestamp and level in front of each line
+    let prefix = Regex::new(r"^\[[^\]]*\] \[[^\]]*\]: ").unwrap();
+    let exception = Regex::new(r"^\s*(?:Caused by: )?[\w.$]+(?:Exception|Error)(?::.*)?$").unwrap();
+    let cause = Regex::new(r"^\s*Caused by: ").unwrap();
+    let frame = Regex::new(r"^\s+at ([\w.$<>]+)").unwrap();
+    let traced = output
+        .iter()
+        .map(|line| prefix.replace(line, "").to_string())
+        .collect::<Vec<String>>();
+
+    let mut problems = Vec::new();
+    let mut i = 0;
+    while i < output.len() {
+        let line = &output[i];
+        let matched = patterns
+            .iter()
+            .enumerate()
+            .find_map(|(n, (pattern, what))| {
+                pattern
+                    .captures(line)
+                    .map(|captures| (n, captures[1].to_string(), *what))
+            });
+
+        // A stack trace on its own is still a problem, though it's left to the frames to say whose
+        let bare = matched.is_none()
+            && exception.is_match(&traced[i])
+            && traced.get(i + 1).is_some_and(|next| frame.is_match(next));
+        if matched.is_none() && !bare {
+            i += 1;
+            continue;
+        }
+
+        let start = match bare {
+            true => Some(i),
+            false => (i + 1..output.len())
+                .take(EXPLANATION_LINES)
+                .find(|j| exception.is_match(&traced[*j])),
+        };
+        let mut frames = Vec::new();
+        let mut end = i + 1;
+        if let Some(start) = start {
+            end = start + 1;
+            while end < output.len() {
+                let next = &traced[end];
+                if let Some(captures) = frame.captures(next) {
+                    frames.push(captures[1].to_string());
+                } else if !cause.is_match(next) && !next.trim_start().starts_with("...") {
+                    break;
+                }
+                end += 1;
+            }
+        }
+
+        let (file, plugin, what) = match matched {
+            Some((0, file, what)) => (Some(file), None, what),
+            Some((_, plugin, what)) => (None, Some(plugin), what),
+            None => (None, None, "threw an exception"),
+        };
+        problems.push(Problem {
+            file: file,
+            plugin: plugin,
+            what: what.to_string(),
+            line: line.trim().to_string(),
+            explanation: start.map(|start| traced[start].trim().to_string()),
+            frames: frames,
+        });
+        i = end;
+    }
+    problems
+}
+
+/// The plugins that an `UnknownDependencyException` says are missing, as in
+/// `Unknown/missing dependency plugins: [Vault]`, or nothing if it's some other exception
+pub fn missing_dependencies(explanation: &str) -> Vec<String> {
+    if !explanation.contains("UnknownDependencyException") {
+        return Vec::new();
+    }
+    let listed = match (explanation.find('['), explanation.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &explanation[start + 1..end],
+        _ => explanation.rsplit(": ").next().unwrap_or(""),
+    };
+    listed
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty() && !name.contains(' '))
+        .collect()
+}
+
+/// The names of the plugins that a server's console output says were enabled
+pub fn enabled(output: &[String]) -> Vec<String> {
+    let enabling = Regex::new(r"\[[^\]]+\] Enabling (\S+) v").unwrap();
+    output
+        .iter()
+        .filter_map(|line| {
+            enabling
+                .captures(line)
+                .map(|captures| captures[1].to_string())
+        })
+        .collect()
+}
+
+/// Reads a crash report, or a log with a watchdog's thread dump in it, or `None` if it's neither
+pub fn crash(output: &[String]) -> Option<Crash> {
+    let prefix = Regex::new(r"^\[[^\]]*\] \[[^\]]*\]: ").unwrap();
+    // Crash reports put `at` in front of frames, and thread dumps don't
+    let frame = Regex::new(r"^\s+(?:at )?([\w$]+(?:\.[\w$<>]+)+)\(").unwrap();
+
+    let mut description = None;
+    let mut dumped = false;
+    let mut frames = Vec::new();
+    for line in output.iter() {
+        let line = prefix.replace(line, "");
+        if description.is_none() && line.starts_with("Description: ") {
+            description = Some(line["Description: ".len()..].trim().to_string());
+        }
+        if line.contains("The server has stopped responding!") {
+            dumped = true;
+        }
+        // A log only has frames worth reading once the watchdog has spoken up
+        if description.is_some() || dumped {
+            if let Some(captures) = frame.captures(&line) {
+                frames.push(captures[1].to_string());
+            }
+        }
+    }
+
+    match (description, dumped) {
+        (Some(description), _) => Some(Crash {
+            description: description,
+            frames: frames,
+        }),
+        (None, true) => Some(Crash {
+            description: "the server stopped responding".to_string(),
+            frames: frames,
+        }),
+        (None, false) => None,
+    }
+}
@@ -1,61 +1,51 @@
 //! Dropper - A Minecraft Package Manager
+pub mod addon;
+pub mod auth;
 pub mod backend;
+pub mod blocklist;
+pub mod build;
+pub mod bundle;
+pub mod cache;
+pub mod cancel;
+pub mod checksum;
+pub mod cli;
+pub mod complete;
+pub mod direct;
+pub mod docs;
+pub mod downloads;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+pub mod format;
+pub mod github;
+pub mod hangar;
+pub mod hash;
+pub mod history;
+pub mod hooks;
+pub mod http;
+pub mod jar;
+pub mod journal;
+pub mod libraries;
+pub mod listing;
+pub mod manual;
+pub mod maven;
+pub mod modrinth;
+pub mod lock;
+pub mod logs;
 pub mod parser;
+pub mod plan;
+pub mod presets;
+pub mod repo;
+pub mod resolver;
+pub mod restart;
+pub mod s3;
+pub mod smoke;
+pub mod state;
+pub mod store;
+pub mod template;
 pub mod text_assets;
-
-use crate::backend::PackageBackend;
-use crate::parser::BukkitHTMLPluginParser;
-use crate::parser::PluginFetchable;
-use crate::parser::PluginSearchable;
+pub mod version;
+pub mod watch;
 
 fn main() {
-    let x = parser::BukkitHTMLPluginParser::new(
-        "https://dev.bukkit.org/search?search={}",
-        ".listing",
-        "div.results-name > a",
-        "1.8".to_string(),
-    );
-
-    match x.enumerate_versions("worldedit") {
-        Ok(Some((names, links))) => {
-            println!("Here is the version names to link mapping:");
-            for (ver, link) in names.iter().zip(links) {
-                println!("{} -> {}", ver, link);
-            }
-
-            println!("\nI found these version tags:");
-            for ver in BukkitHTMLPluginParser::extract_version_numbers(names).unwrap() {
-                println!("{}", ver);
-            }
-        }
-        Ok(None) => println!("Sorry, that package was not found!"),
-        Err(e) => println!("An unexpected error occured: {}", e),
-    }
-
-    match x.fetch("worldedit", "6.1.9") {
-        Ok(Some(url)) => println!("Install your package at: {}", url),
-        Ok(None) => println!("I'm sorry! We couldn't find that version"),
-        Err(e) => println!("An unexpected error occured: {}", e),
-    };
-
-    match PackageBackend::validate() {
-        Ok(_) => println!("All YAML looks valid to me!"),
-        Err(e) => {
-            println!("Error encountered: {}", e);
-            PackageBackend::init();
-        }
-    }
-
-    let pb = match PackageBackend::new(&x) {
-        Ok(pb) => pb,
-        Err(e) => panic!("I ran into an error: {}", e),
-    };
-
-    match pb.pkg_add("worldedit") {
-        Ok(b) => match b {
-            Some((name, version)) => println!("Package {} installed @ version {}!", name, version),
-            None => println!("Did not install package"),
-        },
-        Err(e) => println!("Error while trying to add package: {}", e),
-    }
+    cli::run();
 }
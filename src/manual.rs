@@ -0,0 +1,96 @@
+//! This module reads packages the pkg.yml marks as managed by hand, like premium plugins that
+//! can only be downloaded by whoever bought them. dropper never downloads or updates them, but
+//! it knows which JAR to expect in the plugin directory, and the digests it should have, keyed
+//! by algorithm like in the lockfile, so `status`, `verify` and `checksum` can still check it:
+//!
+//! ```yaml
+//! premiumplugin:
+//!   manual: true
+//!   file: PremiumPlugin.jar
+//!   version: "3.2"
+//!   sha256: 5d1b4cbd2b3ab8e7a3f2fd4e3c7d7a0ad3f1e4b3a8d2c1c7e6de9f2b1a0c3e4f
+//! ```
+//!
+//! The version is only shown, since there's nowhere to look it up. A JAR without digests is
+//! only checked for being there.
+
+use crate::hash;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use yaml_rust::Yaml;
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A pkg.yml entry's `manual` isn't a boolean, or the entry doesn't give the name of a JAR in
+    // the plugin directory as its `file`. Takes the package name as a param.
+    SourceInvalid(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::SourceInvalid(s) => format!(
+                    "'{}' is managed by hand, so it needs `manual: true` and the `file` it's installed as in the plugin directory, like PremiumPlugin.jar",
+                    s
+                ),
+            }
+        )
+    }
+}
+
+/// A package whose JAR is put in the plugin directory by hand
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManualPackage {
+    /// The JAR, relative to the plugin directory
+    pub file: String,
+    pub version: Option<String>,
+    /// The digests the pkg.yml gives for the JAR, keyed by algorithm
+    pub hashes: BTreeMap<String, String>,
+}
+
+impl ManualPackage {
+    /// Reads a pkg.yml entry that's marked as managed by hand, or `None` if the entry isn't
+    ///
+    /// # Errors
+    /// * [`ErrorKind::SourceInvalid`](enum.ErrorKind.html#variant.SourceInvalid) - the entry has a `manual` key, but isn't usable
+    pub fn from_yaml(name: &str, entry: &Yaml) -> Result<Option<ManualPackage>, ErrorKind> {
+        let invalid = || ErrorKind::SourceInvalid(name.to_string());
+        match &entry["manual"] {
+            Yaml::BadValue | Yaml::Boolean(false) => return Ok(None),
+            Yaml::Boolean(true) => {}
+            _ => return Err(invalid()),
+        }
+        // The JAR has to be right in the plugin directory, where the server loads it from
+        let file = match &entry["file"] {
+            Yaml::String(file) if Path::new(file).file_name() == Some(file.as_ref()) => {
+                file.clone()
+            }
+            _ => return Err(invalid()),
+        };
+        let version = match &entry["version"] {
+            Yaml::String(version) | Yaml::Real(version) => Some(version.clone()),
+            Yaml::Integer(version) => Some(version.to_string()),
+            _ => None,
+        };
+
+        Ok(Some(ManualPackage {
+            file: file,
+            version: version,
+            hashes: hash::ALGORITHMS
+                .iter()
+                .filter_map(|algorithm| {
+                    entry[*algorithm]
+                        .as_str()
+                        .map(|digest| (algorithm.to_string(), digest.to_lowercase()))
+                })
+                .collect(),
+        }))
+    }
+}
@@ -0,0 +1,272 @@
+//! This module fetches packages from Maven repositories, which is where many plugin APIs (and
+//! some plugins) are published. A pkg.yml entry with `maven` coordinates is looked up in its
+//! `repository`, or Maven Central if it doesn't give one:
+//!
+//! ```yaml
+//! someapi:
+//!   maven: com.example:someapi
+//!   repository: https://jitpack.io
+//!   version: "1.2.*"
+//! ```
+//!
+//! The coordinates can also pin the version, like `com.example:someapi:1.2.3`. The versions come
+//! from the artifact's `maven-metadata.xml`, and downloads are checked against the checksum files
+//! published alongside the JARs. Maven doesn't have release channels, so a version's channel
+//! comes from its qualifier: `-SNAPSHOT` builds are dev builds, and `-beta` or `-rc` ones are
+//! betas.
+//!
+//! Maven Central can also be searched, which `dropper search --all-sources` does. Other
+//! repositories don't have a search API.
+
+use crate::hash;
+use crate::http::Credentials;
+use crate::parser::{PackageMetadata, PluginFile, PluginListing};
+use regex::Regex;
+use reqwest::{Client, StatusCode};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use url::form_urlencoded;
+use yaml_rust::Yaml;
+
+/// Maven Central, for entries that don't say which repository to use
+pub const DEFAULT_REPOSITORY: &'static str = "https://repo.maven.apache.org/maven2";
+const METADATA_FILE: &'static str = "maven-metadata.xml";
+// Maven Central's search API, where `{}` replaces the query
+const CENTRAL_SEARCH_URL: &'static str =
+    "https://search.maven.org/solrsearch/select?rows=20&wt=json&q={}";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A pkg.yml entry's coordinates aren't group:artifact or group:artifact:version. Takes the
+    // package name as a param.
+    CoordinatesInvalid(String),
+    // A repository's metadata for an artifact couldn't be read. Takes its URL as a param.
+    MetadataInvalid(String),
+    // Maven Central's search couldn't be used. Takes its status as a param.
+    SearchFailed(StatusCode),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::CoordinatesInvalid(s) => format!(
+                    "the maven coordinates of '{}' should look like group:artifact[:version]",
+                    s
+                ),
+                ErrorKind::MetadataInvalid(s) => format!("couldn't read the Maven metadata at {}", s),
+                ErrorKind::SearchFailed(status) => {
+                    format!("Maven Central's search answered {}", status)
+                }
+            }
+        )
+    }
+}
+
+/// An artifact in a Maven repository to install a package from
+#[derive(Debug, Clone, PartialEq)]
+pub struct MavenSource {
+    /// The base URL of the repository
+    pub repository: String,
+    pub group: String,
+    pub artifact: String,
+    /// The version the coordinates pinned, if they did
+    pub version: Option<String>,
+}
+
+impl MavenSource {
+    /// Reads the Maven coordinates out of a pkg.yml entry, or `None` if the entry doesn't have
+    /// them
+    ///
+    /// # Errors
+    /// * [`ErrorKind::CoordinatesInvalid`](enum.ErrorKind.html#variant.CoordinatesInvalid) - the entry has a `maven` key, but it isn't usable
+    pub fn from_yaml(name: &str, entry: &Yaml) -> Result<Option<MavenSource>, ErrorKind> {
+        let invalid = || ErrorKind::CoordinatesInvalid(name.to_string());
+        let coordinates = match &entry["maven"] {
+            Yaml::BadValue => return Ok(None),
+            Yaml::String(coordinates) => coordinates,
+            _ => return Err(invalid()),
+        };
+        let repository = match &entry["repository"] {
+            Yaml::BadValue => DEFAULT_REPOSITORY.to_string(),
+            Yaml::String(repository) => repository.trim_end_matches('/').to_string(),
+            _ => return Err(invalid()),
+        };
+
+        let parts = coordinates.split(':').collect::<Vec<&str>>();
+        if parts.len() < 2 || parts.len() > 3 || parts.iter().any(|part| part.is_empty()) {
+            return Err(invalid());
+        }
+        Ok(Some(MavenSource {
+            repository: repository,
+            group: parts[0].to_string(),
+            artifact: parts[1].to_string(),
+            version: parts.get(2).map(|v| v.to_string()),
+        }))
+    }
+
+    /// Where the artifact's files live in the repository. Groups are laid out as folders.
+    fn artifact_url(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.repository,
+            self.group.replace('.', "/"),
+            self.artifact
+        )
+    }
+
+    /// The download link of a version of the artifact's JAR
+    pub fn jar_url(&self, version: &str) -> String {
+        format!(
+            "{}/{}/{}-{}.jar",
+            self.artifact_url(),
+            version,
+            self.artifact,
+            version
+        )
+    }
+
+    /// Lists the versions of the artifact the repository has, newest first, or `None` if it
+    /// doesn't have the artifact at all
+    ///
+    /// # Errors
+    /// * [`ErrorKind::MetadataInvalid`](enum.ErrorKind.html#variant.MetadataInvalid) - the metadata couldn't be fetched or lists no versions
+    pub fn enumerate_files(
+        &self,
+        client: &Client,
+        credentials: &Credentials,
+    ) -> Result<Option<Vec<PluginFile>>, Box<Error>> {
+        let url = format!("{}/{}", self.artifact_url(), METADATA_FILE);
+        let mut response = credentials.fetch(client, &url)?;
+        match response.status() {
+            StatusCode::NOT_FOUND => return Ok(None),
+            status if !status.is_success() => {
+                return Err(Box::new(ErrorKind::MetadataInvalid(url)))
+            }
+            _ => {}
+        }
+
+        let metadata = response.text()?;
+        let version_re = Regex::new(r"<version>\s*([^<\s]+)\s*</version>").unwrap();
+        let versions_re = Regex::new(r"(?s)<versions>(.*)</versions>").unwrap();
+        let listed = match versions_re.captures(&metadata) {
+            Some(captures) => captures[1].to_string(),
+            None => return Err(Box::new(ErrorKind::MetadataInvalid(url))),
+        };
+
+        // The metadata lists versions oldest first
+        let files = version_re
+            .captures_iter(&listed)
+            .map(|captures| PluginFile::new(captures[1].to_string(), self.jar_url(&captures[1])))
+            .collect::<Vec<PluginFile>>()
+            .into_iter()
+            .rev()
+            .collect::<Vec<PluginFile>>();
+        match files.is_empty() {
+            true => Err(Box::new(ErrorKind::MetadataInvalid(url))),
+            false => Ok(Some(files)),
+        }
+    }
+
+    /// What the repository says about the artifact, or `None` if it doesn't have it. Its ID is
+    /// its `group:artifact` coordinates, and its slug is the artifact; Maven metadata doesn't
+    /// have names, descriptions or authors.
+    ///
+    /// # Errors
+    /// The same as [`MavenSource::enumerate_files`](#method.enumerate_files)
+    pub fn package_metadata(
+        &self,
+        client: &Client,
+        credentials: &Credentials,
+    ) -> Result<Option<PackageMetadata>, Box<Error>> {
+        Ok(self.enumerate_files(client, credentials)?.map(|files| {
+            let mut metadata = PackageMetadata::from_files(&self.artifact, files);
+            metadata.id = Some(format!("{}:{}", self.group, self.artifact));
+            metadata
+        }))
+    }
+
+    /// Fetches the checksums published alongside a download, keyed by algorithm. Checksum files
+    /// are named after the algorithm, like `someapi-1.2.3.jar.sha1`, and repositories don't all
+    /// publish every kind, so the ones that are missing are left out.
+    ///
+    /// # Errors
+    /// * `reqwest::Error` - a checksum file couldn't be fetched
+    pub fn published_hashes(
+        client: &Client,
+        credentials: &Credentials,
+        download_link: &str,
+    ) -> Result<BTreeMap<String, String>, Box<Error>> {
+        let mut hashes = BTreeMap::new();
+        for algorithm in hash::ALGORITHMS.iter() {
+            let mut response =
+                credentials.fetch(client, &format!("{}.{}", download_link, algorithm))?;
+            if !response.status().is_success() {
+                continue;
+            }
+            // Some tools write the file name after the checksum
+            if let Some(digest) = response.text()?.split_whitespace().next() {
+                hashes.insert(algorithm.to_string(), digest.to_lowercase());
+            }
+        }
+        Ok(hashes)
+    }
+}
+
+/// Searches Maven Central for artifacts, best match first. Each is listed by its
+/// `group:artifact` coordinates, which go in a pkg.yml entry's `maven` key. Central doesn't count
+/// downloads, so none of them have a download count.
+///
+/// # Errors
+/// * [`ErrorKind::SearchFailed`](enum.ErrorKind.html#variant.SearchFailed) - the search couldn't be made
+/// * `reqwest::Error` - the request couldn't be made
+pub fn search_central(
+    client: &Client,
+    credentials: &Credentials,
+    query: &str,
+) -> Result<Vec<PluginListing>, Box<Error>> {
+    let url = CENTRAL_SEARCH_URL.replace(
+        "{}",
+        &form_urlencoded::byte_serialize(query.as_bytes()).collect::<String>(),
+    );
+    let mut response = credentials.fetch(client, &url)?;
+    if !response.status().is_success() {
+        return Err(Box::new(ErrorKind::SearchFailed(response.status())));
+    }
+
+    // Each result is a flat JSON object, and only its coordinates are needed
+    let body = response.text()?;
+    let doc_re = Regex::new(r"\{[^{}]*\}").unwrap();
+    let field_re = |field: &str| Regex::new(&format!(r#""{}"\s*:\s*"([^"]*)""#, field)).unwrap();
+    let (group_re, artifact_re) = (field_re("g"), field_re("a"));
+    let docs = match body.find("\"docs\"") {
+        Some(i) => &body[i..],
+        None => return Ok(Vec::new()),
+    };
+
+    let mut listings = Vec::new();
+    for doc in doc_re.find_iter(docs) {
+        let doc = doc.as_str();
+        if let (Some(group), Some(artifact)) = (group_re.captures(doc), artifact_re.captures(doc)) {
+            listings.push(PluginListing {
+                name: format!("{}:{}", &group[1], &artifact[1]),
+                title: artifact[1].to_string(),
+                link: format!(
+                    "{}/{}/{}",
+                    DEFAULT_REPOSITORY,
+                    group[1].replace('.', "/"),
+                    &artifact[1]
+                ),
+                downloads: None,
+                description: None,
+                keywords: Vec::new(),
+            });
+        }
+    }
+    Ok(listings)
+}
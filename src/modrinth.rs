@@ -0,0 +1,133 @@
+//! This module lists plugins on Modrinth, for discovering them with `dropper top --source
+//! modrinth`. Modrinth has mods and plugins side by side, so only projects that run on Bukkit,
+//! Spigot or Paper are listed. Modrinth orders its listings by downloads or by follows, and
+//! doesn't keep a measure of what's popular lately, so plugins that are trending are the most
+//! followed ones.
+
+use crate::http;
+use crate::http::Credentials;
+use crate::parser::{ListingSort, PluginBrowsable, PluginListing, SearchFilters};
+use reqwest::Client;
+use std::error::Error;
+use url::form_urlencoded;
+use yaml_rust::Yaml;
+
+const SEARCH_URL: &'static str = "https://api.modrinth.com/v2/search";
+// Where projects' pages are, by slug
+const PROJECT_URL: &'static str = "https://modrinth.com/plugin";
+// The loaders that Bukkit plugins are filed under
+const PLUGIN_LOADERS: [&'static str; 3] = ["bukkit", "spigot", "paper"];
+// As many plugins as Bukkit shows on the first page of its listing
+const PAGE_SIZE: usize = 20;
+
+/// Lists plugins from Modrinth's search API
+pub struct ModrinthBrowser {
+    client: Client,
+    credentials: Credentials,
+}
+
+impl ModrinthBrowser {
+    pub fn new(client: Client, credentials: Credentials) -> ModrinthBrowser {
+        ModrinthBrowser {
+            client: client,
+            credentials: credentials,
+        }
+    }
+
+    /// The search URL that lists plugins that pass the filters, ordered by `sort`
+    fn search_url(filters: &SearchFilters, sort: ListingSort) -> String {
+        // Facets in the same list are alternatives, and the lists all have to match
+        let mut facets = vec![PLUGIN_LOADERS
+            .iter()
+            .map(|loader| format!("categories:{}", loader))
+            .collect::<Vec<String>>()];
+        if let Some(category) = &filters.category {
+            facets.push(vec![format!("categories:{}", category)]);
+        }
+        if let Some(game_version) = &filters.game_version {
+            facets.push(vec![format!("versions:{}", game_version)]);
+        }
+        let facets = format!(
+            "[{}]",
+            facets
+                .iter()
+                .map(|facet| format!(
+                    "[{}]",
+                    facet
+                        .iter()
+                        .map(|value| json_string(value))
+                        .collect::<Vec<String>>()
+                        .join(",")
+                ))
+                .collect::<Vec<String>>()
+                .join(",")
+        );
+
+        format!(
+            "{}?index={}&limit={}&facets={}",
+            SEARCH_URL,
+            match sort {
+                ListingSort::Downloads => "downloads",
+                ListingSort::Popularity => "follows",
+            },
+            PAGE_SIZE,
+            form_urlencoded::byte_serialize(facets.as_bytes()).collect::<String>()
+        )
+    }
+}
+
+impl PluginBrowsable for ModrinthBrowser {
+    /// # Errors
+    /// * [`http::ErrorKind::ApiFailed`](../http/enum.ErrorKind.html#variant.ApiFailed) - Modrinth's API couldn't be used
+    /// * `reqwest::Error` - the request couldn't be made
+    fn browse(
+        &self,
+        filters: &SearchFilters,
+        sort: ListingSort,
+    ) -> Result<Vec<PluginListing>, Box<Error>> {
+        let url = Self::search_url(filters, sort);
+        let found = match http::get_json(&self.client, &self.credentials, &url)? {
+            Some(found) => found,
+            None => return Ok(Vec::new()),
+        };
+        let hits = match &found["hits"] {
+            Yaml::Array(hits) => hits,
+            _ => {
+                return Err(Box::new(http::ErrorKind::ApiFailed(
+                    url,
+                    "something that isn't a list of projects".to_string(),
+                )))
+            }
+        };
+
+        Ok(hits.iter().filter_map(listing_of).collect())
+    }
+}
+
+/// One of the projects Modrinth's search found, if it has a slug to list it by
+fn listing_of(hit: &Yaml) -> Option<PluginListing> {
+    let slug = hit["slug"].as_str()?;
+    Some(PluginListing {
+        name: slug.to_string(),
+        title: hit["title"].as_str().unwrap_or(slug).to_string(),
+        link: format!("{}/{}", PROJECT_URL, slug),
+        downloads: hit["downloads"].as_i64().map(|downloads| downloads as u64),
+        description: hit["description"]
+            .as_str()
+            .map(|description| description.trim().to_string())
+            .filter(|description| !description.is_empty()),
+        keywords: match &hit["categories"] {
+            Yaml::Array(categories) => categories
+                .iter()
+                .filter_map(|category| category.as_str())
+                .map(|category| category.to_string())
+                .collect(),
+            _ => Vec::new(),
+        },
+    })
+}
+
+/// Writes text as a JSON string, for the facets
+fn json_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
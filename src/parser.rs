@@ -2,17 +2,56 @@
 //!
 //! Plugin parsers have two modi operandi: either users can search for install terms, like "World", and come back with a list of plugins to install, or they can specify a specific version, like `WorldEdit: "6.1.9"`.
 
+use crate::cancel::CancelToken;
+use crate::http;
+use crate::http::Credentials;
+use crate::version::Version;
 use regex::Regex;
-use reqwest::StatusCode;
+use reqwest::{Client, StatusCode};
 use scraper::element_ref::ElementRef;
 use scraper::{Html, Selector};
 use std::boxed::Box;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::fmt;
+use std::path::PathBuf;
+use url::form_urlencoded;
 
 const BUKKIT_PKG_FORMAT_URL: &'static str =
     "https://dev.bukkit.org/projects/{}/files?filter-game-version=<>";
+const BUKKIT_PROJECT_URL: &'static str = "https://dev.bukkit.org/projects/{}";
+const BUKKIT_AUTHOR_SELECTOR: &'static str = ".project-members .info-wrapper a";
+const BUKKIT_SEARCH_URL: &'static str = "https://dev.bukkit.org/search?search={}";
+const BUKKIT_SEARCH_LIST_SELECTOR: &'static str = ".listing";
+const BUKKIT_SEARCH_ITEM_SELECTOR: &'static str = "div.results-name > a";
+// Each search result is a row of the results table, with the project's summary in it
+const BUKKIT_SEARCH_ROW: &'static str = "tr";
+const BUKKIT_SEARCH_SUMMARY_SELECTOR: &'static str = ".results-summary";
+// The project listing, which unlike the search page can be narrowed down by category and game
+// version
+const BUKKIT_LISTING_URL: &'static str = "https://dev.bukkit.org/bukkit-plugins";
+const BUKKIT_LISTING_ENTRY_SELECTOR: &'static str = ".project-list-item";
+const BUKKIT_LISTING_ITEM_SELECTOR: &'static str = ".name-wrapper > a";
+const BUKKIT_LISTING_DOWNLOADS_SELECTOR: &'static str = ".e-download-count";
+const BUKKIT_LISTING_DESCRIPTION_SELECTOR: &'static str = ".description";
+
+// Each server version Bukkit has builds for, newest first, and the code to filter files by it.
+// This will feature more versions soon
+const BUKKIT_GAME_VERSIONS: [(&'static str, &'static str); 10] = [
+    ("1.12", "2020709689:6588"),
+    ("1.11", "2020709689:630"),
+    ("1.10", "2020709689:591"),
+    ("1.9", "2020709689:585"),
+    ("1.8.1", "2020709689:532"),
+    ("1.8", "2020709689:531"),
+    ("CB 1.7.9-R0.2", "2020709689:490"),
+    ("CB 1.7.9-R0.1", "2020709689:473"),
+    ("1.7.4", "2020709689:6391"),
+    ("CB 1.7.2-R0.3", "2020709689:403"),
+];
+
+/// The plugin websites there are parsers for, by the name the config's `plugin_website` uses
+pub const PLUGIN_WEBSITES: [&'static str; 1] = ["bukkit"];
 
 // A version code regular expression that allows for wildcards, and the occasional
 // fourth version sub-code. (Most plugins should follow up to three, but some like WorldEdit
@@ -29,6 +68,11 @@ pub enum ErrorKind {
     ServerVersionNotFound(String),
     // The version format is unknown and could not be parsed.
     BadVersioningFormat,
+    // A parser was built without a setting it can't do without. Takes the setting's name as a
+    // param.
+    SettingMissing(String),
+    // A parser was given a selector that isn't valid CSS. Takes the selector as a param.
+    SelectorInvalid(String),
 }
 
 impl Error for ErrorKind {}
@@ -46,16 +90,140 @@ impl fmt::Display for ErrorKind {
                 ErrorKind::BadVersioningFormat => {
                     "plugin has a version format we cannot handle".to_string()
                 }
+                ErrorKind::SettingMissing(s) => format!("the parser needs a {} to be set", s),
+                ErrorKind::SelectorInvalid(s) => format!("`{}` is not a valid selector", s),
             }
         )
     }
 }
 
+#[derive(Clone)]
 pub struct BukkitHTMLPluginParser {
-    search_url: &'static str,
-    list_selector: &'static str,
-    item_selector: &'static str,
+    search_url: String,
+    list_selector: String,
+    item_selector: String,
     minecraft_version: String,
+    client: Client,
+    /// Headers and cookies for hosts that need a login
+    credentials: Credentials,
+    /// Where to keep pages that are fetched often, if anywhere
+    cache_dir: Option<PathBuf>,
+    /// Whether pages that are cached are only ever read from the cache
+    cache_only: bool,
+    /// Checked before each request, so that a long operation can be cancelled
+    cancel: CancelToken,
+}
+
+/// Builds a `BukkitHTMLPluginParser` out of settings that are only known at runtime, like the
+/// ones in a config file. Anything that isn't set defaults to what dev.bukkit.org uses, except
+/// for the game version, which has to be set.
+#[derive(Default)]
+pub struct BukkitHTMLPluginParserBuilder {
+    search_url: Option<String>,
+    list_selector: Option<String>,
+    item_selector: Option<String>,
+    minecraft_version: Option<String>,
+    client: Option<Client>,
+    credentials: Option<Credentials>,
+    cache_dir: Option<PathBuf>,
+    cache_only: bool,
+    cancel: CancelToken,
+}
+
+impl BukkitHTMLPluginParserBuilder {
+    /// The URL of the search page, where `{}` replaces the query
+    pub fn search_url<S: Into<String>>(mut self, search_url: S) -> Self {
+        self.search_url = Some(search_url.into());
+        self
+    }
+
+    /// The [selector](https://www.w3schools.com/cssref/css_selectors.asp) for the search results container
+    pub fn list_selector<S: Into<String>>(mut self, list_selector: S) -> Self {
+        self.list_selector = Some(list_selector.into());
+        self
+    }
+
+    /// The selector for each search result's name/link
+    pub fn item_selector<S: Into<String>>(mut self, item_selector: S) -> Self {
+        self.item_selector = Some(item_selector.into());
+        self
+    }
+
+    /// The server version to find plugins for
+    pub fn game_version<S: Into<String>>(mut self, minecraft_version: S) -> Self {
+        self.minecraft_version = Some(minecraft_version.into());
+        self
+    }
+
+    /// The HTTP client to make requests with. Defaults to a client with reqwest's settings.
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Headers and cookies to send to hosts that need a login. Defaults to none.
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Where to keep pages that are fetched often, like projects' files pages, so that they're
+    /// only downloaded again when they change. Defaults to not keeping them.
+    pub fn cache_dir<P: Into<PathBuf>>(mut self, cache_dir: P) -> Self {
+        self.cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Reads the pages that get cached only from the cache, without going online, and treats
+    /// ones that aren't there as not found. That's quick enough for shell completion, but may
+    /// be out of date. Defaults to going online.
+    pub fn cache_only(mut self, cache_only: bool) -> Self {
+        self.cache_only = cache_only;
+        self
+    }
+
+    /// A token that cancels the parser's requests once it's cancelled. Defaults to one that
+    /// never is.
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Builds the parser
+    ///
+    /// # Errors
+    /// * [`ErrorKind::SettingMissing`](enum.ErrorKind.html#variant.SettingMissing) - the game version wasn't set
+    /// * [`ErrorKind::SelectorInvalid`](enum.ErrorKind.html#variant.SelectorInvalid) - one of the selectors isn't valid CSS
+    pub fn build(self) -> Result<BukkitHTMLPluginParser, ErrorKind> {
+        let list_selector = self
+            .list_selector
+            .unwrap_or_else(|| BUKKIT_SEARCH_LIST_SELECTOR.to_string());
+        let item_selector = self
+            .item_selector
+            .unwrap_or_else(|| BUKKIT_SEARCH_ITEM_SELECTOR.to_string());
+        // Bad selectors would otherwise only come up on the first search
+        for selector in [&list_selector, &item_selector].iter() {
+            if Selector::parse(selector).is_err() {
+                return Err(ErrorKind::SelectorInvalid(selector.to_string()));
+            }
+        }
+
+        Ok(BukkitHTMLPluginParser {
+            search_url: self
+                .search_url
+                .unwrap_or_else(|| BUKKIT_SEARCH_URL.to_string()),
+            list_selector: list_selector,
+            item_selector: item_selector,
+            minecraft_version: self
+                .minecraft_version
+                .ok_or_else(|| ErrorKind::SettingMissing("game version".to_string()))?,
+            client: self.client.unwrap_or_else(Client::new),
+            credentials: self.credentials.unwrap_or_default(),
+            cache_dir: self.cache_dir,
+            cache_only: self.cache_only,
+            cancel: self.cancel,
+        })
+    }
 }
 
 fn extract_list_from_table(
@@ -86,50 +254,524 @@ fn extract_list_from_table(
     links
 }
 
+/// The text in an element, with its whitespace tidied up, or `None` if there isn't any
+fn text_of(element: ElementRef) -> Option<String> {
+    let text = element.text().collect::<Vec<&str>>().join(" ");
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    match text.is_empty() {
+        true => None,
+        false => Some(text),
+    }
+}
+
+/// Narrows a search down. Each plugin website maps these onto its own query parameters.
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilters {
+    /// The website's name for a category, e.g. `admin-tools`
+    pub category: Option<String>,
+    /// Only show plugins with builds for this server version, e.g. `1.12`
+    pub game_version: Option<String>,
+}
+
+impl SearchFilters {
+    /// Whether there's nothing to filter by
+    pub fn is_empty(&self) -> bool {
+        self.category.is_none() && self.game_version.is_none()
+    }
+}
+
+/// How to order a listing of plugins
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListingSort {
+    /// The most downloaded of all time
+    Downloads,
+    /// The most popular lately
+    Popularity,
+}
+
+/// A plugin in a listing
+#[derive(Debug, Clone)]
+pub struct PluginListing {
+    /// The name to install the plugin by
+    pub name: String,
+    /// The name the website shows
+    pub title: String,
+    pub link: String,
+    /// How many times the plugin has been downloaded, if the website says
+    pub downloads: Option<u64>,
+    /// What the website says the plugin does, if it shows that in the listing
+    pub description: Option<String>,
+    /// The tags the plugin is filed under, if the source has them
+    pub keywords: Vec<String>,
+}
+
+impl PluginListing {
+    /// Whether the plugin is what a search is after: every word of the query is in its name,
+    /// description or keywords, or the query is its name written apart, like `anti xray` for
+    /// AntiXRay. Sources that are searched here, rather than by the website, are matched this way.
+    pub fn matches(&self, query: &str) -> bool {
+        let squashed = |text: &str| {
+            text.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        };
+        let mut text = vec![self.name.clone(), self.title.clone()];
+        text.extend(self.description.clone());
+        text.extend(self.keywords.iter().cloned());
+        let text = text.join(" ").to_lowercase();
+
+        let query_squashed = squashed(query);
+        (!query_squashed.is_empty()
+            && (squashed(&self.name).contains(&query_squashed)
+                || squashed(&self.title).contains(&query_squashed)))
+            || query
+                .split_whitespace()
+                .all(|word| text.contains(&word.to_lowercase()))
+    }
+}
+
+pub trait PluginBrowsable {
+    /// Lists the plugins that pass the filters, best first by `sort`. Only the first page of the
+    /// listing is returned, since this is for discovering plugins rather than for finding one.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::RequestFailed`](enum.ErrorKind.html#variant.RequestFailed) - the listing couldn't be fetched
+    /// * [`ErrorKind::ServerVersionNotFound`](enum.ErrorKind.html#variant.ServerVersionNotFound) - the website doesn't know the game version filtered by
+    fn browse(
+        &self,
+        filters: &SearchFilters,
+        sort: ListingSort,
+    ) -> Result<Vec<PluginListing>, Box<Error>>;
+}
+
 pub trait PluginSearchable {
     /// Searches the search_url for a plugin keyword, and returns a `HashMap` of plugin names to install page URLs.
-    fn search(&self, query: &str) -> HashMap<String, String>;
+    /// The query can be several words long, and is encoded before being sent.
+    fn search(&self, query: &str) -> HashMap<String, String> {
+        self.search_filtered(query, &SearchFilters::default())
+            .unwrap_or_default()
+    }
+
+    /// Like `search`, but only returns the plugins that pass the filters
+    ///
+    /// # Errors
+    /// * [`ErrorKind::ServerVersionNotFound`](enum.ErrorKind.html#variant.ServerVersionNotFound) - the website doesn't know the game version filtered by
+    fn search_filtered(
+        &self,
+        query: &str,
+        filters: &SearchFilters,
+    ) -> Result<HashMap<String, String>, Box<Error>>;
+
+    /// Like `search_filtered`, but returns the plugins as they're listed, with their download
+    /// counts if the website shows them. Websites that only give links list each plugin by its
+    /// install name.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::RequestFailed`](enum.ErrorKind.html#variant.RequestFailed) - the results couldn't be fetched
+    /// * [`ErrorKind::ServerVersionNotFound`](enum.ErrorKind.html#variant.ServerVersionNotFound) - the website doesn't know the game version filtered by
+    fn search_listings(
+        &self,
+        query: &str,
+        filters: &SearchFilters,
+    ) -> Result<Vec<PluginListing>, Box<Error>> {
+        let mut listings = self
+            .search_filtered(query, filters)?
+            .into_iter()
+            .map(|(name, link)| PluginListing {
+                title: name.clone(),
+                name: name,
+                link: link,
+                downloads: None,
+                description: None,
+                keywords: Vec::new(),
+            })
+            .collect::<Vec<PluginListing>>();
+        listings.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(listings)
+    }
+}
+
+/// A plugin that a search of several sources found, with how each source lists it
+#[derive(Debug, Clone)]
+pub struct MergedListing {
+    /// The name the first source to list the plugin shows
+    pub title: String,
+    /// Each source that lists the plugin, by name, and its listing there
+    pub sources: Vec<(String, PluginListing)>,
+}
+
+impl MergedListing {
+    /// The most downloads any source counts, if any of them say
+    pub fn downloads(&self) -> Option<u64> {
+        self.sources
+            .iter()
+            .filter_map(|(_, listing)| listing.downloads)
+            .max()
+    }
+
+    /// What the first source to describe the plugin says it does
+    pub fn description(&self) -> Option<&str> {
+        self.sources
+            .iter()
+            .filter_map(|(_, listing)| listing.description.as_ref())
+            .map(|description| description.as_str())
+            .next()
+    }
+}
+
+/// Merges what several sources listed for the same search, so that a plugin that's on more than
+/// one of them comes up once. Plugins are taken to be the same if the names the sources show
+/// match, ignoring case, spaces and punctuation. The most downloaded plugins come first.
+///
+/// # Arguments
+///
+/// * `results` - Each source's name, and what it listed, best match first
+pub fn merge_listings(results: Vec<(String, Vec<PluginListing>)>) -> Vec<MergedListing> {
+    let key = |title: &str| {
+        title
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    };
+
+    let mut merged: Vec<MergedListing> = Vec::new();
+    for (source, listings) in results {
+        for listing in listings {
+            let existing = merged.iter_mut().find(|m| {
+                key(&m.title) == key(&listing.title)
+                    && !m.sources.iter().any(|(s, _)| *s == source)
+            });
+            match existing {
+                Some(m) => m.sources.push((source.clone(), listing)),
+                None => merged.push(MergedListing {
+                    title: listing.title.clone(),
+                    sources: vec![(source.clone(), listing)],
+                }),
+            }
+        }
+    }
+
+    // Sorting is stable, so plugins nobody counts downloads for keep the order they came in
+    merged.sort_by_key(|m| std::cmp::Reverse(m.downloads()));
+    merged
 }
 
-pub trait PluginFetchable {
-    /// Fetches a download link from a specific package name and version. Returns an optional package URL. If one is not found, the version lookup failed due to no version being present, or bad naming.
+/// How stable a build is meant to be. Channels are ordered from least to most stable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Channel {
+    /// Development builds, like snapshots and alphas
+    Dev,
+    /// Betas and release candidates
+    Beta,
+    Release,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 3] = [Channel::Release, Channel::Beta, Channel::Dev];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::Dev => "dev",
+            Channel::Beta => "beta",
+            Channel::Release => "release",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Channel> {
+        Channel::ALL
+            .iter()
+            .find(|channel| channel.name().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    /// Works out the channel of a build from the qualifiers on its version, like `-SNAPSHOT` or
+    /// `-beta2`, for sources that don't say which channel their builds are in. Versions without
+    /// one are releases.
+    pub fn from_version(version: &str) -> Channel {
+        let version = version.to_lowercase();
+        let qualifier = |words: &[&str]| {
+            words.iter().any(|word| {
+                version
+                    .split(|c: char| !c.is_ascii_alphabetic())
+                    .any(|part| part == *word)
+            })
+        };
+        if qualifier(&["snapshot", "dev", "alpha", "nightly"]) {
+            Channel::Dev
+        } else if qualifier(&["beta", "rc", "pre"]) {
+            Channel::Beta
+        } else {
+            Channel::Release
+        }
+    }
+}
+
+/// A file that a version of a package can be installed from
+#[derive(Debug, Clone)]
+pub struct PluginFile {
+    pub version: String,
+    pub link: String,
+    /// When the file was uploaded, in seconds since the Unix epoch, if the website says
+    pub uploaded: Option<u64>,
+    /// The release channel the file was published to
+    pub channel: Channel,
+    /// The server versions the file is for, if the source says
+    pub game_versions: Vec<String>,
+    /// The digests the source lists alongside the file, keyed by algorithm. Sources that only
+    /// publish them on request leave this empty; see `PluginFetchable::published_hashes`.
+    pub hashes: BTreeMap<String, String>,
+    /// Where the changes in the file are written up, if the source says
+    pub changelog: Option<String>,
+}
+
+impl PluginFile {
+    /// A file with nothing known about it but where it is
+    pub fn new(version: String, link: String) -> PluginFile {
+        PluginFile {
+            channel: Channel::from_version(&version),
+            version: version,
+            link: link,
+            uploaded: None,
+            game_versions: Vec::new(),
+            hashes: BTreeMap::new(),
+            changelog: None,
+        }
+    }
+
+    /// Whether the file is for a server version, or doesn't say which it's for. A server version
+    /// it lists covers the patches of it, so `1.20` covers `1.20.4`, and a range like `1.8-1.21`
+    /// covers everything from one end to the other.
+    pub fn is_for(&self, server_version: &str) -> bool {
+        self.game_versions.is_empty()
+            || self
+                .game_versions
+                .iter()
+                .any(|listed| covers(listed, server_version))
+    }
+}
+
+/// Whether a server version a file lists, or a range of them, covers a server version
+fn covers(listed: &str, server_version: &str) -> bool {
+    if let Some(i) = listed.find('-') {
+        let (low, high) = (listed[..i].trim(), listed[i + 1..].trim());
+        let ends = (Version::parse(low), Version::parse(high));
+        if let ((Some(low), Some(top)), Some(server)) = (ends, Version::parse(server_version)) {
+            return (low <= server && server <= top) || covers(high, server_version);
+        }
+    }
+    server_version == listed || server_version.starts_with(&format!("{}.", listed))
+}
+
+/// Reads an RFC 3339 timestamp, the kind APIs give, like `2024-06-30T12:34:56.789Z`, into
+/// seconds since the Unix epoch. Fractions of a second are dropped. Returns `None` if it isn't
+/// one.
+pub fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let re = Regex::new(
+        r"^(\d{4})-(\d{2})-(\d{2})[Tt ](\d{2}):(\d{2}):(\d{2})(?:\.\d+)?(?:([Zz])|([+-])(\d{2}):?(\d{2}))$",
+    )
+    .unwrap();
+    let groups = re.captures(timestamp.trim())?;
+    let number = |i: usize| groups[i].parse::<i64>().unwrap();
+    let (year, month, day) = (number(1), number(2), number(3));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days since the epoch of the date, counting years from March so leap days come last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let mut seconds = days * 86400 + number(4) * 3600 + number(5) * 60 + number(6);
+    if groups.get(8).is_some() {
+        let offset = number(9) * 3600 + number(10) * 60;
+        seconds -= match &groups[8] {
+            "-" => -offset,
+            _ => offset,
+        };
+    }
+    match seconds >= 0 {
+        true => Some(seconds as u64),
+        false => None,
+    }
+}
+
+/// What looking for a version of a package turned up
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lookup {
+    /// The version was found. Takes its download link.
+    Found(String),
+    /// There's no package by that name
+    NoPackage,
+    /// The package exists, but has no builds for the server version
+    NoBuilds,
+    /// The package has builds, just not of the version that was asked for. Takes the versions it
+    /// does have, newest first.
+    NoVersion(Vec<String>),
+}
+
+impl Lookup {
+    /// Looks for a version among a package's files, by its exact version code
+    pub fn among(files: &[PluginFile], version_code: &str) -> Lookup {
+        match files.iter().find(|file| file.version == version_code) {
+            Some(file) => Lookup::Found(file.link.clone()),
+            None => Lookup::missing(files),
+        }
+    }
+
+    /// Says why a version isn't among a package's files
+    fn missing(files: &[PluginFile]) -> Lookup {
+        match files.is_empty() {
+            true => Lookup::NoBuilds,
+            false => Lookup::NoVersion(files.iter().map(|file| file.version.clone()).collect()),
+        }
+    }
+}
+
+/// Everything a source says about a package, in the same shape whichever source it comes from
+#[derive(Debug, Clone)]
+pub struct PackageMetadata {
+    /// The ID the source gives the project, which doesn't change when it's renamed, if it has one
+    pub id: Option<String>,
+    /// The name to install the package by
+    pub slug: String,
+    /// The name the source shows
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub authors: Vec<String>,
+    /// The tags the project is filed under, if the source has them
+    pub keywords: Vec<String>,
+    /// The files of each version, newest first
+    pub versions: Vec<PluginFile>,
+}
+
+impl PackageMetadata {
+    /// Metadata with nothing in it but the package's files
+    pub fn from_files(slug: &str, files: Vec<PluginFile>) -> PackageMetadata {
+        PackageMetadata {
+            id: None,
+            slug: slug.to_string(),
+            display_name: None,
+            description: None,
+            authors: Vec::new(),
+            keywords: Vec::new(),
+            versions: files,
+        }
+    }
+}
+
+/// Parsers are shared between threads when fetching information about many packages at once
+pub trait PluginFetchable: Sync {
+    /// Fetches a download link from a specific package name and version. If there isn't one,
+    /// the lookup says whether that's because there's no such package, no builds of it for this
+    /// server version, or no such version of it.
     ///
     /// *Note*: `package_name` has to be specifically formatted for the website being used. This name will be slipped into a URL to download the package in this function.
-    fn fetch(&self, package_name: &str, version_code: &str) -> Result<Option<String>, Box<Error>>;
+    fn fetch(&self, package_name: &str, version_code: &str) -> Result<Lookup, Box<Error>> {
+        Ok(match self.enumerate_files(package_name)? {
+            Some(files) => Lookup::among(&files, version_code),
+            None => Lookup::NoPackage,
+        })
+    }
 
+    /// Finds the newest version of a package, and its download link. There may not be one, if
+    /// the package has no builds for this server version.
     fn find_newest_version(
         &self,
         package_name: &str,
-    ) -> Result<Option<(String, String)>, Box<Error>>;
+    ) -> Result<Option<(String, String)>, Box<Error>> {
+        Ok(self
+            .enumerate_files(package_name)?
+            .and_then(|files| files.into_iter().next())
+            .map(|file| (file.version, file.link)))
+    }
 
-    /// Provides a way to list all the versions of the package in question. Can return two Vecs
-    /// of version names and links (1 : 1 in order), or if no package was found, returns `None`.
+    /// Lists the files of every version of the package in question, newest first, or returns
+    /// `None` if no package was found.
+    ///
     /// *Note*: `package_name` has to be specifically formatted for the website being used. This name will be slipped into a URL to download the package in this function.
-    fn enumerate_versions(
+    fn enumerate_files(&self, package_name: &str) -> Result<Option<Vec<PluginFile>>, Box<Error>>;
+
+    /// Everything the website says about a package, or `None` if no package was found. By
+    /// default only the files are filled in, for websites without project pages worth reading.
+    fn package_metadata(
         &self,
         package_name: &str,
-    ) -> Result<Option<(Vec<String>, Vec<String>)>, Box<Error>>;
+    ) -> Result<Option<PackageMetadata>, Box<Error>> {
+        Ok(self
+            .enumerate_files(package_name)?
+            .map(|files| PackageMetadata::from_files(package_name, files)))
+    }
+
+    /// Looks up the MD5 that the website publishes for a download link returned by this parser,
+    /// so the download can be verified. Websites that don't publish one return `None`, which is
+    /// what this does by default.
+    fn published_md5(&self, _download_link: &str) -> Result<Option<String>, Box<Error>> {
+        Ok(None)
+    }
+
+    /// Looks up every digest the website publishes for a download link, keyed by algorithm
+    /// (`md5`, `sha1`, `sha256` or `sha512`). By default this is just the published MD5.
+    fn published_hashes(
+        &self,
+        download_link: &str,
+    ) -> Result<BTreeMap<String, String>, Box<Error>> {
+        let mut hashes = BTreeMap::new();
+        if let Some(md5) = self.published_md5(download_link)? {
+            hashes.insert("md5".to_string(), md5);
+        }
+        Ok(hashes)
+    }
+
+    /// Looks up the package name of a project by its numeric ID, for websites that give projects
+    /// one. Names can change, but IDs don't. Returns `None` if there's no such project, which is
+    /// always the case for websites without IDs, and is what this does by default.
+    fn find_project_by_id(&self, _project_id: &str) -> Result<Option<String>, Box<Error>> {
+        Ok(None)
+    }
+
+    /// The server versions the website has builds for, from newest to oldest. Websites that
+    /// don't tell builds apart by server version return nothing, which is what this does by
+    /// default.
+    fn game_versions(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Makes a parser just like this one, but for another server version, or `None` if the
+    /// website doesn't tell builds apart by server version, which is what this does by default.
+    fn for_game_version(&self, _minecraft_version: &str) -> Option<Box<PluginFetchable>> {
+        None
+    }
 }
 
 pub trait HTMLPluginScrapable {
-    /// Takes the output of the name selector and somehow transforms it into a name that can be used to fetch the package later.
-    /// By default, this just returns the package text
-    fn transform_package_name(package_text: &str) -> String {
-        package_text.to_string()
+    /// Takes the output of the name selector and somehow transforms it into a name that can be used to fetch the package later,
+    /// or `None` if it isn't a package's. By default, this just returns the package text
+    fn transform_package_name(package_text: &str) -> Option<String> {
+        Some(package_text.to_string())
     }
 
     /// Given a query, use the list_selector and item_selector to render a map of names to links
     fn scrape_links_from_list(
+        client: &Client,
+        credentials: &Credentials,
         query: &str,
         search_url: &str,
         list_selector: &str,
         item_selector: &str,
     ) -> Vec<String> {
-        // Construct a URL that allows us to search the website
-        let built_url = str::replace(search_url, "{}", query);
+        // Construct a URL that allows us to search the website. The query is encoded, since
+        // search terms can have spaces and characters like `&` in them.
+        let encoded_query = form_urlencoded::byte_serialize(query.as_bytes()).collect::<String>();
+        let built_url = str::replace(search_url, "{}", &encoded_query);
 
         // Grab the HTML text from that URL
-        let html = reqwest::get(&built_url)
+        let html = credentials.fetch(client, &built_url)
             .unwrap_or_else(|e| panic!("Could not GET from {}", built_url))
             .text()
             .unwrap_or_else(|e| panic!("Could not get HTML body from {}", built_url));
@@ -154,68 +796,268 @@ impl BukkitHTMLPluginParser {
     /// * `search_url` - A URL for the search page where `{}` replaces the query position
     /// * `list_selector` - A [selector](https://www.w3schools.com/cssref/css_selectors.asp) for the search results container
     /// * `item_selector` - A selector for each item's name/link
+    /// * `minecraft_version` - The server version to find plugins for
+    /// * `client` - The HTTP client to make requests with
     pub fn new(
-        search_url: &'static str,
-        list_selector: &'static str,
-        item_selector: &'static str,
+        search_url: &str,
+        list_selector: &str,
+        item_selector: &str,
         minecraft_version: String,
+        client: Client,
     ) -> Self {
         BukkitHTMLPluginParser {
-            search_url: search_url,
-            list_selector: list_selector,
-            item_selector: item_selector,
+            search_url: search_url.to_string(),
+            list_selector: list_selector.to_string(),
+            item_selector: item_selector.to_string(),
             minecraft_version: minecraft_version,
+            client: client,
+            credentials: Credentials::default(),
+            cache_dir: None,
+            cache_only: false,
+            cancel: CancelToken::new(),
         }
     }
+
+    /// Starts building a parser from runtime settings. See
+    /// [`BukkitHTMLPluginParserBuilder`](struct.BukkitHTMLPluginParserBuilder.html).
+    pub fn builder() -> BukkitHTMLPluginParserBuilder {
+        BukkitHTMLPluginParserBuilder::default()
+    }
 }
 
 /// Add the plugin scraping capabilities
 impl HTMLPluginScrapable for BukkitHTMLPluginParser {
-    fn transform_package_name(package_text: &str) -> String {
-        let re = Regex::new(r"^/projects/(\w.*)\?").unwrap();
+    fn transform_package_name(package_text: &str) -> Option<String> {
+        // Search results link to `/projects/<name>?<tracking>`, listings just to `/projects/<name>`
+        let re = Regex::new(r"^/projects/([^/?]+)").unwrap();
         // Return the captured project name
-        re.captures_iter(package_text).next().unwrap()[1].to_string()
+        re.captures(package_text).map(|groups| groups[1].to_string())
     }
 }
 
 /// Add plugin searching capabilities
 impl PluginSearchable for BukkitHTMLPluginParser {
-    fn search(&self, query: &str) -> HashMap<String, String> {
-        let mut map = HashMap::new();
-        for item in BukkitHTMLPluginParser::scrape_links_from_list(
-            query,
-            self.search_url,
-            self.list_selector,
-            self.item_selector,
-        ) {
-            map.insert(BukkitHTMLPluginParser::transform_package_name(&item), item);
+    /// Unfiltered searches use the search page. Bukkit's search page can't be filtered though, so
+    /// filtered searches go through the project listing instead, which can.
+    fn search_filtered(
+        &self,
+        query: &str,
+        filters: &SearchFilters,
+    ) -> Result<HashMap<String, String>, Box<Error>> {
+        self.cancel.check()?;
+        let items = match filters.is_empty() {
+            true => BukkitHTMLPluginParser::scrape_links_from_list(
+                &self.client,
+                &self.credentials,
+                query,
+                &self.search_url,
+                &self.list_selector,
+                &self.item_selector,
+            ),
+            false => {
+                let search_url = format!("{}&filter-search={{}}", bukkit_listing_url(filters, None)?);
+                BukkitHTMLPluginParser::scrape_links_from_list(
+                    &self.client,
+                    &self.credentials,
+                    query,
+                    &search_url,
+                    &self.list_selector,
+                    BUKKIT_LISTING_ITEM_SELECTOR,
+                )
+            }
+        };
+
+        // Other links that happen to be in the results aren't to projects
+        Ok(items
+            .into_iter()
+            .filter_map(|item| {
+                BukkitHTMLPluginParser::transform_package_name(&item).map(|name| (name, item))
+            })
+            .collect())
+    }
+
+    /// The search page doesn't show download counts, so searches go through the project
+    /// listing, which only looks through names. The search page looks through the projects'
+    /// summaries too, though, so unfiltered searches add what it finds after.
+    fn search_listings(
+        &self,
+        query: &str,
+        filters: &SearchFilters,
+    ) -> Result<Vec<PluginListing>, Box<Error>> {
+        let built_url = format!(
+            "{}&filter-search={}",
+            bukkit_listing_url(filters, None)?,
+            form_urlencoded::byte_serialize(query.as_bytes()).collect::<String>()
+        );
+        let mut listings = self.fetch_listing(&built_url)?;
+        if !filters.is_empty() {
+            return Ok(listings);
+        }
+
+        for found in self.fetch_search_page(query)? {
+            match listings.iter_mut().find(|listing| listing.name == found.name) {
+                Some(listing) => {
+                    if listing.description.is_none() {
+                        listing.description = found.description;
+                    }
+                }
+                None => listings.push(found),
+            }
+        }
+        Ok(listings)
+    }
+}
+
+/// Add plugin browsing capabilities
+impl PluginBrowsable for BukkitHTMLPluginParser {
+    fn browse(
+        &self,
+        filters: &SearchFilters,
+        sort: ListingSort,
+    ) -> Result<Vec<PluginListing>, Box<Error>> {
+        let built_url = bukkit_listing_url(filters, Some(sort))?;
+        self.fetch_listing(&built_url)
+    }
+}
+
+impl BukkitHTMLPluginParser {
+    /// Fetches a page of listed plugins, or `None` if there's no such page
+    fn fetch_page(&self, built_url: &str) -> Result<Option<String>, Box<Error>> {
+        self.cancel.check()?;
+        let mut response = self.credentials.fetch(&self.client, built_url)?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status => match status.is_success() {
+                true => Ok(Some(response.text()?)),
+                false => Err(Box::new(ErrorKind::RequestFailed(status))),
+            },
+        }
+    }
+
+    /// Reads the plugins off the search page, with the summary of each
+    fn fetch_search_page(&self, query: &str) -> Result<Vec<PluginListing>, Box<Error>> {
+        let encoded_query = form_urlencoded::byte_serialize(query.as_bytes()).collect::<String>();
+        let html = match self.fetch_page(&self.search_url.replace("{}", &encoded_query))? {
+            Some(html) => html,
+            None => return Ok(Vec::new()),
+        };
+
+        let document = Html::parse_document(&html);
+        // The selectors were checked when the parser was built
+        let list_selector = Selector::parse(&self.list_selector).unwrap();
+        let item_selector = Selector::parse(&self.item_selector).unwrap();
+        let summary_selector = Selector::parse(BUKKIT_SEARCH_SUMMARY_SELECTOR).unwrap();
+
+        let mut listings = Vec::new();
+        for list in document.select(&list_selector) {
+            for link in list.select(&item_selector) {
+                let href = link.value().attr("href").unwrap_or("");
+                let name = match BukkitHTMLPluginParser::transform_package_name(href) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                let summary = link
+                    .ancestors()
+                    .filter_map(ElementRef::wrap)
+                    .find(|element| element.value().name() == BUKKIT_SEARCH_ROW)
+                    .and_then(|row| row.select(&summary_selector).next());
+
+                listings.push(PluginListing {
+                    link: format!("https://dev.bukkit.org/projects/{}", name),
+                    name: name,
+                    title: link.text().collect::<String>().trim().to_string(),
+                    downloads: None,
+                    description: summary.and_then(text_of),
+                    keywords: Vec::new(),
+                });
+            }
+        }
+        Ok(listings)
+    }
+
+    /// Reads the plugins off a page of the project listing
+    fn fetch_listing(&self, built_url: &str) -> Result<Vec<PluginListing>, Box<Error>> {
+        let html = match self.fetch_page(built_url)? {
+            Some(html) => html,
+            // Categories that don't exist have nothing in them
+            None => return Ok(Vec::new()),
+        };
+
+        let document = Html::parse_document(&html);
+        let entry_selector = Selector::parse(BUKKIT_LISTING_ENTRY_SELECTOR).unwrap();
+        let name_selector = Selector::parse(BUKKIT_LISTING_ITEM_SELECTOR).unwrap();
+        let downloads_selector = Selector::parse(BUKKIT_LISTING_DOWNLOADS_SELECTOR).unwrap();
+        let description_selector = Selector::parse(BUKKIT_LISTING_DESCRIPTION_SELECTOR).unwrap();
+
+        let mut listings = Vec::new();
+        for entry in document.select(&entry_selector) {
+            let link = match entry.select(&name_selector).next() {
+                Some(link) => link,
+                None => continue,
+            };
+            let href = link.value().attr("href").unwrap_or("");
+            let name = match BukkitHTMLPluginParser::transform_package_name(href) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            // Counts are written out with separators, like `1,234,567`
+            let downloads = entry.select(&downloads_selector).next().and_then(|e| {
+                e.text()
+                    .collect::<String>()
+                    .chars()
+                    .filter(|c| c.is_ascii_digit())
+                    .collect::<String>()
+                    .parse::<u64>()
+                    .ok()
+            });
+
+            listings.push(PluginListing {
+                name: name,
+                title: link.text().collect::<String>().trim().to_string(),
+                link: format!("https://dev.bukkit.org{}", href),
+                downloads: downloads,
+                description: entry.select(&description_selector).next().and_then(text_of),
+                keywords: Vec::new(),
+            });
         }
 
-        map
+        Ok(listings)
     }
 }
 
 /// Add plugin fetching capabilities
 impl PluginFetchable for BukkitHTMLPluginParser {
-    fn enumerate_versions(
-        &self,
-        package_name: &str,
-    ) -> Result<Option<(Vec<String>, Vec<String>)>, Box<Error>> {
+    /// Bukkit lists files by when they were uploaded, but projects sometimes upload hotfixes for
+    /// old branches after newer releases. So files are ordered by version instead, and the upload
+    /// date only decides between files of the same version.
+    fn enumerate_files(&self, package_name: &str) -> Result<Option<Vec<PluginFile>>, Box<Error>> {
         // Construct a URL that allows us to walk the files table
         let built_url = str::replace(BUKKIT_PKG_FORMAT_URL, "{}", package_name);
         let built_url = str::replace(&built_url, "<>", &self.bukkit_mc_version_code()?);
 
-        // Get the website content first
-        let mut response = reqwest::get(&built_url)?;
+        // Get the website content first. Files pages are checked a lot, so they're cached.
+        let cache_dir = self.cache_dir.as_deref();
+        let (status, html) = match cache_dir {
+            Some(dir) if self.cache_only => match http::cached_page(dir, &built_url) {
+                Some(html) => (StatusCode::OK, html),
+                None => return Ok(None),
+            },
+            _ => {
+                self.cancel.check()?;
+                http::get_page(&self.client, &self.credentials, &built_url, cache_dir)?
+            }
+        };
 
-        let html = match response.status() {
+        match status {
             // In this case, the plugin can't be found.
             StatusCode::NOT_FOUND => return Ok(None),
-            status => match status.is_success() {
-                true => response.text()?.to_string(),
-                false => return Err(Box::new(ErrorKind::RequestFailed(status))),
-            },
-        };
+            status if !status.is_success() => {
+                return Err(Box::new(ErrorKind::RequestFailed(status)))
+            }
+            _ => {}
+        }
 
         // Get a list of the names of each file link
         let plugin_version_names = extract_list_from_table(
@@ -237,62 +1079,200 @@ impl PluginFetchable for BukkitHTMLPluginParser {
             },
         );
 
+        // And a parallel list of upload dates, which are only trusted if every file has one
+        let plugin_upload_dates = extract_list_from_table(
+            &html,
+            ".listing",
+            "abbr.standard-date",
+            &|element: ElementRef| element.value().attr("data-epoch").unwrap_or("").to_string(),
+        )
+        .iter()
+        .map(|epoch| epoch.parse::<u64>().ok())
+        .collect::<Vec<Option<u64>>>();
+        let has_dates = plugin_upload_dates.len() == plugin_version_links.len();
+
+        // The release type of each file is shown as an icon, whose class names its phase
+        let plugin_channels = extract_list_from_table(
+            &html,
+            ".listing",
+            ".project-file-release-type > div",
+            &|element: ElementRef| {
+                let class = element.value().attr("class").unwrap_or("");
+                match (class.contains("alpha-phase"), class.contains("beta-phase")) {
+                    (true, _) => Channel::Dev.name(),
+                    (_, true) => Channel::Beta.name(),
+                    _ => Channel::Release.name(),
+                }
+                .to_string()
+            },
+        );
+        let has_channels = plugin_channels.len() == plugin_version_links.len();
+
+        // And the MD5 each file's row lists, as the first run of 32 hex digits after the label,
+        // so downloads can be checked without looking up each file's page
+        let md5_re = Regex::new(r"(?is)md5.*?\b([0-9a-f]{32})\b").unwrap();
+        let plugin_md5s = extract_list_from_table(
+            &html,
+            ".listing",
+            ".project-file-name-container > a",
+            &|element: ElementRef| {
+                element
+                    .ancestors()
+                    .filter_map(ElementRef::wrap)
+                    .find(|ancestor| ancestor.value().name() == "tr")
+                    .and_then(|row| {
+                        md5_re
+                            .captures(&row.html())
+                            .map(|groups| groups[1].to_lowercase())
+                    })
+                    .unwrap_or_default()
+            },
+        );
+
         // Transform the list of version names to version codes
         let plugin_versions = Self::extract_version_numbers(plugin_version_names)?;
 
-        Ok(Some((plugin_versions, plugin_version_links)))
-    }
-
-    fn find_newest_version(
-        &self,
-        package_name: &str,
-    ) -> Result<Option<(String, String)>, Box<Error>> {
-        // Get the version numbers
-        let (versions, links) = match self.enumerate_versions(package_name)? {
-            Some(tup) => tup,
-            None => return Ok(None),
-        };
+        let mut files = plugin_versions
+            .into_iter()
+            .zip(plugin_version_links)
+            .enumerate()
+            .map(|(i, (version, link))| PluginFile {
+                channel: match has_channels {
+                    true => Channel::from_name(&plugin_channels[i]).unwrap(),
+                    false => Channel::from_version(&version),
+                },
+                version: version,
+                uploaded: match has_dates {
+                    true => plugin_upload_dates[i],
+                    false => None,
+                },
+                // The page only lists files for the server version
+                game_versions: vec![self.minecraft_version.clone()],
+                hashes: plugin_md5s
+                    .get(i)
+                    .filter(|md5| !md5.is_empty())
+                    .map(|md5| ("md5".to_string(), md5.clone()))
+                    .into_iter()
+                    .collect(),
+                // Each file's page has its changelog
+                changelog: Some(link.trim_end_matches("/download").to_string())
+                    .filter(|page| !page.is_empty()),
+                link: link,
+            })
+            .collect::<Vec<PluginFile>>();
+        Self::sort_newest_first(&mut files);
 
-        // Return a tuple of the first of each list
-        Ok(Some((
-            versions.first().cloned().unwrap(),
-            links.first().cloned().unwrap(),
-        )))
+        Ok(Some(files))
     }
 
-    fn fetch(&self, package_name: &str, version_code: &str) -> Result<Option<String>, Box<Error>> {
-        // Get the version numbers
-        let (plugin_version_names, plugin_version_links) =
-            match self.enumerate_versions(package_name)? {
-                Some(tup) => tup,
-                None => return Ok(None),
-            };
-
-        // Set up a mapping between the two above vectors
-        let mut names_to_links: HashMap<String, String> = HashMap::new();
-        for (name, link) in plugin_version_names.iter().zip(plugin_version_links) {
-            names_to_links.insert(name.to_string(), link.to_string());
-        }
+    fn fetch(&self, package_name: &str, version_code: &str) -> Result<Lookup, Box<Error>> {
+        let files = match self.enumerate_files(package_name)? {
+            Some(files) => files,
+            None => return Ok(Lookup::NoPackage),
+        };
 
         // Set up a regular expression that catches version numbers
         // From https://stackoverflow.com/questions/82064/a-regex-for-version-number-parsing
         let re = Regex::new(VERSION_CODE_REGEX).unwrap();
+        let wanted = Version::parse(version_code);
 
-        // The outer loop goes down each version-to-link pair, and the inner loop
+        // The outer loop goes down each file, and the inner loop
         // looks through all of the version numbers found in the version name to see
         // if the one we want shows up. This is somewhat flawed, since some people will
         // put MC server versions in their version names, but this solution should have the
-        // highest hit rate.
-        for (name, link) in names_to_links {
-            for groups in re.captures_iter(&name) {
-                if &groups[0] == version_code {
-                    return Ok(Some(link));
+        // highest hit rate. `1.2` and `1.2.0` are the same version, so they match each other.
+        for file in files.iter() {
+            for groups in re.captures_iter(&file.version) {
+                let found = Version::parse(&groups[0]);
+                if &groups[0] == version_code || (wanted.is_some() && found == wanted) {
+                    return Ok(Lookup::Found(file.link.clone()));
                 }
             }
         }
 
-        // The version wasn't found, so we return None
-        Ok(None)
+        Ok(Lookup::missing(&files))
+    }
+
+    /// The project's page is read for its name, description and authors, on top of the files
+    fn package_metadata(
+        &self,
+        package_name: &str,
+    ) -> Result<Option<PackageMetadata>, Box<Error>> {
+        let files = match self.enumerate_files(package_name)? {
+            Some(files) => files,
+            None => return Ok(None),
+        };
+        let mut metadata = PackageMetadata::from_files(package_name, files);
+
+        let built_url = str::replace(BUKKIT_PROJECT_URL, "{}", package_name);
+        self.cancel.check()?;
+        let mut response = self.credentials.fetch(&self.client, &built_url)?;
+        if !response.status().is_success() {
+            return Err(Box::new(ErrorKind::RequestFailed(response.status())));
+        }
+        let html = response.text()?;
+        let document = Html::parse_document(&html);
+
+        let meta = |property: &str| {
+            let selector = Selector::parse(&format!("meta[property='{}']", property)).unwrap();
+            document
+                .select(&selector)
+                .next()
+                .and_then(|element| element.value().attr("content"))
+                .map(|content| content.trim().to_string())
+                .filter(|content| !content.is_empty())
+        };
+        metadata.display_name = meta("og:title");
+        metadata.description = meta("og:description");
+
+        let author_selector = Selector::parse(BUKKIT_AUTHOR_SELECTOR).unwrap();
+        for author in document.select(&author_selector) {
+            let author = author.text().collect::<String>().trim().to_string();
+            if !author.is_empty() && !metadata.authors.contains(&author) {
+                metadata.authors.push(author);
+            }
+        }
+
+        // The ID is listed with the project's other details
+        let re = Regex::new(r"(?s)Project ID</div>.*?(\d+)").unwrap();
+        metadata.id = re.captures(&html).map(|groups| groups[1].to_string());
+
+        Ok(Some(metadata))
+    }
+
+    fn game_versions(&self) -> Vec<String> {
+        BUKKIT_GAME_VERSIONS
+            .iter()
+            .map(|(version, _)| version.to_string())
+            .collect()
+    }
+
+    fn for_game_version(&self, minecraft_version: &str) -> Option<Box<PluginFetchable>> {
+        Some(Box::new(BukkitHTMLPluginParser {
+            minecraft_version: minecraft_version.to_string(),
+            ..self.clone()
+        }))
+    }
+
+    /// Bukkit redirects a project's ID to its page under its current name, so the name is
+    /// wherever the redirect ends up
+    fn find_project_by_id(&self, project_id: &str) -> Result<Option<String>, Box<Error>> {
+        let built_url = str::replace(BUKKIT_PROJECT_URL, "{}", project_id);
+        self.cancel.check()?;
+        let response = self.credentials.fetch(&self.client, &built_url)?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => return Ok(None),
+            status if !status.is_success() => {
+                return Err(Box::new(ErrorKind::RequestFailed(status)))
+            }
+            _ => {}
+        }
+
+        let re = Regex::new(r"^/projects/([^/?]+)").unwrap();
+        Ok(re
+            .captures(http::response_url(&response).path())
+            .map(|groups| groups[1].to_string()))
     }
 }
 
@@ -312,7 +1292,7 @@ impl BukkitHTMLPluginParser {
 
         // Stores lists of version tuples that it finds
         // e.g. [(6, 1, 9, None), (1, 12, None, None)]
-        let mut version_tuples: Vec<Vec<(u32, u32, Option<u32>, Option<u32>)>> = Vec::new();
+        let mut version_tuples: Vec<Vec<Version>> = Vec::new();
 
         let re = Regex::new(VERSION_CODE_REGEX).unwrap();
 
@@ -322,19 +1302,17 @@ impl BukkitHTMLPluginParser {
             let mut entry_versions = Vec::new();
             for groups in re.captures_iter(&version) {
                 match (groups.get(1), groups.get(2)) {
-                    // Push the appropriate tuple to this entry's version list
-                    (Some(a), Some(b)) => entry_versions.push((
-                        a.as_str().parse::<u32>()?,
-                        b.as_str().parse::<u32>()?,
-                        match groups.get(3) {
-                            Some(s) => Some(s.as_str().parse::<u32>()?),
-                            None => None,
-                        },
-                        match groups.get(4) {
-                            Some(s) => Some(s.as_str().parse::<u32>()?),
-                            None => None,
-                        },
-                    )),
+                    // Push the appropriate version to this entry's version list
+                    (Some(a), Some(b)) => {
+                        let mut components =
+                            vec![a.as_str().parse::<u32>()?, b.as_str().parse::<u32>()?];
+                        for i in 3..5 {
+                            if let Some(s) = groups.get(i) {
+                                components.push(s.as_str().parse::<u32>()?);
+                            }
+                        }
+                        entry_versions.push(Version::from_components(components));
+                    }
                     // If either of the first two are null, we throw an error
                     _ => return Err(Box::new(ErrorKind::BadVersioningFormat)),
                 }
@@ -352,7 +1330,7 @@ impl BukkitHTMLPluginParser {
         if version_tuples.iter().all(|x| x.len() == 1) {
             return Ok(version_tuples
                 .iter()
-                .map(|x| Self::stringify_version_tuple(x[0], None))
+                .map(|x| x[0].to_string())
                 .collect());
         }
 
@@ -369,7 +1347,7 @@ impl BukkitHTMLPluginParser {
 
         for i in 0..max_len {
             let mut version_iter = version_tuples.iter();
-            let mut prev = version_iter.next().unwrap()[i];
+            let mut prev = &version_iter.next().unwrap()[i];
 
             for tuples in version_iter {
                 // It is possible that this entry does not have a tuple for this
@@ -381,11 +1359,11 @@ impl BukkitHTMLPluginParser {
                 }
 
                 // See if the tuple was the same as the prev tuple
-                if tuples[i] == prev {
+                if tuples[i] == *prev {
                     similar_streaks[i] += 1;
                 }
 
-                prev = tuples[i];
+                prev = &tuples[i];
             }
         }
 
@@ -398,57 +1376,192 @@ impl BukkitHTMLPluginParser {
 
         Ok(version_tuples
             .iter()
-            .map(|x| Self::stringify_version_tuple(x[col], None))
+            .map(|x| x[col].to_string())
             .collect())
     }
 
-    /// A private function to take a version tuple and stringify it. Can also take a beta version
-    /// param
-    fn stringify_version_tuple(
-        tup: (u32, u32, Option<u32>, Option<u32>),
-        beta: Option<String>,
-    ) -> String {
-        let mut version_code = format!("{}.{}", tup.0, tup.1);
-        version_code = match tup.2 {
-            Some(num) => format!("{}.{}", version_code, num),
-            None => version_code,
+    /// Orders files from the newest version to the oldest, with newer uploads first among files
+    /// of the same version. Versions we couldn't make sense of leave the order alone.
+    fn sort_newest_first(files: &mut Vec<PluginFile>) {
+        let keys = files
+            .iter()
+            .map(|file| Version::parse(&file.version))
+            .collect::<Option<Vec<Version>>>();
+        let keys = match keys {
+            Some(keys) => keys,
+            None => return,
         };
 
-        version_code = match tup.3 {
-            Some(num) => format!("{}.{}", version_code, num),
-            None => version_code,
-        };
+        let mut keyed = keys.into_iter().zip(files.drain(..)).collect::<Vec<_>>();
+        keyed.sort_by(|(a_key, a), (b_key, b)| {
+            b_key.cmp(a_key).then(b.uploaded.cmp(&a.uploaded))
+        });
+        files.extend(keyed.into_iter().map(|(_, file)| file));
+    }
+
+    /// The filterable version code for the server version this parser was made for
+    fn bukkit_mc_version_code(&self) -> Result<String, ErrorKind> {
+        bukkit_game_version_code(&self.minecraft_version)
+    }
+}
+
+/// Builds a URL for Bukkit's project listing, narrowed down by the filters. The URL always has
+/// a query string, so more parameters can be tacked on with `&`.
+fn bukkit_listing_url(
+    filters: &SearchFilters,
+    sort: Option<ListingSort>,
+) -> Result<String, ErrorKind> {
+    let mut built_url = BUKKIT_LISTING_URL.to_string();
+    if let Some(category) = &filters.category {
+        built_url.push('/');
+        built_url.extend(form_urlencoded::byte_serialize(category.as_bytes()));
+    }
+
+    built_url.push_str(match sort {
+        Some(ListingSort::Downloads) => "?filter-sort=downloads",
+        Some(ListingSort::Popularity) | None => "?filter-sort=popularity",
+    });
+    if let Some(game_version) = &filters.game_version {
+        built_url.push_str("&filter-game-version=");
+        built_url.push_str(&bukkit_game_version_code(game_version)?);
+    }
+
+    Ok(built_url)
+}
+
+/// Bukkit has another annoyance: their filterable MC version codes are a very odd mapping.
+/// This function abstracts that away and handles it.
+fn bukkit_game_version_code(minecraft_version: &str) -> Result<String, ErrorKind> {
+    match BUKKIT_GAME_VERSIONS
+        .iter()
+        .find(|(version, _)| *version == minecraft_version)
+    {
+        Some((_, code)) => Ok(code.to_string()),
+        None => Err(ErrorKind::ServerVersionNotFound(
+            minecraft_version.to_string(),
+        )),
+    }
+}
+
+// Replays the pages in fixtures/bukkit, which are laid out like dev.bukkit.org's, so these run
+// without going online
+#[cfg(all(test, feature = "fixtures"))]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn replaying_parser() -> BukkitHTMLPluginParser {
+        env::set_var(
+            "DROPPER_FIXTURES",
+            format!("replay:{}/fixtures/bukkit", env!("CARGO_MANIFEST_DIR")),
+        );
+        BukkitHTMLPluginParser::builder()
+            .game_version("1.12")
+            .build()
+            .unwrap()
+    }
+
+    fn file_link(id: &str) -> String {
+        format!(
+            "https://dev.bukkit.org/projects/worldedit/files/{}/download",
+            id
+        )
+    }
+
+    #[test]
+    fn files_are_listed_newest_version_first() {
+        let files = replaying_parser()
+            .enumerate_files("worldedit")
+            .unwrap()
+            .unwrap();
+
+        // 6.1.5 was uploaded after 6.1.7.2, as a hotfix, but it's still older
+        let versions = files
+            .iter()
+            .map(|file| file.version.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(versions, vec!["6.1.9", "6.1.8", "6.1.7.2", "6.1.5"]);
+
+        let newest = &files[0];
+        assert_eq!(newest.link, file_link("2597538"));
+        assert_eq!(newest.uploaded, Some(1535826171));
+        assert_eq!(newest.channel, Channel::Release);
+        assert_eq!(newest.game_versions, vec!["1.12".to_string()]);
+        assert_eq!(
+            newest.changelog,
+            Some("https://dev.bukkit.org/projects/worldedit/files/2597538".to_string())
+        );
+        assert_eq!(files[1].channel, Channel::Beta);
+    }
 
-        version_code = match beta {
-            Some(num) => format!("{}b{}", version_code, num),
-            None => version_code,
+    #[test]
+    fn md5s_are_read_off_the_files_page() {
+        let files = replaying_parser()
+            .enumerate_files("worldedit")
+            .unwrap()
+            .unwrap();
+        let md5_of = |version: &str| {
+            files
+                .iter()
+                .find(|file| file.version == version)
+                .and_then(|file| file.hashes.get("md5").cloned())
         };
+        // Each MD5 stays with its own file, though the files are sorted
+        assert_eq!(
+            md5_of("6.1.9"),
+            Some("d7faa518205c01cfb592328fded1a364".to_string())
+        );
+        assert_eq!(
+            md5_of("6.1.7.2"),
+            Some("6a8ff5022c78f399014f3d6870f93596".to_string())
+        );
+    }
 
-        version_code
+    #[test]
+    fn missing_projects_have_no_files() {
+        let files = replaying_parser().enumerate_files("nosuchplugin").unwrap();
+        assert!(files.is_none());
     }
 
-    /// Bukkit has another annoyance: their filterable MC version codes are a very odd mapping.
-    /// This function abstracts that away and handles it.
-    fn bukkit_mc_version_code(&self) -> Result<String, ErrorKind> {
-        // This will feature more versions soon
-        Ok(match self.minecraft_version.as_ref() {
-            "1.12" => "2020709689:6588",
-            "1.11" => "2020709689:630",
-            "1.10" => "2020709689:591",
-            "1.9" => "2020709689:585",
-            "1.8.1" => "2020709689:532",
-            "1.8" => "2020709689:531",
-            "CB 1.7.9-R0.2" => "2020709689:490",
-            "CB 1.7.9-R0.1" => "2020709689:473",
-            "CB 1.7.2-R0.3" => "2020709689:403",
-            "1.7.4" => "2020709689:6391",
-            "CB 1.7.2-R0.3" => "2020709689:403",
-            _ => {
-                return Err(ErrorKind::ServerVersionNotFound(
-                    self.minecraft_version.clone(),
-                ))
-            }
-        }
-        .to_string())
+    #[test]
+    fn newest_version_is_found() {
+        let newest = replaying_parser().find_newest_version("worldedit").unwrap();
+        assert_eq!(newest, Some(("6.1.9".to_string(), file_link("2597538"))));
+    }
+
+    #[test]
+    fn versions_are_fetched_by_number() {
+        let parser = replaying_parser();
+        assert_eq!(
+            parser.fetch("worldedit", "6.1.7.2").unwrap(),
+            Lookup::Found(file_link("2491845"))
+        );
+        assert_eq!(
+            parser.fetch("worldedit", "7.0").unwrap(),
+            Lookup::NoVersion(vec![
+                "6.1.9".to_string(),
+                "6.1.8".to_string(),
+                "6.1.7.2".to_string(),
+                "6.1.5".to_string(),
+            ])
+        );
+        assert_eq!(
+            parser.fetch("nosuchplugin", "1.0").unwrap(),
+            Lookup::NoPackage
+        );
+    }
+
+    #[test]
+    fn search_finds_projects_by_name() {
+        let results = replaying_parser().search("worldedit");
+
+        // The link to the next page of results isn't a project
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results.get("worldedit").map(|link| link.as_str()),
+            Some("/projects/worldedit?gameCategorySlug=bukkit-plugins&projectID=31043")
+        );
+        assert!(results.contains_key("worldguard"));
+        assert!(results.contains_key("fastasyncworldedit"));
     }
 }
@@ -0,0 +1,309 @@
+//! This module holds plans: the changes it would take to bring the server in line with the
+//! pkg.yml, worked out ahead of time so they can be reviewed before anything is touched. A plan
+//! is written to a file by `dropper plan`, and carried out exactly as written by `dropper apply`:
+//!
+//! ```yaml
+//! lock_md5: 51b5bd2ad1c7a96bb2e2fd44d8a8bd33
+//! changes:
+//!   - action: install
+//!     package: worldedit
+//!     version: 6.1.9
+//!     url: "https://dev.bukkit.org/projects/worldedit/files/2597538/download"
+//!     reason: explicit
+//!   - action: update
+//!     package: protocollib
+//!     from: 4.4.0
+//!     to: 4.5.0
+//!     url: "https://dev.bukkit.org/projects/protocollib/files/2702180/download"
+//!   - action: remove
+//!     package: vault
+//!     version: 1.6.7
+//! ```
+//!
+//! The plan remembers the digest of the lockfile it was made against, so that a plan which
+//! has gone stale because the server changed since isn't applied.
+
+use crate::state::InstallReason;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // The plan file couldn't be understood. Takes the path as a param.
+    PlanInvalid(String),
+    // The lockfile has changed since the plan was made.
+    PlanStale,
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::PlanInvalid(s) => format!("the plan {} is corrupt", s),
+                ErrorKind::PlanStale => {
+                    "the server has changed since this plan was made; make a new one".to_string()
+                }
+            }
+        )
+    }
+}
+
+/// A single change to the server
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// Install a package that isn't installed yet
+    Install {
+        package: String,
+        version: String,
+        url: String,
+        reason: InstallReason,
+    },
+    /// Replace the installed version of a package with another one
+    Update {
+        package: String,
+        from: String,
+        to: String,
+        url: String,
+    },
+    /// Remove a package that the pkg.yml no longer wants
+    Remove { package: String, version: String },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Install {
+                package, version, ..
+            } => write!(f, "+ {} {}", package, version),
+            Change::Update {
+                package, from, to, ..
+            } => write!(f, "~ {} {} -> {}", package, from, to),
+            Change::Remove { package, version } => write!(f, "- {} {}", package, version),
+        }
+    }
+}
+
+/// The changes to make, and the lockfile they were worked out against
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    /// The MD5 of the lockfile, or `None` if there was no lockfile yet
+    pub lock_md5: Option<String>,
+    pub changes: Vec<Change>,
+}
+
+impl Plan {
+    /// Reads a plan written by `save`
+    ///
+    /// # Errors
+    /// * [`ErrorKind::PlanInvalid`](enum.ErrorKind.html#variant.PlanInvalid) - the plan file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn load(path: &Path) -> Result<Plan, Box<Error>> {
+        let invalid = || ErrorKind::PlanInvalid(path.display().to_string());
+        let docs = YamlLoader::load_from_str(&fs::read_to_string(path)?).map_err(|_| invalid())?;
+        let doc = docs.first().ok_or_else(invalid)?;
+
+        let mut changes = Vec::new();
+        if let Yaml::Array(items) = &doc["changes"] {
+            for item in items {
+                let field = |key: &str| {
+                    item[key]
+                        .as_str()
+                        .map(|s| s.to_string())
+                        .ok_or_else(invalid)
+                };
+                changes.push(match item["action"].as_str() {
+                    Some("install") => Change::Install {
+                        package: field("package")?,
+                        version: field("version")?,
+                        url: field("url")?,
+                        reason: item["reason"]
+                            .as_str()
+                            .and_then(InstallReason::from_name)
+                            .ok_or_else(invalid)?,
+                    },
+                    Some("update") => Change::Update {
+                        package: field("package")?,
+                        from: field("from")?,
+                        to: field("to")?,
+                        url: field("url")?,
+                    },
+                    Some("remove") => Change::Remove {
+                        package: field("package")?,
+                        version: field("version")?,
+                    },
+                    _ => return Err(Box::new(invalid())),
+                });
+            }
+        }
+
+        Ok(Plan {
+            lock_md5: doc["lock_md5"].as_str().map(|s| s.to_string()),
+            changes: changes,
+        })
+    }
+
+    /// Writes the plan, replacing what was there
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn save(&self, path: &Path) -> Result<(), Box<Error>> {
+        let mut items = Vec::new();
+        for change in self.changes.iter() {
+            let fields = match change {
+                Change::Install {
+                    package,
+                    version,
+                    url,
+                    reason,
+                } => vec![
+                    ("action", "install"),
+                    ("package", package.as_str()),
+                    ("version", version.as_str()),
+                    ("url", url.as_str()),
+                    ("reason", reason.as_str()),
+                ],
+                Change::Update {
+                    package,
+                    from,
+                    to,
+                    url,
+                } => vec![
+                    ("action", "update"),
+                    ("package", package.as_str()),
+                    ("from", from.as_str()),
+                    ("to", to.as_str()),
+                    ("url", url.as_str()),
+                ],
+                Change::Remove { package, version } => vec![
+                    ("action", "remove"),
+                    ("package", package.as_str()),
+                    ("version", version.as_str()),
+                ],
+            };
+
+            let mut item = Hash::new();
+            for (key, value) in fields {
+                item.insert(Yaml::from_str(key), Yaml::String(value.to_string()));
+            }
+            items.push(Yaml::Hash(item));
+        }
+
+        let mut doc = Hash::new();
+        doc.insert(
+            Yaml::from_str("lock_md5"),
+            match &self.lock_md5 {
+                Some(md5) => Yaml::String(md5.clone()),
+                None => Yaml::Null,
+            },
+        );
+        doc.insert(Yaml::from_str("changes"), Yaml::Array(items));
+
+        let mut tmp_string = String::new();
+        YamlEmitter::new(&mut tmp_string)
+            .dump(&Yaml::Hash(doc))
+            .unwrap();
+        fs::write(path, format!("{}\n", tmp_string))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::{env, process};
+
+    fn plan_path(test: &str) -> PathBuf {
+        env::temp_dir().join(format!("dropper-plan-{}-{}.yml", test, process::id()))
+    }
+
+    fn every_change() -> Vec<Change> {
+        vec![
+            Change::Install {
+                package: "worldedit".to_string(),
+                version: "6.1.9".to_string(),
+                url: "https://dev.bukkit.org/projects/worldedit/files/2597538/download".to_string(),
+                reason: InstallReason::Dependency,
+            },
+            Change::Update {
+                package: "protocollib".to_string(),
+                from: "4.4.0".to_string(),
+                to: "4.5.0".to_string(),
+                url: "https://dev.bukkit.org/projects/protocollib/files/2702180/download"
+                    .to_string(),
+            },
+            Change::Remove {
+                package: "vault".to_string(),
+                version: "1.6.7".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn plans_are_read_back_the_way_they_were_saved() {
+        let path = plan_path("round-trip");
+        for lock_md5 in [None, Some("51b5bd2ad1c7a96bb2e2fd44d8a8bd33".to_string())] {
+            let plan = Plan {
+                lock_md5: lock_md5.clone(),
+                changes: every_change(),
+            };
+            plan.save(&path).unwrap();
+
+            let loaded = Plan::load(&path).unwrap();
+            assert_eq!(loaded.lock_md5, lock_md5);
+            assert_eq!(loaded.changes, plan.changes);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn versions_that_look_like_numbers_stay_strings() {
+        let path = plan_path("numeric-versions");
+        fs::write(
+            &path,
+            "lock_md5: ~\nchanges:\n  - action: remove\n    package: vault\n    version: \"1.7\"\n",
+        )
+        .unwrap();
+        let plan = Plan {
+            lock_md5: None,
+            changes: vec![Change::Remove {
+                package: "vault".to_string(),
+                version: "1.7".to_string(),
+            }],
+        };
+        assert_eq!(Plan::load(&path).unwrap().changes, plan.changes);
+
+        plan.save(&path).unwrap();
+        assert_eq!(Plan::load(&path).unwrap().changes, plan.changes);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn corrupt_plans_are_rejected() {
+        let path = plan_path("corrupt");
+        let corrupt = [
+            "changes: [",
+            "changes: [{action: downgrade, package: vault, version: \"1.6\"}]",
+            "changes: [{action: install, package: vault, version: \"1\"}]",
+            "changes: [{action: install, package: vault, version: \"1\", url: x, reason: forced}]",
+            "changes: [{action: update, package: vault, to: \"1.7\", url: x}]",
+        ];
+        for contents in corrupt {
+            fs::write(&path, contents).unwrap();
+            let e = Plan::load(&path).unwrap_err();
+            match e.downcast_ref::<ErrorKind>() {
+                Some(ErrorKind::PlanInvalid(_)) => {}
+                _ => panic!("expected {:?} to be rejected, got {}", contents, e),
+            }
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
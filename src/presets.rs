@@ -0,0 +1,53 @@
+//! A small built-in list of popular plugins that aren't best installed from the plugin website,
+//! along with where they are best installed from. A package named like one of these that the
+//! pkg.yml doesn't have an entry for is installed from the preset's source, and `dropper add`
+//! writes the preset into the pkg.yml, so `dropper add essentialsx` adds:
+//!
+//! ```yaml
+//! essentialsx:
+//!   github: EssentialsX/Essentials
+//!   asset: "EssentialsX-*.jar"
+//!   version: 2.20.1
+//! ```
+//!
+//! Naming a source, like `dropper add bukkit:essentialsx`, skips the preset. The config's
+//! `presets` can add presets of its own, or replace or turn off the built-in ones:
+//!
+//! ```yaml
+//! presets:
+//!   ourplugin:
+//!     repo: https://plugins.example.com
+//!   viaversion: false
+//! ```
+
+/// A plugin that dropper knows where to install from
+pub struct Preset {
+    /// The name to install the package by
+    pub name: &'static str,
+    /// The pkg.yml entry the package is installed with, as YAML
+    pub entry: &'static str,
+}
+
+pub const PRESETS: &'static [Preset] = &[
+    // Every EssentialsX release has the JARs of all its modules attached
+    Preset {
+        name: "essentialsx",
+        entry: "github: EssentialsX/Essentials\nasset: \"EssentialsX-*.jar\"",
+    },
+    Preset {
+        name: "geyser",
+        entry: "downloads: https://download.geysermc.org/v2/projects/geyser\ndownload: spigot",
+    },
+    Preset {
+        name: "floodgate",
+        entry: "downloads: https://download.geysermc.org/v2/projects/floodgate\ndownload: spigot",
+    },
+    Preset {
+        name: "viaversion",
+        entry: "hangar: ViaVersion",
+    },
+    Preset {
+        name: "viabackwards",
+        entry: "hangar: ViaBackwards",
+    },
+];
@@ -0,0 +1,853 @@
+//! This module fetches packages from self-hosted repositories, so that an organization can
+//! publish its internal plugins without a plugin website. A repository is nothing but an
+//! `index.json` listing its packages, and the files it lists, which can all be served from any
+//! static host or object storage. A pkg.yml entry with a `repo` looks the package up in the
+//! repository's index, by its own name unless it gives the `package` to look for:
+//!
+//! ```yaml
+//! ourplugin:
+//!   repo: https://plugins.example.com
+//!   version: "1.4.*"
+//! ```
+//!
+//! `repo` is the folder with the index in it, or the index itself if it ends in `.json`. It can
+//! be in an S3 bucket, like `s3://our-plugins/stable` (see the [`s3`](../s3/index.html) module),
+//! or a folder on this machine, or one shared over NFS, relative to the server root or not.
+//! A local folder doesn't need an index at all: without one, every plugin JAR in it (or in the
+//! folders in it) is a version of the plugin its plugin.yml names, so plugins can be published
+//! by copying them in. The
+//! index lists the versions of each package, in any order, with the URL of each one's file,
+//! relative to the index or not, and the digests it should have, keyed by algorithm like in the
+//! lockfile:
+//!
+//! ```json
+//! {
+//!   "format": 1,
+//!   "packages": {
+//!     "ourplugin": {
+//!       "name": "OurPlugin",
+//!       "description": "Everything our network needs",
+//!       "authors": ["Network Team"],
+//!       "keywords": ["economy", "chat"],
+//!       "versions": [
+//!         {
+//!           "version": "1.4.0",
+//!           "url": "ourplugin/OurPlugin-1.4.0.jar",
+//!           "hashes": { "sha256": "5d1b4cbd2b3ab8e7a3f2fd4e3c7d7a0ad3f1e4b3a8d2c1c7e6de9f2b1a0c3e4f" },
+//!           "released": 1700000000,
+//!           "channel": "release",
+//!           "game_versions": ["1.20"],
+//!           "changelog": "https://plugins.example.com/ourplugin/changes#1.4.0"
+//!         }
+//!       ]
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! Only `version` and `url` are needed, and a package's `keywords` are only for finding it with
+//! `dropper search --all-sources`. `released` is in seconds since the Unix epoch, the channel is
+//! worked out from the version if it isn't given, and a version that lists `game_versions` is
+//! only installed on those server versions, or the patches of them. The `changelog` is linked to
+//! when the version is offered as an update. The credentials in `.dropper/credentials.yml` are
+//! sent to the repository's host like to any other.
+//!
+//! A repository doesn't have to be written by hand. `dropper repo add-file` copies a JAR into a
+//! repository folder and lists it in the index, by the name and version in its plugin.yml, and
+//! `dropper repo index` brings the index up to date with the JARs in the folder. The folder can
+//! then be uploaded wherever it's served from.
+
+use crate::hash;
+use crate::http::Credentials;
+use crate::jar;
+use crate::jar::PluginDescription;
+use crate::parser::{Channel, PackageMetadata, PluginFile};
+use crate::version::Version;
+use reqwest::{Client, StatusCode};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+use yaml_rust::{Yaml, YamlLoader};
+
+/// The file a repository's index is kept in
+pub const INDEX_FILE: &'static str = "index.json";
+/// The newest version of the index format there is
+pub const FORMAT: i64 = 1;
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A pkg.yml entry's `repo` or `package` isn't a string. Takes the package name as a param.
+    SourceInvalid(String),
+    // A repository's index couldn't be fetched. Takes its URL and status as params.
+    IndexUnavailable(String, StatusCode),
+    // A repository's index isn't one dropper can read. Takes its URL and what's wrong as params.
+    IndexInvalid(String, String),
+    // A local repository's folder doesn't exist. Takes its path as a param.
+    FolderMissing(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::SourceInvalid(s) => format!(
+                    "the repo of '{}' should be the URL of a repository, or of its {}",
+                    s, INDEX_FILE
+                ),
+                ErrorKind::IndexUnavailable(url, status) => {
+                    format!("couldn't fetch the repository index at {}: {}", url, status)
+                }
+                ErrorKind::IndexInvalid(url, why) => {
+                    format!("the repository index at {} is invalid: {}", url, why)
+                }
+                ErrorKind::FolderMissing(s) => format!("the repository folder {} doesn't exist", s),
+            }
+        )
+    }
+}
+
+/// A package in a self-hosted repository to install a package from
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoSource {
+    /// Where the repository's index is
+    pub index_url: String,
+    /// The name the index lists the package under
+    pub package: String,
+}
+
+/// What `add_file` published
+#[derive(Debug)]
+pub struct Published {
+    /// The name the JAR is listed under
+    pub package: String,
+    pub version: String,
+    /// Where the JAR is in the repository
+    pub path: String,
+    /// Whether the version was listed already, and has been replaced
+    pub replaced: bool,
+}
+
+/// What `reindex` changed, as (package, version)
+#[derive(Debug, Default)]
+pub struct Reindexed {
+    pub added: Vec<(String, String)>,
+    pub removed: Vec<(String, String)>,
+    /// The JARs that couldn't be listed, with why
+    pub skipped: Vec<(String, String)>,
+}
+
+/// A repository's index, as read by `Index::fetch`
+#[derive(Debug, Clone)]
+pub struct Index {
+    /// Where the index was fetched from, which relative URLs are relative to
+    pub url: String,
+    /// Everything the index says about each package, keyed by the name it's listed under
+    pub packages: BTreeMap<String, PackageMetadata>,
+}
+
+impl RepoSource {
+    /// Reads the repository out of a pkg.yml entry, or `None` if the entry doesn't have one.
+    /// Folders are relative to `project_dir`.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::SourceInvalid`](enum.ErrorKind.html#variant.SourceInvalid) - the entry has a `repo` key, but it isn't usable
+    pub fn from_yaml(
+        name: &str,
+        entry: &Yaml,
+        project_dir: &Path,
+    ) -> Result<Option<RepoSource>, ErrorKind> {
+        let invalid = || ErrorKind::SourceInvalid(name.to_string());
+        let repo = match &entry["repo"] {
+            Yaml::BadValue => return Ok(None),
+            Yaml::String(repo) if !repo.trim().is_empty() => repo.trim(),
+            _ => return Err(invalid()),
+        };
+        let package = match &entry["package"] {
+            Yaml::BadValue => name.to_string(),
+            Yaml::String(package) => package.clone(),
+            _ => return Err(invalid()),
+        };
+        // A folder stands for itself, like a `file://` URL to it would
+        let repo = match Url::parse(repo) {
+            Ok(ref url) if url.scheme().len() > 1 => repo.to_string(),
+            _ => {
+                let path = absolute(&project_dir.join(repo));
+                match repo.ends_with(".json") {
+                    true => Url::from_file_path(path),
+                    false => Url::from_directory_path(path),
+                }
+                .map_err(|_| invalid())?
+                .to_string()
+            }
+        };
+        Ok(Some(RepoSource {
+            index_url: index_url(&repo),
+            package: package,
+        }))
+    }
+
+    /// What the repository's index says about the package, or `None` if it doesn't list it.
+    /// Versions for other server versions than `server_version` are left out.
+    pub fn package_metadata(&self, index: &Index, server_version: &str) -> Option<PackageMetadata> {
+        let mut metadata = index.package(&self.package)?.clone();
+        metadata.versions.retain(|file| file.is_for(server_version));
+        Some(metadata)
+    }
+}
+
+/// The URL of the index of a repository, given the folder it's in or the index itself. A
+/// presigned URL to an index has its signature after the path.
+pub fn index_url(repo: &str) -> String {
+    let path = Url::parse(repo).map_or(repo.to_string(), |url| url.path().to_string());
+    match path.ends_with(".json") {
+        true => repo.to_string(),
+        false => format!("{}/{}", repo.trim_end_matches('/'), INDEX_FILE),
+    }
+}
+
+/// The file a `file://` link is to, or `None` if it's a link to anywhere else
+pub fn local_file(link: &str) -> Option<PathBuf> {
+    match Url::parse(link) {
+        Ok(ref url) if url.scheme() == "file" => url.to_file_path().ok(),
+        _ => None,
+    }
+}
+
+/// A path made absolute without following links, which `canonicalize` does, since a folder
+/// doesn't have to exist yet to be named in the pkg.yml
+fn absolute(path: &Path) -> PathBuf {
+    let path = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or(path.to_path_buf()),
+    };
+    let mut absolute = PathBuf::new();
+    for part in path.components() {
+        match part {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            part => absolute.push(part.as_os_str()),
+        }
+    }
+    absolute
+}
+
+impl Index {
+    /// Fetches and reads a repository's index
+    ///
+    /// # Errors
+    /// * [`ErrorKind::IndexUnavailable`](enum.ErrorKind.html#variant.IndexUnavailable) - the index couldn't be fetched
+    /// * [`ErrorKind::IndexInvalid`](enum.ErrorKind.html#variant.IndexInvalid) - the index couldn't be read
+    /// * `reqwest::Error` - the request couldn't be made
+    pub fn fetch(
+        client: &Client,
+        credentials: &Credentials,
+        url: &str,
+    ) -> Result<Index, Box<Error>> {
+        if let Some(path) = local_file(url) {
+            return Index::open(url, &path);
+        }
+        let mut response = credentials.fetch(client, url)?;
+        if !response.status().is_success() {
+            return Err(Box::new(ErrorKind::IndexUnavailable(
+                url.to_string(),
+                response.status(),
+            )));
+        }
+        Ok(Index::parse(url, &response.text()?)?)
+    }
+
+    /// Reads the index of a local repository, or lists the JARs in its folder if there isn't one
+    ///
+    /// # Errors
+    /// * [`ErrorKind::FolderMissing`](enum.ErrorKind.html#variant.FolderMissing) - the folder doesn't exist
+    /// * [`ErrorKind::IndexInvalid`](enum.ErrorKind.html#variant.IndexInvalid) - the index couldn't be read
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    fn open(url: &str, path: &Path) -> Result<Index, Box<Error>> {
+        if path.is_file() {
+            return Ok(Index::parse(url, &fs::read_to_string(path)?)?);
+        }
+        match path.parent() {
+            Some(dir) if dir.is_dir() => Index::scan(url, dir),
+            _ => Err(Box::new(ErrorKind::FolderMissing(
+                path.parent().unwrap_or(path).display().to_string(),
+            ))),
+        }
+    }
+
+    /// Lists the plugin JARs in a folder, and the folders in it, as versions of the plugins their
+    /// plugin.yml names. Files that aren't plugins are left out, and so are JARs with a version
+    /// that an earlier one had already.
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    fn scan(url: &str, dir: &Path) -> Result<Index, Box<Error>> {
+        let mut jars = Vec::new();
+        find_jars(dir, &mut jars)?;
+        jars.sort();
+
+        let mut packages: BTreeMap<String, PackageMetadata> = BTreeMap::new();
+        let mut descriptions = BTreeMap::new();
+        for path in jars.into_iter() {
+            let description = match PluginDescription::from_jar(&path) {
+                Ok(description) => description,
+                Err(_) => continue,
+            };
+            let link = match Url::from_file_path(&path) {
+                Ok(link) => link.to_string(),
+                Err(_) => continue,
+            };
+            let name = description.name.to_lowercase();
+            let metadata = packages
+                .entry(name.clone())
+                .or_insert_with(|| PackageMetadata::from_files(&name, Vec::new()));
+            if metadata
+                .versions
+                .iter()
+                .any(|file| file.version == description.version)
+            {
+                continue;
+            }
+
+            let mut file = PluginFile::new(description.version.clone(), link);
+            file.uploaded = modified(&path);
+            metadata.versions.push(file);
+            descriptions.insert((name, description.version.clone()), description);
+        }
+
+        for (name, metadata) in packages.iter_mut() {
+            metadata.versions = newest_first(metadata.versions.clone());
+            // The newest version describes the package
+            let newest = (name.clone(), metadata.versions[0].version.clone());
+            describe(metadata, &descriptions[&newest]);
+        }
+        Ok(Index {
+            url: url.to_string(),
+            packages: packages,
+        })
+    }
+
+    /// Reads an index that was fetched from `url`. JSON is YAML too, so it's read like the rest
+    /// of dropper's files are.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::IndexInvalid`](enum.ErrorKind.html#variant.IndexInvalid) - the index is malformed, or in a newer format
+    pub fn parse(url: &str, contents: &str) -> Result<Index, ErrorKind> {
+        Index::read(url, Url::parse(url).ok().as_ref(), contents)
+    }
+
+    /// Reads the index of the repository in `dir`, without making its URLs absolute, or an empty
+    /// one if there isn't one yet
+    ///
+    /// # Errors
+    /// * [`ErrorKind::IndexInvalid`](enum.ErrorKind.html#variant.IndexInvalid) - the index is malformed, or in a newer format
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn load(dir: &Path) -> Result<Index, Box<Error>> {
+        let path = dir.join(INDEX_FILE);
+        match fs::read_to_string(&path) {
+            Ok(contents) => Ok(Index::read(&path.display().to_string(), None, &contents)?),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Index {
+                url: path.display().to_string(),
+                packages: BTreeMap::new(),
+            }),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Writes the index into the repository in `dir`
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let path = dir.join(INDEX_FILE);
+        // The index is written in one go, so a host serving it never sees half of it
+        let partial = dir.join(format!("{}.part", INDEX_FILE));
+        fs::write(&partial, self.to_json())?;
+        fs::rename(&partial, &path)
+    }
+
+    /// Reads an index, with its relative URLs made relative to `base` if there is one
+    fn read(url: &str, base: Option<&Url>, contents: &str) -> Result<Index, ErrorKind> {
+        let invalid = |why: &str| ErrorKind::IndexInvalid(url.to_string(), why.to_string());
+        let doc = YamlLoader::load_from_str(contents)
+            .map_err(|e| invalid(&e.to_string()))?
+            .into_iter()
+            .next()
+            .unwrap_or(Yaml::Null);
+        match &doc["format"] {
+            Yaml::BadValue => {}
+            Yaml::Integer(format) if *format <= FORMAT => {}
+            Yaml::Integer(_) => return Err(invalid("it's in a newer format, so update dropper")),
+            _ => return Err(invalid("format should be a number")),
+        }
+        let listed = match &doc["packages"] {
+            Yaml::Hash(listed) => listed,
+            _ => return Err(invalid("it should have a map of packages")),
+        };
+
+        let mut packages = BTreeMap::new();
+        for (name, package) in listed.iter() {
+            let name = name
+                .as_str()
+                .ok_or_else(|| invalid("package names should be strings"))?;
+            let files = match &package["versions"] {
+                Yaml::Array(versions) => versions
+                    .iter()
+                    .map(|version| read_file(base, version))
+                    .collect::<Option<Vec<PluginFile>>>()
+                    .ok_or_else(|| {
+                        invalid(&format!("the versions of {} need a version and url", name))
+                    })?,
+                _ => return Err(invalid(&format!("{} should have a list of versions", name))),
+            };
+
+            let mut metadata = PackageMetadata::from_files(name, newest_first(files));
+            let text = |key: &str| package[key].as_str().map(|text| text.to_string());
+            metadata.display_name = text("name");
+            metadata.description = text("description");
+            let list = |key: &str| match &package[key] {
+                Yaml::Array(items) => items
+                    .iter()
+                    .filter_map(|item| item.as_str().map(|item| item.to_string()))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            metadata.authors = list("authors");
+            metadata.keywords = list("keywords");
+            packages.insert(name.to_string(), metadata);
+        }
+
+        Ok(Index {
+            url: url.to_string(),
+            packages: packages,
+        })
+    }
+
+    /// What the index says about a package. Names are matched without regard to case, like the
+    /// names of plugins are.
+    pub fn package(&self, name: &str) -> Option<&PackageMetadata> {
+        self.packages.get(name).or_else(|| {
+            self.packages
+                .iter()
+                .find(|(listed, _)| listed.eq_ignore_ascii_case(name))
+                .map(|(_, metadata)| metadata)
+        })
+    }
+
+    /// The index as JSON, with its packages in order and their versions newest first
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{\n  \"format\": {},\n  \"packages\": {{", FORMAT);
+        for (i, (name, metadata)) in self.packages.iter().enumerate() {
+            json.push_str(if i == 0 { "\n" } else { ",\n" });
+            json.push_str(&format!("    {}: {{\n", json_string(name)));
+            if let Some(display_name) = &metadata.display_name {
+                json.push_str(&format!("      \"name\": {},\n", json_string(display_name)));
+            }
+            if let Some(description) = &metadata.description {
+                json.push_str(&format!(
+                    "      \"description\": {},\n",
+                    json_string(description)
+                ));
+            }
+            if !metadata.authors.is_empty() {
+                json.push_str(&format!(
+                    "      \"authors\": {},\n",
+                    json_list(&metadata.authors)
+                ));
+            }
+            if !metadata.keywords.is_empty() {
+                json.push_str(&format!(
+                    "      \"keywords\": {},\n",
+                    json_list(&metadata.keywords)
+                ));
+            }
+            let versions = newest_first(metadata.versions.clone())
+                .iter()
+                .map(file_json)
+                .collect::<Vec<String>>();
+            json.push_str(&format!(
+                "      \"versions\": [\n{}\n      ]\n    }}",
+                versions.join(",\n")
+            ));
+        }
+        json.push_str(match self.packages.is_empty() {
+            true => "}\n}\n",
+            false => "\n  }\n}\n",
+        });
+        json
+    }
+}
+
+/// Publishes a plugin JAR to the repository in `dir`. The JAR is copied into a folder named
+/// after its package, unless it's in the repository already, and listed in the index by the
+/// name and version in its plugin.yml. A version that was already listed is replaced.
+///
+/// # Arguments
+///
+/// * `dir` - The repository
+/// * `jar` - The JAR to publish
+/// * `package` - The name to list it under, instead of its plugin's name in lowercase
+/// * `channel` - The channel to publish it to, instead of the one its version says
+/// * `game_versions` - The server versions it's for, if not every one
+///
+/// # Errors
+/// * [`jar::ErrorKind::*`](../jar/enum.ErrorKind.html) - the JAR isn't a plugin that can be read
+/// * [`ErrorKind::IndexInvalid`](enum.ErrorKind.html#variant.IndexInvalid) - the index is malformed, or in a newer format
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn add_file(
+    dir: &Path,
+    jar: &Path,
+    package: Option<&str>,
+    channel: Option<Channel>,
+    game_versions: &[String],
+) -> Result<Published, Box<Error>> {
+    jar::validate(jar)?;
+    let description = PluginDescription::from_jar(jar)?;
+    let mut index = Index::load(dir)?;
+    let package = match package {
+        Some(package) => package.to_string(),
+        None => index
+            .package(&description.name)
+            .map_or(description.name.to_lowercase(), |metadata| {
+                metadata.slug.clone()
+            }),
+    };
+
+    let path = match relative_path(dir, jar) {
+        Some(path) => path,
+        None => {
+            let path = format!(
+                "{}/{}-{}.jar",
+                package, description.name, description.version
+            );
+            fs::create_dir_all(dir.join(&package))?;
+            fs::copy(jar, dir.join(&path))?;
+            path
+        }
+    };
+    let mut file = PluginFile::new(description.version.clone(), path.clone());
+    file.hashes = hash::digest_file(jar, &hash::RECORDED_ALGORITHMS)?;
+    file.uploaded = Some(now());
+    file.game_versions = game_versions.to_vec();
+    if let Some(channel) = channel {
+        file.channel = channel;
+    }
+
+    let metadata = index
+        .packages
+        .entry(package.clone())
+        .or_insert_with(|| PackageMetadata::from_files(&package, Vec::new()));
+    let listed = metadata.versions.len();
+    metadata
+        .versions
+        .retain(|listed| listed.version != file.version);
+    let replaced = metadata.versions.len() < listed;
+    metadata.versions.push(file);
+    describe(metadata, &description);
+    index.save(dir)?;
+
+    Ok(Published {
+        package: package,
+        version: description.version,
+        path: path,
+        replaced: replaced,
+    })
+}
+
+/// Rebuilds the index of the repository in `dir` from the JARs in it. Versions that were
+/// listed already keep their release date, channel and server versions, JARs that weren't are
+/// added, and versions whose JARs are gone are taken out. Versions hosted somewhere else, with
+/// URLs that aren't relative, are left alone.
+///
+/// # Errors
+/// * [`ErrorKind::IndexInvalid`](enum.ErrorKind.html#variant.IndexInvalid) - the index is malformed, or in a newer format
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn reindex(dir: &Path) -> Result<Reindexed, Box<Error>> {
+    let mut index = Index::load(dir)?;
+    let mut reindexed = Reindexed::default();
+    let mut jars = Vec::new();
+    find_jars(dir, &mut jars)?;
+    jars.sort();
+
+    let mut found = BTreeMap::new();
+    for path in jars.iter() {
+        let relative = match relative_path(dir, path) {
+            Some(relative) => relative,
+            None => continue,
+        };
+        let description = match jar::validate(path).and_then(|_| PluginDescription::from_jar(path))
+        {
+            Ok(description) => description,
+            Err(e) => {
+                reindexed.skipped.push((relative, e.to_string()));
+                continue;
+            }
+        };
+        found.insert(relative, (path.clone(), description));
+    }
+
+    // Versions whose JARs are gone, or are some other version now, are taken out first
+    for (name, metadata) in index.packages.iter_mut() {
+        metadata
+            .versions
+            .retain(|file| match found.get(&file.link) {
+                Some((_, description)) => description.version == file.version,
+                None if Url::parse(&file.link).is_ok() => true,
+                None => {
+                    reindexed.removed.push((name.clone(), file.version.clone()));
+                    false
+                }
+            });
+    }
+    for (relative, (path, description)) in found.into_iter() {
+        let listed = index.packages.iter().find_map(|(name, metadata)| {
+            metadata
+                .versions
+                .iter()
+                .find(|file| file.link == relative)
+                .map(|file| (name.clone(), file.clone()))
+        });
+        let (package, mut file) = match listed {
+            Some(listed) => listed,
+            None => {
+                let package = index
+                    .package(&description.name)
+                    .map_or(description.name.to_lowercase(), |metadata| {
+                        metadata.slug.clone()
+                    });
+                // Only one JAR can be a version of a package
+                let duplicate = index.package(&package).and_then(|metadata| {
+                    metadata
+                        .versions
+                        .iter()
+                        .find(|file| file.version == description.version)
+                });
+                if let Some(duplicate) = duplicate {
+                    reindexed.skipped.push((
+                        relative.clone(),
+                        format!(
+                            "{} @ version {} is {} already",
+                            package, description.version, duplicate.link
+                        ),
+                    ));
+                    continue;
+                }
+                let mut file = PluginFile::new(description.version.clone(), relative.clone());
+                file.uploaded = modified(&path);
+                reindexed
+                    .added
+                    .push((package.clone(), description.version.clone()));
+                (package, file)
+            }
+        };
+        file.hashes = hash::digest_file(&path, &hash::RECORDED_ALGORITHMS)?;
+
+        let metadata = index
+            .packages
+            .entry(package.clone())
+            .or_insert_with(|| PackageMetadata::from_files(&package, Vec::new()));
+        metadata.versions.retain(|listed| listed.link != relative);
+        metadata.versions.push(file);
+        metadata.versions = newest_first(metadata.versions.clone());
+        // The newest version describes the package
+        if metadata.versions[0].link == relative {
+            describe(metadata, &description);
+        }
+    }
+    index
+        .packages
+        .retain(|_, metadata| !metadata.versions.is_empty());
+    index.save(dir)?;
+    Ok(reindexed)
+}
+
+/// Describes a package in the index the way its plugin describes itself
+fn describe(metadata: &mut PackageMetadata, description: &PluginDescription) {
+    metadata.display_name = Some(description.name.clone());
+    if description.description.is_some() {
+        metadata.description = description.description.clone();
+    }
+    if !description.authors.is_empty() {
+        metadata.authors = description.authors.clone();
+    }
+}
+
+/// Where a file is in the repository in `dir`, as a relative URL, or `None` if it isn't in it
+fn relative_path(dir: &Path, path: &Path) -> Option<String> {
+    let relative = path
+        .canonicalize()
+        .ok()?
+        .strip_prefix(dir.canonicalize().ok()?)
+        .ok()?
+        .to_path_buf();
+    let parts = relative
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<String>>();
+    Some(parts.join("/"))
+}
+
+/// Finds the JARs in a folder, and the folders in it
+fn find_jars(dir: &Path, jars: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_jars(&path, jars)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "jar")
+        {
+            jars.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// When a file was last changed, in seconds since the Unix epoch
+fn modified(path: &Path) -> Option<u64> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+/// A version listed in an index, as JSON. The channel is only written if it isn't the one the
+/// version itself says.
+fn file_json(file: &PluginFile) -> String {
+    let mut fields = vec![
+        format!("\"version\": {}", json_string(&file.version)),
+        format!("\"url\": {}", json_string(&file.link)),
+    ];
+    if !file.hashes.is_empty() {
+        let hashes = file
+            .hashes
+            .iter()
+            .map(|(algorithm, digest)| {
+                format!("{}: {}", json_string(algorithm), json_string(digest))
+            })
+            .collect::<Vec<String>>();
+        fields.push(format!("\"hashes\": {{ {} }}", hashes.join(", ")));
+    }
+    if let Some(released) = file.uploaded {
+        fields.push(format!("\"released\": {}", released));
+    }
+    if file.channel != Channel::from_version(&file.version) {
+        fields.push(format!("\"channel\": {}", json_string(file.channel.name())));
+    }
+    if !file.game_versions.is_empty() {
+        fields.push(format!(
+            "\"game_versions\": {}",
+            json_list(&file.game_versions)
+        ));
+    }
+    if let Some(changelog) = &file.changelog {
+        fields.push(format!("\"changelog\": {}", json_string(changelog)));
+    }
+    format!(
+        "        {{\n          {}\n        }}",
+        fields.join(",\n          ")
+    )
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn json_list(items: &[String]) -> String {
+    let items = items
+        .iter()
+        .map(|item| json_string(item))
+        .collect::<Vec<String>>();
+    format!("[{}]", items.join(", "))
+}
+
+/// Reads a version listed in an index, or `None` if it's missing its version or URL
+fn read_file(base: Option<&Url>, entry: &Yaml) -> Option<PluginFile> {
+    let version = match &entry["version"] {
+        Yaml::String(version) | Yaml::Real(version) => version.clone(),
+        Yaml::Integer(version) => version.to_string(),
+        _ => return None,
+    };
+    let url = entry["url"].as_str()?;
+    // Relative URLs are relative to the index
+    let link = match base.and_then(|base| base.join(url).ok()) {
+        Some(link) => link.to_string(),
+        None => url.to_string(),
+    };
+
+    let mut file = PluginFile::new(version, link);
+    if let Some(channel) = entry["channel"].as_str().and_then(Channel::from_name) {
+        file.channel = channel;
+    }
+    if let Yaml::Integer(released) = entry["released"] {
+        file.uploaded = Some(released as u64);
+    }
+    if let Yaml::Array(game_versions) = &entry["game_versions"] {
+        file.game_versions = game_versions
+            .iter()
+            .filter_map(|version| match version {
+                Yaml::String(version) | Yaml::Real(version) => Some(version.clone()),
+                _ => None,
+            })
+            .collect();
+    }
+    if let Yaml::Hash(hashes) = &entry["hashes"] {
+        for (algorithm, digest) in hashes.iter() {
+            if let (Some(algorithm), Some(digest)) = (algorithm.as_str(), digest.as_str()) {
+                if hash::ALGORITHMS.contains(&algorithm) {
+                    file.hashes
+                        .insert(algorithm.to_string(), digest.to_lowercase());
+                }
+            }
+        }
+    }
+    file.changelog = entry["changelog"].as_str().map(|changelog| changelog.to_string());
+    Some(file)
+}
+
+/// Orders files newest first, like sources list them. Versions that can't be read keep the
+/// order they were in, after the rest.
+pub fn newest_first(mut files: Vec<PluginFile>) -> Vec<PluginFile> {
+    files.sort_by(
+        |a, b| match (Version::parse(&a.version), Version::parse(&b.version)) {
+            (Some(a), Some(b)) => b.cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+    );
+    files
+}
@@ -0,0 +1,269 @@
+//! This module decides which version of a package to install. A package can be constrained from
+//! more than one place: the user's own entry in `pkg.yml`, as well as any other packages that
+//! declare a requirement on it. Rather than letting whichever install happens last win, the
+//! resolver looks for a single version that satisfies every constraint at once, and explains the
+//! conflict when there isn't one.
+//!
+//! Constraints use the same syntax as the version half of a package specifier (see the backend
+//! module): an exact version like `6.1.9`, a wildcard like `6.1.*` or `6.*`, or `*` for any.
+//! A package can also track the newest build of a release channel: `latest-release`,
+//! `latest-beta` or `latest-dev`. Each channel includes the ones more stable than it, so
+//! `latest-beta` moves on to a release that's newer than every beta.
+
+use crate::parser::Channel;
+use crate::version::Version;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // No available version satisfies every requirement on the package. Takes the package name
+    // and its requirements as params.
+    Conflict(String, Vec<Requirement>),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::Conflict(package, requirements) => {
+                write!(f, "no version of {} satisfies all of:", package)?;
+                for requirement in requirements {
+                    write!(
+                        f,
+                        "\n  * {} (required by {})",
+                        requirement.constraint, requirement.required_by
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A range of acceptable versions for a package
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint {
+    /// Any version at all, written `*`
+    Any,
+    /// Any version starting with these components, e.g. `6.1.*` is `["6", "1"]`
+    Prefix(Vec<String>),
+    /// Exactly this version
+    Exact(String),
+    /// The newest build in this channel or a more stable one, e.g. `latest-beta`. Every version
+    /// matches, since which channel a version is in comes from the files it's published in.
+    Channel(Channel),
+}
+
+// Channel constraints are the channel's name after this
+const CHANNEL_PREFIX: &'static str = "latest-";
+
+impl Constraint {
+    /// Parses the version half of a package specifier
+    pub fn parse(version_code: &str) -> Constraint {
+        let channel = match version_code.starts_with(CHANNEL_PREFIX) {
+            true => Channel::from_name(&version_code[CHANNEL_PREFIX.len()..]),
+            false => None,
+        };
+        if let Some(channel) = channel {
+            Constraint::Channel(channel)
+        } else if version_code == "*" {
+            Constraint::Any
+        } else if let Some(prefix) = version_code.strip_suffix(".*") {
+            Constraint::Prefix(prefix.split('.').map(|s| s.to_string()).collect())
+        } else {
+            Constraint::Exact(version_code.to_string())
+        }
+    }
+
+    /// Whether a version code falls within this constraint
+    pub fn matches(&self, version_code: &str) -> bool {
+        match self {
+            Constraint::Any => true,
+            Constraint::Prefix(prefix) => {
+                let numbers = prefix
+                    .iter()
+                    .map(|c| c.parse::<u32>().ok())
+                    .collect::<Option<Vec<u32>>>();
+                match (numbers, Version::parse(version_code)) {
+                    (Some(numbers), Some(version)) => version.starts_with(&numbers),
+                    // Versions that aren't numbered can still share a written prefix
+                    _ => {
+                        let components = version_code.split('.').collect::<Vec<&str>>();
+                        components.len() >= prefix.len()
+                            && prefix.iter().zip(components).all(|(a, b)| a == b)
+                    }
+                }
+            }
+            // `1.2` and `1.2.0` are the same version, even though they're written differently
+            Constraint::Exact(version) => {
+                match (Version::parse(version), Version::parse(version_code)) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => version == version_code,
+                }
+            }
+            Constraint::Channel(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constraint::Any => write!(f, "*"),
+            Constraint::Prefix(prefix) => write!(f, "{}.*", prefix.join(".")),
+            Constraint::Exact(version) => write!(f, "{}", version),
+            Constraint::Channel(channel) => write!(f, "{}{}", CHANNEL_PREFIX, channel.name()),
+        }
+    }
+}
+
+/// A single constraint placed on a package, along with who placed it
+#[derive(Debug, Clone)]
+pub struct Requirement {
+    /// Whoever imposed the constraint: `pkg.yml` for the user, or the name of a package
+    pub required_by: String,
+    /// The package being constrained
+    pub package: String,
+    pub constraint: Constraint,
+}
+
+/// Picks the version of `package` to install out of `available`, which should be ordered from
+/// newest to oldest like `PluginFetchable::enumerate_files` returns. The newest version that
+/// satisfies all of the requirements is returned, as its index into `available`.
+///
+/// Requirements on packages other than `package` are ignored, so the full list of requirements
+/// for a dependency graph can be passed in for each package.
+///
+/// # Errors
+/// * [`ErrorKind::Conflict`](enum.ErrorKind.html#variant.Conflict) - no version satisfies every requirement
+pub fn resolve(
+    package: &str,
+    requirements: &[Requirement],
+    available: &[String],
+) -> Result<usize, ErrorKind> {
+    let relevant = requirements
+        .iter()
+        .filter(|r| r.package.eq_ignore_ascii_case(package))
+        .cloned()
+        .collect::<Vec<Requirement>>();
+
+    match available
+        .iter()
+        .position(|version| relevant.iter().all(|r| r.constraint.matches(version)))
+    {
+        Some(i) => Ok(i),
+        None => Err(ErrorKind::Conflict(package.to_string(), relevant)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requirement(required_by: &str, package: &str, constraint: &str) -> Requirement {
+        Requirement {
+            required_by: required_by.to_string(),
+            package: package.to_string(),
+            constraint: Constraint::parse(constraint),
+        }
+    }
+
+    fn versions(versions: &[&str]) -> Vec<String> {
+        versions.iter().map(|version| version.to_string()).collect()
+    }
+
+    #[test]
+    fn constraints_are_parsed_like_specifiers() {
+        assert_eq!(Constraint::parse("*"), Constraint::Any);
+        assert_eq!(
+            Constraint::parse("6.1.*"),
+            Constraint::Prefix(vec!["6".to_string(), "1".to_string()])
+        );
+        assert_eq!(
+            Constraint::parse("6.1.9"),
+            Constraint::Exact("6.1.9".to_string())
+        );
+        assert_eq!(
+            Constraint::parse("latest-beta"),
+            Constraint::Channel(Channel::Beta)
+        );
+        // Channels that don't exist are just oddly named versions
+        assert_eq!(
+            Constraint::parse("latest-nightly"),
+            Constraint::Exact("latest-nightly".to_string())
+        );
+        for written in &["*", "6.1.*", "6.1.9", "latest-dev"] {
+            assert_eq!(Constraint::parse(written).to_string(), *written);
+        }
+    }
+
+    #[test]
+    fn prefixes_match_whole_components() {
+        let constraint = Constraint::parse("6.1.*");
+        assert!(constraint.matches("6.1.9"));
+        assert!(constraint.matches("6.1"));
+        assert!(!constraint.matches("6.10.1"));
+        assert!(!constraint.matches("6.2.0"));
+        assert!(Constraint::parse("1.2").matches("1.2.0"));
+        assert!(Constraint::parse("dev.*").matches("dev.build.7"));
+    }
+
+    #[test]
+    fn the_newest_version_every_requirement_allows_is_picked() {
+        let requirements = vec![
+            requirement("pkg.yml", "protocollib", "4.*"),
+            requirement("someplugin", "protocollib", "4.4.*"),
+            // Requirements on other packages don't get in the way
+            requirement("pkg.yml", "worldedit", "7.*"),
+        ];
+        let available = versions(&["5.0.0", "4.5.1", "4.4.2", "4.4.0", "3.9"]);
+        assert_eq!(
+            resolve("protocollib", &requirements, &available).unwrap(),
+            2
+        );
+        // Names are matched however they're capitalised
+        assert_eq!(
+            resolve("ProtocolLib", &requirements, &available).unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn unconstrained_packages_get_the_newest_version() {
+        let available = versions(&["2.0", "1.0"]);
+        assert_eq!(resolve("foo", &[], &available).unwrap(), 0);
+    }
+
+    #[test]
+    fn conflicting_dependents_are_both_reported() {
+        let requirements = vec![
+            requirement("first", "protocollib", "4.*"),
+            requirement("second", "protocollib", "5.*"),
+            requirement("pkg.yml", "worldedit", "*"),
+        ];
+        let available = versions(&["5.0.0", "4.5.1"]);
+        let e = resolve("protocollib", &requirements, &available).unwrap_err();
+        match &e {
+            ErrorKind::Conflict(package, conflicting) => {
+                assert_eq!(package, "protocollib");
+                let by = conflicting
+                    .iter()
+                    .map(|r| r.required_by.as_str())
+                    .collect::<Vec<&str>>();
+                assert_eq!(by, vec!["first", "second"]);
+            }
+        }
+        assert_eq!(
+            e.to_string(),
+            "no version of protocollib satisfies all of:\n  * 4.* (required by first)\n  * 5.* (required by second)"
+        );
+    }
+
+    #[test]
+    fn packages_with_no_versions_conflict() {
+        let requirements = vec![requirement("pkg.yml", "foo", "*")];
+        assert!(resolve("foo", &requirements, &[]).is_err());
+    }
+}
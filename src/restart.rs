@@ -0,0 +1,263 @@
+//! This module restarts the server once packages have been updated, so that unattended updates
+//! actually take effect. A restart is set up in the config with one of the ways the server can be
+//! restarted:
+//!
+//! ```yaml
+//! restart:
+//!   systemd: minecraft.service
+//!   delay: 60
+//!   warning: Restarting in {seconds} seconds to update plugins
+//! ```
+//!
+//! * `systemd` - the unit to `systemctl restart`
+//! * `screen` or `tmux` - the session the server's console runs in, which is sent
+//!   `console_command` (`restart` unless it's set)
+//! * `panel` - a hosting panel's power API: a `url` to POST `body` to (`{"signal": "restart"}`
+//!   unless it's set), and optionally a `console_url` that console commands can be POSTed to, as
+//!   `{"command": "..."}`. Requests are sent with the credentials of the panel's host.
+//!
+//! Players are warned `delay` seconds before the restart, with `say` on the console. Restarts
+//! through systemd have no console to warn on, so they only wait.
+
+use crate::http;
+use crate::http::Credentials;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use std::error::Error;
+use std::fmt;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use yaml_rust::Yaml;
+
+const DEFAULT_CONSOLE_COMMAND: &'static str = "restart";
+const DEFAULT_PANEL_BODY: &'static str = r#"{"signal": "restart"}"#;
+const DEFAULT_WARNING: &'static str = "Restarting in {seconds} seconds to update plugins";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // The config's restart isn't set up right. Takes what's wrong as a param.
+    RestartInvalid(String),
+    // The restart couldn't be triggered. Takes how it was being triggered and what went wrong
+    // as params.
+    RestartFailed(String, String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::RestartInvalid(s) => format!("the restart config is invalid: {}", s),
+                ErrorKind::RestartFailed(how, why) => {
+                    format!("couldn't restart the server through {}: {}", how, why)
+                }
+            }
+        )
+    }
+}
+
+/// The ways a server can be restarted
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mechanism {
+    /// A systemd unit
+    Systemd(String),
+    /// A console running in a screen session
+    Screen(String),
+    /// A console running in a tmux session
+    Tmux(String),
+    /// A hosting panel's API
+    Panel {
+        url: String,
+        body: String,
+        console_url: Option<String>,
+    },
+}
+
+impl Mechanism {
+    /// What the mechanism is called in the config
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mechanism::Systemd(_) => "systemd",
+            Mechanism::Screen(_) => "screen",
+            Mechanism::Tmux(_) => "tmux",
+            Mechanism::Panel { .. } => "panel",
+        }
+    }
+}
+
+/// How to restart the server, from the config
+#[derive(Debug, Clone)]
+pub struct Restart {
+    pub mechanism: Mechanism,
+    /// How long to warn players for before restarting, in seconds
+    pub delay: u64,
+    /// The warning to broadcast, with `{seconds}` standing in for the delay
+    pub warning: String,
+    /// What to run on the console to restart the server
+    pub console_command: String,
+}
+
+impl Restart {
+    /// Reads the config's `restart` key, or `None` if there isn't one
+    ///
+    /// # Errors
+    /// * [`ErrorKind::RestartInvalid`](enum.ErrorKind.html#variant.RestartInvalid) - the restart doesn't have exactly one mechanism, or a setting is the wrong type
+    pub fn from_yaml(yaml: &Yaml) -> Result<Option<Restart>, ErrorKind> {
+        match yaml {
+            Yaml::Hash(_) => {}
+            Yaml::Null | Yaml::BadValue => return Ok(None),
+            _ => return Err(invalid("it should be a map of settings")),
+        }
+
+        let string = |key: &str| match &yaml[key] {
+            Yaml::String(s) => Ok(Some(s.clone())),
+            Yaml::BadValue => Ok(None),
+            _ => Err(invalid(&format!("{} should be a string", key))),
+        };
+
+        let mut mechanisms = Vec::new();
+        if let Some(unit) = string("systemd")? {
+            mechanisms.push(Mechanism::Systemd(unit));
+        }
+        if let Some(session) = string("screen")? {
+            mechanisms.push(Mechanism::Screen(session));
+        }
+        if let Some(session) = string("tmux")? {
+            mechanisms.push(Mechanism::Tmux(session));
+        }
+        match &yaml["panel"] {
+            Yaml::BadValue => {}
+            panel @ Yaml::Hash(_) => {
+                let url = match panel["url"].as_str() {
+                    Some(url) => url.to_string(),
+                    None => return Err(invalid("the panel needs a url")),
+                };
+                mechanisms.push(Mechanism::Panel {
+                    url: url,
+                    body: panel["body"]
+                        .as_str()
+                        .unwrap_or(DEFAULT_PANEL_BODY)
+                        .to_string(),
+                    console_url: panel["console_url"].as_str().map(|s| s.to_string()),
+                });
+            }
+            _ => return Err(invalid("panel should be a map with a url")),
+        }
+
+        let mechanism = match mechanisms.len() {
+            1 => mechanisms.remove(0),
+            0 => return Err(invalid("it needs one of systemd, screen, tmux or panel")),
+            _ => {
+                return Err(invalid(
+                    "it can only have one of systemd, screen, tmux or panel",
+                ))
+            }
+        };
+        let delay = match &yaml["delay"] {
+            Yaml::Integer(delay) if *delay >= 0 => *delay as u64,
+            Yaml::BadValue => 0,
+            _ => return Err(invalid("delay should be a number of seconds")),
+        };
+
+        Ok(Some(Restart {
+            mechanism: mechanism,
+            delay: delay,
+            warning: string("warning")?.unwrap_or(DEFAULT_WARNING.to_string()),
+            console_command: string("console_command")?
+                .unwrap_or(DEFAULT_CONSOLE_COMMAND.to_string()),
+        }))
+    }
+
+    /// Warns the players, waits out the delay, and restarts the server. This blocks for the
+    /// whole delay.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::RestartFailed`](enum.ErrorKind.html#variant.RestartFailed) - the warning or the restart couldn't be sent
+    pub fn run(&self, client: &Client, credentials: &Credentials) -> Result<(), Box<Error>> {
+        if self.delay > 0 {
+            let warning = self.warning.replace("{seconds}", &self.delay.to_string());
+            self.console(client, credentials, &format!("say {}", warning))?;
+            thread::sleep(Duration::from_secs(self.delay));
+        }
+
+        match &self.mechanism {
+            Mechanism::Systemd(unit) => {
+                self.command(Command::new("systemctl").args(["restart", unit]))
+            }
+            Mechanism::Panel { url, body, .. } => self.post(client, credentials, url, body),
+            _ => self.console(client, credentials, &self.console_command),
+        }
+    }
+
+    /// Runs a command on the server's console, if the mechanism has one
+    fn console(
+        &self,
+        client: &Client,
+        credentials: &Credentials,
+        line: &str,
+    ) -> Result<(), Box<Error>> {
+        match &self.mechanism {
+            Mechanism::Systemd(_) => Ok(()),
+            // screen types what it's given, so the line has to end with a carriage return
+            Mechanism::Screen(session) => self.command(
+                Command::new("screen")
+                    .args(["-S", session, "-p", "0", "-X", "stuff"])
+                    .arg(format!("{}\r", line)),
+            ),
+            Mechanism::Tmux(session) => self.command(
+                Command::new("tmux")
+                    .args(["send-keys", "-t", session, "-l", line])
+                    .arg(";")
+                    .args(["send-keys", "-t", session, "Enter"]),
+            ),
+            Mechanism::Panel { console_url, .. } => match console_url {
+                Some(url) => {
+                    let body = format!("{{\"command\": {:?}}}", line);
+                    self.post(client, credentials, url, &body)
+                }
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Runs a command that triggers part of the restart, waiting for it to finish
+    fn command(&self, command: &mut Command) -> Result<(), Box<Error>> {
+        let failed = |why: String| ErrorKind::RestartFailed(self.mechanism.name().to_string(), why);
+        match command.status() {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(Box::new(failed(status.to_string()))),
+            Err(e) => Err(Box::new(failed(e.to_string()))),
+        }
+    }
+
+    /// POSTs a JSON body to the panel
+    fn post(
+        &self,
+        client: &Client,
+        credentials: &Credentials,
+        url: &str,
+        body: &str,
+    ) -> Result<(), Box<Error>> {
+        let failed = |why: String| ErrorKind::RestartFailed(self.mechanism.name().to_string(), why);
+        let request = credentials
+            .post(client, url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+        let response = http::send(request).map_err(|e| failed(e.to_string()))?;
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(Box::new(failed(format!(
+                "the panel answered {}",
+                response.status()
+            )))),
+        }
+    }
+}
+
+fn invalid(why: &str) -> ErrorKind {
+    ErrorKind::RestartInvalid(why.to_string())
+}
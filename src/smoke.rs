@@ -0,0 +1,349 @@
+//! This module starts a throwaway copy of the server for `dropper test`, to check that every
+//! plugin loads before updates go anywhere near players. The server JAR to run is set up in the
+//! config, relative to the server root:
+//!
+//! ```yaml
+//! test_server:
+//!   jar: paper.jar
+//!   java: /usr/lib/jvm/java-17/bin/java
+//!   args: [-Xmx2G]
+//!   timeout: 300
+//! ```
+//!
+//! `java` is whichever `java` is on the path unless it's set, and `timeout`, how long to wait for
+//! the server to finish starting, is 180 seconds unless it's set. The copy runs in a folder of its
+//! own, with a copy of the plugins folder and the server's `eula.txt` and `server.properties`,
+//! and on a port nothing else is listening on. It's stopped as soon as it has started, and its
+//! console output is read for problems with plugins (see the [`logs`](../logs/index.html)
+//! module).
+
+use crate::cancel::CancelToken;
+use crate::logs;
+use crate::logs::Problem;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use yaml_rust::Yaml;
+
+const DEFAULT_JAVA: &'static str = "java";
+const DEFAULT_TIMEOUT: u64 = 180;
+// How long the server gets to stop once it's told to, before it's killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+// How often to check whether the test has been cancelled while waiting on the server
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// Where the server's console output is kept, in the folder it runs in
+const LOG_FILE: &'static str = "dropper-test.log";
+const EULA_FILE: &'static str = "eula.txt";
+const PROPERTIES_FILE: &'static str = "server.properties";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // The config's test server isn't set up right. Takes what's wrong as a param.
+    TestServerInvalid(String),
+    // There's no test server in the config
+    TestServerMissing,
+    // The test server couldn't be started. Takes the command and what went wrong as params.
+    StartFailed(String, String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::TestServerInvalid(s) => {
+                    format!("the test_server config is invalid: {}", s)
+                }
+                ErrorKind::TestServerMissing => {
+                    "there's no test_server in the config, with the server JAR to test with"
+                        .to_string()
+                }
+                ErrorKind::StartFailed(command, why) => {
+                    format!("couldn't start the test server with {}: {}", command, why)
+                }
+            }
+        )
+    }
+}
+
+fn invalid(why: &str) -> ErrorKind {
+    ErrorKind::TestServerInvalid(why.to_string())
+}
+
+/// How to start a test server, from the config
+#[derive(Debug, Clone)]
+pub struct TestServer {
+    /// The server JAR, relative to the server root
+    pub jar: PathBuf,
+    /// The Java to run it with
+    pub java: String,
+    /// Arguments for Java, before `-jar`
+    pub args: Vec<String>,
+    /// How long to wait for the server to finish starting
+    pub timeout: Duration,
+}
+
+/// How a test server's run ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// The server finished starting
+    Started,
+    /// The server stopped before it finished starting. Takes its exit code as a param, if it had
+    /// one.
+    Exited(Option<i32>),
+    /// The server didn't finish starting in time
+    TimedOut,
+}
+
+/// What happened when a test server was started
+#[derive(Debug)]
+pub struct TestRun {
+    pub outcome: Outcome,
+    /// The problems with plugins the server reported
+    pub problems: Vec<Problem>,
+    /// The names of the plugins the server enabled
+    pub enabled: Vec<String>,
+    /// Where the server's console output was kept
+    pub log: PathBuf,
+}
+
+impl TestServer {
+    /// Reads the config's `test_server` key, or `None` if there isn't one
+    ///
+    /// # Errors
+    /// * [`ErrorKind::TestServerInvalid`](enum.ErrorKind.html#variant.TestServerInvalid) - there's no JAR, or a setting is the wrong type
+    pub fn from_yaml(yaml: &Yaml) -> Result<Option<TestServer>, ErrorKind> {
+        match yaml {
+            Yaml::Hash(_) => {}
+            Yaml::Null | Yaml::BadValue => return Ok(None),
+            _ => return Err(invalid("it should be a map of settings")),
+        }
+
+        let jar = match &yaml["jar"] {
+            Yaml::String(jar) => PathBuf::from(jar),
+            _ => return Err(invalid("it needs the jar to run")),
+        };
+        let java = match &yaml["java"] {
+            Yaml::String(java) => java.clone(),
+            Yaml::BadValue => DEFAULT_JAVA.to_string(),
+            _ => return Err(invalid("java should be a string")),
+        };
+        let args = match &yaml["args"] {
+            Yaml::Array(args) => args
+                .iter()
+                .map(|arg| arg.as_str().map(|arg| arg.to_string()))
+                .collect::<Option<Vec<String>>>()
+                .ok_or_else(|| invalid("args should be a list of strings"))?,
+            Yaml::BadValue => Vec::new(),
+            _ => return Err(invalid("args should be a list of strings")),
+        };
+        let timeout = match &yaml["timeout"] {
+            Yaml::Integer(timeout) if *timeout > 0 => *timeout as u64,
+            Yaml::BadValue => DEFAULT_TIMEOUT,
+            _ => return Err(invalid("timeout should be a number of seconds")),
+        };
+
+        Ok(Some(TestServer {
+            jar: jar,
+            java: java,
+            args: args,
+            timeout: Duration::from_secs(timeout),
+        }))
+    }
+
+    /// Sets up a copy of a server in `dir`, starts it, and stops it again once it has started.
+    /// This blocks until the server has stopped, which can take minutes.
+    ///
+    /// # Arguments
+    ///
+    /// * `project_dir` - The server root, which the JAR and the server's files are in
+    /// * `plugin_dir` - The plugins to load
+    /// * `dir` - Where to run the copy, which shouldn't exist yet
+    /// * `cancel` - Stops the server once it's cancelled
+    ///
+    /// # Errors
+    /// * [`ErrorKind::StartFailed`](enum.ErrorKind.html#variant.StartFailed) - Java couldn't be run
+    /// * [`cancel::ErrorKind::Cancelled`](../cancel/enum.ErrorKind.html#variant.Cancelled) - the test was cancelled
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn run(
+        &self,
+        project_dir: &Path,
+        plugin_dir: &Path,
+        dir: &Path,
+        cancel: &CancelToken,
+    ) -> Result<TestRun, Box<Error>> {
+        fs::create_dir_all(dir)?;
+        if plugin_dir.exists() {
+            copy_dir(plugin_dir, &dir.join("plugins"))?;
+        }
+        if project_dir.join(EULA_FILE).exists() {
+            fs::copy(project_dir.join(EULA_FILE), dir.join(EULA_FILE))?;
+        }
+        let properties = fs::read_to_string(project_dir.join(PROPERTIES_FILE)).unwrap_or_default();
+        fs::write(
+            dir.join(PROPERTIES_FILE),
+            with_free_ports(&properties, free_port()?),
+        )?;
+
+        let jar = project_dir.join(&self.jar);
+        let mut command = Command::new(&self.java);
+        command
+            .args(&self.args)
+            .arg("-jar")
+            .arg(&jar)
+            .arg("nogui")
+            .current_dir(dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let describe = || format!("{} -jar {}", self.java, jar.display());
+        let mut child = command
+            .spawn()
+            .map_err(|e| ErrorKind::StartFailed(describe(), e.to_string()))?;
+
+        let log_path = dir.join(LOG_FILE);
+        let mut log = fs::File::create(&log_path)?;
+        let lines = console_lines(&mut child);
+        let mut output = Vec::new();
+
+        let deadline = Instant::now() + self.timeout;
+        let outcome = loop {
+            if let Err(e) = cancel.check() {
+                child.kill().ok();
+                child.wait().ok();
+                return Err(Box::new(e));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break Outcome::TimedOut;
+            }
+            match lines.recv_timeout(POLL_INTERVAL.min(deadline - now)) {
+                Ok(line) => {
+                    writeln!(log, "{}", line)?;
+                    let started = is_done(&line);
+                    output.push(line);
+                    if started {
+                        break Outcome::Started;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break Outcome::Exited(child.wait()?.code()),
+            }
+        };
+
+        // Whatever it says while stopping may still be about plugins
+        if let Outcome::Started | Outcome::TimedOut = outcome {
+            if let Some(stdin) = child.stdin.as_mut() {
+                writeln!(stdin, "stop").ok();
+            }
+            let deadline = Instant::now() + STOP_TIMEOUT;
+            while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                match lines.recv_timeout(left) {
+                    Ok(line) => {
+                        writeln!(log, "{}", line)?;
+                        output.push(line);
+                    }
+                    Err(_) => break,
+                }
+            }
+            child.kill().ok();
+            child.wait().ok();
+        }
+
+        Ok(TestRun {
+            outcome: outcome,
+            problems: logs::problems(&output),
+            enabled: logs::enabled(&output),
+            log: log_path,
+        })
+    }
+}
+
+/// Reads a server's stdout and stderr a line at a time, on threads of their own so that a
+/// server that goes quiet can be given up on
+fn console_lines(child: &mut Child) -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    let read = |stream: Box<Read + Send>| {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            // Stops at the end of the output, or once nobody is listening anymore
+            for line in BufReader::new(stream).lines() {
+                let sent = match line {
+                    Ok(line) => sender.send(line).is_ok(),
+                    Err(_) => false,
+                };
+                if !sent {
+                    break;
+                }
+            }
+        });
+    };
+    if let Some(out) = child.stdout.take() {
+        read(Box::new(out));
+    }
+    if let Some(err) = child.stderr.take() {
+        read(Box::new(err));
+    }
+    receiver
+}
+
+/// Whether a line of console output says the server has finished starting, like
+/// `Done (3.542s)! For help, type "help"`
+fn is_done(line: &str) -> bool {
+    line.contains("Done (") && line.contains("For help")
+}
+
+/// A `server.properties` with the server and query ports moved to `port` and RCON turned off,
+/// so that the test server doesn't fight the real one over its ports
+fn with_free_ports(properties: &str, port: u16) -> String {
+    let overrides = [
+        ("server-port", port.to_string()),
+        ("query.port", port.to_string()),
+        ("enable-rcon", "false".to_string()),
+    ];
+    let mut lines = properties
+        .lines()
+        .filter(|line| {
+            let key = line.split('=').next().unwrap_or("").trim();
+            !overrides.iter().any(|(name, _)| *name == key)
+        })
+        .map(|line| line.to_string())
+        .collect::<Vec<String>>();
+    for (name, value) in overrides.iter() {
+        lines.push(format!("{}={}", name, value));
+    }
+    format!("{}\n", lines.join("\n"))
+}
+
+/// A port nothing is listening on right now
+fn free_port() -> io::Result<u16> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+/// Copies a folder and everything in it
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let path = entry?.path();
+        let dest = to.join(path.file_name().unwrap());
+        match path.is_dir() {
+            true => copy_dir(&path, &dest)?,
+            false => {
+                fs::copy(&path, &dest)?;
+            }
+        }
+    }
+    Ok(())
+}
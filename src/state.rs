@@ -0,0 +1,468 @@
+//! This module keeps track of what dropper has installed. The pkg.yml says what the user wants,
+//! but not why a package is there or what it needs, so that is recorded separately in
+//! `.dropper/state.yml` each time a package is installed:
+//!
+//! ```yaml
+//! format: 1
+//! packages:
+//!   worldguard:
+//!     version: "6.2"
+//!     reason: explicit
+//!     plugin_name: WorldGuard
+//!     installed_at: 1538352000
+//!     depend:
+//!       - WorldEdit
+//!     files:
+//!       - WorldGuard/config.yml
+//!     configs:
+//!       WorldGuard/messages.yml: 0c0bffe8b1c9b6b5e2d2b8ab2bc1d7e4
+//! project_names:
+//!   bukkit:
+//!     "31043": worldedit
+//! ```
+//!
+//! It also caches what the plugin websites have told dropper about project IDs, so that packages
+//! given by ID don't have to be looked up again on every run.
+//!
+//! Like the lockfile, the state file has a `format`; see the [`format`](../format/index.html)
+//! module.
+
+use crate::format;
+use crate::format::FormatStatus;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+// Every change to the state file's format, oldest first
+const MIGRATIONS: &'static [format::Migration] = &[from_unversioned];
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // The state file couldn't be understood. Takes the path as a param.
+    StateInvalid(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::StateInvalid(s) => format!("the state file {} is corrupt", s),
+            }
+        )
+    }
+}
+
+/// Why a package was installed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstallReason {
+    /// The user asked for it
+    Explicit,
+    /// Another package needed it
+    Dependency,
+}
+
+impl InstallReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstallReason::Explicit => "explicit",
+            InstallReason::Dependency => "dependency",
+        }
+    }
+
+    pub fn from_name(s: &str) -> Option<InstallReason> {
+        match s {
+            "explicit" => Some(InstallReason::Explicit),
+            "dependency" => Some(InstallReason::Dependency),
+            _ => None,
+        }
+    }
+}
+
+/// What dropper knows about a package it installed
+#[derive(Debug, Clone)]
+pub struct InstalledPackage {
+    pub version: String,
+    pub reason: InstallReason,
+    /// The name the plugin registers itself under, if its plugin.yml could be read
+    pub plugin_name: Option<String>,
+    /// The plugins this package needed when it was installed
+    pub depend: Vec<String>,
+    /// When the package was installed, in seconds since the Unix epoch
+    pub installed_at: Option<u64>,
+    /// The files the package put into the plugin directory besides its JAR, relative to the
+    /// plugin directory, like the default configs that came with it
+    pub files: Vec<String>,
+    /// The configs dropper rendered for the package, relative to the plugin directory, with the
+    /// MD5 of what it rendered, so that changes made on the server since can be told apart
+    pub configs: BTreeMap<String, String>,
+}
+
+/// Everything dropper has installed, keyed by package name
+#[derive(Debug, Clone, Default)]
+pub struct State {
+    pub packages: BTreeMap<String, InstalledPackage>,
+    /// The package names of project IDs, keyed by plugin website and then by ID
+    pub project_names: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl State {
+    /// Reads the state file. A missing state file means nothing has been installed yet.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::StateInvalid`](enum.ErrorKind.html#variant.StateInvalid) - the state file is corrupt
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn load(path: &Path) -> Result<State, Box<Error>> {
+        if !path.exists() {
+            return Ok(State::default());
+        }
+
+        let invalid = || ErrorKind::StateInvalid(path.display().to_string());
+        let docs = YamlLoader::load_from_str(&fs::read_to_string(path)?).map_err(|_| invalid())?;
+        let mut doc = match docs.into_iter().next() {
+            Some(Yaml::Hash(doc)) => doc,
+            Some(_) => Hash::new(),
+            None => return Ok(State::default()),
+        };
+        format::migrate(&path.display().to_string(), &mut doc, MIGRATIONS)?;
+        let doc = Yaml::Hash(doc);
+
+        let mut packages = BTreeMap::new();
+        if let Yaml::Hash(entries) = &doc["packages"] {
+            for (name, entry) in entries.iter() {
+                let name = name.as_str().ok_or_else(invalid)?;
+                let version = entry["version"].as_str().ok_or_else(invalid)?;
+                let reason = entry["reason"]
+                    .as_str()
+                    .and_then(InstallReason::from_name)
+                    .ok_or_else(invalid)?;
+                let strings = |yaml: &Yaml| match yaml {
+                    Yaml::Array(items) => items
+                        .iter()
+                        .filter_map(|item| item.as_str().map(|s| s.to_string()))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+
+                packages.insert(
+                    name.to_string(),
+                    InstalledPackage {
+                        version: version.to_string(),
+                        reason: reason,
+                        plugin_name: entry["plugin_name"].as_str().map(|s| s.to_string()),
+                        depend: strings(&entry["depend"]),
+                        installed_at: entry["installed_at"].as_i64().map(|t| t as u64),
+                        files: strings(&entry["files"]),
+                        configs: match &entry["configs"] {
+                            Yaml::Hash(configs) => configs
+                                .iter()
+                                .filter_map(|(file, md5)| match (file.as_str(), md5.as_str()) {
+                                    (Some(file), Some(md5)) => {
+                                        Some((file.to_string(), md5.to_string()))
+                                    }
+                                    _ => None,
+                                })
+                                .collect(),
+                            _ => BTreeMap::new(),
+                        },
+                    },
+                );
+            }
+        }
+
+        let mut project_names = BTreeMap::new();
+        if let Yaml::Hash(websites) = &doc["project_names"] {
+            for (website, ids) in websites.iter() {
+                let mut names = BTreeMap::new();
+                if let Yaml::Hash(ids) = ids {
+                    for (id, name) in ids.iter() {
+                        if let (Some(id), Some(name)) = (id.as_str(), name.as_str()) {
+                            names.insert(id.to_string(), name.to_string());
+                        }
+                    }
+                }
+                project_names.insert(website.as_str().ok_or_else(invalid)?.to_string(), names);
+            }
+        }
+
+        Ok(State {
+            packages: packages,
+            project_names: project_names,
+        })
+    }
+    /// Checks whether the state file on disk is in the newest format
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn format_status(path: &Path) -> io::Result<FormatStatus> {
+        format::file_status(path, MIGRATIONS)
+    }
+
+    /// Upgrades the state file on disk to the newest format, keeping it as it was next to it.
+    /// Returns the format it was in, or `None` if it didn't need upgrading.
+    ///
+    /// # Errors
+    /// * [`ErrorKind::StateInvalid`](enum.ErrorKind.html#variant.StateInvalid) - the state file is corrupt
+    /// * [`format::ErrorKind::FormatTooNew`](../format/enum.ErrorKind.html#variant.FormatTooNew) - the state file was written by a newer dropper
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn upgrade(path: &Path) -> Result<Option<i64>, Box<Error>> {
+        let version = match Self::format_status(path)? {
+            FormatStatus::Outdated(version) => version,
+            FormatStatus::TooNew(version) => {
+                return Err(Box::new(format::ErrorKind::FormatTooNew(
+                    path.display().to_string(),
+                    version,
+                    MIGRATIONS.len() as i64,
+                )))
+            }
+            _ => return Ok(None),
+        };
+        let upgraded = State::load(path)?;
+        fs::copy(path, format::backup_path(path, version))?;
+        upgraded.save(path)?;
+        Ok(Some(version))
+    }
+
+    /// Writes the state file, replacing what was there
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - an IO error occured
+    pub fn save(&self, path: &Path) -> Result<(), Box<Error>> {
+        let mut entries = Hash::new();
+        for (name, package) in self.packages.iter() {
+            let mut entry = Hash::new();
+            entry.insert(
+                Yaml::from_str("version"),
+                Yaml::String(package.version.clone()),
+            );
+            entry.insert(
+                Yaml::from_str("reason"),
+                Yaml::String(package.reason.as_str().to_string()),
+            );
+            if let Some(plugin_name) = &package.plugin_name {
+                entry.insert(
+                    Yaml::from_str("plugin_name"),
+                    Yaml::String(plugin_name.clone()),
+                );
+            }
+            if let Some(installed_at) = package.installed_at {
+                entry.insert(
+                    Yaml::from_str("installed_at"),
+                    Yaml::Integer(installed_at as i64),
+                );
+            }
+            entry.insert(
+                Yaml::from_str("depend"),
+                Yaml::Array(
+                    package
+                        .depend
+                        .iter()
+                        .map(|dep| Yaml::String(dep.clone()))
+                        .collect(),
+                ),
+            );
+            if !package.files.is_empty() {
+                entry.insert(
+                    Yaml::from_str("files"),
+                    Yaml::Array(
+                        package
+                            .files
+                            .iter()
+                            .map(|file| Yaml::String(file.clone()))
+                            .collect(),
+                    ),
+                );
+            }
+            if !package.configs.is_empty() {
+                let mut configs = Hash::new();
+                for (file, md5) in package.configs.iter() {
+                    configs.insert(Yaml::String(file.clone()), Yaml::String(md5.clone()));
+                }
+                entry.insert(Yaml::from_str("configs"), Yaml::Hash(configs));
+            }
+            entries.insert(Yaml::String(name.clone()), Yaml::Hash(entry));
+        }
+
+        let mut websites = Hash::new();
+        for (website, names) in self.project_names.iter() {
+            let mut ids = Hash::new();
+            for (id, name) in names.iter() {
+                ids.insert(Yaml::String(id.clone()), Yaml::String(name.clone()));
+            }
+            websites.insert(Yaml::String(website.clone()), Yaml::Hash(ids));
+        }
+
+        let mut doc = Hash::new();
+        format::stamp(&mut doc, MIGRATIONS);
+        doc.insert(Yaml::from_str("packages"), Yaml::Hash(entries));
+        doc.insert(Yaml::from_str("project_names"), Yaml::Hash(websites));
+
+        let mut tmp_string = String::new();
+        YamlEmitter::new(&mut tmp_string)
+            .dump(&Yaml::Hash(doc))
+            .unwrap();
+        fs::write(path, format!("{}\n", tmp_string))?;
+        Ok(())
+    }
+
+    /// Finds the package that provides a plugin, by the name the plugin registers itself under
+    pub fn provider_of(&self, plugin_name: &str) -> Option<&str> {
+        self.packages
+            .iter()
+            .find(|(_, package)| match &package.plugin_name {
+                Some(name) => name.eq_ignore_ascii_case(plugin_name),
+                None => false,
+            })
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Looks up the cached package name of a project ID on a plugin website
+    pub fn project_name(&self, website: &str, project_id: &str) -> Option<&str> {
+        self.project_names
+            .get(website)
+            .and_then(|names| names.get(project_id))
+            .map(|name| name.as_str())
+    }
+
+    /// Finds the packages that were installed as dependencies, but that no explicitly installed
+    /// package needs anymore, directly or through other dependencies
+    pub fn orphans(&self) -> Vec<String> {
+        // Walk the dependency graph out from everything the user asked for
+        let mut required = self
+            .packages
+            .iter()
+            .filter(|(_, package)| package.reason == InstallReason::Explicit)
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<String>>();
+        let mut i = 0;
+        while i < required.len() {
+            if let Some(package) = self.packages.get(&required[i]) {
+                for dep in package.depend.iter() {
+                    if let Some(provider) = self.provider_of(dep) {
+                        if !required.iter().any(|name| name == provider) {
+                            required.push(provider.to_string());
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        self.packages
+            .keys()
+            .filter(|name| !required.contains(name))
+            .cloned()
+            .collect()
+    }
+}
+
+/// State files from before the format was versioned may have had versions edited in by hand,
+/// written as numbers
+fn from_unversioned(doc: &mut Hash) {
+    format::stringify_fields(doc, "packages", &["version", "plugin_name"]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::{env, process};
+
+    const UNVERSIONED: &str = include_str!("../fixtures/formats/unversioned-state.yml");
+
+    fn state_path(test: &str, contents: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("dropper-state-{}-{}", test, process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.yml");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn unversioned_state_files_are_migrated_as_theyre_read() {
+        let path = state_path("unversioned", UNVERSIONED);
+        assert_eq!(
+            State::format_status(&path).unwrap(),
+            FormatStatus::Outdated(0)
+        );
+
+        let state = State::load(&path).unwrap();
+        let vault = &state.packages["vault"];
+        assert_eq!(vault.version, "1.7");
+        assert_eq!(vault.reason, InstallReason::Explicit);
+        assert_eq!(vault.installed_at, Some(1538352000));
+        let worldedit = &state.packages["worldedit"];
+        assert_eq!(worldedit.version, "7");
+        assert_eq!(worldedit.reason, InstallReason::Dependency);
+        assert_eq!(state.project_names["bukkit"]["33184"], "vault");
+        assert_eq!(fs::read_to_string(&path).unwrap(), UNVERSIONED);
+
+        state.save(&path).unwrap();
+        assert_eq!(State::format_status(&path).unwrap(), FormatStatus::Current);
+        assert_eq!(
+            State::load(&path).unwrap().packages["worldedit"].version,
+            "7"
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn state_files_from_a_newer_dropper_are_refused() {
+        let path = state_path("too-new", "format: 2\npackages: {}\n");
+        let e = State::load(&path).unwrap_err();
+        match e.downcast_ref::<format::ErrorKind>() {
+            Some(format::ErrorKind::FormatTooNew(_, 2, 1)) => {}
+            _ => panic!("expected the state file to be too new, got {}", e),
+        }
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn upgrading_keeps_the_old_state_file_next_to_the_new_one() {
+        let path = state_path("upgrade", UNVERSIONED);
+        let backup = format::backup_path(&path, 0);
+
+        assert_eq!(State::upgrade(&path).unwrap(), Some(0));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), UNVERSIONED);
+        assert_eq!(State::format_status(&path).unwrap(), FormatStatus::Current);
+        let upgraded = fs::read_to_string(&path).unwrap();
+        assert!(upgraded.starts_with("---\nformat: 1\n"), "{}", upgraded);
+        assert_eq!(State::load(&path).unwrap().packages["vault"].version, "1.7");
+
+        // Files that are already upgraded are left alone
+        fs::remove_file(&backup).unwrap();
+        assert_eq!(State::upgrade(&path).unwrap(), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), upgraded);
+        assert!(!backup.exists());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn state_files_from_a_newer_dropper_arent_upgraded() {
+        let contents = "format: 2\npackages: {}\n";
+        let path = state_path("upgrade-too-new", contents);
+        let e = State::upgrade(&path).unwrap_err();
+        match e.downcast_ref::<format::ErrorKind>() {
+            Some(format::ErrorKind::FormatTooNew(_, 2, 1)) => {}
+            _ => panic!("expected the state file to be too new, got {}", e),
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+        assert!(!format::backup_path(&path, 2).exists());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
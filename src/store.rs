@@ -0,0 +1,214 @@
+//! The content store keeps one copy of every JAR dropper installs, named by its SHA-256 digest,
+//! in the cache root that every server on the machine shares. A server can have its plugins
+//! folder link into the store rather than hold copies, which saves a lot of space on hosts that
+//! run many servers with the same plugins:
+//!
+//! ```yaml
+//! install_strategy: symlink
+//! ```
+//!
+//! The strategy can be `copy` (the default), `symlink` or `hardlink`. Hard links need the store
+//! and the plugins folder to be on the same filesystem, and symbolic links aren't allowed
+//! everywhere, so a JAR that can't be linked is copied instead. The store remembers which
+//! plugins folders link into it, so that pruning the cache leaves the JARs they use alone.
+
+use crate::build;
+use crate::hash;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use yaml_rust::Yaml;
+
+const STORE_DIR: &'static str = "store";
+// The digest JARs in the store are named by
+const STORE_ALGORITHM: &'static str = "sha256";
+// The plugins folders with symbolic links into the store, one per line
+const LINKED_DIRS_FILE: &'static str = "linked-dirs";
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // The config's install_strategy isn't one of the strategies. Takes the strategy as a param.
+    StrategyUnknown(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::StrategyUnknown(s) => format!(
+                    "install_strategy '{}' isn't supported; it can be one of: copy, symlink, hardlink",
+                    s
+                ),
+            }
+        )
+    }
+}
+
+/// How installed JARs get into the plugins folder
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstallStrategy {
+    /// Each server has a copy of its own
+    Copy,
+    /// JARs are symbolic links into the store
+    Symlink,
+    /// JARs are hard links to the store's copies
+    Hardlink,
+}
+
+impl InstallStrategy {
+    /// Reads the config's `install_strategy`, which is `copy` if it's left out
+    ///
+    /// # Errors
+    /// * [`ErrorKind::StrategyUnknown`](enum.ErrorKind.html#variant.StrategyUnknown) - the strategy isn't one of the strategies
+    pub fn from_yaml(yaml: &Yaml) -> Result<InstallStrategy, ErrorKind> {
+        match yaml {
+            Yaml::BadValue | Yaml::Null => Ok(InstallStrategy::Copy),
+            Yaml::String(s) => match s.as_str() {
+                "copy" => Ok(InstallStrategy::Copy),
+                "symlink" => Ok(InstallStrategy::Symlink),
+                "hardlink" => Ok(InstallStrategy::Hardlink),
+                _ => Err(ErrorKind::StrategyUnknown(s.clone())),
+            },
+            _ => Err(ErrorKind::StrategyUnknown(format!("{:?}", yaml))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InstallStrategy::Copy => "copy",
+            InstallStrategy::Symlink => "symlink",
+            InstallStrategy::Hardlink => "hardlink",
+        }
+    }
+}
+
+/// Where the content store is, or `None` if there's nowhere to put it
+pub fn store_dir() -> Option<PathBuf> {
+    build::cache_root().map(|root| root.join(STORE_DIR))
+}
+
+/// Puts a copy of `file` in the store, unless the store has one already, and returns where the
+/// store's copy is
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+fn add(store: &Path, file: &Path) -> io::Result<PathBuf> {
+    let digest = hash::digest_file(file, &[STORE_ALGORITHM])?
+        .remove(STORE_ALGORITHM)
+        .unwrap();
+    let stored = store.join(format!("{}.jar", digest));
+    if !stored.exists() {
+        // Copied in under another name first, so other servers never see half a JAR
+        fs::create_dir_all(store)?;
+        let partial = store.join(format!("{}.jar.part", digest));
+        fs::copy(file, &partial)?;
+        fs::rename(&partial, &stored)?;
+    }
+    // Symbolic links have to keep working from wherever the plugins folder is
+    stored.canonicalize()
+}
+
+/// Makes `link` a link to the store's copy of `file`, adding it to the store first if it needs
+/// to be. `file` is left where it is either way.
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured, or the link couldn't be made
+pub fn link(store: &Path, strategy: InstallStrategy, file: &Path, link: &Path) -> io::Result<()> {
+    let stored = add(store, file)?;
+    match strategy {
+        InstallStrategy::Copy => fs::copy(&stored, link).map(|_| ()),
+        InstallStrategy::Symlink => symlink(&stored, link),
+        InstallStrategy::Hardlink => fs::hard_link(&stored, link),
+    }
+}
+
+/// Remembers that a plugins folder has symbolic links into the store
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn remember_linked_dir(store: &Path, plugin_dir: &Path) -> io::Result<()> {
+    let plugin_dir = plugin_dir.canonicalize()?;
+    if linked_dirs(store).contains(&plugin_dir) {
+        return Ok(());
+    }
+    fs::create_dir_all(store)?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(store.join(LINKED_DIRS_FILE))?;
+    writeln!(file, "{}", plugin_dir.display())
+}
+
+/// The plugins folders that have had symbolic links into the store
+fn linked_dirs(store: &Path) -> Vec<PathBuf> {
+    fs::read_to_string(store.join(LINKED_DIRS_FILE))
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Whether a file in the store is one of its JARs, rather than something the store keeps for
+/// itself or a JAR that's still being added
+pub fn is_stored_jar(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "jar")
+}
+
+/// Whether a JAR in the store is still used by a plugins folder, through a hard link or a
+/// symbolic link
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - an IO error occured
+pub fn is_linked(store: &Path, stored: &Path) -> io::Result<bool> {
+    if link_count(stored)? > 1 {
+        return Ok(true);
+    }
+    let stored = stored.canonicalize()?;
+    for dir in linked_dirs(store) {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries {
+            let path = entry?.path();
+            let target = match fs::read_link(&path) {
+                Ok(target) => dir.join(target),
+                Err(_) => continue,
+            };
+            if target.canonicalize().ok().as_ref() == Some(&stored) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(unix)]
+fn link_count(path: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(fs::metadata(path)?.nlink())
+}
+
+// Hard links can't be counted, so they're left for the symbolic link check
+#[cfg(not(unix))]
+fn link_count(_path: &Path) -> io::Result<u64> {
+    Ok(1)
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
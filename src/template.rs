@@ -0,0 +1,262 @@
+//! This module renders plugin config templates. Templates are ordinary config files with
+//! variables written as `{{ name }}`, which are filled in from the variables dropper was given:
+//!
+//! ```yaml
+//! # templates/essentials/config.yml
+//! ops-name-color: '4'
+//! motd: '{{ motd }}'
+//! ```
+//!
+//! Every variable a template uses has to be defined, so that a typo doesn't quietly end up in
+//! a server's config. Variables can be defined under `variables` in the config, or in a
+//! `vars.yml` next to the pkg.yml, which is meant for secrets like database passwords and should
+//! be kept out of git. Its values can be read from environment variables instead of being
+//! written out:
+//!
+//! ```yaml
+//! # vars.yml
+//! motd: Welcome!
+//! db_password:
+//!   env: DB_PASSWORD
+//! ```
+//!
+//! String values in the pkg.yml are templates too, so a download URL can take an API key.
+//!
+//! A `{{` that isn't the start of a variable, like in a plugin's own placeholders, is written
+//! `\{{`.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use yaml_rust::Yaml;
+
+const OPEN: &'static str = "{{";
+const CLOSE: &'static str = "}}";
+// Written before `OPEN` to keep it as it is
+const ESCAPE: char = '\\';
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    // A template uses a variable that wasn't defined. Takes the template and the variable name
+    // as params.
+    VariableMissing(String, String),
+    // A template has a `{{` that is never closed. Takes the template as a param.
+    TemplateInvalid(String),
+}
+
+impl Error for ErrorKind {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ErrorKind::VariableMissing(template, name) => format!(
+                    "{} uses the variable '{}', which isn't defined; define it in vars.yml",
+                    template, name
+                ),
+                ErrorKind::TemplateInvalid(s) => format!("{} has an unclosed {}", s, OPEN),
+            }
+        )
+    }
+}
+
+/// Fills in the variables in a template. A `{{` with a backslash before it is kept, without the
+/// backslash.
+///
+/// # Arguments
+///
+/// * `name` - What to call the template in errors, usually its path
+/// * `template` - The contents of the template
+/// * `variables` - The values to fill in
+///
+/// # Errors
+/// * [`ErrorKind::VariableMissing`](enum.ErrorKind.html#variant.VariableMissing) - the template uses an undefined variable
+/// * [`ErrorKind::TemplateInvalid`](enum.ErrorKind.html#variant.TemplateInvalid) - the template has an unclosed `{{`
+pub fn render(
+    name: &str,
+    template: &str,
+    variables: &BTreeMap<String, String>,
+) -> Result<String, ErrorKind> {
+    let mut rendered = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find(OPEN) {
+        let after = &rest[start + OPEN.len()..];
+        if rest[..start].ends_with(ESCAPE) {
+            rendered.push_str(&rest[..start - ESCAPE.len_utf8()]);
+            rendered.push_str(OPEN);
+            rest = after;
+            continue;
+        }
+        rendered.push_str(&rest[..start]);
+        let end = match after.find(CLOSE) {
+            Some(end) => end,
+            None => return Err(ErrorKind::TemplateInvalid(name.to_string())),
+        };
+
+        let variable = after[..end].trim();
+        match variables.get(variable) {
+            Some(value) => rendered.push_str(value),
+            None => {
+                return Err(ErrorKind::VariableMissing(
+                    name.to_string(),
+                    variable.to_string(),
+                ))
+            }
+        }
+        rest = &after[end + CLOSE.len()..];
+    }
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+/// Reads a map of variables out of YAML. Values are hand-written, so numbers and booleans are
+/// taken as they were written; anything else isn't a variable.
+pub fn variables_from_yaml(yaml: &Yaml) -> BTreeMap<String, String> {
+    let mut variables = BTreeMap::new();
+    if let Yaml::Hash(entries) = yaml {
+        for (name, value) in entries.iter() {
+            let value = match value {
+                Yaml::String(s) | Yaml::Real(s) => s.clone(),
+                Yaml::Integer(i) => i.to_string(),
+                Yaml::Boolean(b) => b.to_string(),
+                _ => continue,
+            };
+            if let Some(name) = name.as_str() {
+                variables.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    variables
+}
+
+/// Reads the variables in a vars.yml. Values are taken like `variables_from_yaml` takes them,
+/// except that a value written as `env: NAME` is read from the environment variable `NAME`.
+/// Returns the variables, along with the (variable, environment variable) of each one whose
+/// environment variable isn't set, which are left undefined.
+pub fn variables_from_vars_file(yaml: &Yaml) -> (BTreeMap<String, String>, Vec<(String, String)>) {
+    let mut variables = variables_from_yaml(yaml);
+    let mut unset = Vec::new();
+    if let Yaml::Hash(entries) = yaml {
+        for (name, value) in entries.iter() {
+            let (name, env) = match (name.as_str(), value["env"].as_str()) {
+                (Some(name), Some(env)) => (name, env),
+                _ => continue,
+            };
+            match env::var(env) {
+                Ok(value) => {
+                    variables.insert(name.to_string(), value);
+                }
+                Err(_) => unset.push((name.to_string(), env.to_string())),
+            }
+        }
+    }
+
+    (variables, unset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn variables_are_filled_in() {
+        let variables = variables(&[("motd", "Welcome!"), ("port", "25565")]);
+        assert_eq!(
+            render(
+                "config.yml",
+                "motd: '{{ motd }}'\nport: {{port}}\n",
+                &variables
+            )
+            .unwrap(),
+            "motd: 'Welcome!'\nport: 25565\n"
+        );
+    }
+
+    #[test]
+    fn undefined_variables_are_refused() {
+        let variables = variables(&[("motd", "Welcome!")]);
+        match render(
+            "config.yml",
+            "motd: {{ motd }}\nname: {{ nmae }}\n",
+            &variables,
+        ) {
+            Err(ErrorKind::VariableMissing(template, name)) => {
+                assert_eq!((template.as_str(), name.as_str()), ("config.yml", "nmae"))
+            }
+            rendered => panic!("expected nmae to be undefined, got {:?}", rendered),
+        }
+    }
+
+    #[test]
+    fn unclosed_variables_are_refused() {
+        let variables = variables(&[("motd", "Welcome!")]);
+        match render("config.yml", "motd: {{ motd", &variables) {
+            Err(ErrorKind::TemplateInvalid(template)) => assert_eq!(template, "config.yml"),
+            rendered => panic!("expected the template to be refused, got {:?}", rendered),
+        }
+    }
+
+    #[test]
+    fn escaped_braces_are_kept() {
+        let variables = variables(&[("motd", "Welcome!")]);
+        assert_eq!(
+            render(
+                "config.yml",
+                "motd: {{ motd }}\njoin: \\{{ player }} joined\nend: \\{{",
+                &variables
+            )
+            .unwrap(),
+            "motd: Welcome!\njoin: {{ player }} joined\nend: {{"
+        );
+    }
+
+    #[test]
+    fn values_are_read_as_they_were_written() {
+        let yaml = &yaml_rust::YamlLoader::load_from_str(
+            "motd: Welcome!\nport: 25565\nratio: 1.5\npvp: false\nlist: [a, b]\n",
+        )
+        .unwrap()[0];
+        assert_eq!(
+            variables_from_yaml(yaml),
+            variables(&[
+                ("motd", "Welcome!"),
+                ("port", "25565"),
+                ("ratio", "1.5"),
+                ("pvp", "false")
+            ])
+        );
+    }
+
+    #[test]
+    fn vars_files_can_read_values_from_the_environment() {
+        let set = format!("DROPPER_TEST_SET_{}", std::process::id());
+        let unset = format!("DROPPER_TEST_UNSET_{}", std::process::id());
+        env::set_var(&set, "hunter2");
+        let yaml = &yaml_rust::YamlLoader::load_from_str(&format!(
+            "motd: Welcome!\ndb_password:\n  env: {}\napi_key:\n  env: {}\n",
+            set, unset
+        ))
+        .unwrap()[0];
+
+        let (defined, missing) = variables_from_vars_file(yaml);
+        env::remove_var(&set);
+        assert_eq!(
+            defined,
+            variables(&[("motd", "Welcome!"), ("db_password", "hunter2")])
+        );
+        assert_eq!(missing, [("api_key".to_string(), unset)]);
+    }
+}
@@ -1,5 +1,5 @@
 //! This module simple defines some text assets for the rest of the program to use.
 
 pub const CONFIG_YAML_DEFAULT: &'static [u8] = b"
-package_website: bukkit
+plugin_website: bukkit
 ";
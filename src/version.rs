@@ -0,0 +1,274 @@
+//! Versions of packages, and of the server. Plugins number their versions every which way, so a
+//! version is read as loosely as it can be: numbers separated by dots, then optionally a
+//! pre-release tag and build metadata, like `1.2.3-beta.2+build.45`. A leading `v` is ignored, and
+//! the tag doesn't need a dash in front of it, so Bukkit's `6.1.9b2` is version 6.1.9 with the tag
+//! `b2`.
+//!
+//! Versions are ordered by their numbers first, with missing numbers counting as zeros, so `1.2`
+//! and `1.2.0` are the same version. A pre-release comes before the release it leads up to, and
+//! pre-release tags are compared part by part, numerically where both parts are numbers. Build
+//! metadata doesn't take part in ordering at all.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A version, as it was written and split into its parts
+#[derive(Debug, Clone)]
+pub struct Version {
+    /// The numbers at the start, like `[1, 2, 3]` for `1.2.3`
+    pub components: Vec<u32>,
+    /// What comes after the numbers, like `beta.2` or `SNAPSHOT`
+    pub prerelease: Option<String>,
+    /// What comes after a `+`, which says how the version was built
+    pub build: Option<String>,
+    original: String,
+}
+
+impl Version {
+    /// Reads a version, or returns `None` if it doesn't start with a number
+    pub fn parse(version: &str) -> Option<Version> {
+        // Versions are parsed a lot while sorting files, so this is done by hand
+        let version = version.trim();
+        let unprefixed = match version.chars().next() {
+            Some('v') | Some('V') => &version[1..],
+            _ => version,
+        };
+        let (numbers, rest) = split_numbers(unprefixed);
+        if numbers.is_empty() {
+            return None;
+        }
+        let components = numbers
+            .split('.')
+            .map(|c| c.parse::<u32>().ok())
+            .collect::<Option<Vec<u32>>>()?;
+
+        let rest = rest.strip_prefix(|c| c == '-' || c == '.').unwrap_or(rest);
+        let (prerelease, build) = match rest.find('+') {
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => (rest, ""),
+        };
+        let text = |s: &str| Some(s.to_string()).filter(|s| !s.is_empty());
+
+        Some(Version {
+            components: components,
+            prerelease: text(prerelease),
+            build: text(build),
+            original: version.to_string(),
+        })
+    }
+
+    /// Finds the first version in some text, like the `1.7.9` in `CB 1.7.9-R0.2`. Only the
+    /// numbers are kept.
+    pub fn find(text: &str) -> Option<Version> {
+        let start = text.find(|c: char| c.is_ascii_digit())?;
+        Version::parse(split_numbers(&text[start..]).0)
+    }
+
+    /// A version that's just numbers
+    pub fn from_components(components: Vec<u32>) -> Version {
+        Version {
+            original: components
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>()
+                .join("."),
+            components: components,
+            prerelease: None,
+            build: None,
+        }
+    }
+
+    /// The version as it was written
+    pub fn as_str(&self) -> &str {
+        &self.original
+    }
+
+    /// Whether the version is a pre-release
+    pub fn is_prerelease(&self) -> bool {
+        self.prerelease.is_some()
+    }
+
+    /// Whether the version's numbers start with these, e.g. `6.1.9` starts with `[6, 1]`
+    pub fn starts_with(&self, prefix: &[u32]) -> bool {
+        self.components.len() >= prefix.len() && self.components[..prefix.len()] == *prefix
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        let len = self.components.len().max(other.components.len());
+        let component = |version: &Version, i: usize| *version.components.get(i).unwrap_or(&0);
+        for i in 0..len {
+            match component(self, i).cmp(&component(other, i)) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            }
+        }
+
+        match (&self.prerelease, &other.prerelease) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) => compare_prereleases(a, b),
+        }
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Version) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.original)
+    }
+}
+
+/// Splits the numbers a version starts with, like the `1.2.3` in `1.2.3-beta`, off the rest
+fn split_numbers(text: &str) -> (&str, &str) {
+    let bytes = text.as_bytes();
+    let (mut i, mut end) = (0, 0);
+    loop {
+        let start = i;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        if i == start {
+            break;
+        }
+        end = i;
+        match bytes.get(i) {
+            Some(b'.') => i += 1,
+            _ => break,
+        }
+    }
+    text.split_at(end)
+}
+
+/// Splits a pre-release tag into its runs of digits and of letters, lowercased, so that `beta2`
+/// is `beta` and `2`. Anything else just separates parts.
+fn prerelease_parts(tag: &str) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    // Whether the last character was a digit, or `None` if it separated parts
+    let mut last_digit = None;
+    for c in tag.chars() {
+        let digit = match c {
+            c if c.is_ascii_digit() => true,
+            c if c.is_ascii_alphabetic() => false,
+            _ => {
+                last_digit = None;
+                continue;
+            }
+        };
+        match parts.last_mut() {
+            Some(part) if last_digit == Some(digit) => part.push(c.to_ascii_lowercase()),
+            _ => parts.push(c.to_ascii_lowercase().to_string()),
+        }
+        last_digit = Some(digit);
+    }
+    parts
+}
+
+/// Compares pre-release tags part by part. Numeric parts come before words, like in semver, and
+/// a tag that runs out first is the older one.
+fn compare_prereleases(a: &str, b: &str) -> Ordering {
+    // `beta10` comes after `beta9`, since the numbers are compared as numbers
+    let (a, b) = (prerelease_parts(a), prerelease_parts(b));
+
+    for (a, b) in a.iter().zip(b.iter()) {
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(text: &str) -> Version {
+        Version::parse(text).unwrap()
+    }
+
+    #[test]
+    fn versions_are_split_into_parts() {
+        let parsed = version("1.2.3-beta.2+build.45");
+        assert_eq!(parsed.components, vec![1, 2, 3]);
+        assert_eq!(parsed.prerelease, Some("beta.2".to_string()));
+        assert_eq!(parsed.build, Some("build.45".to_string()));
+        assert_eq!(parsed.as_str(), "1.2.3-beta.2+build.45");
+
+        let bukkit = version("6.1.9b2");
+        assert_eq!(bukkit.components, vec![6, 1, 9]);
+        assert_eq!(bukkit.prerelease, Some("b2".to_string()));
+        assert_eq!(version("2.0.").prerelease, None);
+    }
+
+    #[test]
+    fn versions_start_with_a_number() {
+        assert!(Version::parse("beta").is_none());
+        assert!(Version::parse("").is_none());
+        assert!(Version::parse("99999999999").is_none());
+    }
+
+    #[test]
+    fn missing_numbers_count_as_zeros() {
+        assert_eq!(version("1.2"), version("1.2.0"));
+        assert_eq!(version("1"), version("1.0.0.0"));
+        assert!(version("1.2") < version("1.2.1"));
+        assert!(version("1.10") > version("1.9"));
+    }
+
+    #[test]
+    fn prereleases_come_before_releases() {
+        assert!(version("6.1.9b2") < version("6.1.9"));
+        assert!(version("6.1.9b2") > version("6.1.8"));
+        assert!(version("2.0-SNAPSHOT") < version("2.0"));
+    }
+
+    #[test]
+    fn prerelease_numbers_are_compared_as_numbers() {
+        assert!(version("1.0-beta9") < version("1.0-beta10"));
+        assert!(version("1.0-beta.9") < version("1.0-beta.10"));
+        assert!(version("1.0-alpha") < version("1.0-beta"));
+        assert!(version("1.0-beta") < version("1.0-beta2"));
+        assert_eq!(version("1.0-BETA2"), version("1.0-beta2"));
+    }
+
+    #[test]
+    fn leading_vs_are_ignored() {
+        assert_eq!(version("v1.2.3").components, vec![1, 2, 3]);
+        assert_eq!(version("v1.2.3"), version("1.2.3"));
+        assert_eq!(version("V2.0"), version("2"));
+        assert_eq!(version("v1.2.3").as_str(), "v1.2.3");
+    }
+
+    #[test]
+    fn build_metadata_is_not_ordered() {
+        assert_eq!(version("1.2.3+45"), version("1.2.3+46"));
+    }
+
+    #[test]
+    fn versions_are_found_in_text() {
+        assert_eq!(Version::find("CB 1.7.9-R0.2").unwrap().as_str(), "1.7.9");
+        assert_eq!(Version::find("WorldEdit 6.1.9").unwrap().as_str(), "6.1.9");
+        assert!(Version::find("no version").is_none());
+    }
+}
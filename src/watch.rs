@@ -0,0 +1,163 @@
+//! This module watches the plugins folder for `dropper watch`, so that JARs added or removed by
+//! hand, by a hosting panel's file manager, or by anything else that isn't dropper get noticed.
+//! On Linux the folder is watched with inotify, so changes are picked up as they happen;
+//! elsewhere it's checked every so often instead.
+//!
+//! The watcher only says that something changed. Working out what changed is up to comparing
+//! snapshots of the folder, which are taken with `snapshot`.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait for a burst of changes to die down, so that e.g. an upload being written,
+/// or a whole dropper operation, is seen as one change
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Something that blocks until a folder may have changed
+pub struct Watcher {
+    /// How long to wait before looking again, even if nothing seemed to change
+    interval: Duration,
+    #[cfg(target_os = "linux")]
+    fd: libc::c_int,
+}
+
+impl Watcher {
+    /// Starts watching `dir`
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - the folder couldn't be watched
+    #[cfg(target_os = "linux")]
+    pub fn new(dir: &Path, interval: Duration) -> io::Result<Watcher> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Closed on drop from here on, even if adding the watch fails
+        let watcher = Watcher {
+            interval: interval,
+            fd: fd,
+        };
+
+        let mask = libc::IN_CREATE
+            | libc::IN_DELETE
+            | libc::IN_MOVED_FROM
+            | libc::IN_MOVED_TO
+            | libc::IN_CLOSE_WRITE;
+        if unsafe { libc::inotify_add_watch(fd, path.as_ptr(), mask) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(watcher)
+    }
+
+    /// Starts watching `dir`
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - the folder couldn't be watched
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(dir: &Path, interval: Duration) -> io::Result<Watcher> {
+        fs::read_dir(dir)?;
+        Ok(Watcher { interval: interval })
+    }
+
+    /// Blocks until something in the folder changes, or the interval runs out, and then until
+    /// the changes have settled
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - waiting on the folder failed
+    #[cfg(target_os = "linux")]
+    pub fn wait(&self) -> io::Result<()> {
+        let mut poll = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = self.interval.as_millis().min(libc::c_int::MAX as u128);
+        if unsafe { libc::poll(&mut poll, 1, timeout as libc::c_int) } < 0 {
+            let e = io::Error::last_os_error();
+            // A signal isn't a failure, it just cuts the wait short
+            if e.kind() != io::ErrorKind::Interrupted {
+                return Err(e);
+            }
+        }
+
+        // The events themselves don't matter, only that there were some, so they're read and
+        // thrown away until a whole settle time goes by without any
+        loop {
+            thread::sleep(SETTLE_TIME);
+            if !self.drain()? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Blocks for the interval, since there's no way to be told about changes
+    ///
+    /// # Errors
+    /// * `std::io::ErrorKind::*` - waiting on the folder failed
+    #[cfg(not(target_os = "linux"))]
+    pub fn wait(&self) -> io::Result<()> {
+        thread::sleep(self.interval);
+        Ok(())
+    }
+
+    /// Reads every event that's waiting, and returns whether there were any
+    #[cfg(target_os = "linux")]
+    fn drain(&self) -> io::Result<bool> {
+        let mut buffer = [0u8; 4096];
+        let mut any = false;
+        loop {
+            let read = unsafe {
+                libc::read(
+                    self.fd,
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                )
+            };
+            if read > 0 {
+                any = true;
+                continue;
+            }
+
+            let e = io::Error::last_os_error();
+            return match e.kind() {
+                io::ErrorKind::WouldBlock => Ok(any),
+                io::ErrorKind::Interrupted => continue,
+                _ => Err(e),
+            };
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// The file names of the JARs in a folder. Folders in it, like dropper's staging folder, aren't
+/// looked into.
+///
+/// # Errors
+/// * `std::io::ErrorKind::*` - the folder couldn't be read
+pub fn snapshot(dir: &Path) -> io::Result<BTreeSet<String>> {
+    let mut jars = BTreeSet::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "jar") {
+            jars.insert(path.file_name().unwrap().to_string_lossy().to_string());
+        }
+    }
+    Ok(jars)
+}